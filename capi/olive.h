@@ -0,0 +1,18 @@
+#ifndef OLIVE_H
+#define OLIVE_H
+
+typedef struct Engine OliveEngine;
+typedef struct OliveResult OliveResult;
+
+OliveEngine *olive_new_engine(void);
+void olive_free_engine(OliveEngine *engine);
+
+/* evaluates source in the engine, the result has to be freed with olive_free.
+   a null engine or source gives an error result */
+OliveResult *olive_eval(OliveEngine *engine, const char *source);
+int olive_is_error(const OliveResult *result);
+/* the returned value or the error messages, valid until the result is freed */
+const char *olive_get_string(const OliveResult *result);
+void olive_free(OliveResult *result);
+
+#endif
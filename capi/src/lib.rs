@@ -0,0 +1,116 @@
+use olive::{Engine, Mistake, Value};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+
+// the library is only built as a cdylib, so its tests can't be integration
+// tests linking against it
+#[cfg(test)]
+mod tests;
+
+// the result of olive_eval, either the returned value or the error messages
+pub struct OliveResult {
+    value: Option<Value>,
+    text: CString,
+}
+
+fn to_c_string(text: String) -> CString {
+    CString::new(text.replace('\0', "")).unwrap()
+}
+
+#[no_mangle]
+pub extern "C" fn olive_new_engine() -> *mut Engine {
+    Box::into_raw(Box::new(Engine::new()))
+}
+
+/// # Safety
+/// `engine` must come from `olive_new_engine` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn olive_free_engine(engine: *mut Engine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+fn error_result(message: String) -> OliveResult {
+    OliveResult {
+        value: None,
+        text: to_c_string(message),
+    }
+}
+
+fn eval(engine: &mut Engine, source: &CStr) -> OliveResult {
+    let source = match source.to_str() {
+        Ok(source) => source,
+        Err(_) => return error_result(String::from("source is not valid utf-8")),
+    };
+    match engine.eval_str(source) {
+        Mistake::Fine(value, _) => OliveResult {
+            text: to_c_string(value.to_string()),
+            value: Some(value),
+        },
+        Mistake::Fail(errors) => error_result(
+            errors
+                .iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<String>>()
+                .join("\n"),
+        ),
+    }
+}
+
+/// A null `engine` or `source` gives an error result, and so does a panic in
+/// the interpreter, which must not unwind into the caller.
+///
+/// # Safety
+/// `engine` must come from `olive_new_engine` and `source` must be a
+/// nul-terminated string, or either may be null. The result has to be freed
+/// with `olive_free`.
+#[no_mangle]
+pub unsafe extern "C" fn olive_eval(
+    engine: *mut Engine,
+    source: *const c_char,
+) -> *mut OliveResult {
+    let result = if engine.is_null() {
+        error_result(String::from("engine is null"))
+    } else if source.is_null() {
+        error_result(String::from("source is null"))
+    } else {
+        let (engine, source) = (&mut *engine, CStr::from_ptr(source));
+        panic::catch_unwind(AssertUnwindSafe(|| eval(engine, source))).unwrap_or_else(|panic| {
+            let message = match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+                (Some(message), _) => message,
+                (_, Some(message)) => message.as_str(),
+                _ => "unknown error",
+            };
+            error_result(format!("the interpreter panicked: {}", message))
+        })
+    };
+    Box::into_raw(Box::new(result))
+}
+
+/// # Safety
+/// `result` must come from `olive_eval`.
+#[no_mangle]
+pub unsafe extern "C" fn olive_is_error(result: *const OliveResult) -> c_int {
+    (*result).value.is_none() as c_int
+}
+
+/// The value formatted like `print` would, or the error messages. The string
+/// belongs to the result and stays valid until it is freed.
+///
+/// # Safety
+/// `result` must come from `olive_eval`.
+#[no_mangle]
+pub unsafe extern "C" fn olive_get_string(result: *const OliveResult) -> *const c_char {
+    (*result).text.as_ptr()
+}
+
+/// # Safety
+/// `result` must come from `olive_eval` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn olive_free(result: *mut OliveResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
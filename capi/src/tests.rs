@@ -0,0 +1,46 @@
+use super::{
+    olive_eval, olive_free, olive_free_engine, olive_get_string, olive_is_error, olive_new_engine,
+    Engine,
+};
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+// evaluates the source like a c caller would, returns whether it failed and
+// the string of the result
+fn eval(engine: *mut Engine, source: Option<&str>) -> (bool, String) {
+    let source = source.map(|source| CString::new(source).unwrap());
+    unsafe {
+        let result = olive_eval(
+            engine,
+            source
+                .as_ref()
+                .map_or(ptr::null(), |source| source.as_ptr()),
+        );
+        let failed = olive_is_error(result) != 0;
+        let text = CStr::from_ptr(olive_get_string(result))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        olive_free(result);
+        (failed, text)
+    }
+}
+
+#[test]
+fn scripts_run_through_the_c_api() {
+    let engine = olive_new_engine();
+    assert_eq!(eval(engine, Some("x = 20;")), (false, String::from("none")));
+    assert_eq!(
+        eval(engine, Some("return x + 1;")),
+        (false, String::from("21"))
+    );
+    let (failed, text) = eval(engine, Some("return y;"));
+    assert!(failed);
+    assert!(text.contains("couldn't find variable 'y'"), "{}", text);
+    assert_eq!(eval(engine, None), (true, String::from("source is null")));
+    assert_eq!(
+        eval(ptr::null_mut(), Some("return 1;")),
+        (true, String::from("engine is null"))
+    );
+    unsafe { olive_free_engine(engine) };
+}
@@ -0,0 +1,138 @@
+// yaml and toml documents for scripts, added to an engine with register.
+// documents are read into and written from bendys, lists and plain values
+use olive::{Engine, Value};
+use serde_yaml::Value as Yaml;
+use std::collections::HashMap;
+use toml::Value as Toml;
+
+// adds the functions to global yaml and toml bendys:
+//   yaml.parse(data) and toml.parse(data) read a document from a string
+//   yaml.write(value) and toml.write(value) write a value as a document.
+//     toml has no null, so none can't be written as toml, and a toml
+//     document has to be a bendy
+// toml datetimes are read as strings. functions can't be written at all
+pub fn register(engine: &mut Engine) {
+    engine.register_module_fn("yaml", "parse", 1, |args| {
+        let data = string(&args[0], "yaml.parse")?;
+        let yaml =
+            serde_yaml::from_str(data).map_err(|error| format!("yaml.parse failed: {}", error))?;
+        from_yaml(yaml)
+    });
+    engine.register_module_fn("yaml", "write", 1, |args| {
+        let yaml = to_yaml(&args[0])?;
+        serde_yaml::to_string(&yaml)
+            .map(Value::String)
+            .map_err(|error| format!("yaml.write failed: {}", error))
+    });
+    engine.register_module_fn("toml", "parse", 1, |args| {
+        let data = string(&args[0], "toml.parse")?;
+        let toml = data
+            .parse::<Toml>()
+            .map_err(|error| format!("toml.parse failed: {}", error))?;
+        Ok(from_toml(toml))
+    });
+    engine.register_module_fn("toml", "write", 1, |args| {
+        let toml = to_toml(&args[0])?;
+        toml::to_string(&toml)
+            .map(Value::String)
+            .map_err(|error| format!("toml.write failed: {}", error))
+    });
+}
+
+fn string<'a>(value: &'a Value, function: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(value) => Ok(value),
+        _ => Err(format!("{} expects the document as a string", function)),
+    }
+}
+
+fn from_yaml(yaml: Yaml) -> Result<Value, String> {
+    Ok(match yaml {
+        Yaml::Null => Value::None,
+        Yaml::Bool(value) => Value::Boolean(value),
+        Yaml::Number(value) => match value.as_i64() {
+            Some(value) => Value::Integer(value),
+            None => Value::Float(value.as_f64().unwrap_or(f64::NAN)),
+        },
+        Yaml::String(value) => Value::String(value),
+        Yaml::Sequence(values) => Value::List(
+            values
+                .into_iter()
+                .map(from_yaml)
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Mapping(mapping) => {
+            let mut bendy = HashMap::new();
+            for (key, value) in mapping {
+                match key {
+                    Yaml::String(key) => bendy.insert(key, from_yaml(value)?),
+                    _ => return Err(String::from("yaml.parse can only read string keys")),
+                };
+            }
+            Value::Bendy(bendy)
+        }
+    })
+}
+
+// keys are sorted so the same bendy is always written the same way
+fn to_yaml(value: &Value) -> Result<Yaml, String> {
+    Ok(match value {
+        Value::None => Yaml::Null,
+        Value::Boolean(value) => Yaml::Bool(*value),
+        Value::Integer(value) => Yaml::Number((*value).into()),
+        Value::Float(value) => Yaml::Number((*value).into()),
+        Value::String(value) => Yaml::String(value.clone()),
+        Value::List(values) => {
+            Yaml::Sequence(values.iter().map(to_yaml).collect::<Result<_, _>>()?)
+        }
+        Value::Bendy(bendy) => {
+            let mut keys: Vec<&String> = bendy.keys().collect();
+            keys.sort();
+            let mut mapping = serde_yaml::Mapping::new();
+            for key in keys {
+                mapping.insert(Yaml::String(key.clone()), to_yaml(&bendy[key])?);
+            }
+            Yaml::Mapping(mapping)
+        }
+        Value::Function => return Err(String::from("yaml.write can't write functions")),
+    })
+}
+
+fn from_toml(toml: Toml) -> Value {
+    match toml {
+        Toml::Boolean(value) => Value::Boolean(value),
+        Toml::Integer(value) => Value::Integer(value),
+        Toml::Float(value) => Value::Float(value),
+        Toml::String(value) => Value::String(value),
+        Toml::Datetime(value) => Value::String(value.to_string()),
+        Toml::Array(values) => Value::List(values.into_iter().map(from_toml).collect()),
+        Toml::Table(table) => Value::Bendy(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, from_toml(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn to_toml(value: &Value) -> Result<Toml, String> {
+    Ok(match value {
+        Value::Boolean(value) => Toml::Boolean(*value),
+        Value::Integer(value) => Toml::Integer(*value),
+        Value::Float(value) => Toml::Float(*value),
+        Value::String(value) => Toml::String(value.clone()),
+        Value::List(values) => Toml::Array(values.iter().map(to_toml).collect::<Result<_, _>>()?),
+        Value::Bendy(bendy) => Toml::Table(
+            bendy
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), to_toml(value)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        Value::None => {
+            return Err(String::from(
+                "toml.write can't write none, toml has no null",
+            ))
+        }
+        Value::Function => return Err(String::from("toml.write can't write functions")),
+    })
+}
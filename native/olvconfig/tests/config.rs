@@ -0,0 +1,74 @@
+use olive::testing::{error, eval, string};
+use olive::{Engine, Value};
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    olvconfig::register(&mut engine);
+    engine
+}
+
+#[test]
+fn yaml_is_read_and_written() {
+    let mut engine = engine();
+    eval(
+        &mut engine,
+        "config = yaml.parse(\"name: api\\nport: 8080\\nratio: 0.5\\ndebug: false\\nhosts:\\n  - a\\n  - b\\nextra: ~\\n\");",
+    );
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return new [config.name, config.port, config.ratio, config.debug, config.hosts, config.extra];"
+        ),
+        Value::List(vec![
+            string("api"),
+            Value::Integer(8080),
+            Value::Float(0.5),
+            Value::Boolean(false),
+            Value::List(vec![string("a"), string("b")]),
+            Value::None,
+        ])
+    );
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return yaml.parse(yaml.write(config)) == config;"
+        ),
+        Value::Boolean(true)
+    );
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return yaml.write(new { b: 1, a: new [true] });"
+        ),
+        string("---\na:\n  - true\nb: 1\n")
+    );
+}
+
+#[test]
+fn toml_is_read_and_written() {
+    let mut engine = engine();
+    eval(
+        &mut engine,
+        "config = toml.parse(\"title = \\\"site\\\"\\n[server]\\nport = 80\\nhosts = [\\\"a\\\", \\\"b\\\"]\\n\");",
+    );
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return new [config.title, config.server.port, config.server.hosts];"
+        ),
+        Value::List(vec![
+            string("site"),
+            Value::Integer(80),
+            Value::List(vec![string("a"), string("b")]),
+        ])
+    );
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return toml.parse(toml.write(config)) == config;"
+        ),
+        Value::Boolean(true)
+    );
+    assert!(error(&mut engine, "toml.write(new { a: none });").contains("toml has no null"));
+    assert!(error(&mut engine, "toml.parse(\"a = \");").contains("toml.parse failed"));
+}
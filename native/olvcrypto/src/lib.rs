@@ -0,0 +1,66 @@
+// hashing, hmac and secure random for scripts, added to an engine with
+// register. strings can't hold arbitrary bytes, so digests and random data
+// are hex encoded
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use olive::{Engine, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+// adds the functions to a global crypto bendy, e.g. crypto.sha256(data):
+//   sha256(data), sha1(data) and md5(data) hash the bytes of the string
+//   hmac_sha256(key, data) signs the data, e.g. to check webhook signatures
+//   constant_time_eq(a, b) compares two strings in a time that doesn't
+//     depend on where they differ, for comparing signatures and tokens
+//   random_bytes(count) returns count bytes from the operating system's
+//     secure random source
+pub fn register(engine: &mut Engine) {
+    engine.register_module_fn("crypto", "sha256", 1, |args| {
+        digest::<Sha256>(&args[0], "sha256")
+    });
+    engine.register_module_fn("crypto", "sha1", 1, |args| digest::<Sha1>(&args[0], "sha1"));
+    engine.register_module_fn("crypto", "md5", 1, |args| digest::<Md5>(&args[0], "md5"));
+    engine.register_module_fn("crypto", "hmac_sha256", 2, |args| {
+        let key = string(&args[0], "hmac_sha256", "the key as a string")?;
+        let data = string(&args[1], "hmac_sha256", "the data as a string")?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+            .map_err(|error| format!("hmac_sha256 failed: {}", error))?;
+        mac.update(data.as_bytes());
+        Ok(Value::String(hex(&mac.finalize().into_bytes())))
+    });
+    engine.register_module_fn("crypto", "constant_time_eq", 2, |args| {
+        let a = string(&args[0], "constant_time_eq", "two strings")?.as_bytes();
+        let b = string(&args[1], "constant_time_eq", "two strings")?.as_bytes();
+        let mut diff = (a.len() != b.len()) as u8;
+        for i in 0..a.len().max(b.len()) {
+            diff |= a.get(i).unwrap_or(&0) ^ b.get(i).unwrap_or(&0);
+        }
+        Ok(Value::Boolean(diff == 0))
+    });
+    engine.register_module_fn("crypto", "random_bytes", 1, |args| {
+        let count = match &args[0] {
+            Value::Integer(count) if *count >= 0 => *count as usize,
+            _ => return Err(String::from("random_bytes expects a count of at least 0")),
+        };
+        let mut bytes = vec![0; count];
+        getrandom::getrandom(&mut bytes)
+            .map_err(|error| format!("random_bytes failed: {}", error))?;
+        Ok(Value::String(hex(&bytes)))
+    });
+}
+
+fn string<'a>(value: &'a Value, function: &str, what: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(value) => Ok(value),
+        _ => Err(format!("{} expects {}", function, what)),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn digest<D: Digest>(value: &Value, function: &str) -> Result<Value, String> {
+    let data = string(value, function, "the data as a string")?;
+    Ok(Value::String(hex(&D::digest(data.as_bytes()))))
+}
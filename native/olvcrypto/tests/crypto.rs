@@ -0,0 +1,59 @@
+use olive::testing::{eval, string};
+use olive::{Engine, Value};
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    olvcrypto::register(&mut engine);
+    engine
+}
+
+#[test]
+fn digests_are_hex_encoded() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return new [crypto.sha256(\"abc\"), crypto.sha1(\"abc\"), crypto.md5(\"abc\")];"
+        ),
+        Value::List(vec![
+            string("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            string("a9993e364706816aba3e25717850c26c9cd0d89d"),
+            string("900150983cd24fb0d6963f7d28e17f72"),
+        ])
+    );
+    // the example from rfc 4231
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return crypto.hmac_sha256(\"Jefe\", \"what do ya want for nothing?\");"
+        ),
+        string("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+    );
+}
+
+#[test]
+fn comparisons_and_random_bytes() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return new [crypto.constant_time_eq(\"abc\", \"abc\"), crypto.constant_time_eq(\"abc\", \"abd\"), crypto.constant_time_eq(\"abc\", \"ab\")];"
+        ),
+        Value::List(vec![
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Boolean(false),
+        ])
+    );
+    match eval(&mut engine, "return crypto.random_bytes(16);") {
+        Value::String(bytes) => {
+            assert_eq!(bytes.len(), 32);
+            assert!(bytes.chars().all(|c| c.is_ascii_hexdigit()));
+        }
+        other => panic!("{}", other),
+    }
+    assert_ne!(
+        eval(&mut engine, "return crypto.random_bytes(16);"),
+        eval(&mut engine, "return crypto.random_bytes(16);")
+    );
+}
@@ -0,0 +1,107 @@
+// reading and writing csv for scripts, added to an engine with register
+use csv::{ReaderBuilder, WriterBuilder};
+use olive::{Engine, Value};
+
+// adds the functions to a global csv bendy:
+//   parse(data, header) returns the rows of the csv string as lists of
+//     fields, or with header as bendys by the names in the first row. rows
+//     may have different lengths and all fields are strings
+//   write(rows) returns the rows as a csv string. rows are lists of fields
+//     of any length, or bendys, which write a header of their sorted names
+//     first
+pub fn register(engine: &mut Engine) {
+    engine.register_module_fn("csv", "parse", 2, |args| parse(&args));
+    engine.register_module_fn("csv", "write", 1, |args| write(&args));
+}
+
+fn failed(function: &str) -> impl Fn(csv::Error) -> String + '_ {
+    move |error| format!("{} failed: {}", function, error)
+}
+
+fn field(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(value) => Ok(value.clone()),
+        Value::Integer(value) => Ok(value.to_string()),
+        Value::Float(value) => Ok(value.to_string()),
+        Value::Boolean(value) => Ok(value.to_string()),
+        Value::None => Ok(String::new()),
+        _ => Err(String::from(
+            "csv.write can only write none, numbers, booleans and strings",
+        )),
+    }
+}
+
+fn parse(args: &[Value]) -> Result<Value, String> {
+    let (data, header) = match (&args[0], &args[1]) {
+        (Value::String(data), Value::Boolean(header)) => (data, *header),
+        _ => {
+            return Err(String::from(
+                "csv.parse expects the data and whether it has a header",
+            ))
+        }
+    };
+    let mut reader = ReaderBuilder::new()
+        .has_headers(header)
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    let names: Vec<String> = if header {
+        reader
+            .headers()
+            .map_err(failed("csv.parse"))?
+            .iter()
+            .map(String::from)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(failed("csv.parse"))?;
+        let fields = record
+            .iter()
+            .map(|field| Value::String(String::from(field)));
+        rows.push(if header {
+            Value::Bendy(names.iter().cloned().zip(fields).collect())
+        } else {
+            Value::List(fields.collect())
+        });
+    }
+    Ok(Value::List(rows))
+}
+
+fn write(args: &[Value]) -> Result<Value, String> {
+    let rows = match &args[0] {
+        Value::List(rows) => rows,
+        _ => return Err(String::from("csv.write expects a list of rows")),
+    };
+    let mut writer = WriterBuilder::new().flexible(true).from_writer(Vec::new());
+    // the header the bendys are written by, from the first one
+    let mut names: Option<Vec<String>> = None;
+    for row in rows {
+        let fields = match row {
+            Value::List(fields) => fields.iter().map(field).collect::<Result<Vec<_>, _>>()?,
+            Value::Bendy(bendy) => {
+                let names = match &names {
+                    Some(names) => names,
+                    None => {
+                        let mut keys: Vec<String> = bendy.keys().cloned().collect();
+                        keys.sort();
+                        writer.write_record(&keys).map_err(failed("csv.write"))?;
+                        names.get_or_insert(keys)
+                    }
+                };
+                names
+                    .iter()
+                    .map(|name| bendy.get(name).map_or(Ok(String::new()), field))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+            _ => return Err(String::from("csv.write expects rows as lists or bendys")),
+        };
+        writer.write_record(&fields).map_err(failed("csv.write"))?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|error| format!("csv.write failed: {}", error))?;
+    // all fields came from strings, so this stays valid utf-8
+    Ok(Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+}
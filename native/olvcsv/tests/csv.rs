@@ -0,0 +1,51 @@
+use olive::testing::{eval, string};
+use olive::{Engine, Value};
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    olvcsv::register(&mut engine);
+    engine
+}
+
+#[test]
+fn rows_are_parsed_with_and_without_a_header() {
+    let mut engine = engine();
+    eval(
+        &mut engine,
+        "data = \"name,age\\nada,36\\n\\\"lin, jr\\\",7\\n\";",
+    );
+    assert_eq!(
+        eval(&mut engine, "return csv.parse(data, false);"),
+        Value::List(vec![
+            Value::List(vec![string("name"), string("age")]),
+            Value::List(vec![string("ada"), string("36")]),
+            Value::List(vec![string("lin, jr"), string("7")]),
+        ])
+    );
+    assert_eq!(
+        eval(
+            &mut engine,
+            "rows = csv.parse(data, true);\nreturn new [len(rows), rows[1].name, rows[0].age];"
+        ),
+        Value::List(vec![Value::Integer(2), string("lin, jr"), string("36")])
+    );
+}
+
+#[test]
+fn rows_are_written() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return csv.write(new [new [\"a, b\", 1, 2.5], new [true, none]]);"
+        ),
+        string("\"a, b\",1,2.5\ntrue,\n")
+    );
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return csv.write(new [new { name: \"ada\", age: 36 }, new { name: \"lin\" }]);"
+        ),
+        string("age,name\n36,ada\n,lin\n")
+    );
+}
@@ -1,24 +1,133 @@
-use olvnative::{Object, RuntimeError};
-use std::cell::RefCell;
-use std::rc::Rc;
+// math functions for scripts, added to an engine with register. integers
+// are taken wherever floats are
+use olive::{Engine, Value};
+use std::convert::TryFrom;
+use std::f64::consts;
 
-macro_rules! rc {
-    ($e: expr) => {
-        Rc::new(RefCell::new($e))
-    };
+mod random;
+mod rational;
+
+// adds the functions to a global math bendy, e.g. math.sqrt(x):
+//   sqrt(x)
+//   sin(x), cos(x) and tan(x) of an angle in radians
+//   asin(x), acos(x) and atan(x) return the angle in radians
+//   atan2(y, x) returns the angle of the point (x, y)
+//   ln(x), log10(x) and log2(x) of a positive number
+//   exp(x) returns e to the power of x
+//   pow(base, exp) stays an integer for integers with an exponent of at
+//     least 0, unless it overflows
+//   pi(), e() and tau() return the constants
+//   is_nan(x) and is_inf(x)
+//   clamp(x, lo, hi) stays an integer if all three are
+//   round_to(x, digits) rounds to digits after the point, negative ones round
+//     to tens, hundreds and so on
+//   rng(seed) returns a random number generator, the same seed gives the
+//     same samples. free_rng(rng) drops it
+//   uniform(rng, lo, hi) samples from lo up to but not including hi
+//   normal(rng, mean, std_dev)
+//   exponential(rng, rate)
+//   rational(numer, denom) or rational(text, none) returns an exact fraction,
+//     see rational.rs. rational_add(a, b), rational_sub(a, b),
+//     rational_mul(a, b) and rational_div(a, b) calculate with them,
+//     rational_cmp(a, b) returns -1, 0 or 1, rational_str(a) returns e.g.
+//     "7/8", rational_decimal(a, digits) returns a decimal rounded to that
+//     many places and rational_float(a) the closest float
+// results that aren't real numbers, like the logarithm of 0 or
+// pow(-1, 0.5), are errors instead of nan
+pub fn register(engine: &mut Engine) {
+    unary(engine, "sqrt", f64::sqrt);
+    unary(engine, "sin", f64::sin);
+    unary(engine, "cos", f64::cos);
+    unary(engine, "tan", f64::tan);
+    unary(engine, "asin", f64::asin);
+    unary(engine, "acos", f64::acos);
+    unary(engine, "atan", f64::atan);
+    engine.register_module_fn("math", "atan2", 2, |args| {
+        let y = float(&args[0], "atan2")?;
+        let x = float(&args[1], "atan2")?;
+        Ok(Value::Float(y.atan2(x)))
+    });
+    logarithm(engine, "ln", f64::ln);
+    logarithm(engine, "log10", f64::log10);
+    logarithm(engine, "log2", f64::log2);
+    unary(engine, "exp", f64::exp);
+    engine.register_module_fn("math", "pow", 2, |args| {
+        if let (Value::Integer(base), Value::Integer(exp)) = (&args[0], &args[1]) {
+            if let Some(value) = u32::try_from(*exp)
+                .ok()
+                .and_then(|exp| base.checked_pow(exp))
+            {
+                return Ok(Value::Integer(value));
+            }
+        }
+        let base = float(&args[0], "pow")?;
+        let exp = float(&args[1], "pow")?;
+        let value = base.powf(exp);
+        if value.is_nan() && !base.is_nan() && !exp.is_nan() {
+            return Err(format!(
+                "{} to the power of {} is not a real number",
+                base, exp
+            ));
+        }
+        Ok(Value::Float(value))
+    });
+    engine.register_module_fn("math", "pi", 0, |_| Ok(Value::Float(consts::PI)));
+    engine.register_module_fn("math", "e", 0, |_| Ok(Value::Float(consts::E)));
+    engine.register_module_fn("math", "tau", 0, |_| Ok(Value::Float(2.0 * consts::PI)));
+    engine.register_module_fn("math", "is_nan", 1, |args| {
+        Ok(Value::Boolean(float(&args[0], "is_nan")?.is_nan()))
+    });
+    engine.register_module_fn("math", "is_inf", 1, |args| {
+        Ok(Value::Boolean(float(&args[0], "is_inf")?.is_infinite()))
+    });
+    engine.register_module_fn("math", "clamp", 3, |args| {
+        if let (Value::Integer(x), Value::Integer(lo), Value::Integer(hi)) =
+            (&args[0], &args[1], &args[2])
+        {
+            if lo > hi {
+                return Err(format!("clamp got {} and {} in the wrong order", lo, hi));
+            }
+            return Ok(Value::Integer(*x.clamp(lo, hi)));
+        }
+        let x = float(&args[0], "clamp")?;
+        let lo = float(&args[1], "clamp")?;
+        let hi = float(&args[2], "clamp")?;
+        // also keeps nan bounds away from f64::clamp, which panics on them
+        if lo > hi || lo.is_nan() || hi.is_nan() {
+            return Err(format!("clamp got {} and {} in the wrong order", lo, hi));
+        }
+        Ok(Value::Float(x.clamp(lo, hi)))
+    });
+    engine.register_module_fn("math", "round_to", 2, |args| {
+        let x = float(&args[0], "round_to")?;
+        let digits = match &args[1] {
+            Value::Integer(digits) => *digits,
+            _ => return Err(String::from("round_to expects the digits as an integer")),
+        };
+        let factor = 10f64.powi(digits.clamp(-308, 308) as i32);
+        Ok(Value::Float((x * factor).round() / factor))
+    });
+    random::register(engine);
+    rational::register(engine);
+}
+
+fn unary(engine: &mut Engine, name: &'static str, function: fn(f64) -> f64) {
+    engine.register_module_fn("math", name, 1, move |args| {
+        Ok(Value::Float(function(float(&args[0], name)?)))
+    });
+}
+
+fn logarithm(engine: &mut Engine, name: &'static str, function: fn(f64) -> f64) {
+    engine.register_module_fn("math", name, 1, move |args| match float(&args[0], name)? {
+        x if x > 0.0 => Ok(Value::Float(function(x))),
+        x => Err(format!("{} expects a positive number, got {}", name, x)),
+    });
 }
 
-#[no_mangle]
-pub extern "C" fn n_sqrt(
-    args: Box<Vec<Rc<RefCell<Object>>>>,
-) -> Result<Rc<RefCell<Object>>, RuntimeError> {
-    if let Object::Float(f) = &*args[0].borrow() {
-        let val = Object::Float(f.sqrt());
-        Ok(rc!(val))
-    } else if let Object::Int(i) = &*args[0].borrow() {
-        let val = Object::Float((*i as f64).sqrt());
-        Ok(rc!(val))
-    } else {
-        Err(RuntimeError::TypeError)
+fn float(value: &Value, function: &str) -> Result<f64, String> {
+    match value {
+        Value::Float(value) => Ok(*value),
+        Value::Integer(value) => Ok(*value as f64),
+        _ => Err(format!("{} expects a number", function)),
     }
 }
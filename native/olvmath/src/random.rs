@@ -0,0 +1,100 @@
+// seeded random numbers, apart from the engine's own so a simulation can be
+// run again with the same samples. generators reach scripts as integer
+// handles, which only mean something to the engine they came from
+use crate::float;
+use olive::{Engine, Value};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Exp, Normal, Uniform};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct Random {
+    next_handle: i64,
+    rngs: HashMap<i64, StdRng>,
+}
+
+pub fn register(engine: &mut Engine) {
+    let random = Rc::new(RefCell::new(Random::default()));
+    function(engine, &random, "rng", 1, Random::rng);
+    function(engine, &random, "free_rng", 1, Random::free_rng);
+    function(engine, &random, "uniform", 3, |random, args| {
+        let lo = float(&args[1], "uniform")?;
+        let hi = float(&args[2], "uniform")?;
+        if lo >= hi || lo.is_nan() || hi.is_nan() {
+            return Err(format!("uniform got {} and {} in the wrong order", lo, hi));
+        }
+        random.sample(&args[0], "uniform", Uniform::new(lo, hi))
+    });
+    function(engine, &random, "normal", 3, |random, args| {
+        let mean = float(&args[1], "normal")?;
+        let std_dev = float(&args[2], "normal")?;
+        // rand_distr takes negative ones and mirrors the samples
+        let normal = Normal::new(mean, std_dev)
+            .ok()
+            .filter(|_| std_dev >= 0.0)
+            .ok_or_else(|| format!("normal got an invalid standard deviation {}", std_dev))?;
+        random.sample(&args[0], "normal", normal)
+    });
+    function(engine, &random, "exponential", 2, |random, args| {
+        let rate = float(&args[1], "exponential")?;
+        let exp =
+            Exp::new(rate).map_err(|_| format!("exponential got an invalid rate {}", rate))?;
+        random.sample(&args[0], "exponential", exp)
+    });
+}
+
+fn function<F>(
+    engine: &mut Engine,
+    random: &Rc<RefCell<Random>>,
+    name: &str,
+    arg_count: u32,
+    method: F,
+) where
+    F: Fn(&mut Random, &[Value]) -> Result<Value, String> + 'static,
+{
+    let random = random.clone();
+    engine.register_module_fn("math", name, arg_count, move |args| {
+        method(&mut random.borrow_mut(), &args)
+    });
+}
+
+fn handle(value: &Value, function: &str) -> Result<i64, String> {
+    match value {
+        Value::Integer(handle) => Ok(*handle),
+        _ => Err(format!("{} expects a random number generator", function)),
+    }
+}
+
+impl Random {
+    fn rng(&mut self, args: &[Value]) -> Result<Value, String> {
+        let seed = match &args[0] {
+            Value::Integer(seed) => *seed,
+            _ => return Err(String::from("rng expects the seed as an integer")),
+        };
+        self.next_handle += 1;
+        self.rngs
+            .insert(self.next_handle, StdRng::seed_from_u64(seed as u64));
+        Ok(Value::Integer(self.next_handle))
+    }
+
+    fn free_rng(&mut self, args: &[Value]) -> Result<Value, String> {
+        self.rngs
+            .remove(&handle(&args[0], "free_rng")?)
+            .map(|_| Value::None)
+            .ok_or_else(|| String::from("free_rng got an unknown random number generator"))
+    }
+
+    fn sample<D>(&mut self, value: &Value, function: &str, distribution: D) -> Result<Value, String>
+    where
+        D: Distribution<f64>,
+    {
+        let rng = self
+            .rngs
+            .get_mut(&handle(value, function)?)
+            .ok_or_else(|| format!("{} got an unknown random number generator", function))?;
+        Ok(Value::Float(distribution.sample(rng)))
+    }
+}
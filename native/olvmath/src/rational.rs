@@ -0,0 +1,152 @@
+// exact fractions, which never pick up float rounding errors. a rational is a
+// bendy of its numer and denom as strings of digits, always reduced and with
+// the sign on numer, so equal rationals are equal values. operands may also
+// be integers or strings like "3", "-1.25" and "7/8"
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+use olive::{Engine, Value};
+use std::collections::HashMap;
+
+pub fn register(engine: &mut Engine) {
+    engine.register_module_fn("math", "rational", 2, |args| {
+        let rational = match (&args[0], &args[1]) {
+            (Value::Integer(_), Value::Integer(0)) => {
+                return Err(String::from("rational got a denominator of 0"))
+            }
+            (Value::Integer(numer), Value::Integer(denom)) => {
+                BigRational::new(BigInt::from(*numer), BigInt::from(*denom))
+            }
+            (Value::String(text), Value::None) => {
+                parse(text).ok_or_else(|| format!("rational got an invalid number '{}'", text))?
+            }
+            _ => {
+                return Err(String::from(
+                    "rational expects two integers, or a string and none",
+                ))
+            }
+        };
+        Ok(to_value(rational))
+    });
+    operation(engine, "rational_add", |a, b| Ok(a + b));
+    operation(engine, "rational_sub", |a, b| Ok(a - b));
+    operation(engine, "rational_mul", |a, b| Ok(a * b));
+    operation(engine, "rational_div", |a, b| {
+        if b.is_zero() {
+            return Err(String::from("rational_div can't divide by 0"));
+        }
+        Ok(a / b)
+    });
+    engine.register_module_fn("math", "rational_cmp", 2, |args| {
+        let a = to_rational(&args[0], "rational_cmp")?;
+        let b = to_rational(&args[1], "rational_cmp")?;
+        Ok(Value::Integer(a.cmp(&b) as i64))
+    });
+    engine.register_module_fn("math", "rational_str", 1, |args| {
+        Ok(Value::String(
+            to_rational(&args[0], "rational_str")?.to_string(),
+        ))
+    });
+    engine.register_module_fn("math", "rational_decimal", 2, |args| {
+        let rational = to_rational(&args[0], "rational_decimal")?;
+        let digits = match &args[1] {
+            Value::Integer(digits) if *digits >= 0 => *digits as usize,
+            _ => {
+                return Err(String::from(
+                    "rational_decimal expects the digits as an integer from 0",
+                ))
+            }
+        };
+        Ok(Value::String(decimal(rational, digits)))
+    });
+    engine.register_module_fn("math", "rational_float", 1, |args| {
+        let rational = to_rational(&args[0], "rational_float")?;
+        let numer = rational.numer().to_f64().unwrap_or(f64::NAN);
+        let denom = rational.denom().to_f64().unwrap_or(f64::NAN);
+        Ok(Value::Float(numer / denom))
+    });
+}
+
+fn operation<F>(engine: &mut Engine, name: &'static str, operation: F)
+where
+    F: Fn(BigRational, BigRational) -> Result<BigRational, String> + 'static,
+{
+    engine.register_module_fn("math", name, 2, move |args| {
+        let a = to_rational(&args[0], name)?;
+        let b = to_rational(&args[1], name)?;
+        Ok(to_value(operation(a, b)?))
+    });
+}
+
+fn to_value(rational: BigRational) -> Value {
+    let mut bendy = HashMap::new();
+    bendy.insert(
+        String::from("numer"),
+        Value::String(rational.numer().to_string()),
+    );
+    bendy.insert(
+        String::from("denom"),
+        Value::String(rational.denom().to_string()),
+    );
+    Value::Bendy(bendy)
+}
+
+fn to_rational(value: &Value, function: &str) -> Result<BigRational, String> {
+    let invalid = || format!("{} expects rationals, integers or number strings", function);
+    match value {
+        Value::Integer(value) => Ok(BigRational::from_integer(BigInt::from(*value))),
+        Value::String(text) => parse(text).ok_or_else(invalid),
+        Value::Bendy(bendy) => match (bendy.get("numer"), bendy.get("denom")) {
+            (Some(Value::String(numer)), Some(Value::String(denom))) => {
+                let numer: BigInt = numer.parse().map_err(|_| invalid())?;
+                let denom: BigInt = denom.parse().map_err(|_| invalid())?;
+                if denom.is_zero() {
+                    return Err(invalid());
+                }
+                Ok(BigRational::new(numer, denom))
+            }
+            _ => Err(invalid()),
+        },
+        _ => Err(invalid()),
+    }
+}
+
+// accepts "3", "-1.25" and "7/8"
+fn parse(text: &str) -> Option<BigRational> {
+    let text = text.trim();
+    if let Some((numer, denom)) = text.split_once('/') {
+        let numer: BigInt = numer.trim().parse().ok()?;
+        let denom: BigInt = denom.trim().parse().ok()?;
+        if denom.is_zero() {
+            return None;
+        }
+        return Some(BigRational::new(numer, denom));
+    }
+    match text.split_once('.') {
+        Some((whole, fraction)) => {
+            if !fraction.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            let digits: BigInt = format!("{}{}", whole, fraction).parse().ok()?;
+            let denom = num_traits::pow(BigInt::from(10), fraction.len());
+            Some(BigRational::new(digits, denom))
+        }
+        None => Some(BigRational::from_integer(text.parse().ok()?)),
+    }
+}
+
+// exactly digits places, rounding halves away from zero
+fn decimal(rational: BigRational, digits: usize) -> String {
+    let scale = BigRational::from_integer(num_traits::pow(BigInt::from(10), digits));
+    let scaled = (rational * scale).round().to_integer();
+    let sign = if scaled.is_negative() { "-" } else { "" };
+    let mut text = scaled.abs().to_string();
+    if digits == 0 {
+        return format!("{}{}", sign, text);
+    }
+    if text.len() <= digits {
+        text = format!("{}{}", "0".repeat(digits + 1 - text.len()), text);
+    }
+    let point = text.len() - digits;
+    format!("{}{}.{}", sign, &text[..point], &text[point..])
+}
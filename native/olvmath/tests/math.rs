@@ -0,0 +1,207 @@
+use olive::testing::{error, eval};
+use olive::{Engine, Value};
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    olvmath::register(&mut engine);
+    engine
+}
+
+fn floats(value: Value) -> Vec<f64> {
+    match value {
+        Value::List(values) => values
+            .into_iter()
+            .map(|value| match value {
+                Value::Float(value) => value,
+                other => panic!("not a float: {}", other),
+            })
+            .collect(),
+        other => panic!("not a list: {}", other),
+    }
+}
+
+fn assert_close(actual: Vec<f64>, expected: &[f64]) {
+    assert_eq!(actual.len(), expected.len());
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn integers_and_floats_are_taken() {
+    let mut engine = engine();
+    assert_close(
+        floats(eval(
+            &mut engine,
+            "return new [math.sqrt(16), math.sqrt(2.25)];",
+        )),
+        &[4.0, 1.5],
+    );
+    assert!(error(&mut engine, "math.sqrt(\"4\");").contains("sqrt expects a number"));
+}
+
+#[test]
+fn trigonometric_functions_use_radians() {
+    let mut engine = engine();
+    let half_pi = std::f64::consts::FRAC_PI_2;
+    assert_close(
+        floats(eval(
+            &mut engine,
+            &format!(
+                "return new [math.sin({0}), math.cos(0), math.tan(0.5), math.asin(1), math.acos(1), math.atan(1), math.atan2(1, -1)];",
+                half_pi
+            ),
+        )),
+        &[
+            1.0,
+            1.0,
+            0.5f64.tan(),
+            half_pi,
+            0.0,
+            std::f64::consts::FRAC_PI_4,
+            3.0 * std::f64::consts::FRAC_PI_4,
+        ],
+    );
+    assert!(error(&mut engine, "math.atan2(1, none);").contains("atan2 expects a number"));
+}
+
+#[test]
+fn logarithms_and_powers_reject_other_than_real_results() {
+    let mut engine = engine();
+    assert_close(
+        floats(eval(
+            &mut engine,
+            "return new [math.ln(1), math.log10(1000), math.log2(0.5), math.exp(0), math.pow(2, -1), math.pow(2.0, 3)];",
+        )),
+        &[0.0, 3.0, -1.0, 1.0, 0.5, 8.0],
+    );
+    assert_eq!(
+        eval(&mut engine, "return math.pow(3, 4);"),
+        Value::Integer(81)
+    );
+    // too large for an integer
+    assert_eq!(
+        eval(&mut engine, "return math.pow(2, 64);"),
+        Value::Float(2f64.powi(64))
+    );
+    assert!(error(&mut engine, "math.ln(0);").contains("ln expects a positive number, got 0"));
+    assert!(error(&mut engine, "math.log10(-2.5);")
+        .contains("log10 expects a positive number, got -2.5"));
+    assert!(error(&mut engine, "math.pow(-1, 0.5);")
+        .contains("-1 to the power of 0.5 is not a real number"));
+}
+
+#[test]
+fn constants_and_float_helpers() {
+    let mut engine = engine();
+    assert_close(
+        floats(eval(
+            &mut engine,
+            "return new [math.pi(), math.e(), math.tau(), math.clamp(1.5, 0, 1), math.round_to(2.345, 2), math.round_to(1234, -2)];",
+        )),
+        &[
+            std::f64::consts::PI,
+            std::f64::consts::E,
+            2.0 * std::f64::consts::PI,
+            1.0,
+            2.35,
+            1200.0,
+        ],
+    );
+    assert_eq!(
+        eval(
+            &mut engine,
+            "nan = math.pow(2.0, 2000) - math.pow(2.0, 2000);\n\
+             return new [math.clamp(-5, 0, 10), math.is_nan(nan), math.is_nan(1), math.is_inf(math.pow(2.0, 2000)), math.is_inf(nan)];",
+        ),
+        Value::List(vec![
+            Value::Integer(0),
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Boolean(true),
+            Value::Boolean(false),
+        ])
+    );
+    assert!(error(&mut engine, "math.clamp(1, 10, 0);")
+        .contains("clamp got 10 and 0 in the wrong order"));
+    assert!(error(&mut engine, "math.round_to(1.5, 0.5);")
+        .contains("round_to expects the digits as an integer"));
+}
+
+#[test]
+fn seeded_samples_repeat() {
+    let mut engine = engine();
+    let script = "a = math.rng(42);\n\
+                  samples = new [math.uniform(a, 1, 2), math.normal(a, 0, 1), math.exponential(a, 2)];\n\
+                  math.free_rng(a);\n\
+                  return samples;";
+    let first = floats(eval(&mut engine, script));
+    assert_eq!(floats(eval(&mut engine, script)), first);
+    assert!((1.0..2.0).contains(&first[0]), "{:?}", first);
+    assert!(first[2] >= 0.0, "{:?}", first);
+    let other = floats(eval(
+        &mut engine,
+        "b = math.rng(7);\nreturn new [math.uniform(b, 1, 2)];",
+    ));
+    assert_ne!(other[0], first[0]);
+    assert!(error(&mut engine, "math.uniform(a, 0, 1);")
+        .contains("uniform got an unknown random number generator"));
+    assert!(error(&mut engine, "math.uniform(b, 2, 1);")
+        .contains("uniform got 2 and 1 in the wrong order"));
+    assert!(error(&mut engine, "math.normal(b, 0, -1);")
+        .contains("normal got an invalid standard deviation -1"));
+    assert!(error(&mut engine, "math.exponential(b, -1);")
+        .contains("exponential got an invalid rate -1"));
+}
+
+#[test]
+fn rationals_are_exact() {
+    let mut engine = engine();
+    let string = |text: &str| Value::String(String::from(text));
+    assert_eq!(
+        eval(
+            &mut engine,
+            "total = math.rational(0, 1);\n\
+             i = 0;\n\
+             while (i < 10) { total = math.rational_add(total, \"0.1\"); i = i + 1; }\n\
+             third = math.rational_div(1, 3);\n\
+             return new [\n\
+                 total == math.rational(1, 1),\n\
+                 math.rational_str(math.rational_mul(third, \"6/4\")),\n\
+                 math.rational_str(math.rational_sub(math.rational(\"-1.25\", none), 1)),\n\
+                 math.rational_decimal(third, 4),\n\
+                 math.rational_decimal(math.rational(-5, 2), 0),\n\
+                 math.rational_cmp(third, \"0.33\"),\n\
+                 math.rational_float(math.rational(6, -8)),\n\
+                 math.rational(6, -8)\n\
+             ];",
+        ),
+        Value::List(vec![
+            Value::Boolean(true),
+            string("1/2"),
+            string("-9/4"),
+            string("0.3333"),
+            string("-3"),
+            Value::Integer(1),
+            Value::Float(-0.75),
+            Value::Bendy(
+                vec![
+                    (String::from("numer"), string("-3")),
+                    (String::from("denom"), string("4")),
+                ]
+                .into_iter()
+                .collect()
+            ),
+        ])
+    );
+    assert!(error(&mut engine, "math.rational(1, 0);").contains("rational got a denominator of 0"));
+    assert!(error(&mut engine, "math.rational_div(1, \"0.0\");")
+        .contains("rational_div can't divide by 0"));
+    assert!(error(&mut engine, "math.rational_add(1, 0.5);")
+        .contains("rational_add expects rationals, integers or number strings"));
+}
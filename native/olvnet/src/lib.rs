@@ -0,0 +1,205 @@
+// tcp and udp client sockets for scripts, added to an engine with register.
+// sockets reach scripts as integer handles, which only mean something to the
+// engine they came from
+use olive::{Engine, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::rc::Rc;
+use std::time::Duration;
+
+enum Socket {
+    Tcp(TcpStream),
+    // with the address send goes to
+    Udp(UdpSocket, Option<SocketAddr>),
+}
+
+#[derive(Default)]
+struct Net {
+    next_handle: i64,
+    sockets: HashMap<i64, Socket>,
+}
+
+// adds the functions to a global net bendy, e.g. net.tcp_connect(address):
+//   tcp_connect(address) connects to e.g. "example.com:80"
+//   udp_socket(address) binds a udp socket, e.g. to "0.0.0.0:0"
+//   send(socket, data) sends the string, over udp to the address given to
+//     send_to(socket, data, address) or received from last
+//   recv(socket, max) returns up to max bytes as a string, "" once a tcp
+//     connection was closed
+//   recv_from(socket, max) returns a bendy with the data and the addr it came
+//     from, udp only
+//   set_timeout(socket, millis) makes sends and receives that take longer
+//     fail, 0 waits forever again
+//   close(socket)
+pub fn register(engine: &mut Engine) {
+    let net = Rc::new(RefCell::new(Net::default()));
+    function(engine, &net, "tcp_connect", 1, Net::tcp_connect);
+    function(engine, &net, "udp_socket", 1, Net::udp_socket);
+    function(engine, &net, "send", 2, Net::send);
+    function(engine, &net, "send_to", 3, Net::send_to);
+    function(engine, &net, "recv", 2, Net::recv);
+    function(engine, &net, "recv_from", 2, Net::recv_from);
+    function(engine, &net, "set_timeout", 2, Net::set_timeout);
+    function(engine, &net, "close", 1, Net::close);
+}
+
+fn function<F>(engine: &mut Engine, net: &Rc<RefCell<Net>>, name: &str, arg_count: u32, method: F)
+where
+    F: Fn(&mut Net, &[Value]) -> Result<Value, String> + 'static,
+{
+    let net = net.clone();
+    engine.register_module_fn("net", name, arg_count, move |args| {
+        method(&mut net.borrow_mut(), &args)
+    });
+}
+
+fn string<'a>(value: &'a Value, function: &str, what: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(value) => Ok(value),
+        _ => Err(format!("{} expects {}", function, what)),
+    }
+}
+
+fn size(value: &Value, function: &str, what: &str) -> Result<usize, String> {
+    match value {
+        Value::Integer(size) if *size >= 0 => Ok(*size as usize),
+        _ => Err(format!("{} expects {}", function, what)),
+    }
+}
+
+fn failed(function: &str) -> impl Fn(io::Error) -> String + '_ {
+    move |error| format!("{} failed: {}", function, error)
+}
+
+fn received(mut buffer: Vec<u8>, len: usize) -> Value {
+    buffer.truncate(len);
+    Value::String(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+impl Net {
+    fn add(&mut self, socket: Socket) -> Value {
+        self.next_handle += 1;
+        self.sockets.insert(self.next_handle, socket);
+        Value::Integer(self.next_handle)
+    }
+
+    fn socket(&mut self, value: &Value, function: &str) -> Result<&mut Socket, String> {
+        let socket = match value {
+            Value::Integer(socket) => socket,
+            _ => return Err(format!("{} expects a socket", function)),
+        };
+        self.sockets
+            .get_mut(socket)
+            .ok_or_else(|| format!("{} got an unknown or closed socket", function))
+    }
+
+    fn udp(
+        &mut self,
+        value: &Value,
+        function: &str,
+    ) -> Result<(&UdpSocket, &mut Option<SocketAddr>), String> {
+        match self.socket(value, function)? {
+            Socket::Udp(socket, peer) => Ok((socket, peer)),
+            Socket::Tcp(_) => Err(format!("{} expects a udp socket", function)),
+        }
+    }
+
+    fn tcp_connect(&mut self, args: &[Value]) -> Result<Value, String> {
+        let address = string(&args[0], "tcp_connect", "an address")?;
+        let stream = TcpStream::connect(address)
+            .map_err(|error| format!("couldn't connect to '{}': {}", address, error))?;
+        Ok(self.add(Socket::Tcp(stream)))
+    }
+
+    fn udp_socket(&mut self, args: &[Value]) -> Result<Value, String> {
+        let address = string(&args[0], "udp_socket", "an address")?;
+        let socket = UdpSocket::bind(address)
+            .map_err(|error| format!("couldn't bind to '{}': {}", address, error))?;
+        Ok(self.add(Socket::Udp(socket, None)))
+    }
+
+    fn send(&mut self, args: &[Value]) -> Result<Value, String> {
+        let data = string(&args[1], "send", "the data as a string")?;
+        match self.socket(&args[0], "send")? {
+            Socket::Tcp(stream) => stream.write_all(data.as_bytes()),
+            Socket::Udp(socket, Some(peer)) => socket.send_to(data.as_bytes(), *peer).map(|_| ()),
+            Socket::Udp(_, None) => {
+                return Err(String::from(
+                    "send on a udp socket needs an address from send_to or recv_from first",
+                ))
+            }
+        }
+        .map_err(failed("send"))?;
+        Ok(Value::None)
+    }
+
+    fn send_to(&mut self, args: &[Value]) -> Result<Value, String> {
+        let data = string(&args[1], "send_to", "the data as a string")?;
+        let address = string(&args[2], "send_to", "an address")?;
+        let address = address
+            .to_socket_addrs()
+            .map_err(failed("send_to"))?
+            .next()
+            .ok_or_else(|| format!("send_to couldn't resolve '{}'", address))?;
+        let (socket, peer) = self.udp(&args[0], "send_to")?;
+        socket
+            .send_to(data.as_bytes(), address)
+            .map_err(failed("send_to"))?;
+        *peer = Some(address);
+        Ok(Value::None)
+    }
+
+    fn recv(&mut self, args: &[Value]) -> Result<Value, String> {
+        let mut buffer = vec![0; size(&args[1], "recv", "the most bytes to receive")?];
+        let len = match self.socket(&args[0], "recv")? {
+            Socket::Tcp(stream) => stream.read(&mut buffer),
+            Socket::Udp(socket, peer) => socket.recv_from(&mut buffer).map(|(len, from)| {
+                *peer = Some(from);
+                len
+            }),
+        }
+        .map_err(failed("recv"))?;
+        Ok(received(buffer, len))
+    }
+
+    fn recv_from(&mut self, args: &[Value]) -> Result<Value, String> {
+        let mut buffer = vec![0; size(&args[1], "recv_from", "the most bytes to receive")?];
+        let (socket, peer) = self.udp(&args[0], "recv_from")?;
+        let (len, address) = socket.recv_from(&mut buffer).map_err(failed("recv_from"))?;
+        *peer = Some(address);
+        let mut bendy = HashMap::new();
+        bendy.insert(String::from("data"), received(buffer, len));
+        bendy.insert(String::from("addr"), Value::String(address.to_string()));
+        Ok(Value::Bendy(bendy))
+    }
+
+    fn set_timeout(&mut self, args: &[Value]) -> Result<Value, String> {
+        let timeout = match size(&args[1], "set_timeout", "the timeout in milliseconds")? {
+            0 => None,
+            millis => Some(Duration::from_millis(millis as u64)),
+        };
+        match self.socket(&args[0], "set_timeout")? {
+            Socket::Tcp(stream) => stream
+                .set_read_timeout(timeout)
+                .and_then(|_| stream.set_write_timeout(timeout)),
+            Socket::Udp(socket, _) => socket
+                .set_read_timeout(timeout)
+                .and_then(|_| socket.set_write_timeout(timeout)),
+        }
+        .map_err(failed("set_timeout"))?;
+        Ok(Value::None)
+    }
+
+    fn close(&mut self, args: &[Value]) -> Result<Value, String> {
+        let socket = match &args[0] {
+            Value::Integer(socket) => socket,
+            _ => return Err(String::from("close expects a socket")),
+        };
+        self.sockets
+            .remove(socket)
+            .map(|_| Value::None)
+            .ok_or_else(|| String::from("close got an unknown or closed socket"))
+    }
+}
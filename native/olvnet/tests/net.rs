@@ -0,0 +1,87 @@
+use olive::testing::{error, eval};
+use olive::{Engine, Value};
+use std::io::{Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::thread;
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    olvnet::register(&mut engine);
+    engine
+}
+
+#[test]
+fn tcp_sockets_talk_to_servers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = [0; 4];
+        stream.read_exact(&mut buffer).unwrap();
+        stream.write_all(b"pong").unwrap();
+        buffer
+    });
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            &format!(
+                "s = net.tcp_connect(\"{}\");\nnet.send(s, \"ping\");\nanswer = net.recv(s, 16);\nreturn answer $ net.recv(s, 16);",
+                address
+            )
+        ),
+        Value::String(String::from("pong"))
+    );
+    assert_eq!(&server.join().unwrap(), b"ping");
+    eval(&mut engine, "net.close(s);");
+    assert!(error(&mut engine, "net.send(s, \"x\");").contains("unknown or closed socket"));
+}
+
+#[test]
+fn udp_sockets_answer_the_last_sender() {
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut engine = engine();
+    let address = match eval(
+        &mut engine,
+        &format!(
+            "u = net.udp_socket(\"127.0.0.1:0\");\nnet.send_to(u, \"hi\", \"{}\");\nreturn none;",
+            peer.local_addr().unwrap()
+        ),
+    ) {
+        Value::None => {
+            let mut buffer = [0; 8];
+            let (len, from) = peer.recv_from(&mut buffer).unwrap();
+            assert_eq!(&buffer[..len], b"hi");
+            from
+        }
+        other => panic!("{}", other),
+    };
+    peer.send_to(b"back", address).unwrap();
+    let received = eval(
+        &mut engine,
+        "got = net.recv_from(u, 8);\nnet.send(u, \"again\");\nreturn got;",
+    );
+    let mut buffer = [0; 8];
+    let (len, _) = peer.recv_from(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"again");
+    match received {
+        Value::Bendy(received) => {
+            assert_eq!(received["data"], Value::String(String::from("back")));
+            assert_eq!(
+                received["addr"],
+                Value::String(peer.local_addr().unwrap().to_string())
+            );
+        }
+        other => panic!("{}", other),
+    }
+}
+
+#[test]
+fn receives_fail_after_the_timeout() {
+    let mut engine = engine();
+    let message = error(
+        &mut engine,
+        "u = net.udp_socket(\"127.0.0.1:0\");\nnet.set_timeout(u, 20);\nnet.recv(u, 8);",
+    );
+    assert!(message.contains("recv failed"), "{}", message);
+}
@@ -0,0 +1,220 @@
+// sqlite databases for scripts, added to an engine with register.
+// connections and prepared statements reach scripts as integer handles,
+// which only mean something to the engine they came from
+use olive::{Engine, Value};
+use rusqlite::types::Value as Column;
+use rusqlite::{params_from_iter, CachedStatement, Connection, Statement};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct Sqlite {
+    next_handle: i64,
+    connections: HashMap<i64, Connection>,
+    // a statement is kept as its sql, sqlite keeps the compiled one in the
+    // cache of the connection
+    statements: HashMap<i64, (i64, String)>,
+}
+
+// adds the functions to a global sqlite bendy, e.g. sqlite.open(path):
+//   open(path) opens or creates the database file, ":memory:" for one that
+//     only lives in memory
+//   exec(db, sql, params) runs a statement, returns how many rows it changed
+//   query(db, sql, params) returns the rows as a list of bendys by column
+//   prepare(db, sql) compiles a statement once for many runs with
+//     exec_prepared(statement, params) and query_prepared(statement, params)
+//   begin(db), commit(db) and rollback(db) for transactions
+// params is a list of values for the ? in the sql, or none. booleans are
+// stored as 0 and 1, and sql null is none
+pub fn register(engine: &mut Engine) {
+    let sqlite = Rc::new(RefCell::new(Sqlite::default()));
+    function(engine, &sqlite, "open", 1, Sqlite::open);
+    function(engine, &sqlite, "exec", 3, Sqlite::exec);
+    function(engine, &sqlite, "query", 3, Sqlite::query);
+    function(engine, &sqlite, "prepare", 2, Sqlite::prepare);
+    function(engine, &sqlite, "exec_prepared", 2, Sqlite::exec_prepared);
+    function(engine, &sqlite, "query_prepared", 2, Sqlite::query_prepared);
+    function(engine, &sqlite, "begin", 1, |sqlite, args| {
+        sqlite.batch(args, "begin", "BEGIN")
+    });
+    function(engine, &sqlite, "commit", 1, |sqlite, args| {
+        sqlite.batch(args, "commit", "COMMIT")
+    });
+    function(engine, &sqlite, "rollback", 1, |sqlite, args| {
+        sqlite.batch(args, "rollback", "ROLLBACK")
+    });
+}
+
+fn function<F>(
+    engine: &mut Engine,
+    sqlite: &Rc<RefCell<Sqlite>>,
+    name: &str,
+    arg_count: u32,
+    method: F,
+) where
+    F: Fn(&mut Sqlite, &[Value]) -> Result<Value, String> + 'static,
+{
+    let sqlite = sqlite.clone();
+    engine.register_module_fn("sqlite", name, arg_count, move |args| {
+        method(&mut sqlite.borrow_mut(), &args)
+    });
+}
+
+fn string<'a>(value: &'a Value, function: &str, what: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(value) => Ok(value),
+        _ => Err(format!("{} expects {}", function, what)),
+    }
+}
+
+fn handle(value: &Value, function: &str, what: &str) -> Result<i64, String> {
+    match value {
+        Value::Integer(handle) => Ok(*handle),
+        _ => Err(format!("{} expects {}", function, what)),
+    }
+}
+
+fn params(value: &Value, function: &str) -> Result<Vec<Column>, String> {
+    let values = match value {
+        Value::List(values) => values,
+        Value::None => return Ok(Vec::new()),
+        _ => return Err(format!("{} expects the params as a list", function)),
+    };
+    values
+        .iter()
+        .map(|value| match value {
+            Value::None => Ok(Column::Null),
+            Value::Integer(value) => Ok(Column::Integer(*value)),
+            Value::Float(value) => Ok(Column::Real(*value)),
+            Value::Boolean(value) => Ok(Column::Integer(*value as i64)),
+            Value::String(value) => Ok(Column::Text(value.clone())),
+            _ => Err(format!(
+                "{} can only store none, numbers, booleans and strings",
+                function
+            )),
+        })
+        .collect()
+}
+
+fn to_value(column: Column) -> Value {
+    match column {
+        Column::Null => Value::None,
+        Column::Integer(value) => Value::Integer(value),
+        Column::Real(value) => Value::Float(value),
+        Column::Text(value) => Value::String(value),
+        Column::Blob(value) => Value::String(String::from_utf8_lossy(&value).into_owned()),
+    }
+}
+
+fn rows(statement: &mut Statement, params: Vec<Column>) -> rusqlite::Result<Value> {
+    let names: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let mut rows = statement.query(params_from_iter(params))?;
+    let mut list = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut bendy = HashMap::new();
+        for (index, name) in names.iter().enumerate() {
+            bendy.insert(name.clone(), to_value(row.get(index)?));
+        }
+        list.push(Value::Bendy(bendy));
+    }
+    Ok(Value::List(list))
+}
+
+fn failed(function: &str) -> impl Fn(rusqlite::Error) -> String + '_ {
+    move |error| format!("{} failed: {}", function, error)
+}
+
+impl Sqlite {
+    fn add_handle(&mut self) -> i64 {
+        self.next_handle += 1;
+        self.next_handle
+    }
+
+    fn connection(&self, value: &Value, function: &str) -> Result<&Connection, String> {
+        let db = handle(value, function, "a database")?;
+        self.connections
+            .get(&db)
+            .ok_or_else(|| format!("{} got an unknown database", function))
+    }
+
+    fn statement(&self, value: &Value, function: &str) -> Result<CachedStatement<'_>, String> {
+        let statement = handle(value, function, "a prepared statement")?;
+        let (db, sql) = self
+            .statements
+            .get(&statement)
+            .ok_or_else(|| format!("{} got an unknown prepared statement", function))?;
+        self.connections[db]
+            .prepare_cached(sql)
+            .map_err(failed(function))
+    }
+
+    fn open(&mut self, args: &[Value]) -> Result<Value, String> {
+        let path = string(&args[0], "open", "a path")?;
+        let connection = if path == ":memory:" {
+            Connection::open_in_memory()
+        } else {
+            Connection::open(path)
+        }
+        .map_err(|error| format!("couldn't open database '{}': {}", path, error))?;
+        let db = self.add_handle();
+        self.connections.insert(db, connection);
+        Ok(Value::Integer(db))
+    }
+
+    fn exec(&mut self, args: &[Value]) -> Result<Value, String> {
+        let connection = self.connection(&args[0], "exec")?;
+        let sql = string(&args[1], "exec", "sql")?;
+        let params = params(&args[2], "exec")?;
+        let changed = connection
+            .execute(sql, params_from_iter(params))
+            .map_err(failed("exec"))?;
+        Ok(Value::Integer(changed as i64))
+    }
+
+    fn query(&mut self, args: &[Value]) -> Result<Value, String> {
+        let connection = self.connection(&args[0], "query")?;
+        let sql = string(&args[1], "query", "sql")?;
+        let params = params(&args[2], "query")?;
+        let mut statement = connection.prepare(sql).map_err(failed("query"))?;
+        rows(&mut statement, params).map_err(failed("query"))
+    }
+
+    fn prepare(&mut self, args: &[Value]) -> Result<Value, String> {
+        let db = handle(&args[0], "prepare", "a database")?;
+        let sql = string(&args[1], "prepare", "sql")?;
+        // compiled now so mistakes in the sql show up here
+        self.connection(&args[0], "prepare")?
+            .prepare_cached(sql)
+            .map_err(failed("prepare"))?;
+        let statement = self.add_handle();
+        self.statements.insert(statement, (db, String::from(sql)));
+        Ok(Value::Integer(statement))
+    }
+
+    fn exec_prepared(&mut self, args: &[Value]) -> Result<Value, String> {
+        let mut statement = self.statement(&args[0], "exec_prepared")?;
+        let params = params(&args[1], "exec_prepared")?;
+        let changed = statement
+            .execute(params_from_iter(params))
+            .map_err(failed("exec_prepared"))?;
+        Ok(Value::Integer(changed as i64))
+    }
+
+    fn query_prepared(&mut self, args: &[Value]) -> Result<Value, String> {
+        let mut statement = self.statement(&args[0], "query_prepared")?;
+        let params = params(&args[1], "query_prepared")?;
+        rows(&mut statement, params).map_err(failed("query_prepared"))
+    }
+
+    fn batch(&mut self, args: &[Value], function: &str, sql: &str) -> Result<Value, String> {
+        self.connection(&args[0], function)?
+            .execute_batch(sql)
+            .map_err(failed(function))?;
+        Ok(Value::None)
+    }
+}
@@ -0,0 +1,82 @@
+use olive::testing::{error, eval};
+use olive::{Engine, Value};
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    olvsqlite::register(&mut engine);
+    eval(
+        &mut engine,
+        "db = sqlite.open(\":memory:\");\n\
+         sqlite.exec(db, \"create table users (name text, age integer, admin integer)\", none);",
+    );
+    engine
+}
+
+fn user(name: &str, age: i64, admin: i64) -> Value {
+    let mut user = std::collections::HashMap::new();
+    user.insert(String::from("name"), Value::String(String::from(name)));
+    user.insert(String::from("age"), Value::Integer(age));
+    user.insert(String::from("admin"), Value::Integer(admin));
+    Value::Bendy(user)
+}
+
+#[test]
+fn queries_return_rows_as_bendys() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return sqlite.exec(db, \"insert into users values (?, ?, ?), ('bob', 30, 0)\", new [\"ada\", 36, true]);"
+        ),
+        Value::Integer(2)
+    );
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return sqlite.query(db, \"select * from users where age > ? order by age\", new [20]);"
+        ),
+        Value::List(vec![user("bob", 30, 0), user("ada", 36, 1)])
+    );
+    assert!(
+        error(&mut engine, "sqlite.query(db, \"select nope\", none);").contains("query failed")
+    );
+}
+
+#[test]
+fn prepared_statements_run_many_times() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "insert = sqlite.prepare(db, \"insert into users values (?, ?, 0)\");\n\
+             i = 0;\n\
+             while (i < 3) { sqlite.exec_prepared(insert, new [\"u\" $ i, i]); i = i + 1; }\n\
+             by_age = sqlite.prepare(db, \"select name from users where age = ?\");\n\
+             return sqlite.query_prepared(by_age, new [2]);"
+        ),
+        Value::List(vec![Value::Bendy(
+            vec![(String::from("name"), Value::String(String::from("u2")))]
+                .into_iter()
+                .collect()
+        )])
+    );
+    assert!(error(&mut engine, "sqlite.prepare(db, \"selec\");").contains("prepare failed"));
+}
+
+#[test]
+fn transactions_can_be_rolled_back() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "sqlite.begin(db);\n\
+             sqlite.exec(db, \"insert into users values ('kept', 1, 0)\", none);\n\
+             sqlite.commit(db);\n\
+             sqlite.begin(db);\n\
+             sqlite.exec(db, \"insert into users values ('dropped', 2, 0)\", none);\n\
+             sqlite.rollback(db);\n\
+             return len(sqlite.query(db, \"select * from users\", none));"
+        ),
+        Value::Integer(1)
+    );
+}
@@ -0,0 +1,142 @@
+// terminal output and input for scripts, added to an engine with register.
+// everything goes to and comes from the terminal of the process
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Color, Stylize};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{execute, queue};
+use olive::{Engine, Value};
+use std::collections::HashMap;
+use std::io::{self, stdout, Write};
+
+// adds the functions to a global term bendy, e.g. term.color(text, name):
+//   color(text, name) returns the text styled to print in the color, one of
+//     black, red, green, yellow, blue, magenta, cyan, white and grey, or
+//     "bold"
+//   write(text) prints the text without a newline, e.g. for progress bars
+//   move_to(col, row) moves the cursor, 0, 0 is the top left
+//   clear() clears the terminal and moves the cursor to the top left
+//   show_cursor(visible) shows or hides the cursor
+//   size() returns a bendy with the cols and rows of the terminal
+//   raw_mode(enabled) turns off line buffering and echo, for read_key
+//   read_key() waits for a key press and returns the character, or the name
+//     of a special key: enter, backspace, tab, esc, up, down, left or right
+pub fn register(engine: &mut Engine) {
+    engine.register_module_fn("term", "color", 2, |args| {
+        let text = string(&args[0], "color", "the text and a color")?;
+        let name = string(&args[1], "color", "the text and a color")?;
+        let styled = match name {
+            "bold" => text.attribute(Attribute::Bold).to_string(),
+            name => text
+                .with(parse_color(name).ok_or_else(|| format!("unknown color '{}'", name))?)
+                .to_string(),
+        };
+        Ok(Value::String(styled))
+    });
+    engine.register_module_fn("term", "write", 1, |args| {
+        let text = string(&args[0], "write", "a string")?;
+        let mut out = stdout();
+        out.write_all(text.as_bytes())
+            .and_then(|_| out.flush())
+            .map_err(failed("write"))?;
+        Ok(Value::None)
+    });
+    engine.register_module_fn("term", "move_to", 2, |args| {
+        let (col, row) = match (&args[0], &args[1]) {
+            (Value::Integer(col), Value::Integer(row))
+                if (0..=u16::MAX as i64).contains(col) && (0..=u16::MAX as i64).contains(row) =>
+            {
+                (*col as u16, *row as u16)
+            }
+            _ => return Err(String::from("move_to expects a column and a row")),
+        };
+        execute!(stdout(), MoveTo(col, row)).map_err(failed("move_to"))?;
+        Ok(Value::None)
+    });
+    engine.register_module_fn("term", "clear", 0, |_| {
+        execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0)).map_err(failed("clear"))?;
+        Ok(Value::None)
+    });
+    engine.register_module_fn("term", "show_cursor", 1, |args| {
+        let mut out = stdout();
+        match boolean(&args[0], "show_cursor")? {
+            true => queue!(out, Show),
+            false => queue!(out, Hide),
+        }
+        .and_then(|_| out.flush())
+        .map_err(failed("show_cursor"))?;
+        Ok(Value::None)
+    });
+    engine.register_module_fn("term", "size", 0, |_| {
+        let (cols, rows) = terminal::size().map_err(failed("size"))?;
+        let mut bendy = HashMap::new();
+        bendy.insert(String::from("cols"), Value::Integer(cols as i64));
+        bendy.insert(String::from("rows"), Value::Integer(rows as i64));
+        Ok(Value::Bendy(bendy))
+    });
+    engine.register_module_fn("term", "raw_mode", 1, |args| {
+        match boolean(&args[0], "raw_mode")? {
+            true => terminal::enable_raw_mode(),
+            false => terminal::disable_raw_mode(),
+        }
+        .map_err(failed("raw_mode"))?;
+        Ok(Value::None)
+    });
+    engine.register_module_fn("term", "read_key", 0, |_| read_key());
+}
+
+fn string<'a>(value: &'a Value, function: &str, what: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(value) => Ok(value),
+        _ => Err(format!("{} expects {}", function, what)),
+    }
+}
+
+fn boolean(value: &Value, function: &str) -> Result<bool, String> {
+    match value {
+        Value::Boolean(value) => Ok(*value),
+        _ => Err(format!("{} expects a boolean", function)),
+    }
+}
+
+fn failed(function: &str) -> impl Fn(io::Error) -> String + '_ {
+    move |error| format!("{} failed: {}", function, error)
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    Some(match name {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "grey" => Color::Grey,
+        _ => return None,
+    })
+}
+
+// other events, releases and keys without a name are skipped
+fn read_key() -> Result<Value, String> {
+    loop {
+        let key = match event::read().map_err(failed("read_key"))? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        let name = match key.code {
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => String::from("enter"),
+            KeyCode::Backspace => String::from("backspace"),
+            KeyCode::Tab => String::from("tab"),
+            KeyCode::Esc => String::from("esc"),
+            KeyCode::Up => String::from("up"),
+            KeyCode::Down => String::from("down"),
+            KeyCode::Left => String::from("left"),
+            KeyCode::Right => String::from("right"),
+            _ => continue,
+        };
+        return Ok(Value::String(name));
+    }
+}
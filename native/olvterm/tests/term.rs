@@ -0,0 +1,26 @@
+use olive::testing::{error, eval};
+use olive::{Engine, Value};
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    olvterm::register(&mut engine);
+    engine
+}
+
+// the rest needs a terminal, which tests don't have
+#[test]
+fn text_is_styled_with_escape_codes() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "return term.color(\"ok\", \"green\");"),
+        Value::String(String::from("\u{1b}[38;5;10mok\u{1b}[39m"))
+    );
+    assert_eq!(
+        eval(&mut engine, "return term.color(\"loud\", \"bold\");"),
+        Value::String(String::from("\u{1b}[1mloud\u{1b}[0m"))
+    );
+    assert!(error(&mut engine, "term.color(\"x\", \"plaid\");").contains("unknown color 'plaid'"));
+    assert!(
+        error(&mut engine, "term.move_to(-1, 0);").contains("move_to expects a column and a row")
+    );
+}
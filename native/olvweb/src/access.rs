@@ -0,0 +1,73 @@
+use crate::socket::Peer;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+// shared by a server and the clients it accepted, which may be on other
+// threads
+pub type AccessLog = Arc<Mutex<Box<dyn Write + Send>>>;
+
+// target is a file the lines are appended to, or "-" for stdout
+pub fn open(target: &str) -> io::Result<AccessLog> {
+    let log: Box<dyn Write + Send> = if target == "-" {
+        Box::new(io::stdout())
+    } else {
+        Box::new(OpenOptions::new().create(true).append(true).open(target)?)
+    };
+    Ok(Arc::new(Mutex::new(log)))
+}
+
+// what is logged about a request once it was answered
+pub struct Entry {
+    request_line: String,
+    started: Instant,
+    pub status: i64,
+    pub sent: u64,
+}
+
+impl Entry {
+    pub fn new(request_line: String) -> Self {
+        Entry {
+            request_line,
+            started: Instant::now(),
+            status: 0,
+            sent: 0,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+// a line in common log format, with the milliseconds the answer took at the
+// end. a log that can't be written to doesn't fail the response
+pub fn write(log: &AccessLog, peer: Peer, entry: &Entry) {
+    let host = match peer {
+        Peer::Tcp(address) => address.ip().to_string(),
+        #[cfg(unix)]
+        Peer::Unix => String::from("-"),
+    };
+    let line = format!(
+        "{} - - [{}] \"{}\" {} {} {}\n",
+        host,
+        log_date(SystemTime::now()),
+        entry.request_line,
+        entry.status,
+        entry.sent,
+        entry.elapsed().as_millis()
+    );
+    if let Ok(mut log) = log.lock() {
+        let _ = log.write_all(line.as_bytes());
+        let _ = log.flush();
+    }
+}
+
+// common log format wants "06/Nov/1994:08:49:37 +0000" instead of the http
+// date
+fn log_date(time: SystemTime) -> String {
+    let date = httpdate::fmt_http_date(time);
+    let parts: Vec<&str> = date.split(' ').collect();
+    format!("{}/{}/{}:{} +0000", parts[1], parts[2], parts[3], parts[4])
+}
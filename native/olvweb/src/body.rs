@@ -0,0 +1,126 @@
+use olive::Value;
+use std::collections::HashMap;
+
+// + is a space and %xx a byte, invalid escapes are kept as they are
+fn url_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// the fields of an application/x-www-form-urlencoded body, a field given
+// twice keeps the last value
+pub fn form(body: &[u8]) -> Value {
+    let body = String::from_utf8_lossy(body);
+    let fields = body
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let name = url_decode(parts.next().unwrap_or(""));
+            let value = url_decode(parts.next().unwrap_or(""));
+            (name, Value::String(value))
+        })
+        .collect::<HashMap<_, _>>();
+    Value::Bendy(fields)
+}
+
+fn find(haystack: &[u8], needle: &[u8], start: usize) -> Option<usize> {
+    if start > haystack.len() {
+        return None;
+    }
+    haystack[start..]
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|pos| pos + start)
+}
+
+// a parameter of a header value like the boundary in
+// multipart/form-data; boundary=x, with the quotes taken off
+pub fn header_param(header: &str, param: &str) -> Option<String> {
+    header.split(';').skip(1).find_map(|part| {
+        let mut pair = part.trim().splitn(2, '=');
+        if pair.next()?.trim().eq_ignore_ascii_case(param) {
+            Some(String::from(pair.next()?.trim().trim_matches('"')))
+        } else {
+            None
+        }
+    })
+}
+
+// the parts of a multipart/form-data body as bendys with the name, filename
+// and content_type they were sent with, the content as a list of bytes and
+// the content as text. parsing stops at the first part that is cut off
+pub fn multipart(body: &[u8], boundary: &str) -> Value {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let next_delimiter = [&b"\r\n"[..], &delimiter].concat();
+    let mut parts = Vec::new();
+    let mut pos = match find(body, &delimiter, 0) {
+        Some(pos) => pos + delimiter.len(),
+        None => return Value::List(parts),
+    };
+    // the last delimiter is followed by -- instead
+    while body[pos..].starts_with(b"\r\n") {
+        let headers_end = match find(body, b"\r\n\r\n", pos) {
+            Some(end) => end,
+            None => break,
+        };
+        let next = match find(body, &next_delimiter, headers_end) {
+            Some(next) => next,
+            None => break,
+        };
+        let mut part = HashMap::new();
+        for line in String::from_utf8_lossy(&body[pos + 2..headers_end]).split("\r\n") {
+            let mut header = line.splitn(2, ':');
+            let name = header.next().unwrap_or("").trim().to_ascii_lowercase();
+            let value = header.next().unwrap_or("").trim();
+            if name == "content-disposition" {
+                for param in &["name", "filename"] {
+                    if let Some(param_value) = header_param(value, param) {
+                        part.insert(String::from(*param), Value::String(param_value));
+                    }
+                }
+            } else if name == "content-type" {
+                part.insert(
+                    String::from("content_type"),
+                    Value::String(String::from(value)),
+                );
+            }
+        }
+        let content = &body[headers_end + 4..next];
+        part.insert(
+            String::from("content"),
+            Value::List(
+                content
+                    .iter()
+                    .map(|byte| Value::Integer(*byte as i64))
+                    .collect(),
+            ),
+        );
+        part.insert(
+            String::from("text"),
+            Value::String(String::from_utf8_lossy(content).into_owned()),
+        );
+        parts.push(Value::Bendy(part));
+        pos = next + next_delimiter.len();
+    }
+    Value::List(parts)
+}
@@ -0,0 +1,149 @@
+use crate::is_token;
+use olive::Value;
+use std::collections::HashMap;
+
+// the cookies of a Cookie header by name
+pub fn parse(header: &str) -> Value {
+    let cookies = header
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            Some((String::from(name), Value::String(String::from(value))))
+        })
+        .collect();
+    Value::Bendy(cookies)
+}
+
+// the characters RFC 6265 allows in a cookie value, which may also be
+// wrapped in double quotes. no whitespace, quotes, commas, semicolons or
+// backslashes
+fn is_cookie_value(value: &str) -> bool {
+    let value = match value.strip_prefix('"') {
+        Some(quoted) => match quoted.strip_suffix('"') {
+            Some(inner) => inner,
+            None => return false,
+        },
+        None => value,
+    };
+    value
+        .bytes()
+        .all(|c| matches!(c, 0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e))
+}
+
+// path and extension values may be any text but control characters and ;
+fn is_attribute_value(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|c| c >= 0x20 && c != 0x7f && c != b';')
+}
+
+fn is_domain(domain: &str) -> bool {
+    let domain = domain.strip_prefix('.').unwrap_or(domain);
+    !domain.is_empty()
+        && domain
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'.')
+}
+
+// sorted, so the same options always give the same header
+fn attributes(options: &HashMap<String, Value>) -> Result<String, String> {
+    let mut attributes = String::new();
+    let mut names: Vec<&String> = options.keys().collect();
+    names.sort();
+    for name in names {
+        let attribute = match (name.as_str(), &options[name]) {
+            ("path", Value::String(path)) if is_attribute_value(path) => {
+                format!("; Path={}", path)
+            }
+            ("domain", Value::String(domain)) if is_domain(domain) => {
+                format!("; Domain={}", domain)
+            }
+            ("expires", Value::String(expires)) if httpdate::parse_http_date(expires).is_ok() => {
+                format!("; Expires={}", expires)
+            }
+            ("max_age", Value::Integer(max_age)) => format!("; Max-Age={}", max_age),
+            ("same_site", Value::String(same_site))
+                if ["strict", "lax", "none"]
+                    .iter()
+                    .any(|valid| same_site.eq_ignore_ascii_case(valid)) =>
+            {
+                format!("; SameSite={}", same_site)
+            }
+            ("http_only", Value::Boolean(true)) => String::from("; HttpOnly"),
+            ("secure", Value::Boolean(true)) => String::from("; Secure"),
+            ("http_only", Value::Boolean(false)) | ("secure", Value::Boolean(false)) => {
+                String::new()
+            }
+            _ => return Err(format!("invalid cookie attribute '{}'", name)),
+        };
+        attributes.push_str(&attribute);
+    }
+    Ok(attributes)
+}
+
+// set_cookie(response, name, value, options) returns the response with a
+// Set-Cookie header added
+pub fn set_cookie(args: Vec<Value>) -> Result<Value, String> {
+    let mut args = args.into_iter();
+    let (response, name, value, options) =
+        match (args.next(), args.next(), args.next(), args.next()) {
+            (Some(response), Some(name), Some(value), Some(options)) => {
+                (response, name, value, options)
+            }
+            _ => return Err(String::from("set_cookie expects 4 arguments")),
+        };
+    let cookie = match (name, value) {
+        (Value::String(name), _) if !is_token(&name) => {
+            return Err(format!("set_cookie got an invalid cookie name {:?}", name))
+        }
+        (_, Value::String(value)) if !is_cookie_value(&value) => {
+            return Err(format!(
+                "set_cookie got an invalid cookie value {:?}",
+                value
+            ))
+        }
+        (Value::String(name), Value::String(value)) => format!("{}={}", name, value),
+        _ => {
+            return Err(String::from(
+                "set_cookie expects the name and value as strings",
+            ))
+        }
+    };
+    let attributes = match options {
+        Value::Bendy(options) => attributes(&options)?,
+        Value::None => String::new(),
+        _ => {
+            return Err(String::from(
+                "set_cookie expects the options as a bendy or none",
+            ))
+        }
+    };
+    let mut response = match response {
+        Value::Bendy(response) => response,
+        Value::String(body) => {
+            let mut response = HashMap::new();
+            response.insert(String::from("body"), Value::String(body));
+            response
+        }
+        _ => return Err(String::from("set_cookie expects a response")),
+    };
+    let headers = response
+        .entry(String::from("headers"))
+        .or_insert_with(|| Value::Bendy(HashMap::new()));
+    let headers = match headers {
+        Value::Bendy(headers) => headers,
+        _ => return Err(String::from("set_cookie expects the headers as a bendy")),
+    };
+    let cookie = Value::String(cookie + &attributes);
+    match headers.remove("Set-Cookie") {
+        Some(Value::List(mut cookies)) => {
+            cookies.push(cookie);
+            headers.insert(String::from("Set-Cookie"), Value::List(cookies));
+        }
+        Some(other) => {
+            headers.insert(String::from("Set-Cookie"), Value::List(vec![other, cookie]));
+        }
+        None => {
+            headers.insert(String::from("Set-Cookie"), Value::List(vec![cookie]));
+        }
+    }
+    Ok(Value::Bendy(response))
+}
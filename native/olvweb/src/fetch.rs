@@ -0,0 +1,100 @@
+use crate::{add_headers, string};
+use olive::Value;
+use std::collections::HashMap;
+use std::io::Read;
+use std::time::Duration;
+
+struct Options {
+    method: String,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+    // in milliseconds, 0 waits forever
+    timeout: u64,
+}
+
+fn options(value: &Value) -> Result<Options, String> {
+    let mut options = Options {
+        method: String::from("GET"),
+        headers: Vec::new(),
+        body: None,
+        timeout: 0,
+    };
+    let bendy = match value {
+        Value::Bendy(bendy) => bendy,
+        Value::None => return Ok(options),
+        _ => return Err(String::from("fetch expects the options as a bendy")),
+    };
+    for (name, value) in bendy {
+        match (name.as_str(), value) {
+            ("method", Value::String(method)) => options.method = method.to_ascii_uppercase(),
+            ("body", Value::String(body)) => options.body = Some(body.clone()),
+            ("timeout", Value::Integer(timeout)) if *timeout >= 0 => {
+                options.timeout = *timeout as u64
+            }
+            ("headers", Value::Bendy(headers)) => {
+                for (name, value) in headers {
+                    add_headers(&mut options.headers, name, value)?;
+                }
+            }
+            _ => return Err(format!("invalid fetch option '{}'", name)),
+        }
+    }
+    Ok(options)
+}
+
+// headers sent more than once become a list, like the ones send_res takes
+fn to_bendy(response: ureq::Response) -> Result<Value, String> {
+    let mut headers = HashMap::new();
+    for name in response.headers_names() {
+        let mut values: Vec<Value> = response
+            .all(&name)
+            .into_iter()
+            .map(|value| Value::String(String::from(value)))
+            .collect();
+        let value = if values.len() == 1 {
+            values.remove(0)
+        } else {
+            Value::List(values)
+        };
+        headers.insert(name.to_ascii_lowercase(), value);
+    }
+    let mut bendy = HashMap::new();
+    bendy.insert(
+        String::from("status"),
+        Value::Integer(i64::from(response.status())),
+    );
+    bendy.insert(String::from("headers"), Value::Bendy(headers));
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut body)
+        .map_err(|error| format!("fetch failed: {}", error))?;
+    bendy.insert(
+        String::from("body"),
+        Value::String(String::from_utf8_lossy(&body).into_owned()),
+    );
+    Ok(Value::Bendy(bendy))
+}
+
+// fetch(url, options) sends a request and returns the response as a bendy of
+// status, headers by lowercase name and body. responses with error statuses
+// are returned like the others, only requests that got no response fail
+pub fn fetch(args: Vec<Value>) -> Result<Value, String> {
+    let url = string(&args[0], "fetch", "a url")?;
+    let options = options(&args[1])?;
+    let mut request = ureq::request(&options.method, url);
+    for (name, value) in &options.headers {
+        request = request.set(name, value);
+    }
+    if options.timeout > 0 {
+        request = request.timeout(Duration::from_millis(options.timeout));
+    }
+    let sent = match &options.body {
+        Some(body) => request.send_string(body),
+        None => request.call(),
+    };
+    match sent {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => to_bendy(response),
+        Err(error) => Err(format!("fetch failed: {}", error)),
+    }
+}
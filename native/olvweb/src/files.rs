@@ -0,0 +1,237 @@
+use crate::http::{reason_phrase, Client, Conditions, Response};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// the extension may start with a dot and is compared ignoring case
+pub fn mime_for_extension(extension: &str) -> &'static str {
+    let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "application/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "wasm" => "application/wasm",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+// request paths are resolved relative to the working directory, anything
+// trying to escape it is treated as missing
+fn safe_path(path: &str) -> Option<PathBuf> {
+    let path = path.split(['?', '#']).next().unwrap_or("");
+    let mut result = PathBuf::new();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => result.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+// an etag made of the length and modification time of a file and the time
+// for Last-Modified, if the file system knows it
+struct Validators {
+    etag: String,
+    last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    fn new(len: u64, modified: Option<SystemTime>) -> Self {
+        let since_epoch = modified.and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+        let etag = match since_epoch {
+            Some(time) => format!(
+                "\"{:x}-{:x}{:08x}\"",
+                len,
+                time.as_secs(),
+                time.subsec_nanos()
+            ),
+            None => format!("\"{:x}\"", len),
+        };
+        Validators {
+            etag,
+            // http dates only have seconds
+            last_modified: since_epoch.map(|time| UNIX_EPOCH + Duration::from_secs(time.as_secs())),
+        }
+    }
+
+    fn headers(&self) -> Vec<(String, String)> {
+        let mut headers = vec![(String::from("ETag"), self.etag.clone())];
+        if let Some(time) = self.last_modified {
+            headers.push((String::from("Last-Modified"), httpdate::fmt_http_date(time)));
+        }
+        headers
+    }
+
+    // If-None-Match compares weakly and takes precedence, If-Modified-Since is
+    // only looked at without it
+    fn not_modified(&self, conditions: &Conditions) -> bool {
+        if let Some(if_none_match) = &conditions.if_none_match {
+            let etag = self.etag.trim_start_matches("W/");
+            return if_none_match
+                .split(',')
+                .map(|tag| tag.trim())
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+        }
+        match (&conditions.if_modified_since, self.last_modified) {
+            (Some(since), Some(modified)) => match httpdate::parse_http_date(since) {
+                Ok(since) => modified <= since,
+                Err(_) => false,
+            },
+            _ => false,
+        }
+    }
+
+    // a range is only sent if the file is still the one If-Range names
+    fn range_allowed(&self, conditions: &Conditions) -> bool {
+        match &conditions.if_range {
+            Some(if_range) if if_range.trim().starts_with('"') => if_range.trim() == self.etag,
+            Some(if_range) => match (httpdate::parse_http_date(if_range), self.last_modified) {
+                (Ok(since), Some(modified)) => modified == since,
+                _ => false,
+            },
+            None => true,
+        }
+    }
+}
+
+// the first and last byte of a single range like bytes=0-99, bytes=100- or
+// bytes=-100. none for headers that are ignored so the whole file is sent,
+// which includes several ranges; Err if the range lies outside of the file
+fn parse_range(header: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+    if first.is_empty() {
+        let suffix: u64 = last.parse().ok()?;
+        if suffix == 0 || len == 0 {
+            return Some(Err(()));
+        }
+        return Some(Ok((len.saturating_sub(suffix), len - 1)));
+    }
+    let first: u64 = first.parse().ok()?;
+    let last = match last {
+        "" => u64::MAX,
+        last => last.parse().ok()?,
+    };
+    if last < first {
+        return None;
+    }
+    if first >= len {
+        return Some(Err(()));
+    }
+    Some(Ok((first, last.min(len - 1))))
+}
+
+fn open(path: &str) -> Option<(PathBuf, File, u64, Option<SystemTime>)> {
+    let path = safe_path(path)?;
+    let file = File::open(&path).ok()?;
+    let metadata = file.metadata().ok()?;
+    if metadata.is_file() {
+        Some((path, file, metadata.len(), metadata.modified().ok()))
+    } else {
+        None
+    }
+}
+
+// answers with the file at path, or 404 if there is none. a Range header of
+// the request gets only that part with 206, or 416 if it lies outside of the
+// file, and a file the request names in If-None-Match or that wasn't changed
+// since If-Modified-Since gets 304 without a body. returns the status
+pub fn serve(client: &mut Client, path: &str) -> io::Result<i64> {
+    let (path, mut file, len, modified) = match open(path) {
+        Some(found) => found,
+        None => {
+            let response = Response {
+                status: 404,
+                headers: Vec::new(),
+                body: String::from("Not Found"),
+            };
+            client.respond(&response)?;
+            return Ok(404);
+        }
+    };
+    let validators = Validators::new(len, modified);
+    if validators.not_modified(&client.conditions) {
+        let response = Response {
+            status: 304,
+            headers: validators.headers(),
+            body: String::new(),
+        };
+        client.send_head(&response, Some(len))?;
+        client.log();
+        return Ok(304);
+    }
+    let range = match &client.conditions.range {
+        Some(range) if validators.range_allowed(&client.conditions) => parse_range(range, len),
+        _ => None,
+    };
+    let (status, first, last) = match range {
+        Some(Ok((first, last))) => (206, first, last),
+        Some(Err(())) => {
+            let response = Response {
+                status: 416,
+                headers: vec![(String::from("Content-Range"), format!("bytes */{}", len))],
+                body: String::from(reason_phrase(416)),
+            };
+            client.respond(&response)?;
+            return Ok(416);
+        }
+        None => (200, 0, len.saturating_sub(1)),
+    };
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let mut headers = validators.headers();
+    headers.push((String::from("Accept-Ranges"), String::from("bytes")));
+    headers.push((
+        String::from("Content-Type"),
+        String::from(mime_for_extension(&extension)),
+    ));
+    let mut response = Response {
+        status,
+        headers,
+        body: String::new(),
+    };
+    let len = if status == 206 {
+        response.headers.push((
+            String::from("Content-Range"),
+            format!("bytes {}-{}/{}", first, last, len),
+        ));
+        file.seek(SeekFrom::Start(first))?;
+        last - first + 1
+    } else {
+        len
+    };
+    client.send_head(&response, Some(len))?;
+    client.send_from(&mut file.take(len))?;
+    client.log();
+    Ok(status)
+}
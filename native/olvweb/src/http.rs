@@ -0,0 +1,675 @@
+use crate::access::{self, AccessLog, Entry};
+use crate::middleware::{self, Middleware};
+use crate::socket::{Listener, Peer, Stream};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+// how much is read from a client at once
+const READ_SIZE: usize = 4096;
+
+// what a server takes from its clients. a request over a limit is answered
+// with 431, 413, 429 or 408 and the connection closed
+#[derive(Clone, Copy)]
+pub struct Limits {
+    // how long a read from a client may take, none waits forever
+    pub read_timeout: Option<Duration>,
+    // how long reading the head and body of a request may take all together,
+    // so a client can't hold a connection by sending a byte at a time. it
+    // starts when the server waits for the request, none waits forever
+    pub request_timeout: Option<Duration>,
+    pub max_headers: usize,
+    pub max_head_size: usize,
+    pub max_body_size: usize,
+    // 0 for no limit
+    pub max_connections_per_ip: usize,
+    // bodies over this are written to a temporary file instead of memory, 0
+    // keeps all of them in memory
+    pub spool_body_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            read_timeout: Some(Duration::from_secs(30)),
+            request_timeout: Some(Duration::from_secs(60)),
+            max_headers: 64,
+            max_head_size: 16 * 1024,
+            max_body_size: 16 * 1024 * 1024,
+            max_connections_per_ip: 0,
+            spool_body_size: 0,
+        }
+    }
+}
+
+// a listener with the limits for the clients it accepts
+pub struct Server {
+    listener: Listener,
+    pub limits: Limits,
+    pub log: Option<AccessLog>,
+    pub middleware: Arc<Vec<Middleware>>,
+    connections: Connections,
+}
+
+// the open connections per address, only counted while max_connections_per_ip
+// is set
+type Connections = Arc<Mutex<HashMap<IpAddr, usize>>>;
+
+// a client's place in the connection count of its address, given back when
+// the client is dropped
+struct ConnectionSlot {
+    connections: Connections,
+    ip: IpAddr,
+}
+
+impl ConnectionSlot {
+    // none if the address already has max connections open
+    fn take(connections: &Connections, ip: IpAddr, max: usize) -> Option<Self> {
+        let mut counts = connections.lock().ok()?;
+        let count = counts.entry(ip).or_insert(0);
+        if *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionSlot {
+            connections: connections.clone(),
+            ip,
+        })
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.remove_body_file();
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        if let Ok(mut counts) = self.connections.lock() {
+            if let Some(count) = counts.get_mut(&self.ip) {
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(&self.ip);
+                }
+            }
+        }
+    }
+}
+
+// a connection accepted by a server, with what was read from it but not
+// parsed yet
+pub struct Client {
+    stream: Stream,
+    pub peer: Peer,
+    buffer: Vec<u8>,
+    pub limits: Limits,
+    log: Option<AccessLog>,
+    middleware: Arc<Vec<Middleware>>,
+    // the request read last until its answer is logged
+    entry: Option<Entry>,
+    // how much of the body of the request read last is still to be read
+    body_left: usize,
+    // whether the connection stays open after the response to the request
+    // read last, which http/1.1 does unless the client says otherwise
+    pub keep_alive: bool,
+    // Set-Cookie headers from the session functions for the response to the
+    // request read last
+    pub cookies: Vec<String>,
+    // none if max_connections_per_ip is 0 or the address had that many open
+    // already when the client was accepted
+    connection: Option<ConnectionSlot>,
+    // the file the body of the request read last was spooled to, removed with
+    // the next request or the client
+    body_file: Option<PathBuf>,
+    // of the request read last, for serve_file
+    pub conditions: Conditions,
+    // when the request_timeout of the request being read runs out
+    deadline: Option<Instant>,
+}
+
+// the headers of a request that decide whether and what part of a file is
+// sent
+#[derive(Default)]
+pub struct Conditions {
+    pub range: Option<String>,
+    pub if_range: Option<String>,
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<String>,
+}
+
+impl Conditions {
+    fn new(headers: &[(String, String)]) -> Self {
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.clone())
+        };
+        Conditions {
+            range: header("range"),
+            if_range: header("if-range"),
+            if_none_match: header("if-none-match"),
+            if_modified_since: header("if-modified-since"),
+        }
+    }
+}
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub version: String,
+    // names are in lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    // where the body is instead if it was over spool_body_size
+    pub body_file: Option<PathBuf>,
+}
+
+impl Server {
+    pub fn bind(address: &str) -> io::Result<Self> {
+        Ok(Server {
+            listener: Listener::bind(address)?,
+            limits: Limits::default(),
+            log: None,
+            middleware: Arc::new(Vec::new()),
+            connections: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    // for accepting on another thread, with the limits as they are now
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Server {
+            listener: self.listener.try_clone()?,
+            limits: self.limits,
+            log: self.log.clone(),
+            middleware: self.middleware.clone(),
+            connections: self.connections.clone(),
+        })
+    }
+
+    // wakes a thread of this server waiting in accept
+    pub fn wake(&self) -> io::Result<()> {
+        self.listener.wake()
+    }
+
+    // waits for a connection that sends a request. connections that are
+    // closed before that or fail are skipped
+    pub fn accept(&self) -> io::Result<(Client, Request)> {
+        loop {
+            if let Some(accepted) = self.accept_one()? {
+                return Ok(accepted);
+            }
+        }
+    }
+
+    // waits for one connection, none if it was closed without a request or
+    // failed
+    pub fn accept_one(&self) -> io::Result<Option<(Client, Request)>> {
+        let mut client = self.client()?;
+        Ok(match client.read_request() {
+            Ok(Some(request)) => Some((client, request)),
+            Ok(None) | Err(_) => None,
+        })
+    }
+
+    // like accept, but the body is left to be read with Client::read_body
+    pub fn accept_head(&self) -> io::Result<(Client, Request)> {
+        loop {
+            let mut client = self.client()?;
+            if let Ok(Some(request)) = client.read_head() {
+                return Ok((client, request));
+            }
+        }
+    }
+
+    fn client(&self) -> io::Result<Client> {
+        let (stream, peer) = self.listener.accept()?;
+        stream.set_read_timeout(self.limits.read_timeout)?;
+        let connection = match self.limits.max_connections_per_ip {
+            0 => None,
+            max => ConnectionSlot::take(&self.connections, peer.ip(), max),
+        };
+        Ok(Client {
+            stream,
+            peer,
+            buffer: Vec::new(),
+            limits: self.limits,
+            log: self.log.clone(),
+            middleware: self.middleware.clone(),
+            entry: None,
+            body_left: 0,
+            keep_alive: false,
+            cookies: Vec::new(),
+            connection,
+            body_file: None,
+            conditions: Conditions::default(),
+            deadline: None,
+        })
+    }
+}
+
+impl Client {
+    // the next request on the connection, or none if the client closed it
+    // first
+    pub fn read_request(&mut self) -> io::Result<Option<Request>> {
+        let mut request = match self.read_head()? {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+        let spool = self.limits.spool_body_size;
+        if spool > 0 && self.body_left > spool {
+            request.body_file = Some(self.spool_body()?);
+        }
+        while self.body_left > 0 {
+            let piece = self.read_body(self.body_left)?;
+            request.body.extend_from_slice(&piece);
+        }
+        Ok(Some(request))
+    }
+
+    // writes the body to a new temporary file in pieces
+    fn spool_body(&mut self) -> io::Result<PathBuf> {
+        let mut name = [0; 16];
+        getrandom::getrandom(&mut name).map_err(|error| io::Error::other(error.to_string()))?;
+        let name: String = name.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let path = std::env::temp_dir().join(format!("olvweb-{}.body", name));
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        self.body_file = Some(path.clone());
+        while self.body_left > 0 {
+            let piece = self.read_body(64 * 1024)?;
+            file.write_all(&piece)?;
+        }
+        Ok(path)
+    }
+
+    fn remove_body_file(&mut self) {
+        if let Some(path) = self.body_file.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    // the next request with an empty body, which is read with read_body.
+    // what is left of the body of the request before is skipped
+    pub fn read_head(&mut self) -> io::Result<Option<Request>> {
+        loop {
+            let request = match self.read_next_head()? {
+                Some(request) => request,
+                None => return Ok(None),
+            };
+            if self.limits.max_connections_per_ip > 0 && self.connection.is_none() {
+                return Err(self.reject(429));
+            }
+            match middleware::before(&self.middleware, &request, self.peer.ip()) {
+                Some(response) => self.respond(&response)?,
+                None => return Ok(Some(request)),
+            }
+            // answered by middleware, the script gets the next one
+            if !self.keep_alive {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn read_next_head(&mut self) -> io::Result<Option<Request>> {
+        self.deadline = self
+            .limits
+            .request_timeout
+            .map(|timeout| Instant::now() + timeout);
+        while self.body_left > 0 {
+            self.read_body(READ_SIZE)?;
+        }
+        self.remove_body_file();
+        loop {
+            if let Some(request) = self.parse()? {
+                return Ok(Some(request));
+            }
+            if self.buffer.len() > self.limits.max_head_size {
+                return Err(self.reject(431));
+            }
+            if !self.fill()? {
+                return if self.buffer.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(closed_early())
+                };
+            }
+        }
+    }
+
+    // up to max bytes of the body, empty once all of it was read
+    pub fn read_body(&mut self, max: usize) -> io::Result<Vec<u8>> {
+        let len = max.min(self.body_left);
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        if self.buffer.is_empty() && !self.fill()? {
+            return Err(closed_early());
+        }
+        let len = len.min(self.buffer.len());
+        self.body_left -= len;
+        Ok(self.buffer.drain(..len).collect())
+    }
+
+    // reads more from the client, false once it closed the connection. a
+    // request that isn't complete by its deadline is answered with 408
+    fn fill(&mut self) -> io::Result<bool> {
+        if let Some(deadline) = self.deadline {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left == Duration::from_secs(0) {
+                return Err(self.reject(408));
+            }
+            let timeout = self
+                .limits
+                .read_timeout
+                .map_or(left, |timeout| timeout.min(left));
+            self.stream.set_read_timeout(Some(timeout))?;
+        }
+        let mut chunk = [0; READ_SIZE];
+        let read = match self.stream.read(&mut chunk) {
+            Err(_)
+                if self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline) =>
+            {
+                return Err(self.reject(408));
+            }
+            read => read?,
+        };
+        self.buffer.extend_from_slice(&chunk[..read]);
+        Ok(read > 0)
+    }
+
+    // a request if the buffer has its complete head, which is then taken out
+    fn parse(&mut self) -> io::Result<Option<Request>> {
+        let mut headers = vec![httparse::EMPTY_HEADER; self.limits.max_headers];
+        let mut parsed = httparse::Request::new(&mut headers);
+        let head_len = match parsed.parse(&self.buffer) {
+            Ok(httparse::Status::Complete(len)) if len > self.limits.max_head_size => {
+                return Err(self.reject(431))
+            }
+            Ok(httparse::Status::Complete(len)) => len,
+            Ok(httparse::Status::Partial) => return Ok(None),
+            Err(httparse::Error::TooManyHeaders) => return Err(self.reject(431)),
+            Err(error) => return Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+        };
+        let headers: Vec<(String, String)> = parsed
+            .headers
+            .iter()
+            .map(|header| {
+                (
+                    header.name.to_ascii_lowercase(),
+                    String::from_utf8_lossy(header.value).into_owned(),
+                )
+            })
+            .collect();
+        // bodies are only taken with a length. a request with both, or with
+        // lengths that differ, could be read differently by a proxy in front
+        let lengths: Vec<&str> = headers
+            .iter()
+            .filter(|(name, _)| name == "content-length")
+            .map(|(_, value)| value.trim())
+            .collect();
+        if headers.iter().any(|(name, _)| name == "transfer-encoding") {
+            return Err(self.reject(if lengths.is_empty() { 501 } else { 400 }));
+        }
+        if lengths.windows(2).any(|pair| pair[0] != pair[1]) {
+            return Err(self.reject(400));
+        }
+        let content_length = match lengths.first() {
+            Some(value) => value.parse::<usize>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid content-length header")
+            })?,
+            None => 0,
+        };
+        if content_length > self.limits.max_body_size {
+            return Err(self.reject(413));
+        }
+        let version = parsed.version.unwrap_or(1);
+        let connection = headers
+            .iter()
+            .find(|(name, _)| name == "connection")
+            .map(|(_, value)| value.to_ascii_lowercase());
+        self.keep_alive = match connection.as_deref() {
+            Some("close") => false,
+            Some("keep-alive") => true,
+            _ => version == 1,
+        };
+        self.conditions = Conditions::new(&headers);
+        let request = Request {
+            method: String::from(parsed.method.unwrap_or_default()),
+            path: String::from(parsed.path.unwrap_or_default()),
+            version: format!("HTTP/1.{}", version),
+            headers,
+            body: Vec::new(),
+            body_file: None,
+        };
+        self.entry = Some(Entry::new(format!(
+            "{} {} {}",
+            request.method, request.path, request.version
+        )));
+        self.buffer.drain(..head_len);
+        self.body_left = content_length;
+        self.cookies.clear();
+        Ok(Some(request))
+    }
+
+    // answers a request over the limits or one that can't be read with the
+    // status and returns the error for the read. the connection can't be
+    // used after that
+    fn reject(&mut self, status: i64) -> io::Error {
+        let response = Response {
+            status,
+            headers: Vec::new(),
+            body: String::from(reason_phrase(status)),
+        };
+        self.keep_alive = false;
+        let _ = self.respond(&response);
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the request was rejected with {}", status),
+        )
+    }
+
+    fn send(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.write_all(bytes)?;
+        self.stream.flush()
+    }
+
+    // answers the request read last with the whole response
+    pub fn respond(&mut self, response: &Response) -> io::Result<()> {
+        // an error page replaces the body, so its length is only known after
+        let response = self.prepare(response);
+        self.send_whole(&response)
+    }
+
+    fn send_whole(&mut self, response: &Response) -> io::Result<()> {
+        self.write_head(response, Some(response.body.len() as u64))?;
+        self.send(response.body.as_bytes())?;
+        self.count_sent(response.body.len() as u64);
+        self.log();
+        Ok(())
+    }
+
+    // starts a response whose body follows with send_from, or in chunks if
+    // it has no length
+    pub fn send_head(&mut self, response: &Response, content_len: Option<u64>) -> io::Result<()> {
+        let response = self.prepare(response);
+        self.write_head(&response, content_len)
+    }
+
+    // send_head for a response relayed from another server. false if the
+    // middleware replaced the body with an error page, which was sent whole
+    // instead
+    pub fn relay_head(
+        &mut self,
+        response: &Response,
+        content_len: Option<u64>,
+    ) -> io::Result<bool> {
+        let response = self.prepare(response);
+        if !response.body.is_empty() {
+            self.send_whole(&response)?;
+            return Ok(false);
+        }
+        self.write_head(&response, content_len)?;
+        Ok(true)
+    }
+
+    // adds the session cookies and runs the middleware over a response
+    fn prepare(&mut self, response: &Response) -> Response {
+        let mut response = response.clone();
+        for cookie in self.cookies.drain(..) {
+            response.headers.push((String::from("Set-Cookie"), cookie));
+        }
+        middleware::after(&self.middleware, &mut response);
+        response
+    }
+
+    fn write_head(&mut self, response: &Response, content_len: Option<u64>) -> io::Result<()> {
+        if let Some(entry) = &mut self.entry {
+            entry.status = response.status;
+        }
+        self.send(response.head(self.keep_alive, content_len).as_bytes())
+    }
+
+    // copies a body that isn't in memory, returns how many bytes were sent
+    pub fn send_from(&mut self, body: &mut dyn Read) -> io::Result<u64> {
+        let sent = io::copy(body, &mut self.stream)?;
+        self.stream.flush()?;
+        self.count_sent(sent);
+        Ok(sent)
+    }
+
+    // a piece of a chunked body, an empty one ends it
+    pub fn send_chunk(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut chunk = format!("{:x}\r\n", bytes.len()).into_bytes();
+        chunk.extend_from_slice(bytes);
+        chunk.extend_from_slice(b"\r\n");
+        self.send(&chunk)?;
+        self.count_sent(bytes.len() as u64);
+        Ok(())
+    }
+
+    fn count_sent(&mut self, len: u64) {
+        if let Some(entry) = &mut self.entry {
+            entry.sent += len;
+        }
+    }
+
+    // counts the answered request for the metrics middleware and writes it
+    // to the access log of the server, if it has one. called once the whole
+    // response was sent
+    pub fn log(&mut self) {
+        let entry = match self.entry.take() {
+            Some(entry) => entry,
+            None => return,
+        };
+        middleware::record(&self.middleware, entry.status, entry.elapsed());
+        if let Some(log) = &self.log {
+            access::write(log, self.peer, &entry);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Response {
+    pub status: i64,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    // the head for a body of content_len bytes, which may be sent separately,
+    // or for a chunked body without a length. Date, Server and Content-Type
+    // are added unless the response has them
+    pub fn head(&self, keep_alive: bool, content_len: Option<u64>) -> String {
+        let has_header = |name: &str| {
+            self.headers
+                .iter()
+                .any(|(header, _)| header.eq_ignore_ascii_case(name))
+        };
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nConnection: {}\r\n",
+            self.status,
+            reason_phrase(self.status),
+            if keep_alive { "keep-alive" } else { "close" }
+        );
+        if !has_header("date") {
+            head.push_str(&format!(
+                "Date: {}\r\n",
+                httpdate::fmt_http_date(SystemTime::now())
+            ));
+        }
+        if !has_header("server") {
+            head.push_str(concat!(
+                "Server: olvweb/",
+                env!("CARGO_PKG_VERSION"),
+                "\r\n"
+            ));
+        }
+        if !has_header("content-type") {
+            head.push_str("Content-Type: text/html; charset=utf-8\r\n");
+        }
+        for (name, value) in &self.headers {
+            if !name.eq_ignore_ascii_case("connection")
+                && !name.eq_ignore_ascii_case("content-length")
+                && !name.eq_ignore_ascii_case("transfer-encoding")
+            {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        match content_len {
+            Some(len) => head.push_str(&format!("Content-Length: {}\r\n\r\n", len)),
+            None => head.push_str("Transfer-Encoding: chunked\r\n\r\n"),
+        }
+        head
+    }
+}
+
+// the phrase is only there for people reading the response, statuses not in
+// here get an empty one
+pub fn reason_phrase(status: i64) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+fn closed_early() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "the client closed the connection in the middle of a request",
+    )
+}
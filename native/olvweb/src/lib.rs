@@ -1,138 +1,777 @@
-use httparse;
-use olvnative::{Object, RuntimeError};
+// http server functions for scripts, added to an engine with register.
+// servers, pools, clients and pending requests reach scripts as integer
+// handles, which only mean something to the engine they came from
+use http::{Client, Request, Response, Server};
+use olive::{Engine, Value};
+use pool::Pool;
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::io;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
-macro_rules! rc {
-    ($e: expr) => {
-        Rc::new(RefCell::new($e))
-    };
+mod access;
+mod body;
+mod cookie;
+mod fetch;
+mod files;
+mod http;
+mod metrics;
+mod middleware;
+mod negotiate;
+mod pool;
+mod proxy;
+mod session;
+mod socket;
+mod template;
+
+// how many threads recv_req_async may have reading at once. a cancelled
+// one still counts until it stopped
+const MAX_PENDING: usize = 16;
+
+// a request read on another thread, see recv_req_async. dropping it before
+// the request came in stops the thread
+struct Pending {
+    receiver: Receiver<Result<(Client, Request), String>>,
+    cancelled: Arc<AtomicBool>,
+    // for waking the thread
+    server: Server,
 }
 
-macro_rules! generic_err {
-    ($e: expr) => {
-        $e.map_err(|e| RuntimeError::Error(format!("{}", e)))?
-    };
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // a thread still waiting in accept only sees the flag once a
+        // connection comes in. one that got its request has already stopped
+        if let Err(TryRecvError::Empty) = self.receiver.try_recv() {
+            let _ = self.server.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+struct Web {
+    next_handle: i64,
+    servers: HashMap<i64, Server>,
+    clients: HashMap<i64, Client>,
+    pending: HashMap<i64, Pending>,
+    // the threads of pending requests that are still running
+    readers: Arc<AtomicUsize>,
+    pools: HashMap<i64, Pool>,
+    sessions: session::Sessions,
 }
 
-type RcObject = Rc<RefCell<Object>>;
+// adds the functions to a global web bendy, e.g. web.create_server(address):
+//   create_server(address) listens on the address, e.g. "127.0.0.1:8080", or
+//     on a unix domain socket for addresses like "unix:/run/app.sock". the
+//     addr of requests from a unix socket is "unix"
+//   configure(server, options) sets the limits for clients accepted after
+//     that, options is a bendy of read_timeout and request_timeout, the
+//     time for the whole request, in milliseconds (0 waits forever),
+//     max_headers, max_head_size and max_body_size in bytes, and
+//     max_connections_per_ip (0 for no limit). requests over them are
+//     answered with 431, 413, 429 or 408 and skipped, and requests with a
+//     Transfer-Encoding with 501, or 400 if they have a Content-Length as
+//     well. bodies over
+//     spool_body_size (0 keeps all in memory) are written to a temporary
+//     file, whose path is body_file of the request instead of the content
+//     (none for bodies in memory). the file is removed with the next request
+//     on the connection
+//   access_log(server, target) logs the requests of clients accepted after
+//     that in common log format, with the milliseconds the answer took at
+//     the end. target is a file the lines are appended to, "-" for stdout or
+//     none to stop logging
+//   use(server, name, options) runs middleware for every request of clients
+//     accepted after that, in the order it was added. options is none or a
+//     bendy, name is one of
+//       headers: adds the options as headers to every response
+//       cors: answers preflight requests and adds the Access-Control-Allow
+//         headers for the origin, methods and headers options
+//       basic_auth: answers requests without the user and password options
+//         with 401, for the realm option
+//       error_page: replaces the body of responses with the status option
+//         with the body option
+//       rate_limit: answers with 429 once an address sent more than the
+//         requests option within the seconds option (1 if left out),
+//         allowing bursts of up to the burst option
+//       metrics: counts all responses and answers GET requests for the path
+//         option (/metrics if left out) with the counts, error counts,
+//         latency percentiles and the memory of the process in the
+//         prometheus text format
+//   recv_req(server) waits for the next request
+//   recv_req_async(server) returns a pending handle right away, the request
+//     is read on another thread. at most 16 can be pending at once
+//   poll_req(pending) returns none while the request isn't there yet, then
+//     the request once. a failed read fails the call
+//   cancel_req(pending) stops waiting for a pending request, a connection
+//     it already accepted is closed
+//   serve_pool(server, workers) starts worker threads that accept clients
+//     and read their requests concurrently, returns the pool
+//   next_req(pool) waits for the next request the workers read
+//   keep_alive(pool, client) hands an answered client back to the pool, its
+//     next request comes out of next_req. the handle can't be used after that
+//   send_res(client, response) answers the request read last from the
+//     client. the response is the content as a string, or a bendy with a
+//     status (200 if left out), headers by name and a body. the connection
+//     is closed afterwards if the client asked for that
+//   recv_from(client) waits for the next request on a connection that was
+//     kept open, none once the client closed it
+//   close(client) closes the connection
+//   serve_file(client, path) answers with the file at the request path,
+//     relative to the working directory, or 404 if there is none. a Range
+//     header gets only that part of the file with 206. files are sent with
+//     an ETag and Last-Modified, and requests whose If-None-Match or
+//     If-Modified-Since they match get 304. returns the status sent
+//   proxy(client, upstream, request) forwards the request, which may have
+//     been changed, e.g. its path, to the server at the upstream address,
+//     which may be a unix: one too, and relays the response through the
+//     middleware. the client's address is added to X-Forwarded-For. answers
+//     with 502 if the upstream can't be reached, returns the status sent
+//   recv_head(server) is recv_req for large bodies, the request has an empty
+//     content and read_body(client, max) returns the next up to max bytes of
+//     it as a list, none once all of it was read
+//   send_headers(client, response) starts a response with a body of any
+//     size, the body of the response is left out. send_chunk(client, data)
+//     sends the next piece as a string or a list of bytes, and finish(client)
+//     ends the response
+//   set_cookie(response, name, value, options) returns the response with a
+//     Set-Cookie header added. options is none or a bendy of path, domain,
+//     expires, max_age, same_site, http_only and secure. names, values and
+//     attributes RFC 6265 doesn't allow are errors
+//   fetch(url, options) sends a request to another server and returns the
+//     response as a bendy of status, headers and body. options is none or a
+//     bendy of method, headers, body and timeout in milliseconds
+//   render(template, context) fills the template from the context bendy:
+//     {{ name }} inserts a value with html escaped, {{ name | raw }} inserts
+//     it as is, names may be paths like user.name or items.0. {% if name %},
+//     {% if not name %}, {% else %} and {% for item in list %} blocks are
+//     closed with {% end %}. missing values are none, which inserts nothing,
+//     counts as false and loops zero times
+//   mime_for_extension(extension) returns the content type serve_file sends
+//     for files with the extension, e.g. "png" or ".png"
+//   session_start(request) continues the session named by the olvsession
+//     cookie of the request or starts a new one and returns its id. the
+//     response to the request sets the cookie again, so the session lasts
+//     for its ttl after the last request that started it
+//   session_get(id, key) returns a value of the session, none if it or the
+//     session doesn't exist, and session_set(id, key, value) sets one
+//   session_end(request) ends the session of the request, e.g. on logout,
+//     and removes its cookie with the response
+//   session_options(options) sets cookie (the name), ttl in seconds and
+//     secure for sessions started after that
+//   accepts(request, type) is true if the Accept header of the request allows
+//     the media type, preferred_type(request, types) returns the one of the
+//     list it gives the highest q-value, or none if it allows none of them
+// requests are bendys with method, path, version, headers (by lowercase
+// name), content, addr and the client to answer. form bodies are decoded into
+// a form bendy as well, and multipart bodies into a list of parts, see
+// body::multipart. cookies are in a bendy by name
+pub fn register(engine: &mut Engine) {
+    let web = Rc::new(RefCell::new(Web::default()));
+    function(engine, &web, "create_server", 1, Web::create_server);
+    function(engine, &web, "configure", 2, Web::configure);
+    function(engine, &web, "access_log", 2, Web::access_log);
+    function(engine, &web, "use", 3, Web::use_middleware);
+    function(engine, &web, "recv_req", 1, Web::recv_req);
+    function(engine, &web, "recv_req_async", 1, Web::recv_req_async);
+    function(engine, &web, "poll_req", 1, Web::poll_req);
+    function(engine, &web, "cancel_req", 1, Web::cancel_req);
+    function(engine, &web, "serve_pool", 2, Web::serve_pool);
+    function(engine, &web, "next_req", 1, Web::next_req);
+    function(engine, &web, "keep_alive", 2, Web::keep_alive);
+    function(engine, &web, "send_res", 2, Web::send_res);
+    function(engine, &web, "recv_from", 1, Web::recv_from);
+    function(engine, &web, "close", 1, Web::close);
+    function(engine, &web, "serve_file", 2, Web::serve_file);
+    function(engine, &web, "proxy", 3, Web::proxy);
+    function(engine, &web, "recv_head", 1, Web::recv_head);
+    function(engine, &web, "read_body", 2, Web::read_body);
+    function(engine, &web, "send_headers", 2, Web::send_headers);
+    function(engine, &web, "send_chunk", 2, Web::send_chunk);
+    function(engine, &web, "finish", 1, Web::finish);
+    function(engine, &web, "session_start", 1, Web::session_start);
+    function(engine, &web, "session_get", 2, Web::session_get);
+    function(engine, &web, "session_set", 3, Web::session_set);
+    function(engine, &web, "session_end", 1, Web::session_end);
+    function(engine, &web, "session_options", 1, Web::session_options);
+    engine.register_module_fn("web", "set_cookie", 4, cookie::set_cookie);
+    engine.register_module_fn("web", "fetch", 2, fetch::fetch);
+    engine.register_module_fn("web", "render", 2, template::render);
+    engine.register_module_fn(
+        "web",
+        "mime_for_extension",
+        1,
+        negotiate::mime_for_extension,
+    );
+    engine.register_module_fn("web", "accepts", 2, negotiate::accepts);
+    engine.register_module_fn("web", "preferred_type", 2, negotiate::preferred_type);
+}
 
-fn to_ptr<T>(obj: T) -> *mut usize {
-    let listener = Box::new(obj);
-    Box::into_raw(listener) as *mut usize
+fn function<F>(engine: &mut Engine, web: &Rc<RefCell<Web>>, name: &str, arg_count: u32, method: F)
+where
+    F: Fn(&mut Web, &[Value]) -> Result<Value, String> + 'static,
+{
+    let web = web.clone();
+    engine.register_module_fn("web", name, arg_count, move |args| {
+        method(&mut web.borrow_mut(), &args)
+    });
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn n_bind(args: Box<Vec<RcObject>>) -> Result<RcObject, RuntimeError> {
-    if let Object::Str(addr) = &*args[0].borrow() {
-        let listener = generic_err!(TcpListener::bind(addr));
-        Ok(rc!(Object::Pointer(to_ptr(listener))))
-    } else {
-        Err(RuntimeError::TypeError)
+fn string<'a>(value: &'a Value, function: &str, what: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(value) => Ok(value),
+        _ => Err(format!("{} expects {}", function, what)),
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn n_recv(args: Box<Vec<RcObject>>) -> Result<RcObject, RuntimeError> {
-    if let Object::Pointer(ptr) = &*args[0].borrow() {
-        let listener_ptr: *mut TcpListener = std::mem::transmute(*ptr);
-        let listener = &*listener_ptr;
-        let (mut client, addr) = generic_err!(listener.accept());
-
-        let mut data: Vec<u8> = Vec::new();
-        loop {
-            let mut buffer: [u8; 20] = [0; 20];
-            let read_bytes = generic_err!(client.read(&mut buffer));
-            data.append(&mut (buffer[0..read_bytes].to_vec()));
-            if let Some(mut result) = parse(&data, addr.to_string())? {
-                result.insert(String::from("client"), rc!(Object::Pointer(to_ptr(client))));
-                return Ok(rc!(Object::Bendy(result)));
+// a line break in a value would end the header there and let the rest of
+// it become a header or body of its own
+fn header_value(value: &Value) -> Result<String, String> {
+    let value = match value {
+        Value::String(value) => value.clone(),
+        Value::Integer(value) => value.to_string(),
+        _ => return Err(String::from("header values have to be strings or integers")),
+    };
+    if value.contains(['\r', '\n', '\0']) {
+        return Err(format!("invalid header value {:?}", value));
+    }
+    Ok(value)
+}
+
+// names are tokens, see RFC 7230 section 3.2.6
+fn is_token(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c))
+}
+
+// a list of values sends the header once for each of them, like the
+// Set-Cookie headers from set_cookie
+fn add_headers(
+    headers: &mut Vec<(String, String)>,
+    name: &str,
+    value: &Value,
+) -> Result<(), String> {
+    if !is_token(name) {
+        return Err(format!("invalid header name {:?}", name));
+    }
+    match value {
+        Value::List(values) => {
+            for value in values {
+                headers.push((String::from(name), header_value(value)?));
             }
         }
-    } else {
-        Err(RuntimeError::TypeError)
+        value => headers.push((String::from(name), header_value(value)?)),
     }
+    Ok(())
 }
 
-fn parse(content: &[u8], addr: String) -> Result<Option<HashMap<String, RcObject>>, RuntimeError> {
-    let mut headers = [httparse::EMPTY_HEADER; 64];
-    let mut req = httparse::Request::new(&mut headers);
-    if let Ok(r) = req.parse(content) {
-        if r.is_partial() {
-            return Ok(None);
+fn to_response(value: &Value, function: &str) -> Result<Response, String> {
+    let bendy = match value {
+        Value::String(body) => {
+            return Ok(Response {
+                status: 200,
+                headers: Vec::new(),
+                body: body.clone(),
+            })
+        }
+        Value::Bendy(bendy) => bendy,
+        _ => {
+            return Err(format!(
+                "{} expects the response as a string or bendy",
+                function
+            ))
         }
-    } else {
-        return Ok(None);
-    }
-    let strval = match String::from_utf8(content.to_vec()) {
-        Ok(s) => s,
-        Err(_) => return Ok(None),
     };
-    let parts: Vec<&str> = strval.split("\r\n\r\n").collect();
-    if parts.len() != 2 {
-        return Ok(None);
-    }
-    let content: String = String::from(parts[1]);
-    let path: String = String::from(req.path.unwrap());
-    let method: String = String::from(req.method.unwrap());
-    let version: String = req.version.unwrap().to_string();
-    let mut headers = HashMap::new();
-
-    for header in req.headers {
-        let name: String = String::from(header.name);
-        headers.insert(
-            name.to_ascii_lowercase(),
-            rc!(Object::Str(generic_err!(String::from_utf8(
-                header.value.to_vec()
-            )))),
+    let status = match bendy.get("status") {
+        Some(Value::Integer(status)) if (100..1000).contains(status) => *status,
+        None => 200,
+        _ => return Err(format!("{} expects a status from 100 to 999", function)),
+    };
+    let body = match bendy.get("body") {
+        Some(Value::String(body)) => body.clone(),
+        None | Some(Value::None) => String::new(),
+        _ => return Err(format!("{} expects the body as a string", function)),
+    };
+    let mut headers = Vec::new();
+    match bendy.get("headers") {
+        Some(Value::Bendy(values)) => {
+            for (name, value) in values {
+                add_headers(&mut headers, name, value)?;
+            }
+        }
+        None | Some(Value::None) => {}
+        _ => return Err(format!("{} expects the headers as a bendy", function)),
+    }
+    // bendys have no order, this keeps responses the same from run to run.
+    // values from one list stay in their order
+    headers.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(Response {
+        status,
+        headers,
+        body,
+    })
+}
+
+fn bytes(value: &Value, function: &str) -> Result<Vec<u8>, String> {
+    match value {
+        Value::String(value) => Ok(value.as_bytes().to_vec()),
+        Value::List(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Integer(byte) if *byte >= 0 && *byte < 256 => Ok(*byte as u8),
+                _ => Err(format!(
+                    "{} expects a list of bytes from 0 to 255",
+                    function
+                )),
+            })
+            .collect(),
+        _ => Err(format!("{} expects a string or a list of bytes", function)),
+    }
+}
+
+fn handle(value: &Value, function: &str, what: &str) -> Result<i64, String> {
+    match value {
+        Value::Integer(handle) => Ok(*handle),
+        _ => Err(format!("{} expects {}", function, what)),
+    }
+}
+
+impl Web {
+    fn add_handle(&mut self) -> i64 {
+        self.next_handle += 1;
+        self.next_handle
+    }
+
+    fn server(&mut self, value: &Value, function: &str) -> Result<&mut Server, String> {
+        let server = handle(value, function, "a server")?;
+        self.servers
+            .get_mut(&server)
+            .ok_or_else(|| format!("{} got an unknown server", function))
+    }
+
+    fn create_server(&mut self, args: &[Value]) -> Result<Value, String> {
+        let address = string(&args[0], "create_server", "an address")?;
+        let server = Server::bind(address)
+            .map_err(|error| format!("couldn't listen on '{}': {}", address, error))?;
+        let handle = self.add_handle();
+        self.servers.insert(handle, server);
+        Ok(Value::Integer(handle))
+    }
+
+    fn access_log(&mut self, args: &[Value]) -> Result<Value, String> {
+        let log = match &args[1] {
+            Value::String(target) => Some(
+                access::open(target)
+                    .map_err(|error| format!("couldn't open access log '{}': {}", target, error))?,
+            ),
+            Value::None => None,
+            _ => return Err(String::from("access_log expects a path, \"-\" or none")),
+        };
+        self.server(&args[0], "access_log")?.log = log;
+        Ok(Value::None)
+    }
+
+    fn use_middleware(&mut self, args: &[Value]) -> Result<Value, String> {
+        let name = string(&args[1], "use", "the name of a middleware")?;
+        let middleware = match &args[2] {
+            Value::Bendy(options) => middleware::new(name, options)?,
+            Value::None => middleware::new(name, &HashMap::new())?,
+            _ => return Err(String::from("use expects the options as a bendy")),
+        };
+        let server = self.server(&args[0], "use")?;
+        Arc::make_mut(&mut server.middleware).push(middleware);
+        Ok(Value::None)
+    }
+
+    fn configure(&mut self, args: &[Value]) -> Result<Value, String> {
+        let options = match &args[1] {
+            Value::Bendy(options) => options,
+            _ => return Err(String::from("configure expects the options as a bendy")),
+        };
+        let limits = &mut self.server(&args[0], "configure")?.limits;
+        for (name, value) in options {
+            let value = match value {
+                Value::Integer(value) if *value >= 0 => *value as usize,
+                _ => return Err(format!("configure expects {} as an integer from 0", name)),
+            };
+            match name.as_str() {
+                "read_timeout" if value == 0 => limits.read_timeout = None,
+                "read_timeout" => limits.read_timeout = Some(Duration::from_millis(value as u64)),
+                "request_timeout" if value == 0 => limits.request_timeout = None,
+                "request_timeout" => {
+                    limits.request_timeout = Some(Duration::from_millis(value as u64))
+                }
+                "max_headers" => limits.max_headers = value,
+                "max_head_size" => limits.max_head_size = value,
+                "max_body_size" => limits.max_body_size = value,
+                "max_connections_per_ip" => limits.max_connections_per_ip = value,
+                "spool_body_size" => limits.spool_body_size = value,
+                _ => return Err(format!("invalid server option '{}'", name)),
+            }
+        }
+        Ok(Value::None)
+    }
+
+    fn recv_req(&mut self, args: &[Value]) -> Result<Value, String> {
+        let accepted = self
+            .server(&args[0], "recv_req")?
+            .accept()
+            .map_err(|error| format!("recv_req failed: {}", error))?;
+        Ok(self.add_client(accepted))
+    }
+
+    fn recv_req_async(&mut self, args: &[Value]) -> Result<Value, String> {
+        if self.readers.load(Ordering::SeqCst) >= MAX_PENDING {
+            return Err(format!(
+                "recv_req_async can't have more than {} pending requests",
+                MAX_PENDING
+            ));
+        }
+        let server = self.server(&args[0], "recv_req_async")?;
+        let (reader, waker) = match (server.try_clone(), server.try_clone()) {
+            (Ok(reader), Ok(waker)) => (reader, waker),
+            (Err(error), _) | (_, Err(error)) => {
+                return Err(format!("recv_req_async failed: {}", error))
+            }
+        };
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let stop = cancelled.clone();
+        let readers = self.readers.clone();
+        readers.fetch_add(1, Ordering::SeqCst);
+        thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                match reader.accept_one() {
+                    Ok(Some(accepted)) if !stop.load(Ordering::SeqCst) => {
+                        let _ = sender.send(Ok(accepted));
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(error) => {
+                        let _ = sender.send(Err(error.to_string()));
+                        break;
+                    }
+                }
+            }
+            readers.fetch_sub(1, Ordering::SeqCst);
+        });
+        let pending = self.add_handle();
+        self.pending.insert(
+            pending,
+            Pending {
+                receiver,
+                cancelled,
+                server: waker,
+            },
         );
+        Ok(Value::Integer(pending))
+    }
+
+    fn poll_req(&mut self, args: &[Value]) -> Result<Value, String> {
+        let pending = handle(&args[0], "poll_req", "a pending request")?;
+        let receiver = &self
+            .pending
+            .get(&pending)
+            .ok_or_else(|| String::from("poll_req got an unknown pending request"))?
+            .receiver;
+        let accepted = match receiver.try_recv() {
+            Ok(accepted) => accepted,
+            Err(TryRecvError::Empty) => return Ok(Value::None),
+            Err(TryRecvError::Disconnected) => Err(String::from("the reading thread stopped")),
+        };
+        self.pending.remove(&pending);
+        let accepted = accepted.map_err(|error| format!("poll_req failed: {}", error))?;
+        Ok(self.add_client(accepted))
+    }
+
+    fn cancel_req(&mut self, args: &[Value]) -> Result<Value, String> {
+        let pending = handle(&args[0], "cancel_req", "a pending request")?;
+        self.pending
+            .remove(&pending)
+            .ok_or_else(|| String::from("cancel_req got an unknown pending request"))?;
+        Ok(Value::None)
     }
 
-    let content_len = if let Some(content_len_obj) = headers.get(&String::from("content-length")) {
-        if let Object::Str(content_len_str) = &*content_len_obj.borrow() {
-            content_len_str.parse::<usize>().unwrap()
-        } else {
-            return Err(RuntimeError::TypeError)
+    fn pool(&self, value: &Value, function: &str) -> Result<&Pool, String> {
+        let pool = handle(value, function, "a pool")?;
+        self.pools
+            .get(&pool)
+            .ok_or_else(|| format!("{} got an unknown pool", function))
+    }
+
+    fn serve_pool(&mut self, args: &[Value]) -> Result<Value, String> {
+        let workers = match &args[1] {
+            Value::Integer(workers) if *workers > 0 => *workers as usize,
+            _ => return Err(String::from("serve_pool expects at least one worker")),
+        };
+        let pool = Pool::new(self.server(&args[0], "serve_pool")?, workers)
+            .map_err(|error| format!("serve_pool failed: {}", error))?;
+        let handle = self.add_handle();
+        self.pools.insert(handle, pool);
+        Ok(Value::Integer(handle))
+    }
+
+    fn next_req(&mut self, args: &[Value]) -> Result<Value, String> {
+        let accepted = self
+            .pool(&args[0], "next_req")?
+            .next()
+            .map_err(|_| String::from("next_req failed: the workers stopped"))?;
+        Ok(self.add_client(accepted))
+    }
+
+    fn keep_alive(&mut self, args: &[Value]) -> Result<Value, String> {
+        self.pool(&args[0], "keep_alive")?;
+        let (client, _) = self.client(&args[1], "keep_alive")?;
+        let client = self.clients.remove(&client).unwrap();
+        self.pool(&args[0], "keep_alive")?.keep_alive(client);
+        Ok(Value::None)
+    }
+
+    fn client(&mut self, value: &Value, function: &str) -> Result<(i64, &mut Client), String> {
+        let client = handle(value, function, "a client")?;
+        match self.clients.get_mut(&client) {
+            Some(found) => Ok((client, found)),
+            None => Err(format!("{} got an unknown or closed client", function)),
         }
-    } else {
-        0
-    };
+    }
 
-    if content.len() != content_len {
-        return Ok(None);
+    // keeps the client for the answer and gives the request to the script
+    fn add_client(&mut self, (client, request): (Client, Request)) -> Value {
+        let handle = self.add_handle();
+        let addr = client.peer.to_string();
+        self.clients.insert(handle, client);
+        to_bendy(request, handle, addr)
     }
 
-    let mut map = HashMap::new();
-    map.insert(String::from("content"), rc!(Object::Str(content)));
-    map.insert(String::from("path"), rc!(Object::Str(path)));
-    map.insert(String::from("version"), rc!(Object::Str(version)));
-    map.insert(String::from("method"), rc!(Object::Str(method)));
-    map.insert(String::from("headers"), rc!(Object::Bendy(headers)));
-    map.insert(String::from("addr"), rc!(Object::Str(addr)));
-    Ok(Some(map))
-}
+    // answers the client with respond and closes the connection if it isn't
+    // kept alive
+    fn answer<F>(&mut self, value: &Value, function: &str, respond: F) -> Result<Value, String>
+    where
+        F: FnOnce(&mut Client) -> io::Result<Value>,
+    {
+        let (handle, client) = self.client(value, function)?;
+        let keep_alive = client.keep_alive;
+        let answered = respond(client);
+        // a connection that failed once is no use anymore
+        if answered.is_err() || !keep_alive {
+            self.clients.remove(&handle);
+        }
+        answered.map_err(|error| format!("{} failed: {}", function, error))
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn n_send(args: Box<Vec<RcObject>>) -> Result<RcObject, RuntimeError> {
-    if let Object::Pointer(ptr) = &*args[0].borrow() {
-        if let Object::Str(data) = &*args[1].borrow() {
-            let stream_ptr: *mut TcpStream = std::mem::transmute(*ptr);
-            let mut stream = &*stream_ptr;
-            generic_err!(stream.write(create_res(data).as_bytes()));
-            Ok(rc!(Object::None))
-        } else {
-            Err(RuntimeError::TypeError)
+    fn send_res(&mut self, args: &[Value]) -> Result<Value, String> {
+        let response = to_response(&args[1], "send_res")?;
+        self.answer(&args[0], "send_res", |client| {
+            client.respond(&response)?;
+            Ok(Value::None)
+        })
+    }
+
+    fn serve_file(&mut self, args: &[Value]) -> Result<Value, String> {
+        let path = string(&args[1], "serve_file", "a path")?;
+        self.answer(&args[0], "serve_file", |client| {
+            files::serve(client, path).map(Value::Integer)
+        })
+    }
+
+    fn proxy(&mut self, args: &[Value]) -> Result<Value, String> {
+        let upstream = string(&args[1], "proxy", "an upstream address")?;
+        let forward = match &args[2] {
+            Value::Bendy(request) => proxy::forward(request, upstream)?,
+            _ => return Err(String::from("proxy expects a request")),
+        };
+        self.answer(&args[0], "proxy", |client| {
+            proxy::relay(client, upstream, forward).map(Value::Integer)
+        })
+    }
+
+    fn recv_head(&mut self, args: &[Value]) -> Result<Value, String> {
+        let accepted = self
+            .server(&args[0], "recv_head")?
+            .accept_head()
+            .map_err(|error| format!("recv_head failed: {}", error))?;
+        Ok(self.add_client(accepted))
+    }
+
+    fn read_body(&mut self, args: &[Value]) -> Result<Value, String> {
+        let max = match &args[1] {
+            Value::Integer(max) if *max > 0 => *max as usize,
+            _ => return Err(String::from("read_body expects the most bytes to read")),
+        };
+        let (handle, client) = self.client(&args[0], "read_body")?;
+        match client.read_body(max) {
+            Ok(piece) if piece.is_empty() => Ok(Value::None),
+            Ok(piece) => Ok(Value::List(
+                piece
+                    .into_iter()
+                    .map(|byte| Value::Integer(byte as i64))
+                    .collect(),
+            )),
+            Err(error) => {
+                self.clients.remove(&handle);
+                Err(format!("read_body failed: {}", error))
+            }
         }
-    } else {
-    Err(RuntimeError::TypeError)
     }
-}
 
+    fn send_headers(&mut self, args: &[Value]) -> Result<Value, String> {
+        let response = to_response(&args[1], "send_headers")?;
+        let (handle, client) = self.client(&args[0], "send_headers")?;
+        client.send_head(&response, None).map_err(|error| {
+            self.clients.remove(&handle);
+            format!("send_headers failed: {}", error)
+        })?;
+        Ok(Value::None)
+    }
+
+    fn send_chunk(&mut self, args: &[Value]) -> Result<Value, String> {
+        let data = bytes(&args[1], "send_chunk")?;
+        let (handle, client) = self.client(&args[0], "send_chunk")?;
+        // an empty chunk would end the body
+        if data.is_empty() {
+            return Ok(Value::None);
+        }
+        client.send_chunk(&data).map_err(|error| {
+            self.clients.remove(&handle);
+            format!("send_chunk failed: {}", error)
+        })?;
+        Ok(Value::None)
+    }
+
+    fn finish(&mut self, args: &[Value]) -> Result<Value, String> {
+        self.answer(&args[0], "finish", |client| {
+            client.send_chunk(&[])?;
+            client.log();
+            Ok(Value::None)
+        })
+    }
+
+    fn recv_from(&mut self, args: &[Value]) -> Result<Value, String> {
+        let (handle, client) = self.client(&args[0], "recv_from")?;
+        match client.read_request() {
+            Ok(Some(request)) => {
+                let addr = client.peer.to_string();
+                Ok(to_bendy(request, handle, addr))
+            }
+            // a broken connection is as good as a closed one
+            Ok(None) | Err(_) => {
+                self.clients.remove(&handle);
+                Ok(Value::None)
+            }
+        }
+    }
+
+    // the session id in the cookies of the request and the client answering it
+    fn session_request(
+        &mut self,
+        value: &Value,
+        function: &str,
+    ) -> Result<(Option<String>, i64), String> {
+        let request = match value {
+            Value::Bendy(request) => request,
+            _ => return Err(format!("{} expects a request", function)),
+        };
+        let client = request.get("client").unwrap_or(&Value::None);
+        let (client, _) = self.client(client, function)?;
+        Ok((self.sessions.id_of(request), client))
+    }
+
+    fn session_start(&mut self, args: &[Value]) -> Result<Value, String> {
+        let (id, client) = self.session_request(&args[0], "session_start")?;
+        let (id, cookie) = self.sessions.start(id)?;
+        self.clients.get_mut(&client).unwrap().cookies.push(cookie);
+        Ok(Value::String(id))
+    }
 
-fn create_res(content: &String) -> String {
-    format!("HTTP/1.1 200 OK\nConnection: keep-alive\nContent-Length: {}\nDate: Sat, 29 Feb 2020 14:14:31 GMT\n\n{}", content.len(), content)
+    fn session_get(&mut self, args: &[Value]) -> Result<Value, String> {
+        let id = string(&args[0], "session_get", "a session id")?;
+        let key = string(&args[1], "session_get", "the key as a string")?;
+        Ok(self.sessions.value(id, key))
+    }
+
+    fn session_set(&mut self, args: &[Value]) -> Result<Value, String> {
+        let id = string(&args[0], "session_set", "a session id")?;
+        let key = string(&args[1], "session_set", "the key as a string")?;
+        self.sessions.set(id, key, args[2].clone())?;
+        Ok(Value::None)
+    }
+
+    fn session_end(&mut self, args: &[Value]) -> Result<Value, String> {
+        let (id, client) = self.session_request(&args[0], "session_end")?;
+        let cookie = self.sessions.end(id);
+        self.clients.get_mut(&client).unwrap().cookies.push(cookie);
+        Ok(Value::None)
+    }
+
+    fn session_options(&mut self, args: &[Value]) -> Result<Value, String> {
+        match &args[0] {
+            Value::Bendy(options) => self.sessions.configure(options)?,
+            _ => {
+                return Err(String::from(
+                    "session_options expects the options as a bendy",
+                ))
+            }
+        }
+        Ok(Value::None)
+    }
+
+    fn close(&mut self, args: &[Value]) -> Result<Value, String> {
+        let (handle, _) = self.client(&args[0], "close")?;
+        self.clients.remove(&handle);
+        Ok(Value::None)
+    }
 }
 
+fn to_bendy(request: Request, client: i64, addr: String) -> Value {
+    let mut bendy = HashMap::new();
+    // the boundary is case sensitive, the media type isn't
+    let content_type = request
+        .headers
+        .iter()
+        .find(|(name, _)| name == "content-type")
+        .map(|(_, value)| value.clone())
+        .unwrap_or_default();
+    let media_type = content_type.to_ascii_lowercase();
+    if media_type.starts_with("application/x-www-form-urlencoded") {
+        bendy.insert(String::from("form"), body::form(&request.body));
+    } else if media_type.starts_with("multipart/form-data") {
+        if let Some(boundary) = body::header_param(&content_type, "boundary") {
+            bendy.insert(
+                String::from("parts"),
+                body::multipart(&request.body, &boundary),
+            );
+        }
+    }
+    let cookies = match request.headers.iter().find(|(name, _)| name == "cookie") {
+        Some((_, header)) => cookie::parse(header),
+        None => Value::Bendy(HashMap::new()),
+    };
+    bendy.insert(String::from("cookies"), cookies);
+    let body_file = match request.body_file {
+        Some(path) => Value::String(path.to_string_lossy().into_owned()),
+        None => Value::None,
+    };
+    bendy.insert(String::from("body_file"), body_file);
+    let headers = request
+        .headers
+        .into_iter()
+        .map(|(name, value)| (name, Value::String(value)))
+        .collect();
+    bendy.insert(String::from("method"), Value::String(request.method));
+    bendy.insert(String::from("path"), Value::String(request.path));
+    bendy.insert(String::from("version"), Value::String(request.version));
+    bendy.insert(String::from("headers"), Value::Bendy(headers));
+    bendy.insert(
+        String::from("content"),
+        Value::String(String::from_utf8_lossy(&request.body).into_owned()),
+    );
+    bendy.insert(String::from("addr"), Value::String(addr));
+    bendy.insert(String::from("client"), Value::Integer(client));
+    Value::Bendy(bendy)
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+// the number of latest response times kept for the percentiles
+const LATENCY_WINDOW: usize = 1024;
+
+// what the metrics middleware counts, for all responses since it was added
+#[derive(Default)]
+pub struct Metrics {
+    // by status class, 1xx to 5xx
+    responses: [u64; 5],
+    duration_sum: f64,
+    duration_count: u64,
+    latencies: Vec<f64>,
+    next: usize,
+}
+
+impl Metrics {
+    pub fn record(&mut self, status: i64, duration: Duration) {
+        if let 100..=599 = status {
+            self.responses[(status / 100 - 1) as usize] += 1;
+        }
+        let seconds = duration.as_secs_f64();
+        self.duration_sum += seconds;
+        self.duration_count += 1;
+        if self.latencies.len() < LATENCY_WINDOW {
+            self.latencies.push(seconds);
+        } else {
+            self.latencies[self.next] = seconds;
+        }
+        self.next = (self.next + 1) % LATENCY_WINDOW;
+    }
+
+    // the prometheus text format
+    pub fn render(&self) -> String {
+        let mut text = String::from(
+            "# HELP olvweb_responses_total Responses sent, by status class.\n\
+             # TYPE olvweb_responses_total counter\n",
+        );
+        for (class, count) in self.responses.iter().enumerate() {
+            text.push_str(&format!(
+                "olvweb_responses_total{{code=\"{}xx\"}} {}\n",
+                class + 1,
+                count
+            ));
+        }
+        text.push_str(&format!(
+            "# HELP olvweb_errors_total Responses with a 5xx status.\n\
+             # TYPE olvweb_errors_total counter\n\
+             olvweb_errors_total {}\n",
+            self.responses[4]
+        ));
+        text.push_str(
+            "# HELP olvweb_request_duration_seconds Time from reading a request to \
+             completing its response, quantiles over the latest 1024.\n\
+             # TYPE olvweb_request_duration_seconds summary\n",
+        );
+        let mut latencies = self.latencies.clone();
+        latencies.sort_by(f64::total_cmp);
+        for quantile in &[0.5, 0.9, 0.99] {
+            let value = match latencies.len() {
+                0 => f64::NAN,
+                len => latencies[((len - 1) as f64 * quantile).round() as usize],
+            };
+            text.push_str(&format!(
+                "olvweb_request_duration_seconds{{quantile=\"{}\"}} {}\n",
+                quantile, value
+            ));
+        }
+        text.push_str(&format!(
+            "olvweb_request_duration_seconds_sum {}\n\
+             olvweb_request_duration_seconds_count {}\n",
+            self.duration_sum, self.duration_count
+        ));
+        if let Some(bytes) = resident_memory() {
+            text.push_str(&format!(
+                "# HELP process_resident_memory_bytes Resident memory size in bytes.\n\
+                 # TYPE process_resident_memory_bytes gauge\n\
+                 process_resident_memory_bytes {}\n",
+                bytes
+            ));
+        }
+        text
+    }
+}
+
+// the memory the process, interpreter included, occupies; only known on linux
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
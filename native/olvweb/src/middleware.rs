@@ -0,0 +1,266 @@
+use crate::add_headers;
+use crate::http::{reason_phrase, Request, Response};
+use crate::metrics::Metrics;
+use olive::Value;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// added to a server with use, runs for every request of the clients it
+// accepts afterwards in the order it was added
+#[derive(Clone)]
+pub enum Middleware {
+    Headers(Vec<(String, String)>),
+    Cors {
+        origin: String,
+        methods: String,
+        headers: String,
+    },
+    BasicAuth {
+        authorization: String,
+        realm: String,
+    },
+    ErrorPage {
+        status: i64,
+        body: String,
+    },
+    // shared by all clients of the server
+    RateLimit(Arc<Mutex<RateLimiter>>),
+    Metrics {
+        path: String,
+        metrics: Arc<Mutex<Metrics>>,
+    },
+}
+
+// a token bucket per address holding up to burst tokens, refilled at rate
+// tokens per second; every request takes one
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: HashMap<IpAddr, (f64, Instant)>,
+}
+
+impl RateLimiter {
+    // none if the request may pass, otherwise the seconds until it would
+    fn take(&mut self, ip: IpAddr) -> Option<u64> {
+        let now = Instant::now();
+        let (rate, burst) = (self.rate, self.burst);
+        let refill = |tokens: f64, updated: Instant| {
+            (tokens + now.duration_since(updated).as_secs_f64() * rate).min(burst)
+        };
+        // buckets that filled up again are the same as new ones
+        if self.buckets.len() > 4096 {
+            self.buckets
+                .retain(|_, (tokens, updated)| refill(*tokens, *updated) < burst);
+        }
+        let (tokens, updated) = self.buckets.entry(ip).or_insert((burst, now));
+        *tokens = refill(*tokens, *updated);
+        *updated = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            None
+        } else {
+            Some(((1.0 - *tokens) / rate).ceil() as u64)
+        }
+    }
+}
+
+fn string_option(
+    options: &HashMap<String, Value>,
+    name: &str,
+    default: Option<&str>,
+) -> Result<String, String> {
+    match (options.get(name), default) {
+        (Some(Value::String(value)), _) => Ok(value.clone()),
+        (None, Some(default)) => Ok(String::from(default)),
+        (None, None) => Err(format!("missing middleware option '{}'", name)),
+        _ => Err(format!("use expects {} as a string", name)),
+    }
+}
+
+fn count_option(
+    options: &HashMap<String, Value>,
+    name: &str,
+    default: Option<i64>,
+) -> Result<i64, String> {
+    match (options.get(name), default) {
+        (Some(Value::Integer(value)), _) if *value > 0 => Ok(*value),
+        (None, Some(default)) => Ok(default),
+        (None, None) => Err(format!("missing middleware option '{}'", name)),
+        _ => Err(format!("use expects {} as an integer from 1", name)),
+    }
+}
+
+pub fn new(name: &str, options: &HashMap<String, Value>) -> Result<Middleware, String> {
+    match name {
+        "headers" => {
+            let mut headers = Vec::new();
+            for (name, value) in options {
+                add_headers(&mut headers, name, value)?;
+            }
+            headers.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Ok(Middleware::Headers(headers))
+        }
+        "cors" => Ok(Middleware::Cors {
+            origin: string_option(options, "origin", Some("*"))?,
+            methods: string_option(options, "methods", Some("GET, POST, PUT, DELETE, OPTIONS"))?,
+            headers: string_option(options, "headers", Some("Content-Type, Authorization"))?,
+        }),
+        "basic_auth" => {
+            let user = string_option(options, "user", None)?;
+            let password = string_option(options, "password", None)?;
+            Ok(Middleware::BasicAuth {
+                authorization: format!(
+                    "Basic {}",
+                    base64::encode(format!("{}:{}", user, password))
+                ),
+                realm: string_option(options, "realm", Some("olvweb"))?,
+            })
+        }
+        "error_page" => match options.get("status") {
+            Some(Value::Integer(status)) => Ok(Middleware::ErrorPage {
+                status: *status,
+                body: string_option(options, "body", None)?,
+            }),
+            _ => Err(String::from("use expects the status of an error page")),
+        },
+        "rate_limit" => {
+            let requests = count_option(options, "requests", None)?;
+            let seconds = count_option(options, "seconds", Some(1))?;
+            let burst = count_option(options, "burst", Some(requests))?;
+            Ok(Middleware::RateLimit(Arc::new(Mutex::new(RateLimiter {
+                rate: requests as f64 / seconds as f64,
+                burst: burst as f64,
+                buckets: HashMap::new(),
+            }))))
+        }
+        "metrics" => Ok(Middleware::Metrics {
+            path: string_option(options, "path", Some("/metrics"))?,
+            metrics: Arc::new(Mutex::new(Metrics::default())),
+        }),
+        _ => Err(format!("invalid middleware '{}'", name)),
+    }
+}
+
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers
+        .iter()
+        .find(|(header, _)| header == name)
+        .map(|(_, value)| value.as_str())
+}
+
+// the answer to a request from ip that some middleware doesn't let through to
+// the script
+pub fn before(middleware: &[Middleware], request: &Request, ip: IpAddr) -> Option<Response> {
+    for entry in middleware {
+        match entry {
+            Middleware::Cors { .. }
+                if request.method == "OPTIONS"
+                    && header(request, "access-control-request-method").is_some() =>
+            {
+                return Some(Response {
+                    status: 204,
+                    headers: Vec::new(),
+                    body: String::new(),
+                });
+            }
+            Middleware::BasicAuth {
+                authorization,
+                realm,
+            } if header(request, "authorization") != Some(authorization) => {
+                return Some(Response {
+                    status: 401,
+                    headers: vec![(
+                        String::from("WWW-Authenticate"),
+                        format!("Basic realm=\"{}\"", realm),
+                    )],
+                    body: String::from(reason_phrase(401)),
+                });
+            }
+            Middleware::RateLimit(limiter) => {
+                let wait = limiter.lock().ok().and_then(|mut limiter| limiter.take(ip));
+                if let Some(wait) = wait {
+                    return Some(Response {
+                        status: 429,
+                        headers: vec![(String::from("Retry-After"), wait.to_string())],
+                        body: String::from(reason_phrase(429)),
+                    });
+                }
+            }
+            Middleware::Metrics { path, metrics }
+                if request.method == "GET"
+                    && request.path.split('?').next() == Some(path.as_str()) =>
+            {
+                if let Ok(metrics) = metrics.lock() {
+                    return Some(Response {
+                        status: 200,
+                        headers: vec![(
+                            String::from("Content-Type"),
+                            String::from("text/plain; version=0.0.4; charset=utf-8"),
+                        )],
+                        body: metrics.render(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// headers the script set itself take precedence over the ones added by
+// middleware
+fn add_header(headers: &mut Vec<(String, String)>, name: &str, value: &str) {
+    if !headers
+        .iter()
+        .any(|(header, _)| header.eq_ignore_ascii_case(name))
+    {
+        headers.push((String::from(name), String::from(value)));
+    }
+}
+
+pub fn after(middleware: &[Middleware], response: &mut Response) {
+    for entry in middleware {
+        match entry {
+            Middleware::Headers(headers) => {
+                for (name, value) in headers {
+                    add_header(&mut response.headers, name, value);
+                }
+            }
+            Middleware::Cors {
+                origin,
+                methods,
+                headers,
+            } => {
+                add_header(&mut response.headers, "Access-Control-Allow-Origin", origin);
+                add_header(
+                    &mut response.headers,
+                    "Access-Control-Allow-Methods",
+                    methods,
+                );
+                add_header(
+                    &mut response.headers,
+                    "Access-Control-Allow-Headers",
+                    headers,
+                );
+            }
+            Middleware::ErrorPage { status, body } if *status == response.status => {
+                response.body = body.clone();
+            }
+            _ => {}
+        }
+    }
+}
+
+// counts a complete response for the metrics
+pub fn record(middleware: &[Middleware], status: i64, duration: Duration) {
+    for entry in middleware {
+        if let Middleware::Metrics { metrics, .. } = entry {
+            if let Ok(mut metrics) = metrics.lock() {
+                metrics.record(status, duration);
+            }
+        }
+    }
+}
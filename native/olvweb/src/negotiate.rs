@@ -0,0 +1,108 @@
+use crate::files;
+use olive::Value;
+
+// the q-value an accept header gives a media type, taken from the most specific
+// range matching it: type/subtype before type/* before */*. parameters other
+// than q are ignored and without a header every type is acceptable
+fn quality(accept: Option<&str>, media_type: &str) -> f64 {
+    let accept = match accept {
+        Some(accept) => accept,
+        None => return 1.0,
+    };
+    let media_type = media_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    let (main, sub) = match media_type.split_once('/') {
+        Some(parts) => parts,
+        None => return 0.0,
+    };
+    let mut best = (0, 0.0);
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let range = params.next().unwrap_or("").trim().to_ascii_lowercase();
+        let specificity = match range.split_once('/') {
+            Some(("*", "*")) => 1,
+            Some((range_main, "*")) if range_main == main => 2,
+            Some((range_main, range_sub)) if range_main == main && range_sub == sub => 3,
+            _ => continue,
+        };
+        let q = params
+            .filter_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                if name.trim().eq_ignore_ascii_case("q") {
+                    value.trim().parse::<f64>().ok()
+                } else {
+                    None
+                }
+            })
+            .next()
+            .unwrap_or(1.0);
+        if specificity > best.0 {
+            best = (specificity, q.clamp(0.0, 1.0));
+        }
+    }
+    best.1
+}
+
+fn accept_header<'a>(request: &'a Value, function: &str) -> Result<Option<&'a str>, String> {
+    let headers = match request {
+        Value::Bendy(request) => request.get("headers"),
+        _ => None,
+    };
+    match headers {
+        Some(Value::Bendy(headers)) => match headers.get("accept") {
+            Some(Value::String(accept)) => Ok(Some(accept)),
+            _ => Ok(None),
+        },
+        _ => Err(format!("{} expects a request", function)),
+    }
+}
+
+// mime_for_extension(extension) returns the content type for files with it
+pub fn mime_for_extension(args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::String(extension) => Ok(Value::String(String::from(files::mime_for_extension(
+            extension,
+        )))),
+        _ => Err(String::from("mime_for_extension expects an extension")),
+    }
+}
+
+// accepts(request, type) is true if the accept header of the request allows
+// the media type with a q-value above zero
+pub fn accepts(args: Vec<Value>) -> Result<Value, String> {
+    let accept = accept_header(&args[0], "accepts")?;
+    match &args[1] {
+        Value::String(media_type) => Ok(Value::Boolean(quality(accept, media_type) > 0.0)),
+        _ => Err(String::from("accepts expects a media type")),
+    }
+}
+
+// preferred_type(request, types) picks the media type from the list the
+// request accepts with the highest q-value, the first one on a tie; none if it
+// accepts none of them
+pub fn preferred_type(args: Vec<Value>) -> Result<Value, String> {
+    let accept = accept_header(&args[0], "preferred_type")?;
+    let media_types = match &args[1] {
+        Value::List(media_types) => media_types,
+        _ => return Err(String::from("preferred_type expects a list of media types")),
+    };
+    let mut best: Option<(f64, &String)> = None;
+    for media_type in media_types {
+        let media_type = match media_type {
+            Value::String(media_type) => media_type,
+            _ => return Err(String::from("preferred_type expects a list of media types")),
+        };
+        let q = quality(accept, media_type);
+        if q > 0.0 && best.is_none_or(|(best_q, _)| q > best_q) {
+            best = Some((q, media_type));
+        }
+    }
+    Ok(match best {
+        Some((_, media_type)) => Value::String(media_type.clone()),
+        None => Value::None,
+    })
+}
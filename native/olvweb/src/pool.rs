@@ -0,0 +1,76 @@
+use crate::http::{Client, Request, Server};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvError, Sender};
+use std::sync::Arc;
+use std::thread;
+
+type Accepted = (Client, Request);
+
+// worker threads that accept connections and read requests concurrently, so
+// a slow client only holds up its own worker while the script answers the
+// requests that are complete
+pub struct Pool {
+    sender: Sender<Accepted>,
+    receiver: Receiver<Accepted>,
+    closed: Arc<AtomicBool>,
+    // for waking the workers
+    server: Server,
+    workers: usize,
+}
+
+impl Pool {
+    pub fn new(server: &Server, workers: usize) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let closed = Arc::new(AtomicBool::new(false));
+        for _ in 0..workers {
+            let server = server.try_clone()?;
+            let sender = sender.clone();
+            let closed = closed.clone();
+            thread::spawn(move || {
+                while !closed.load(Ordering::SeqCst) {
+                    // a client that fails is dropped, the others are still served
+                    if let Ok(Some(accepted)) = server.accept_one() {
+                        if closed.load(Ordering::SeqCst) || sender.send(accepted).is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+        Ok(Pool {
+            sender,
+            receiver,
+            closed,
+            server: server.try_clone()?,
+            workers,
+        })
+    }
+
+    // waits for the next request any worker read
+    pub fn next(&self) -> Result<Accepted, RecvError> {
+        self.receiver.recv()
+    }
+
+    // reads the next request of a client that was answered on its own
+    // thread, it comes out of next like the others
+    pub fn keep_alive(&self, mut client: Client) {
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            if let Ok(Some(request)) = client.read_request() {
+                let _ = sender.send((client, request));
+            }
+        });
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::SeqCst);
+        // a worker waiting in accept only sees the flag once a connection
+        // comes in. workers reading from a client stop after that client
+        for _ in 0..self.workers {
+            let _ = self.server.wake();
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::header_value;
+use crate::http::{reason_phrase, Client, Response};
+use crate::socket::Stream;
+use olive::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+
+// headers that only apply to one connection and are never forwarded
+const HOP_BY_HOP: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "expect",
+];
+
+fn field<'a>(request: &'a HashMap<String, Value>, name: &str) -> Result<&'a str, String> {
+    match request.get(name) {
+        Some(Value::String(value)) => Ok(value),
+        _ => Err(format!(
+            "proxy expects the {} of the request as a string",
+            name
+        )),
+    }
+}
+
+// the head of the request sent upstream: the host is kept, the client is
+// added to X-Forwarded-For and the connection is closed after the response
+fn upstream_head(
+    request: &HashMap<String, Value>,
+    upstream: &str,
+    content_len: u64,
+) -> Result<String, String> {
+    let mut forwarded = Vec::new();
+    let mut host = String::from(upstream);
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\n",
+        field(request, "method")?,
+        field(request, "path")?
+    );
+    if let Some(Value::Bendy(headers)) = request.get("headers") {
+        let mut names: Vec<&String> = headers.keys().collect();
+        names.sort();
+        for name in names {
+            let value = header_value(&headers[name])?;
+            match name.to_ascii_lowercase().as_str() {
+                "host" => host = value,
+                "x-forwarded-for" => forwarded.push(value),
+                "content-length" => {}
+                lower if HOP_BY_HOP.contains(&lower) => {}
+                _ => head.push_str(&format!("{}: {}\r\n", name, value)),
+            }
+        }
+    }
+    // clients of a unix socket have no address to add
+    if let Ok(addr) = field(request, "addr")?.parse::<SocketAddr>() {
+        forwarded.push(addr.ip().to_string());
+    }
+    if !forwarded.is_empty() {
+        head.push_str(&format!("X-Forwarded-For: {}\r\n", forwarded.join(", ")));
+    }
+    head.push_str(&format!(
+        "Host: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        host, content_len
+    ));
+    Ok(head)
+}
+
+// rest is the part of the body that was read together with the head
+struct UpstreamHead {
+    status: i64,
+    headers: Vec<(String, String)>,
+    content_len: Option<u64>,
+    chunked: bool,
+    rest: Vec<u8>,
+}
+
+fn read_upstream_head(stream: &mut Stream, max_head_size: usize) -> io::Result<UpstreamHead> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut buffer = Vec::new();
+    let mut piece = [0; 4096];
+    loop {
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut response = httparse::Response::new(&mut headers);
+        let parsed = response
+            .parse(&buffer)
+            .map_err(|error| invalid(error.to_string()))?;
+        if let httparse::Status::Complete(len) = parsed {
+            let mut head = UpstreamHead {
+                status: response.code.unwrap_or(502) as i64,
+                headers: Vec::new(),
+                content_len: None,
+                chunked: false,
+                rest: buffer[len..].to_vec(),
+            };
+            for header in response.headers.iter() {
+                let name = header.name.to_ascii_lowercase();
+                let value = String::from_utf8_lossy(header.value).into_owned();
+                match name.as_str() {
+                    "content-length" => {
+                        head.content_len = Some(value.trim().parse().map_err(|_| {
+                            invalid(String::from("invalid content length from upstream"))
+                        })?)
+                    }
+                    "transfer-encoding" => head.chunked = value.contains("chunked"),
+                    lower if HOP_BY_HOP.contains(&lower) => {}
+                    _ => head.headers.push((String::from(header.name), value)),
+                }
+            }
+            return Ok(head);
+        }
+        if buffer.len() > max_head_size {
+            return Err(invalid(String::from(
+                "response head from upstream too large",
+            )));
+        }
+        let read = stream.read(&mut piece)?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "upstream closed the connection",
+            ));
+        }
+        buffer.extend_from_slice(&piece[..read]);
+    }
+}
+
+fn send_upstream(
+    client: &Client,
+    upstream: &str,
+    head: &str,
+    body: &mut dyn Read,
+) -> io::Result<(Stream, UpstreamHead)> {
+    let mut stream = Stream::connect(upstream)?;
+    stream.set_read_timeout(client.limits.read_timeout)?;
+    stream.write_all(head.as_bytes())?;
+    io::copy(body, &mut stream)?;
+    let head = read_upstream_head(&mut stream, client.limits.max_head_size)?;
+    Ok((stream, head))
+}
+
+// copies the body as is when it has a length or is chunked already, in
+// chunks otherwise
+fn relay_body(client: &mut Client, upstream: Stream, head: UpstreamHead) -> io::Result<()> {
+    if let Some(content_len) = head.content_len {
+        let rest = &head.rest[..head.rest.len().min(content_len as usize)];
+        let left = content_len - rest.len() as u64;
+        client.send_from(&mut rest.chain(upstream.take(left)))?;
+        return Ok(());
+    }
+    if head.chunked {
+        client.send_from(&mut head.rest.chain(upstream))?;
+        return Ok(());
+    }
+    let mut upstream = head.rest.chain(upstream);
+    let mut piece = [0; 4096];
+    loop {
+        let read = upstream.read(&mut piece)?;
+        if read == 0 {
+            return client.send_chunk(&[]);
+        }
+        client.send_chunk(&piece[..read])?;
+    }
+}
+
+// a request ready to be forwarded
+pub struct Forward {
+    method: String,
+    head: String,
+    body: Box<dyn Read>,
+}
+
+// the request bendy, which the script may have changed, e.g. its path, as
+// it is sent to upstream. a spooled body is sent from its file
+pub fn forward(request: &HashMap<String, Value>, upstream: &str) -> Result<Forward, String> {
+    let (body, content_len): (Box<dyn Read>, u64) = match request.get("body_file") {
+        Some(Value::String(path)) => {
+            let file = File::open(path).map_err(|error| format!("proxy failed: {}", error))?;
+            let len = file
+                .metadata()
+                .map_err(|error| format!("proxy failed: {}", error))?
+                .len();
+            (Box::new(file), len)
+        }
+        _ => {
+            let content = field(request, "content")?.as_bytes().to_vec();
+            let len = content.len() as u64;
+            (Box::new(io::Cursor::new(content)), len)
+        }
+    };
+    Ok(Forward {
+        method: String::from(field(request, "method")?),
+        head: upstream_head(request, upstream, content_len)?,
+        body,
+    })
+}
+
+// sends the request to upstream and relays the response to the client
+// through the middleware, or answers with 502 if the upstream can't be
+// reached or answers garbage. returns the status sent
+pub fn relay(client: &mut Client, upstream: &str, mut forward: Forward) -> io::Result<i64> {
+    let (stream, head) = match send_upstream(client, upstream, &forward.head, &mut forward.body) {
+        Ok(sent) => sent,
+        Err(_) => {
+            let response = Response {
+                status: 502,
+                headers: Vec::new(),
+                body: String::from(reason_phrase(502)),
+            };
+            client.respond(&response)?;
+            return Ok(502);
+        }
+    };
+    let response = Response {
+        status: head.status,
+        headers: head.headers.clone(),
+        body: String::new(),
+    };
+    let bodiless =
+        forward.method == "HEAD" || head.status < 200 || head.status == 204 || head.status == 304;
+    let content_len = if bodiless {
+        Some(head.content_len.unwrap_or(0))
+    } else {
+        head.content_len
+    };
+    // an error page from the middleware replaces the body of the upstream
+    if client.relay_head(&response, content_len)? && !bodiless {
+        relay_body(client, stream, head)?;
+    }
+    client.log();
+    Ok(response.status)
+}
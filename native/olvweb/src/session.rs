@@ -0,0 +1,128 @@
+use olive::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub struct Options {
+    cookie: String,
+    ttl: Duration,
+    secure: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            cookie: String::from("olvsession"),
+            ttl: Duration::from_secs(30 * 60),
+            secure: false,
+        }
+    }
+}
+
+// a session ends once it wasn't started for its ttl
+struct Session {
+    data: HashMap<String, Value>,
+    expires: Instant,
+}
+
+// sessions only live in memory and end with the engine
+#[derive(Default)]
+pub struct Sessions {
+    options: Options,
+    sessions: HashMap<String, Session>,
+}
+
+// 32 random bytes, so ids can't be guessed
+fn new_id() -> Result<String, String> {
+    let mut bytes = [0; 32];
+    getrandom::getrandom(&mut bytes).map_err(|error| format!("session_start failed: {}", error))?;
+    Ok(base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+}
+
+impl Sessions {
+    fn get(&mut self, id: &str) -> Option<&mut Session> {
+        let now = Instant::now();
+        self.sessions.retain(|_, session| session.expires > now);
+        self.sessions.get_mut(id)
+    }
+
+    fn cookie(&self, id: &str, max_age: u64) -> String {
+        format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+            self.options.cookie,
+            id,
+            max_age,
+            if self.options.secure { "; Secure" } else { "" }
+        )
+    }
+
+    // the session id the cookies of a request name, if any
+    pub fn id_of(&self, request: &HashMap<String, Value>) -> Option<String> {
+        match request.get("cookies") {
+            Some(Value::Bendy(cookies)) => match cookies.get(&self.options.cookie) {
+                Some(Value::String(id)) => Some(id.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn configure(&mut self, options: &HashMap<String, Value>) -> Result<(), String> {
+        for (name, value) in options {
+            match (name.as_str(), value) {
+                ("cookie", Value::String(cookie)) if crate::is_token(cookie) => {
+                    self.options.cookie = cookie.clone()
+                }
+                ("ttl", Value::Integer(ttl)) if *ttl > 0 => {
+                    self.options.ttl = Duration::from_secs(*ttl as u64)
+                }
+                ("secure", Value::Boolean(secure)) => self.options.secure = *secure,
+                _ => return Err(format!("invalid session option '{}'", name)),
+            }
+        }
+        Ok(())
+    }
+
+    // continues the session with the id or starts a new one, returns the id
+    // and the Set-Cookie header that keeps it for another ttl
+    pub fn start(&mut self, id: Option<String>) -> Result<(String, String), String> {
+        let expires = Instant::now() + self.options.ttl;
+        let id = match id.filter(|id| self.get(id).is_some()) {
+            Some(id) => id,
+            None => new_id()?,
+        };
+        self.sessions
+            .entry(id.clone())
+            .or_insert_with(|| Session {
+                data: HashMap::new(),
+                expires,
+            })
+            .expires = expires;
+        let cookie = self.cookie(&id, self.options.ttl.as_secs());
+        Ok((id, cookie))
+    }
+
+    // none if the session or the key doesn't exist
+    pub fn value(&mut self, id: &str, key: &str) -> Value {
+        self.get(id)
+            .and_then(|session| session.data.get(key).cloned())
+            .unwrap_or(Value::None)
+    }
+
+    pub fn set(&mut self, id: &str, key: &str, value: Value) -> Result<(), String> {
+        match self.get(id) {
+            Some(session) => {
+                session.data.insert(String::from(key), value);
+                Ok(())
+            }
+            None => Err(String::from("session_set got a session that has ended")),
+        }
+    }
+
+    // ends the session and returns the Set-Cookie header that removes it
+    pub fn end(&mut self, id: Option<String>) -> String {
+        if let Some(id) = id {
+            self.sessions.remove(&id);
+        }
+        self.cookie("", 0)
+    }
+}
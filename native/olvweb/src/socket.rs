@@ -0,0 +1,177 @@
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+
+// addresses starting with unix: are paths of unix domain sockets
+const UNIX_PREFIX: &str = "unix:";
+
+#[cfg(not(unix))]
+fn no_unix_sockets() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "unix sockets are not supported on this platform",
+    )
+}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    pub fn bind(address: &str) -> io::Result<Self> {
+        match address.strip_prefix(UNIX_PREFIX) {
+            #[cfg(unix)]
+            Some(path) => {
+                // a socket file nobody listens on is left over from an earlier run
+                let stale = std::fs::metadata(path)
+                    .map(|metadata| metadata.file_type().is_socket())
+                    .unwrap_or(false);
+                if stale && UnixStream::connect(path).is_err() {
+                    std::fs::remove_file(path)?;
+                }
+                Ok(Listener::Unix(
+                    UnixListener::bind(path)?,
+                    PathBuf::from(path),
+                ))
+            }
+            #[cfg(not(unix))]
+            Some(_) => Err(no_unix_sockets()),
+            None => Ok(Listener::Tcp(TcpListener::bind(address)?)),
+        }
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Listener::Tcp(listener) => Ok(Listener::Tcp(listener.try_clone()?)),
+            #[cfg(unix)]
+            Listener::Unix(listener, path) => {
+                Ok(Listener::Unix(listener.try_clone()?, path.clone()))
+            }
+        }
+    }
+
+    pub fn accept(&self) -> io::Result<(Stream, Peer)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, address) = listener.accept()?;
+                Ok((Stream::Tcp(stream), Peer::Tcp(address)))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept()?;
+                Ok((Stream::Unix(stream), Peer::Unix))
+            }
+        }
+    }
+
+    // connects to the listener itself, which wakes a thread waiting in accept
+    pub fn wake(&self) -> io::Result<()> {
+        match self {
+            Listener::Tcp(listener) => {
+                let mut address = listener.local_addr()?;
+                if address.ip().is_unspecified() {
+                    address.set_ip(match address {
+                        SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                        SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                    });
+                }
+                TcpStream::connect(address).map(|_| ())
+            }
+            #[cfg(unix)]
+            Listener::Unix(_, path) => UnixStream::connect(path).map(|_| ()),
+        }
+    }
+}
+
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    pub fn connect(address: &str) -> io::Result<Self> {
+        match address.strip_prefix(UNIX_PREFIX) {
+            #[cfg(unix)]
+            Some(path) => Ok(Stream::Unix(UnixStream::connect(path)?)),
+            #[cfg(not(unix))]
+            Some(_) => Err(no_unix_sockets()),
+            None => Ok(Stream::Tcp(TcpStream::connect(address)?)),
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+// where a client connected from; clients of a unix socket have no address
+#[derive(Clone, Copy)]
+pub enum Peer {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix,
+}
+
+impl Peer {
+    // unix clients all count as the local host for rate limits and
+    // connection caps
+    pub fn ip(&self) -> IpAddr {
+        match self {
+            Peer::Tcp(address) => address.ip(),
+            #[cfg(unix)]
+            Peer::Unix => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        }
+    }
+}
+
+impl Display for Peer {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Peer::Tcp(address) => write!(f, "{}", address),
+            #[cfg(unix)]
+            Peer::Unix => write!(f, "unix"),
+        }
+    }
+}
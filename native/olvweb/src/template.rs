@@ -0,0 +1,276 @@
+use olive::Value;
+use std::collections::HashMap;
+
+// a piece of a template between two tags, with the line it starts on
+enum Token {
+    Text(String),
+    Output(usize, String),
+    Tag(usize, String),
+}
+
+enum Node {
+    Text(String),
+    Value {
+        line: usize,
+        path: Vec<String>,
+        raw: bool,
+    },
+    If {
+        path: Vec<String>,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        line: usize,
+        name: String,
+        path: Vec<String>,
+        body: Vec<Node>,
+    },
+}
+
+// what ended a block of nodes: the end of the template or an else or end tag
+enum Ending {
+    Eof,
+    Else(usize),
+    End(usize),
+}
+
+fn tokenize(template: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    let mut line = 1;
+    while let Some(start) = rest.find("{{").into_iter().chain(rest.find("{%")).min() {
+        let (text, tag) = rest.split_at(start);
+        line += text.matches('\n').count();
+        if !text.is_empty() {
+            tokens.push(Token::Text(String::from(text)));
+        }
+        let close = if tag.starts_with("{{") { "}}" } else { "%}" };
+        let end = match tag[2..].find(close) {
+            Some(end) => end + 2,
+            None => return Err(format!("line {}: unclosed '{}'", line, &tag[..2])),
+        };
+        let content = String::from(tag[2..end].trim());
+        tokens.push(if close == "}}" {
+            Token::Output(line, content)
+        } else {
+            Token::Tag(line, content)
+        });
+        line += tag[..end].matches('\n').count();
+        rest = &tag[end + 2..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(String::from(rest)));
+    }
+    Ok(tokens)
+}
+
+fn to_path(line: usize, name: &str) -> Result<Vec<String>, String> {
+    let path: Vec<String> = name.split('.').map(String::from).collect();
+    if path.iter().any(|part| part.is_empty()) {
+        return Err(format!("line {}: invalid name '{}'", line, name));
+    }
+    Ok(path)
+}
+
+fn parse_block(tokens: &mut std::vec::IntoIter<Token>) -> Result<(Vec<Node>, Ending), String> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        let node = match token {
+            Token::Text(text) => Node::Text(text),
+            Token::Output(line, content) => {
+                let mut parts = content.split('|').map(str::trim);
+                let path = to_path(line, parts.next().unwrap_or_default())?;
+                let raw = match (parts.next(), parts.next()) {
+                    (None, _) => false,
+                    (Some("raw"), None) => true,
+                    _ => return Err(format!("line {}: unknown filter in '{}'", line, content)),
+                };
+                Node::Value { line, path, raw }
+            }
+            Token::Tag(line, content) => {
+                let words: Vec<&str> = content.split_whitespace().collect();
+                match words.as_slice() {
+                    ["else"] => return Ok((nodes, Ending::Else(line))),
+                    ["end"] => return Ok((nodes, Ending::End(line))),
+                    ["if", name] | ["if", "not", name] => {
+                        let (then, ending) = parse_block(tokens)?;
+                        let otherwise = match ending {
+                            Ending::Else(_) => match parse_block(tokens)? {
+                                (otherwise, Ending::End(_)) => otherwise,
+                                (_, Ending::Else(line)) => {
+                                    return Err(format!("line {}: second 'else' in 'if'", line))
+                                }
+                                (_, Ending::Eof) => {
+                                    return Err(format!("line {}: 'if' without 'end'", line))
+                                }
+                            },
+                            Ending::End(_) => Vec::new(),
+                            Ending::Eof => {
+                                return Err(format!("line {}: 'if' without 'end'", line))
+                            }
+                        };
+                        Node::If {
+                            path: to_path(line, name)?,
+                            negate: words.len() == 3,
+                            then,
+                            otherwise,
+                        }
+                    }
+                    ["for", name, "in", list] => match parse_block(tokens)? {
+                        (body, Ending::End(_)) => Node::For {
+                            line,
+                            name: String::from(*name),
+                            path: to_path(line, list)?,
+                            body,
+                        },
+                        (_, Ending::Else(line)) => {
+                            return Err(format!("line {}: 'else' in 'for'", line))
+                        }
+                        (_, Ending::Eof) => {
+                            return Err(format!("line {}: 'for' without 'end'", line))
+                        }
+                    },
+                    _ => return Err(format!("line {}: unknown tag '{}'", line, content)),
+                }
+            }
+        };
+        nodes.push(node);
+    }
+    Ok((nodes, Ending::Eof))
+}
+
+fn parse(template: &str) -> Result<Vec<Node>, String> {
+    match parse_block(&mut tokenize(template)?.into_iter())? {
+        (nodes, Ending::Eof) => Ok(nodes),
+        (_, Ending::Else(line)) => Err(format!("line {}: 'else' outside of 'if'", line)),
+        (_, Ending::End(line)) => Err(format!("line {}: 'end' without a block", line)),
+    }
+}
+
+// loop variables shadow the context; a part of the path that doesn't exist
+// makes the whole value none, numbers pick elements of lists
+fn lookup<'a>(
+    context: &'a HashMap<String, Value>,
+    loops: &[(&str, &'a Value)],
+    path: &[String],
+) -> Option<&'a Value> {
+    let mut value = match loops.iter().rev().find(|(name, _)| *name == path[0]) {
+        Some((_, value)) => *value,
+        None => context.get(&path[0])?,
+    };
+    for part in &path[1..] {
+        value = match value {
+            Value::Bendy(bendy) => bendy.get(part)?,
+            Value::List(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn is_truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::None) | Some(Value::Boolean(false)) | Some(Value::Integer(0)) => false,
+        Some(Value::Float(value)) => *value != 0.0,
+        Some(Value::String(value)) => !value.is_empty(),
+        Some(Value::List(items)) => !items.is_empty(),
+        Some(Value::Bendy(bendy)) => !bendy.is_empty(),
+        _ => true,
+    }
+}
+
+fn escape_html(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+fn render_block<'a>(
+    nodes: &'a [Node],
+    context: &'a HashMap<String, Value>,
+    loops: &mut Vec<(&'a str, &'a Value)>,
+    out: &mut String,
+) -> Result<(), String> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value { line, path, raw } => {
+                let text = match lookup(context, loops, path) {
+                    Some(Value::String(value)) => value.clone(),
+                    Some(Value::Integer(value)) => value.to_string(),
+                    Some(Value::Float(value)) => value.to_string(),
+                    Some(Value::Boolean(value)) => value.to_string(),
+                    None | Some(Value::None) => String::new(),
+                    _ => {
+                        return Err(format!(
+                            "line {}: '{}' can't be inserted as text",
+                            line,
+                            path.join(".")
+                        ))
+                    }
+                };
+                if *raw {
+                    out.push_str(&text);
+                } else {
+                    escape_html(&text, out);
+                }
+            }
+            Node::If {
+                path,
+                negate,
+                then,
+                otherwise,
+            } => {
+                if is_truthy(lookup(context, loops, path)) != *negate {
+                    render_block(then, context, loops, out)?;
+                } else {
+                    render_block(otherwise, context, loops, out)?;
+                }
+            }
+            Node::For {
+                line,
+                name,
+                path,
+                body,
+            } => {
+                let items = match lookup(context, loops, path) {
+                    Some(Value::List(items)) => items.as_slice(),
+                    None | Some(Value::None) => &[],
+                    _ => return Err(format!("line {}: '{}' is not a list", line, path.join("."))),
+                };
+                for item in items {
+                    loops.push((name, item));
+                    let result = render_block(body, context, loops, out);
+                    loops.pop();
+                    result?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// render(template, context) fills the template from the context bendy
+pub fn render(args: Vec<Value>) -> Result<Value, String> {
+    match (&args[0], &args[1]) {
+        (Value::String(template), Value::Bendy(context)) => {
+            let nodes = parse(template).map_err(|error| format!("render failed: {}", error))?;
+            let mut out = String::new();
+            render_block(&nodes, context, &mut Vec::new(), &mut out)
+                .map_err(|error| format!("render failed: {}", error))?;
+            Ok(Value::String(out))
+        }
+        _ => Err(String::from(
+            "render expects a template string and a context bendy",
+        )),
+    }
+}
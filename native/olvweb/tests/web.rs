@@ -0,0 +1,1427 @@
+use olive::testing::{error, eval};
+use olive::{Engine, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    olvweb::register(&mut engine);
+    engine
+}
+
+fn free_address() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+// sends the raw request once the server is up and returns everything the
+// server answers
+fn client(address: &str, request: &str) -> JoinHandle<String> {
+    let address = String::from(address);
+    let request = String::from(request);
+    thread::spawn(move || {
+        let mut stream = loop {
+            match TcpStream::connect(&address) {
+                Ok(stream) => break stream,
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+        answer
+    })
+}
+
+#[test]
+fn requests_are_read_and_answered() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let answer = client(
+        &address,
+        "POST /submit HTTP/1.1\r\nHost: x\r\nX-Token: abc\r\nConnection: close\r\nContent-Length: 5\r\n\r\nhello",
+    );
+    let request = eval(
+        &mut engine,
+        "req = web.recv_req(server);\nweb.send_res(req.client, \"got \" $ req.content);\nreturn req;",
+    );
+    let answer = answer.join().unwrap();
+    assert!(answer.starts_with("HTTP/1.1 200 OK"), "{}", answer);
+    assert!(answer.ends_with("\r\n\r\ngot hello"), "{}", answer);
+    assert!(
+        answer.contains("\r\nServer: olvweb/0.1.0\r\n"),
+        "{}",
+        answer
+    );
+    assert!(
+        answer.contains("\r\nContent-Type: text/html; charset=utf-8\r\n"),
+        "{}",
+        answer
+    );
+    let date = answer
+        .lines()
+        .find_map(|line| line.strip_prefix("Date: "))
+        .expect("no Date header");
+    assert!(date.ends_with(" GMT"), "{}", date);
+    let request = match request {
+        Value::Bendy(request) => request,
+        other => panic!("not a request: {}", other),
+    };
+    assert_eq!(request["method"], Value::String(String::from("POST")));
+    assert_eq!(request["path"], Value::String(String::from("/submit")));
+    assert_eq!(request["version"], Value::String(String::from("HTTP/1.1")));
+    match &request["headers"] {
+        Value::Bendy(headers) => assert_eq!(headers["x-token"], Value::String(String::from("abc"))),
+        other => panic!("not headers: {}", other),
+    }
+}
+
+#[test]
+fn pending_requests_are_polled() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\npending = web.recv_req_async(server);",
+            address
+        ),
+    );
+    // nobody connected yet
+    assert_eq!(
+        eval(&mut engine, "return web.poll_req(pending);"),
+        Value::None
+    );
+    let answer = client(&address, "GET /later HTTP/1.0\r\n\r\n");
+    let path = eval(
+        &mut engine,
+        "req = none;\n\
+         while (req == none) { req = web.poll_req(pending); }\n\
+         web.send_res(req.client, \"done\");\n\
+         return req.path;",
+    );
+    assert_eq!(path, Value::String(String::from("/later")));
+    assert!(answer.join().unwrap().ends_with("done"));
+    assert!(error(&mut engine, "web.poll_req(pending);").contains("unknown pending request"));
+}
+
+#[test]
+fn pending_requests_are_capped_and_cancelled() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    for i in 0..16 {
+        eval(
+            &mut engine,
+            &format!("p{} = web.recv_req_async(server);", i),
+        );
+    }
+    assert!(error(&mut engine, "web.recv_req_async(server);").contains("pending requests"));
+    eval(&mut engine, "web.cancel_req(p0);");
+    assert!(error(&mut engine, "web.poll_req(p0);").contains("unknown pending request"));
+    assert!(error(&mut engine, "web.cancel_req(p0);").contains("unknown pending request"));
+}
+
+#[test]
+fn responses_set_status_and_headers() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let answer = client(&address, "GET /old HTTP/1.1\r\nConnection: close\r\n\r\n");
+    eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         web.send_res(req.client, new { status: 301, headers: new { Location: \"/new\", [\"X-Count\"]: 2 }, body: \"moved\" });",
+    );
+    let answer = answer.join().unwrap();
+    assert!(
+        answer.starts_with("HTTP/1.1 301 Moved Permanently\r\n"),
+        "{}",
+        answer
+    );
+    assert!(answer.contains("\r\nLocation: /new\r\n"), "{}", answer);
+    assert!(answer.contains("\r\nX-Count: 2\r\n"), "{}", answer);
+    assert!(answer.ends_with("\r\n\r\nmoved"), "{}", answer);
+    let answer = client(&address, "GET / HTTP/1.1\r\n\r\n");
+    assert!(error(
+        &mut engine,
+        "req = web.recv_req(server);\nweb.send_res(req.client, new { status: 42 });"
+    )
+    .contains("send_res expects a status from 100 to 999"));
+    // the client stays open after a failed send, until the engine is dropped
+    drop(engine);
+    assert_eq!(answer.join().unwrap(), "");
+}
+
+#[test]
+fn headers_with_line_breaks_are_rejected() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let answer = client(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    eval(&mut engine, "req = web.recv_req(server);");
+    assert!(error(
+        &mut engine,
+        "web.send_res(req.client, new { headers: new { Location: \"/\\r\\nSet-Cookie: a=b\" } });"
+    )
+    .contains("invalid header value"));
+    assert!(error(
+        &mut engine,
+        "web.send_res(req.client, new { headers: new { [\"X-A: b\\r\\nX-C\"]: \"d\" } });"
+    )
+    .contains("invalid header name"));
+    assert!(error(
+        &mut engine,
+        "web.send_res(req.client, new { headers: new { [\"X-List\"]: new [\"a\", \"b\\nc\"] } });"
+    )
+    .contains("invalid header value"));
+    eval(&mut engine, "web.send_res(req.client, \"ok\");");
+    let answer = answer.join().unwrap();
+    assert!(!answer.contains("Set-Cookie"), "{}", answer);
+    assert!(answer.ends_with("\r\n\r\nok"), "{}", answer);
+}
+
+#[test]
+fn connections_are_kept_alive() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let answer = client(&address, "GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n");
+    let paths = eval(
+        &mut engine,
+        "first = web.recv_req(server);\n\
+         web.send_res(first.client, \"a\");\n\
+         second = web.recv_from(first.client);\n\
+         web.send_res(second.client, \"b\");\n\
+         web.close(second.client);\n\
+         return new [first.path, second.path, first.client == second.client];",
+    );
+    assert_eq!(
+        paths,
+        Value::List(vec![
+            Value::String(String::from("/a")),
+            Value::String(String::from("/b")),
+            Value::Boolean(true),
+        ])
+    );
+    let answer = answer.join().unwrap();
+    assert_eq!(
+        answer.matches("Connection: keep-alive").count(),
+        2,
+        "{}",
+        answer
+    );
+    assert!(answer.ends_with("\r\n\r\nb"), "{}", answer);
+    assert!(error(&mut engine, "web.recv_from(first.client);").contains("unknown or closed client"));
+}
+
+#[test]
+fn form_bodies_are_decoded() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let answer = client(
+        &address,
+        "POST /login HTTP/1.1\r\nConnection: close\r\n\
+         Content-Type: application/x-www-form-urlencoded\r\nContent-Length: 34\r\n\r\n\
+         user=ada+l&pass=a%26b%3Dc&empty=&x",
+    );
+    let form = eval(
+        &mut engine,
+        "req = web.recv_req(server);\nweb.send_res(req.client, \"\");\nreturn req.form;",
+    );
+    answer.join().unwrap();
+    let mut expected = HashMap::new();
+    for (name, value) in &[
+        ("user", "ada l"),
+        ("pass", "a&b=c"),
+        ("empty", ""),
+        ("x", ""),
+    ] {
+        expected.insert(String::from(*name), Value::String(String::from(*value)));
+    }
+    assert_eq!(form, Value::Bendy(expected));
+}
+
+#[test]
+fn multipart_bodies_are_split_into_parts() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let body = "--XyZ\r\n\
+                Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+                notes\r\n\
+                --XyZ\r\n\
+                Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+                Content-Type: application/octet-stream\r\n\r\n\
+                \u{1}\u{2}\r\n\
+                --XyZ--\r\n";
+    let request = format!(
+        "POST /upload HTTP/1.1\r\nConnection: close\r\n\
+         Content-Type: multipart/form-data; boundary=XyZ\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let answer = client(&address, &request);
+    let parts = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         web.send_res(req.client, \"\");\n\
+         title = req.parts[0];\n\
+         file = req.parts[1];\n\
+         return new [len(req.parts), title.name, title.text, file.name, file.filename, file.content_type, file.content];",
+    );
+    answer.join().unwrap();
+    assert_eq!(
+        parts,
+        Value::List(vec![
+            Value::Integer(2),
+            Value::String(String::from("title")),
+            Value::String(String::from("notes")),
+            Value::String(String::from("file")),
+            Value::String(String::from("a.bin")),
+            Value::String(String::from("application/octet-stream")),
+            Value::List(vec![Value::Integer(1), Value::Integer(2)]),
+        ])
+    );
+}
+
+#[test]
+fn cookies_are_read_and_set() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let answer = client(
+        &address,
+        "GET / HTTP/1.1\r\nConnection: close\r\nCookie: theme=dark; visits=3\r\n\r\n",
+    );
+    let cookies = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         res = web.set_cookie(\"hi\", \"visits\", \"4\", new { path: \"/\", max_age: 60, http_only: true });\n\
+         res = web.set_cookie(res, \"seen\", \"yes\", none);\n\
+         web.send_res(req.client, res);\n\
+         return req.cookies;",
+    );
+    let answer = answer.join().unwrap();
+    let mut expected = HashMap::new();
+    expected.insert(String::from("theme"), Value::String(String::from("dark")));
+    expected.insert(String::from("visits"), Value::String(String::from("3")));
+    assert_eq!(cookies, Value::Bendy(expected));
+    assert!(
+        answer.contains(
+            "\r\nSet-Cookie: visits=4; HttpOnly; Max-Age=60; Path=/\r\nSet-Cookie: seen=yes\r\n"
+        ),
+        "{}",
+        answer
+    );
+    assert!(answer.ends_with("\r\n\r\nhi"), "{}", answer);
+    assert!(error(
+        &mut engine,
+        "web.set_cookie(\"\", \"a\", \"b\", new { colour: \"red\" });"
+    )
+    .contains("invalid cookie attribute 'colour'"));
+    for (name, value, options, message) in &[
+        ("a;b", "c", "none", "invalid cookie name"),
+        ("a", "b; Secure", "none", "invalid cookie value"),
+        ("a", "b\\r\\nX: y", "none", "invalid cookie value"),
+        (
+            "a",
+            "b",
+            "new { path: \"/; Domain=evil\" }",
+            "attribute 'path'",
+        ),
+        (
+            "a",
+            "b",
+            "new { domain: \"x.com\\r\\n\" }",
+            "attribute 'domain'",
+        ),
+        ("a", "b", "new { expires: \"soon\" }", "attribute 'expires'"),
+        (
+            "a",
+            "b",
+            "new { same_site: \"Lax; Secure\" }",
+            "attribute 'same_site'",
+        ),
+    ] {
+        let got = error(
+            &mut engine,
+            &format!(
+                "web.set_cookie(\"\", \"{}\", \"{}\", {});",
+                name, value, options
+            ),
+        );
+        assert!(got.contains(message), "{}", got);
+    }
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return web.set_cookie(\"\", \"a\", \"\\\"b\\\"\", new { same_site: \"Strict\", expires: \"Wed, 21 Oct 2015 07:28:00 GMT\", domain: \".example.com\" }).headers[\"Set-Cookie\"];"
+        ),
+        Value::List(vec![Value::String(String::from(
+            "a=\"b\"; Domain=.example.com; Expires=Wed, 21 Oct 2015 07:28:00 GMT; SameSite=Strict"
+        ))])
+    );
+}
+
+#[test]
+fn files_are_served_from_the_working_directory() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    // the working directory of tests is the crate
+    let answer = client(
+        &address,
+        "GET /tests/files/hello.html?v=1 HTTP/1.1\r\n\r\n\
+         GET /../olvweb/Cargo.toml HTTP/1.1\r\n\r\n\
+         GET /tests/files/missing.html HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    let statuses = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         statuses = new [web.serve_file(req.client, req.path)];\n\
+         req = web.recv_from(req.client);\n\
+         statuses = statuses $ new [web.serve_file(req.client, req.path)];\n\
+         req = web.recv_from(req.client);\n\
+         return statuses $ new [web.serve_file(req.client, req.path)];",
+    );
+    assert_eq!(
+        statuses,
+        Value::List(vec![
+            Value::Integer(200),
+            Value::Integer(404),
+            Value::Integer(404),
+        ])
+    );
+    let answer = answer.join().unwrap();
+    assert!(answer.starts_with("HTTP/1.1 200 OK\r\n"), "{}", answer);
+    assert!(
+        answer.contains(
+            "\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: 13\r\n\r\n<p>hello</p>\n"
+        ),
+        "{}",
+        answer
+    );
+    assert_eq!(
+        answer.matches("HTTP/1.1 404 Not Found\r\n").count(),
+        2,
+        "{}",
+        answer
+    );
+}
+
+#[test]
+fn pool_requests_are_answered_concurrently() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\npool = web.serve_pool(server, 2);",
+            address
+        ),
+    );
+    // sends half its request and holds a worker until the other one is done
+    let mut slow = TcpStream::connect(&address).unwrap();
+    slow.write_all(b"GET /slow HTTP/1.1\r\nConnection: cl")
+        .unwrap();
+    let fast = client(
+        &address,
+        "GET /fast HTTP/1.1\r\n\r\nGET /again HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    let paths = eval(
+        &mut engine,
+        "first = web.next_req(pool);\n\
+         web.send_res(first.client, \"1\");\n\
+         web.keep_alive(pool, first.client);\n\
+         second = web.next_req(pool);\n\
+         web.send_res(second.client, \"2\");\n\
+         return new [first.path, second.path];",
+    );
+    assert_eq!(
+        paths,
+        Value::List(vec![
+            Value::String(String::from("/fast")),
+            Value::String(String::from("/again")),
+        ])
+    );
+    assert!(fast.join().unwrap().ends_with("\r\n\r\n2"));
+    slow.write_all(b"ose\r\n\r\n").unwrap();
+    let path = eval(
+        &mut engine,
+        "req = web.next_req(pool);\nweb.send_res(req.client, \"3\");\nreturn req.path;",
+    );
+    assert_eq!(path, Value::String(String::from("/slow")));
+    let mut answer = String::new();
+    slow.read_to_string(&mut answer).unwrap();
+    assert!(answer.ends_with("\r\n\r\n3"), "{}", answer);
+    assert!(error(&mut engine, "web.keep_alive(pool, req.client);")
+        .contains("unknown or closed client"));
+    assert!(error(&mut engine, "web.serve_pool(server, 0);")
+        .contains("serve_pool expects at least one worker"));
+}
+
+// answers one request with the raw response and returns the request it got
+fn upstream(response: &'static str) -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut byte = [0];
+        while !request.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            request.push(byte[0]);
+        }
+        let mut request = String::from_utf8(request).unwrap();
+        let length = request
+            .lines()
+            .find_map(|line| {
+                line.to_ascii_lowercase()
+                    .strip_prefix("content-length: ")
+                    .map(String::from)
+            })
+            .map_or(0, |length| length.parse().unwrap());
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).unwrap();
+        request.push_str(&String::from_utf8(body).unwrap());
+        stream.write_all(response.as_bytes()).unwrap();
+        request
+    });
+    (address, handle)
+}
+
+#[test]
+fn fetch_sends_requests_to_other_servers() {
+    let mut engine = engine();
+    let (address, request) = upstream(
+        "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\n\
+         Set-Cookie: a=1\r\nSet-Cookie: b=2\r\nContent-Length: 11\r\n\r\n{\"id\": 7}\r\n",
+    );
+    let response = eval(
+        &mut engine,
+        &format!(
+            "res = web.fetch(\"http://{}/items\", new {{ method: \"post\", headers: new {{ [\"X-Token\"]: \"abc\" }}, body: \"name=a\", timeout: 5000 }});\n\
+             return new [res.status, res.headers[\"content-type\"], res.headers[\"set-cookie\"], res.body];",
+            address
+        ),
+    );
+    assert_eq!(
+        response,
+        Value::List(vec![
+            Value::Integer(201),
+            Value::String(String::from("application/json")),
+            Value::List(vec![
+                Value::String(String::from("a=1")),
+                Value::String(String::from("b=2")),
+            ]),
+            Value::String(String::from("{\"id\": 7}\r\n")),
+        ])
+    );
+    let request = request.join().unwrap().to_ascii_lowercase();
+    assert!(
+        request.starts_with("post /items http/1.1\r\n"),
+        "{}",
+        request
+    );
+    assert!(request.contains("\r\nx-token: abc\r\n"), "{}", request);
+    assert!(request.ends_with("\r\n\r\nname=a"), "{}", request);
+    // error statuses are responses too
+    let (address, _) = upstream("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+    let status = eval(
+        &mut engine,
+        &format!("return web.fetch(\"http://{}/\", none).status;", address),
+    );
+    assert_eq!(status, Value::Integer(404));
+    assert!(error(
+        &mut engine,
+        &format!("web.fetch(\"http://{}/\", none);", free_address())
+    )
+    .contains("fetch failed: "));
+    assert!(error(
+        &mut engine,
+        "web.fetch(\"http://x/\", new { verb: \"GET\" });"
+    )
+    .contains("invalid fetch option 'verb'"));
+}
+
+#[test]
+fn bodies_are_streamed() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let answer = client(
+        &address,
+        "POST /upload HTTP/1.1\r\nContent-Length: 10\r\n\r\n0123456789\
+         POST /next HTTP/1.1\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok",
+    );
+    let read = eval(
+        &mut engine,
+        "req = web.recv_head(server);\n\
+         first = web.read_body(req.client, 4);\n\
+         web.send_headers(req.client, new { headers: new { [\"Content-Type\"]: \"text/plain\" } });\n\
+         web.send_chunk(req.client, \"ab\");\n\
+         web.send_chunk(req.client, \"\");\n\
+         web.send_chunk(req.client, new [99]);\n\
+         web.finish(req.client);\n\
+         next = web.recv_from(req.client);\n\
+         rest = web.read_body(req.client, 4);\n\
+         web.send_res(next.client, \"\");\n\
+         return new [req.content, first, next.path, next.content, rest];",
+    );
+    assert_eq!(
+        read,
+        Value::List(vec![
+            Value::String(String::new()),
+            Value::List(vec![
+                Value::Integer(48),
+                Value::Integer(49),
+                Value::Integer(50),
+                Value::Integer(51),
+            ]),
+            Value::String(String::from("/next")),
+            Value::String(String::from("ok")),
+            Value::None,
+        ])
+    );
+    let answer = answer.join().unwrap();
+    assert!(
+        answer.contains("\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n1\r\nc\r\n0\r\n\r\nHTTP/1.1 200 OK\r\n"),
+        "{}",
+        answer
+    );
+    assert!(error(&mut engine, "web.read_body(req.client, 0);")
+        .contains("read_body expects the most bytes to read"));
+}
+
+#[test]
+fn requests_over_the_limits_are_rejected() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\n\
+             web.configure(server, new {{ read_timeout: 100, max_headers: 2, max_body_size: 4 }});",
+            address
+        ),
+    );
+    // accepted in the order they connect, all before the one that is answered
+    let mut rejected = Vec::new();
+    for request in &[
+        "POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello",
+        "GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n",
+        "GET / HTTP/1.1\r\n",
+    ] {
+        let mut stream = TcpStream::connect(&address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        rejected.push(stream);
+    }
+    let answer = client(&address, "GET /fine HTTP/1.0\r\n\r\n");
+    let path = eval(
+        &mut engine,
+        "req = web.recv_req(server);\nweb.send_res(req.client, \"\");\nreturn req.path;",
+    );
+    assert_eq!(path, Value::String(String::from("/fine")));
+    answer.join().unwrap();
+    let answers: Vec<String> = rejected
+        .into_iter()
+        .map(|mut stream| {
+            let mut answer = String::new();
+            stream.read_to_string(&mut answer).unwrap();
+            answer
+        })
+        .collect();
+    assert!(
+        answers[0].starts_with("HTTP/1.1 413 Payload Too Large\r\nConnection: close\r\n"),
+        "{}",
+        answers[0]
+    );
+    assert!(
+        answers[1].starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"),
+        "{}",
+        answers[1]
+    );
+    // timed out without an answer
+    assert_eq!(answers[2], "");
+    assert!(error(
+        &mut engine,
+        "web.configure(server, new { max_clients: 1 });"
+    )
+    .contains("invalid server option 'max_clients'"));
+}
+
+#[test]
+fn requests_without_a_length_or_taking_too_long_are_rejected() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\n\
+             web.configure(server, new {{ read_timeout: 1000, request_timeout: 300 }});",
+            address
+        ),
+    );
+    let mut rejected = Vec::new();
+    for request in &[
+        "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        "POST / HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n",
+        "POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 50\r\n\r\nhello",
+    ] {
+        let mut stream = TcpStream::connect(&address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        rejected.push(stream);
+    }
+    // every read is quick, but the head is never complete
+    let slow = TcpStream::connect(&address).unwrap();
+    let trickle = thread::spawn(move || {
+        let mut slow = slow;
+        for byte in b"GET / HTTP/1.1\r\nX-Slow: aaaaaaaaaa" {
+            if slow.write_all(&[*byte]).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        let mut answer = String::new();
+        let _ = slow.read_to_string(&mut answer);
+        answer
+    });
+    let answer = client(&address, "GET /fine HTTP/1.0\r\n\r\n");
+    let path = eval(
+        &mut engine,
+        "req = web.recv_req(server);\nweb.send_res(req.client, \"\");\nreturn req.path;",
+    );
+    assert_eq!(path, Value::String(String::from("/fine")));
+    answer.join().unwrap();
+    let answers: Vec<String> = rejected
+        .into_iter()
+        .map(|mut stream| {
+            let mut answer = String::new();
+            stream.read_to_string(&mut answer).unwrap();
+            answer
+        })
+        .collect();
+    assert!(
+        answers[0].starts_with("HTTP/1.1 501 Not Implemented\r\nConnection: close\r\n"),
+        "{}",
+        answers[0]
+    );
+    assert!(
+        answers[1].starts_with("HTTP/1.1 400 Bad Request\r\n"),
+        "{}",
+        answers[1]
+    );
+    assert!(
+        answers[2].starts_with("HTTP/1.1 400 Bad Request\r\n"),
+        "{}",
+        answers[2]
+    );
+    let slow = trickle.join().unwrap();
+    assert!(
+        slow.starts_with("HTTP/1.1 408 Request Timeout\r\n"),
+        "{}",
+        slow
+    );
+}
+
+#[test]
+fn answered_requests_are_logged() {
+    let mut engine = engine();
+    let address = free_address();
+    let log = std::env::temp_dir().join(format!("olvweb-access-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&log);
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\nweb.access_log(server, {:?});",
+            address,
+            log.to_str().unwrap()
+        ),
+    );
+    let answer = client(
+        &address,
+        "GET /a?x=1 HTTP/1.1\r\n\r\nGET /missing HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         web.send_res(req.client, new { status: 201, body: \"hi\" });\n\
+         req = web.recv_from(req.client);\n\
+         web.serve_file(req.client, req.path);",
+    );
+    answer.join().unwrap();
+    let lines = std::fs::read_to_string(&log).unwrap();
+    std::fs::remove_file(&log).unwrap();
+    let lines: Vec<&str> = lines.lines().collect();
+    assert_eq!(lines.len(), 2, "{:?}", lines);
+    assert!(lines[0].starts_with("127.0.0.1 - - ["), "{}", lines[0]);
+    assert!(
+        lines[0].contains(" +0000] \"GET /a?x=1 HTTP/1.1\" 201 2 "),
+        "{}",
+        lines[0]
+    );
+    assert!(
+        lines[1].contains("] \"GET /missing HTTP/1.1\" 404 9 "),
+        "{}",
+        lines[1]
+    );
+    assert!(error(&mut engine, "web.access_log(server, 1);")
+        .contains("access_log expects a path, \"-\" or none"));
+}
+
+#[test]
+fn middleware_runs_for_every_request() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\n\
+             web.use(server, \"headers\", new {{ [\"X-Frame-Options\"]: \"DENY\" }});\n\
+             web.use(server, \"cors\", new {{ origin: \"https://example.com\" }});\n\
+             web.use(server, \"basic_auth\", new {{ user: \"ada\", password: \"pw\" }});\n\
+             web.use(server, \"error_page\", new {{ status: 404, body: \"<h1>gone</h1>\" }});",
+            address
+        ),
+    );
+    let answer = client(
+        &address,
+        "OPTIONS / HTTP/1.1\r\nAccess-Control-Request-Method: PUT\r\n\r\n\
+         GET /private HTTP/1.1\r\n\r\n\
+         GET /missing HTTP/1.1\r\nAuthorization: Basic YWRhOnB3\r\nConnection: close\r\n\r\n",
+    );
+    let path = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         web.send_res(req.client, new { status: 404, headers: new { [\"X-Frame-Options\"]: \"SAMEORIGIN\" }, body: \"no\" });\n\
+         return req.path;",
+    );
+    assert_eq!(path, Value::String(String::from("/missing")));
+    let answer = answer.join().unwrap();
+    let responses: Vec<&str> = answer.split("HTTP/1.1 ").skip(1).collect();
+    assert_eq!(responses.len(), 3, "{}", answer);
+    assert!(responses[0].starts_with("204 No Content\r\n"), "{}", answer);
+    assert!(
+        responses[0].contains("\r\nAccess-Control-Allow-Origin: https://example.com\r\n"),
+        "{}",
+        answer
+    );
+    assert!(
+        responses[1].starts_with("401 Unauthorized\r\n"),
+        "{}",
+        answer
+    );
+    assert!(
+        responses[1].contains("\r\nWWW-Authenticate: Basic realm=\"olvweb\"\r\n"),
+        "{}",
+        answer
+    );
+    assert!(
+        responses[1].contains("\r\nX-Frame-Options: DENY\r\n"),
+        "{}",
+        answer
+    );
+    assert!(responses[2].starts_with("404 Not Found\r\n"), "{}", answer);
+    assert!(
+        responses[2].contains("\r\nX-Frame-Options: SAMEORIGIN\r\n"),
+        "{}",
+        answer
+    );
+    assert!(
+        responses[2].ends_with("\r\n\r\n<h1>gone</h1>"),
+        "{}",
+        answer
+    );
+    assert!(error(&mut engine, "web.use(server, \"gzip\", none);")
+        .contains("invalid middleware 'gzip'"));
+    assert!(error(&mut engine, "web.use(server, \"basic_auth\", none);")
+        .contains("missing middleware option 'user'"));
+}
+
+#[test]
+fn templates_are_rendered_from_a_context() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return web.render(\"<h1>{{ title }}</h1>{% for item in items %}<p>{{ item.name }}\
+             {% if item.sale %} sale{% else %}!{% end %}</p>{% end %}{{ html | raw }}\
+             {% if not missing %}{{ missing.name }}-{{ items.1.name }}{% end %}\", new {\n\
+                 title: \"<Shop & co>\",\n\
+                 items: new [new { name: \"tea\", sale: true }, new { name: \"cake\", sale: false }],\n\
+                 html: \"<hr>\"\n\
+             });",
+        ),
+        Value::String(String::from(
+            "<h1>&lt;Shop &amp; co&gt;</h1><p>tea sale</p><p>cake!</p><hr>-cake"
+        ))
+    );
+    assert!(
+        error(&mut engine, "web.render(\"a\\n{% if x %}\", new {});")
+            .contains("render failed: line 2: 'if' without 'end'")
+    );
+    assert!(
+        error(&mut engine, "web.render(\"{{ x | upper }}\", new {});")
+            .contains("render failed: line 1: unknown filter in 'x | upper'")
+    );
+    assert!(error(
+        &mut engine,
+        "web.render(\"{% for x in y %}{% end %}\", new { y: 1 });"
+    )
+    .contains("render failed: line 1: 'y' is not a list"));
+}
+
+#[test]
+fn content_types_are_negotiated() {
+    let mut engine = engine();
+    let string = |text: &str| Value::String(String::from(text));
+    assert_eq!(
+        eval(
+            &mut engine,
+            "req = new { headers: new { accept: \"text/html;q=0.8, application/json, image/*;q=0.5, text/csv;q=0\" } };\n\
+             any = new { headers: new {} };\n\
+             return new [\n\
+                 web.mime_for_extension(\".PNG\"),\n\
+                 web.mime_for_extension(\"unknown\"),\n\
+                 web.accepts(req, \"image/webp\"),\n\
+                 web.accepts(req, \"text/csv\"),\n\
+                 web.accepts(req, \"text/plain\"),\n\
+                 web.accepts(any, \"text/plain\"),\n\
+                 web.preferred_type(req, new [\"text/html\", \"application/json; charset=utf-8\"]),\n\
+                 web.preferred_type(any, new [\"text/html\", \"application/json\"]),\n\
+                 web.preferred_type(req, new [\"text/csv\"])\n\
+             ];",
+        ),
+        Value::List(vec![
+            string("image/png"),
+            string("application/octet-stream"),
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Boolean(false),
+            Value::Boolean(true),
+            string("application/json; charset=utf-8"),
+            string("text/html"),
+            Value::None,
+        ])
+    );
+    assert!(
+        error(&mut engine, "web.accepts(\"text/html\", \"text/html\");")
+            .contains("accepts expects a request")
+    );
+}
+
+#[test]
+fn sessions_are_kept_by_cookie() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\n\
+             web.session_options(new {{ ttl: 60, secure: true }});",
+            address
+        ),
+    );
+    let answer = client(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    let id = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         id = web.session_start(req);\n\
+         web.session_set(id, \"user\", \"ada\");\n\
+         web.send_res(req.client, \"hi\");\n\
+         return id;",
+    );
+    let answer = answer.join().unwrap();
+    let id = match id {
+        Value::String(id) => id,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(id.len(), 43);
+    assert!(
+        answer.contains(&format!(
+            "\r\nSet-Cookie: olvsession={}; Path=/; Max-Age=60; HttpOnly; SameSite=Lax; Secure\r\n",
+            id
+        )),
+        "{}",
+        answer
+    );
+    let answer = client(
+        &address,
+        &format!(
+            "GET / HTTP/1.1\r\nConnection: close\r\nCookie: olvsession={}\r\n\r\n",
+            id
+        ),
+    );
+    let again = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         again = web.session_start(req);\n\
+         user = web.session_get(again, \"user\");\n\
+         web.session_end(req);\n\
+         web.send_res(req.client, \"bye\");\n\
+         return new [again, user, web.session_get(again, \"user\")];",
+    );
+    let answer = answer.join().unwrap();
+    assert_eq!(
+        again,
+        Value::List(vec![
+            Value::String(id.clone()),
+            Value::String(String::from("ada")),
+            Value::None,
+        ])
+    );
+    assert!(
+        answer.contains("\r\nSet-Cookie: olvsession=; Path=/; Max-Age=0;"),
+        "{}",
+        answer
+    );
+    assert!(error(
+        &mut engine,
+        &format!("web.session_set({:?}, \"a\", 1);", id)
+    )
+    .contains("session_set got a session that has ended"));
+    assert!(error(&mut engine, "web.session_options(new { ttl: 0 });")
+        .contains("invalid session option 'ttl'"));
+}
+
+#[test]
+fn requests_over_the_rate_are_answered_with_429() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\n\
+             web.use(server, \"rate_limit\", new {{ requests: 1, seconds: 60 }});",
+            address
+        ),
+    );
+    let answer = client(
+        &address,
+        "GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    let second = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         web.send_res(req.client, \"a\");\n\
+         return web.recv_from(req.client);",
+    );
+    assert_eq!(second, Value::None);
+    let answer = answer.join().unwrap();
+    assert!(
+        answer.contains("\r\n\r\naHTTP/1.1 429 Too Many Requests\r\n"),
+        "{}",
+        answer
+    );
+    assert!(answer.contains("\r\nRetry-After: 60\r\n"), "{}", answer);
+    assert!(error(
+        &mut engine,
+        "web.use(server, \"rate_limit\", new { requests: 0 });"
+    )
+    .contains("use expects requests as an integer from 1"));
+}
+
+#[test]
+fn connections_over_the_limit_per_address_are_rejected() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\n\
+             web.configure(server, new {{ max_connections_per_ip: 1 }});",
+            address
+        ),
+    );
+    let first = client(&address, "GET /first HTTP/1.1\r\nConnection: close\r\n\r\n");
+    eval(&mut engine, "first = web.recv_req(server);");
+    // accepted while the first connection is still open
+    let over = client(&address, "GET /over HTTP/1.1\r\nConnection: close\r\n\r\n");
+    eval(&mut engine, "pending = web.recv_req_async(server);");
+    let over = over.join().unwrap();
+    assert!(
+        over.starts_with("HTTP/1.1 429 Too Many Requests\r\n"),
+        "{}",
+        over
+    );
+    eval(&mut engine, "web.send_res(first.client, \"1\");");
+    assert!(first.join().unwrap().ends_with("\r\n\r\n1"));
+    let answer = client(&address, "GET /after HTTP/1.1\r\nConnection: close\r\n\r\n");
+    let path = eval(
+        &mut engine,
+        "req = web.poll_req(pending);\n\
+         while (req == none) { req = web.poll_req(pending); }\n\
+         web.send_res(req.client, \"2\");\n\
+         return req.path;",
+    );
+    assert_eq!(path, Value::String(String::from("/after")));
+    assert!(answer.join().unwrap().ends_with("\r\n\r\n2"));
+}
+
+#[test]
+fn large_bodies_are_spooled_to_a_file() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\n\
+             web.configure(server, new {{ spool_body_size: 4 }});",
+            address
+        ),
+    );
+    let answer = client(
+        &address,
+        "POST /big HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world\
+         POST /small HTTP/1.1\r\nConnection: close\r\nContent-Length: 3\r\n\r\nhey",
+    );
+    let big = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         web.send_res(req.client, \"\");\n\
+         return new [req.body_file, req.content];",
+    );
+    let path = match &big {
+        Value::List(values) => match &values[..] {
+            [Value::String(path), Value::String(content)] if content.is_empty() => path.clone(),
+            _ => panic!("{:?}", big),
+        },
+        _ => panic!("{:?}", big),
+    };
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello world");
+    let small = eval(
+        &mut engine,
+        "small = web.recv_from(req.client);\n\
+         web.send_res(small.client, \"\");\n\
+         return new [small.body_file, small.content];",
+    );
+    answer.join().unwrap();
+    assert_eq!(
+        small,
+        Value::List(vec![Value::None, Value::String(String::from("hey"))])
+    );
+    assert!(!std::path::Path::new(&path).exists());
+}
+
+#[test]
+fn requests_are_proxied_to_upstream_servers() {
+    let mut engine = engine();
+    let address = free_address();
+    let (upstream_address, upstream_request) = upstream(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\nfrom upstream",
+    );
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let answer = client(
+        &address,
+        "POST /api/items HTTP/1.1\r\nConnection: close\r\nHost: example.com\r\n\
+         X-Token: abc\r\nContent-Length: 4\r\n\r\nname",
+    );
+    let status = eval(
+        &mut engine,
+        &format!(
+            "req = web.recv_req(server);\n\
+             req.path = \"/items\";\n\
+             return web.proxy(req.client, {:?}, req);",
+            upstream_address
+        ),
+    );
+    assert_eq!(status, Value::Integer(200));
+    let request = upstream_request.join().unwrap();
+    assert!(
+        request.starts_with("POST /items HTTP/1.1\r\nx-token: abc\r\nX-Forwarded-For: 127.0.0.1\r\nHost: example.com\r\nContent-Length: 4\r\nConnection: close\r\n\r\nname"),
+        "{}",
+        request
+    );
+    let answer = answer.join().unwrap();
+    assert!(answer.starts_with("HTTP/1.1 200 OK\r\n"), "{}", answer);
+    assert!(
+        answer.contains("\r\nContent-Type: text/plain\r\n"),
+        "{}",
+        answer
+    );
+    assert!(
+        answer.ends_with("\r\nTransfer-Encoding: chunked\r\n\r\nd\r\nfrom upstream\r\n0\r\n\r\n"),
+        "{}",
+        answer
+    );
+    // nothing listens there
+    let answer = client(&address, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    let status = eval(
+        &mut engine,
+        &format!(
+            "req = web.recv_req(server);\nreturn web.proxy(req.client, {:?}, req);",
+            free_address()
+        ),
+    );
+    assert_eq!(status, Value::Integer(502));
+    assert!(answer
+        .join()
+        .unwrap()
+        .starts_with("HTTP/1.1 502 Bad Gateway\r\n"));
+}
+
+#[test]
+fn ranges_of_files_are_served() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let answer = client(
+        &address,
+        "GET /tests/files/hello.html HTTP/1.1\r\nRange: bytes=3-7\r\n\r\n\
+         GET /tests/files/hello.html HTTP/1.1\r\nRange: bytes=-5\r\n\r\n\
+         GET /tests/files/hello.html HTTP/1.1\r\nRange: bytes=0-1, 4-5\r\n\r\n\
+         GET /tests/files/hello.html HTTP/1.1\r\nConnection: close\r\nRange: bytes=13-\r\n\r\n",
+    );
+    let statuses = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         statuses = new [web.serve_file(req.client, req.path)];\n\
+         i = 0;\n\
+         while (i < 3) {\n\
+             req = web.recv_from(req.client);\n\
+             statuses = statuses $ new [web.serve_file(req.client, req.path)];\n\
+             i = i + 1;\n\
+         }\n\
+         return statuses;",
+    );
+    assert_eq!(
+        statuses,
+        Value::List(vec![
+            Value::Integer(206),
+            Value::Integer(206),
+            Value::Integer(200),
+            Value::Integer(416),
+        ])
+    );
+    let answer = answer.join().unwrap();
+    let responses: Vec<&str> = answer.split("HTTP/1.1 ").skip(1).collect();
+    assert!(
+        responses[0].starts_with("206 Partial Content\r\n")
+            && responses[0].contains("\r\nAccept-Ranges: bytes\r\n")
+            && responses[0]
+                .ends_with("\r\nContent-Range: bytes 3-7/13\r\nContent-Length: 5\r\n\r\nhello"),
+        "{}",
+        answer
+    );
+    assert!(
+        responses[1]
+            .ends_with("\r\nContent-Range: bytes 8-12/13\r\nContent-Length: 5\r\n\r\n</p>\n"),
+        "{}",
+        answer
+    );
+    // several ranges at once get the whole file
+    assert!(
+        responses[2].ends_with("\r\nContent-Length: 13\r\n\r\n<p>hello</p>\n"),
+        "{}",
+        answer
+    );
+    assert!(
+        responses[3].starts_with("416 Range Not Satisfiable\r\n")
+            && responses[3].contains("\r\nContent-Range: bytes */13\r\n"),
+        "{}",
+        answer
+    );
+}
+
+#[test]
+fn unchanged_files_are_answered_with_304() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!("server = web.create_server({:?});", address),
+    );
+    let path = "tests/files/hello.html";
+    let modified = std::fs::metadata(path).unwrap().modified().unwrap();
+    let since = modified.duration_since(std::time::UNIX_EPOCH).unwrap();
+    let etag = format!("\"d-{:x}{:08x}\"", since.as_secs(), since.subsec_nanos());
+    let last_modified = httpdate::fmt_http_date(modified);
+    let answer = client(
+        &address,
+        &format!(
+            "GET /{path} HTTP/1.1\r\n\r\n\
+             GET /{path} HTTP/1.1\r\nIf-None-Match: \"other\", W/{etag}\r\n\r\n\
+             GET /{path} HTTP/1.1\r\nIf-Modified-Since: {last_modified}\r\n\r\n\
+             GET /{path} HTTP/1.1\r\nIf-None-Match: \"other\"\r\nIf-Modified-Since: {last_modified}\r\n\r\n\
+             GET /{path} HTTP/1.1\r\nConnection: close\r\nRange: bytes=3-7\r\nIf-Range: \"old\"\r\n\r\n",
+            path = path,
+            etag = etag,
+            last_modified = last_modified
+        ),
+    );
+    let statuses = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         statuses = new [web.serve_file(req.client, req.path)];\n\
+         i = 0;\n\
+         while (i < 4) {\n\
+             req = web.recv_from(req.client);\n\
+             statuses = statuses $ new [web.serve_file(req.client, req.path)];\n\
+             i = i + 1;\n\
+         }\n\
+         return statuses;",
+    );
+    // the range is for another version of the file, so all of it is sent
+    assert_eq!(
+        statuses,
+        Value::List(vec![
+            Value::Integer(200),
+            Value::Integer(304),
+            Value::Integer(304),
+            Value::Integer(200),
+            Value::Integer(200),
+        ])
+    );
+    let answer = answer.join().unwrap();
+    let responses: Vec<&str> = answer.split("HTTP/1.1 ").skip(1).collect();
+    assert!(
+        responses[0].contains(&format!(
+            "\r\nETag: {}\r\nLast-Modified: {}\r\n",
+            etag, last_modified
+        )),
+        "{}",
+        answer
+    );
+    assert!(
+        responses[1].starts_with("304 Not Modified\r\n") && responses[1].ends_with("\r\n\r\n"),
+        "{}",
+        answer
+    );
+    assert!(
+        responses[4].ends_with("\r\n\r\n<p>hello</p>\n"),
+        "{}",
+        answer
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn servers_listen_on_unix_sockets() {
+    use std::os::unix::net::UnixStream;
+    let mut engine = engine();
+    let path = std::env::temp_dir().join(format!("olvweb-test-{}.sock", std::process::id()));
+    let path = path.to_str().unwrap();
+    // left over from an earlier run, which bind removes
+    drop(std::os::unix::net::UnixListener::bind(path));
+    eval(
+        &mut engine,
+        &format!("server = web.create_server(\"unix:{}\");", path),
+    );
+    let mut stream = UnixStream::connect(path).unwrap();
+    stream
+        .write_all(b"GET /local HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let request = eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         web.send_res(req.client, \"over a socket\");\n\
+         return new [req.path, req.addr];",
+    );
+    assert_eq!(
+        request,
+        Value::List(vec![
+            Value::String(String::from("/local")),
+            Value::String(String::from("unix")),
+        ])
+    );
+    let mut answer = String::new();
+    stream.read_to_string(&mut answer).unwrap();
+    assert!(answer.ends_with("\r\n\r\nover a socket"), "{}", answer);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn metrics_count_all_responses() {
+    let mut engine = engine();
+    let address = free_address();
+    eval(
+        &mut engine,
+        &format!(
+            "server = web.create_server({:?});\n\
+             web.use(server, \"metrics\", none);",
+            address
+        ),
+    );
+    let answer = client(
+        &address,
+        "GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\nGET /c HTTP/1.1\r\n\r\n\
+         GET /metrics?x=1 HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    eval(
+        &mut engine,
+        "req = web.recv_req(server);\n\
+         web.send_res(req.client, \"ok\");\n\
+         req = web.recv_from(req.client);\n\
+         web.send_res(req.client, new { status: 404 });\n\
+         req = web.recv_from(req.client);\n\
+         web.send_res(req.client, new { status: 500 });\n\
+         req = web.recv_from(req.client);",
+    );
+    let answer = answer.join().unwrap();
+    let metrics = answer.split("\r\n\r\n").last().unwrap();
+    assert!(
+        answer.contains("\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\n"),
+        "{}",
+        answer
+    );
+    for line in &[
+        "olvweb_responses_total{code=\"2xx\"} 1\n",
+        "olvweb_responses_total{code=\"4xx\"} 1\n",
+        "olvweb_responses_total{code=\"5xx\"} 1\n",
+        "olvweb_errors_total 1\n",
+        "olvweb_request_duration_seconds_count 3\n",
+        "olvweb_request_duration_seconds{quantile=\"0.99\"} ",
+    ] {
+        assert!(metrics.contains(line), "{}", metrics);
+    }
+    // only known on linux
+    if cfg!(target_os = "linux") {
+        assert!(
+            metrics.contains("\nprocess_resident_memory_bytes "),
+            "{}",
+            metrics
+        );
+    }
+}
@@ -0,0 +1,86 @@
+// gzip and deflate compression for scripts, added to an engine with
+// register. compressed data isn't valid utf-8, so it is passed around as a
+// list of byte integers
+use flate2::read::{DeflateDecoder, GzDecoder};
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+use olive::{Engine, Value};
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+// adds the functions to a global zip bendy, e.g. zip.gzip(data):
+//   gzip(data) and deflate(data) compress a string or a list of bytes
+//   gunzip(bytes) and inflate(bytes) decompress a list of bytes back into a
+//     string
+//   read_gz(path) reads and decompresses a .gz file
+pub fn register(engine: &mut Engine) {
+    engine.register_module_fn("zip", "gzip", 1, |args| {
+        let encoder = GzEncoder::new(Vec::new(), Compression::default());
+        compress(encoder, &args[0], "gzip", |encoder| encoder.finish())
+    });
+    engine.register_module_fn("zip", "deflate", 1, |args| {
+        let encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        compress(encoder, &args[0], "deflate", |encoder| encoder.finish())
+    });
+    engine.register_module_fn("zip", "gunzip", 1, |args| {
+        let bytes = bytes(&args[0], "gunzip")?;
+        read_string(GzDecoder::new(&bytes[..])).map_err(failed("gunzip"))
+    });
+    engine.register_module_fn("zip", "inflate", 1, |args| {
+        let bytes = bytes(&args[0], "inflate")?;
+        read_string(DeflateDecoder::new(&bytes[..])).map_err(failed("inflate"))
+    });
+    engine.register_module_fn("zip", "read_gz", 1, |args| {
+        let path = match &args[0] {
+            Value::String(path) => path,
+            _ => return Err(String::from("read_gz expects a path")),
+        };
+        let file =
+            File::open(path).map_err(|error| format!("couldn't open '{}': {}", path, error))?;
+        read_string(GzDecoder::new(file)).map_err(failed("read_gz"))
+    });
+}
+
+fn bytes(value: &Value, function: &str) -> Result<Vec<u8>, String> {
+    match value {
+        Value::String(value) => Ok(value.as_bytes().to_vec()),
+        Value::List(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Integer(byte) if *byte >= 0 && *byte < 256 => Ok(*byte as u8),
+                _ => Err(format!(
+                    "{} expects a list of bytes from 0 to 255",
+                    function
+                )),
+            })
+            .collect(),
+        _ => Err(format!("{} expects a string or a list of bytes", function)),
+    }
+}
+
+fn compress<W, F>(mut encoder: W, value: &Value, function: &str, finish: F) -> Result<Value, String>
+where
+    W: Write,
+    F: FnOnce(W) -> io::Result<Vec<u8>>,
+{
+    encoder
+        .write_all(&bytes(value, function)?)
+        .map_err(failed(function))?;
+    let compressed = finish(encoder).map_err(failed(function))?;
+    Ok(Value::List(
+        compressed
+            .into_iter()
+            .map(|byte| Value::Integer(byte as i64))
+            .collect(),
+    ))
+}
+
+fn read_string<R: Read>(mut reader: R) -> io::Result<Value> {
+    let mut string = String::new();
+    reader.read_to_string(&mut string)?;
+    Ok(Value::String(string))
+}
+
+fn failed(function: &str) -> impl Fn(io::Error) -> String + '_ {
+    move |error| format!("{} failed: {}", function, error)
+}
@@ -0,0 +1,52 @@
+use olive::testing::{error, eval, string};
+use olive::{Engine, Value};
+use std::io::Write;
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    olvzip::register(&mut engine);
+    engine
+}
+
+#[test]
+fn compressed_data_comes_back() {
+    let mut engine = engine();
+    let text = "hello hello hello hello hello hello hello hello";
+    eval(&mut engine, &format!("text = {:?};", text));
+    assert_eq!(
+        eval(&mut engine, "return zip.gunzip(zip.gzip(text));"),
+        string(text)
+    );
+    assert_eq!(
+        eval(&mut engine, "return zip.inflate(zip.deflate(text));"),
+        string(text)
+    );
+    // the gzip magic number
+    assert_eq!(
+        eval(
+            &mut engine,
+            "data = zip.gzip(text);\nreturn new [data[0], data[1]];"
+        ),
+        Value::List(vec![Value::Integer(0x1f), Value::Integer(0x8b)])
+    );
+    assert!(error(&mut engine, "zip.gunzip(new [1, 2, 3]);").contains("gunzip failed"));
+    assert!(error(&mut engine, "zip.gzip(new [256]);").contains("bytes from 0 to 255"));
+}
+
+#[test]
+fn gz_files_are_read() {
+    let path = std::env::temp_dir().join(format!("olvzip-{}.gz", std::process::id()));
+    let mut encoder = flate2::write::GzEncoder::new(
+        std::fs::File::create(&path).unwrap(),
+        flate2::Compression::default(),
+    );
+    encoder.write_all(b"from a file").unwrap();
+    encoder.finish().unwrap();
+    let mut engine = engine();
+    let read = eval(
+        &mut engine,
+        &format!("return zip.read_gz({:?});", path.to_str().unwrap()),
+    );
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(read, string("from a file"));
+}
@@ -25,6 +25,14 @@ pub enum Statement<'a> {
         expression: Box<Located<Expression<'a>>>,
         args: Vec<Located<Expression<'a>>>,
     },
+    // runs the statement when the function or script it is in is left
+    Defer {
+        statement: Box<Located<Statement<'a>>>,
+    },
+    // a pipe used as a statement, its result is dropped
+    Pipe {
+        pipe: Located<Expression<'a>>,
+    },
 }
 
 #[derive(Debug)]
@@ -50,6 +58,8 @@ pub enum BinaryOperator {
     BoolOr,
     Concat,
     Access,
+    // obj?.name, none when obj is none or has no such key
+    OptionalAccess,
 }
 
 #[derive(Debug)]
@@ -64,7 +74,7 @@ pub enum Expression<'a> {
         elements: Vec<Located<Expression<'a>>>,
     },
     Bendy {
-        elements: Vec<(Located<&'a str>, Located<Expression<'a>>)>,
+        elements: Vec<(Key<'a>, Located<Expression<'a>>)>,
     },
     Integer {
         value: &'a str,
@@ -91,18 +101,44 @@ pub enum Expression<'a> {
         expression: Box<Located<Expression<'a>>>,
         operator: UnaryOperator,
     },
+    // optional for expression?[index], which is none when the expression is
+    // none or the index is out of bounds
     Index {
         expression: Box<Located<Expression<'a>>>,
         index: Box<Located<Expression<'a>>>,
+        optional: bool,
     },
     Call {
         expression: Box<Located<Expression<'a>>>,
         args: Vec<Located<Expression<'a>>>,
     },
     Function {
-        parameters: Vec<Located<&'a str>>,
+        parameters: Vec<Parameter<'a>>,
         block: Vec<Located<Statement<'a>>>,
     },
+    // value |> function calls the function with the value. if the function
+    // is a call itself, the value goes in front of its arguments, so
+    // x |> f(y) is f(x, y)
+    Pipe {
+        value: Box<Located<Expression<'a>>>,
+        function: Box<Located<Expression<'a>>>,
+    },
+}
+
+// a function parameter, optionally with a type that arguments are checked
+// against when the function is called, as in fun(x: integer)
+#[derive(Debug)]
+pub struct Parameter<'a> {
+    pub name: Located<&'a str>,
+    pub type_name: Option<Located<&'a str>>,
+}
+
+// the key of a bendy literal element
+#[derive(Debug)]
+pub enum Key<'a> {
+    Name(Located<&'a str>),
+    // new { [expression]: value }, the expression has to give a string
+    Computed(Located<Expression<'a>>),
 }
 
 #[derive(Debug)]
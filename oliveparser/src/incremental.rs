@@ -0,0 +1,214 @@
+// reparsing after an edit for editor tooling. only the top level statements
+// touching the edit are parsed again, together with the whitespace and
+// comments around them up to the neighbouring unchanged statements. callers
+// keep the spans of the top level statements of their last parse and splice
+// the new statements in where the old ones were replaced
+use crate::ast::{Expression, Key, Located, Statement};
+use crate::tokens::{self, Category};
+use crate::SyntaxError;
+use lalrpop_util::lexer::Token;
+use lalrpop_util::ParseError;
+use std::ops::Range;
+
+// old[start..old_end] was replaced by new[start..new_end]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edit {
+    pub start: usize,
+    pub old_end: usize,
+    pub new_end: usize,
+}
+
+impl Edit {
+    // where a position behind the edit moved to
+    fn moved(&self, position: usize) -> usize {
+        position + self.new_end - self.old_end
+    }
+}
+
+#[derive(Debug)]
+pub struct Reparsed<'a> {
+    // indices of the previous statements that were replaced
+    pub replaced: Range<usize>,
+    // the statements replacing them, located in the new source
+    pub statements: Vec<Located<Statement<'a>>>,
+    edit: Edit,
+}
+
+impl<'a> Reparsed<'a> {
+    // brings the spans the reparse started from up to date with the new source
+    pub fn update_spans(&self, spans: &mut Vec<Range<usize>>) {
+        let end = self.replaced.start + self.statements.len();
+        spans.splice(self.replaced.clone(), statement_spans(&self.statements));
+        for span in &mut spans[end..] {
+            *span = self.edit.moved(span.start)..self.edit.moved(span.end);
+        }
+    }
+}
+
+pub fn statement_spans(statements: &[Located<Statement>]) -> Vec<Range<usize>> {
+    statements
+        .iter()
+        .map(|statement| statement.start..statement.end)
+        .collect()
+}
+
+pub fn reparse<'a>(
+    previous: &[Range<usize>],
+    source: &'a str,
+    edit: Edit,
+) -> Result<Reparsed<'a>, ParseError<usize, Token<'a>, SyntaxError>> {
+    // statements ending right where the edit starts count as touched, text
+    // typed behind them could continue them (e.g. an else after an if)
+    let first = previous.partition_point(|span| span.end < edit.start);
+    let last = previous.partition_point(|span| span.start <= edit.old_end);
+    let from = if first > 0 {
+        previous[first - 1].end
+    } else {
+        0
+    };
+    let to = match previous.get(last) {
+        Some(span) => edit.moved(span.start),
+        None => source.len(),
+    };
+    if let Some(statements) = parse_between(source, from, to) {
+        return Ok(Reparsed {
+            replaced: first..last,
+            statements,
+            edit,
+        });
+    }
+    // the edit reaches into the statements around it, e.g. a removed
+    // semicolon or an unclosed comment, so everything is parsed again
+    let statements = crate::parse(source)?;
+    Ok(Reparsed {
+        replaced: 0..previous.len(),
+        statements,
+        edit,
+    })
+}
+
+// parses source[from..to] on its own, unless the result could differ from
+// parsing it as part of the whole source
+fn parse_between(source: &str, from: usize, to: usize) -> Option<Vec<Located<Statement<'_>>>> {
+    let text = &source[from..to];
+    // a comment running up to the end would also cover what follows it
+    if let Some(token) = tokens::tokenize(text).last() {
+        let unclosed = token.category == Category::Comment
+            && token.end == text.len()
+            && (!token.text.starts_with("##")
+                || tokens::block_comment_length(token.text).is_none());
+        if unclosed && to < source.len() {
+            return None;
+        }
+    }
+    let mut statements = crate::parse(text).ok()?;
+    for statement in &mut statements {
+        move_statement(statement, from);
+    }
+    Some(statements)
+}
+
+fn move_block(statements: &mut [Located<Statement>], offset: usize) {
+    for statement in statements {
+        move_statement(statement, offset);
+    }
+}
+
+fn move_statement(statement: &mut Located<Statement>, offset: usize) {
+    statement.start += offset;
+    statement.end += offset;
+    match &mut statement.inner {
+        Statement::Break | Statement::Continue => {}
+        Statement::Return { value } => move_expression(value, offset),
+        Statement::Block { statements } => move_block(statements, offset),
+        Statement::While { condition, block } => {
+            move_expression(condition, offset);
+            move_block(block, offset);
+        }
+        Statement::If {
+            condition,
+            block,
+            elseblock,
+        } => {
+            move_expression(condition, offset);
+            move_block(block, offset);
+            if let Some(elseblock) = elseblock {
+                move_block(elseblock, offset);
+            }
+        }
+        Statement::Assign { left, right } => {
+            move_expression(left, offset);
+            move_expression(right, offset);
+        }
+        Statement::Call { expression, args } => {
+            move_expression(expression, offset);
+            for arg in args {
+                move_expression(arg, offset);
+            }
+        }
+        Statement::Defer { statement } => move_statement(statement, offset),
+        Statement::Pipe { pipe } => move_expression(pipe, offset),
+    }
+}
+
+fn move_expression(expression: &mut Located<Expression>, offset: usize) {
+    expression.start += offset;
+    expression.end += offset;
+    match &mut expression.inner {
+        Expression::List { elements } => {
+            for element in elements {
+                move_expression(element, offset);
+            }
+        }
+        Expression::Bendy { elements } => {
+            for (key, element) in elements {
+                match key {
+                    Key::Name(name) => {
+                        name.start += offset;
+                        name.end += offset;
+                    }
+                    Key::Computed(key) => move_expression(key, offset),
+                }
+                move_expression(element, offset);
+            }
+        }
+        Expression::Binary { left, right, .. } => {
+            move_expression(left, offset);
+            move_expression(right, offset);
+        }
+        Expression::Unary { expression, .. } => move_expression(expression, offset),
+        Expression::Index {
+            expression, index, ..
+        } => {
+            move_expression(expression, offset);
+            move_expression(index, offset);
+        }
+        Expression::Call { expression, args } => {
+            move_expression(expression, offset);
+            for arg in args {
+                move_expression(arg, offset);
+            }
+        }
+        Expression::Pipe { value, function } => {
+            move_expression(value, offset);
+            move_expression(function, offset);
+        }
+        Expression::Function { parameters, block } => {
+            for parameter in parameters {
+                parameter.name.start += offset;
+                parameter.name.end += offset;
+                if let Some(type_name) = &mut parameter.type_name {
+                    type_name.start += offset;
+                    type_name.end += offset;
+                }
+            }
+            move_block(block, offset);
+        }
+        Expression::Integer { .. }
+        | Expression::Float { .. }
+        | Expression::String { .. }
+        | Expression::Boolean { .. }
+        | Expression::None
+        | Expression::Variable { .. } => {}
+    }
+}
@@ -2,18 +2,59 @@
 extern crate lalrpop_util;
 
 pub mod ast;
+pub mod incremental;
+pub mod printer;
+pub mod tokens;
+pub mod visit;
 
 pub use lalrpop_util::lexer::Token;
 pub use lalrpop_util::ParseError;
 
-lalrpop_mod!(pub olive);
+// generated by lalrpop from olive.lalrpop
+lalrpop_mod!(#[allow(clippy::all)] pub olive);
+
+// errors found before the grammar runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxError {
+    // start is where the hashes opening the comment are
+    UnterminatedComment { start: usize },
+}
 
 pub fn parse<'a>(
     source: &'a str,
-) -> Result<
-    Vec<ast::Located<ast::Statement<'a>>>,
-    lalrpop_util::ParseError<usize, lalrpop_util::lexer::Token, &str>,
-> {
+) -> Result<Vec<ast::Located<ast::Statement<'a>>>, ParseError<usize, Token<'a>, SyntaxError>> {
+    let code = tokens::strip_comments(source).map_err(|start| ParseError::User {
+        error: SyntaxError::UnterminatedComment { start },
+    })?;
     let parser = olive::FileParser::new();
-    parser.parse(source)
+    parser
+        .parse(source, &code)
+        .map_err(|error| original_tokens(error, source))
+}
+
+// errors of the parser point into the copy without comments, the tokens they
+// carry are taken from the original instead
+fn original_tokens<'a>(
+    error: ParseError<usize, Token<'_>, SyntaxError>,
+    source: &'a str,
+) -> ParseError<usize, Token<'a>, SyntaxError> {
+    match error {
+        ParseError::UnrecognizedToken {
+            token: (start, Token(id, _), end),
+            expected,
+        } => ParseError::UnrecognizedToken {
+            token: (start, Token(id, &source[start..end]), end),
+            expected,
+        },
+        ParseError::ExtraToken {
+            token: (start, Token(id, _), end),
+        } => ParseError::ExtraToken {
+            token: (start, Token(id, &source[start..end]), end),
+        },
+        ParseError::InvalidToken { location } => ParseError::InvalidToken { location },
+        ParseError::UnrecognizedEOF { location, expected } => {
+            ParseError::UnrecognizedEOF { location, expected }
+        }
+        ParseError::User { error } => ParseError::User { error },
+    }
 }
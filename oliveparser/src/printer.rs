@@ -0,0 +1,339 @@
+// renders an ast back to olivescript source in one canonical layout: four
+// space indents, one statement per line and only the parentheses the grammar
+// needs. the ast doesn't keep comments, so they are not part of the output
+use crate::ast::{BinaryOperator, Expression, Key, Located, Statement, UnaryOperator};
+
+const INDENT: &str = "    ";
+
+// binding strength of each grammar level, from pipes up to plain values
+const PIPE: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const BIT_OR: u8 = 4;
+const BIT_XOR: u8 = 5;
+const BIT_AND: u8 = 6;
+const EQUALITY: u8 = 7;
+const CONCAT: u8 = 8;
+const COMPARISON: u8 = 9;
+const SHIFT: u8 = 10;
+const SUM: u8 = 11;
+const PRODUCT: u8 = 12;
+const UNARY: u8 = 13;
+const POSTFIX: u8 = 14;
+const VALUE: u8 = 15;
+
+pub fn print(statements: &[Located<Statement>]) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        depth: 0,
+    };
+    for statement in statements {
+        printer.statement(&statement.inner);
+    }
+    if !printer.out.is_empty() {
+        printer.out.push('\n');
+    }
+    printer.out
+}
+
+pub fn print_expression(expression: &Expression) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        depth: 0,
+    };
+    printer.expression(expression, PIPE);
+    printer.out
+}
+
+fn binary_operator(operator: &BinaryOperator) -> (&'static str, u8) {
+    match operator {
+        BinaryOperator::BoolOr => ("or", OR),
+        BinaryOperator::BoolAnd => ("and", AND),
+        BinaryOperator::BitOr => ("|", BIT_OR),
+        BinaryOperator::BitXOr => ("^", BIT_XOR),
+        BinaryOperator::BitAnd => ("&", BIT_AND),
+        BinaryOperator::Equals => ("==", EQUALITY),
+        BinaryOperator::NotEquals => ("!=", EQUALITY),
+        BinaryOperator::Concat => ("$", CONCAT),
+        BinaryOperator::LessThan => ("<", COMPARISON),
+        BinaryOperator::LessEquals => ("<=", COMPARISON),
+        BinaryOperator::GreaterThan => (">", COMPARISON),
+        BinaryOperator::GreaterEquals => (">=", COMPARISON),
+        BinaryOperator::BitLsh => ("<<", SHIFT),
+        BinaryOperator::BitRsh => (">>", SHIFT),
+        BinaryOperator::Add => ("+", SUM),
+        BinaryOperator::Sub => ("-", SUM),
+        BinaryOperator::Mul => ("*", PRODUCT),
+        BinaryOperator::FloatDiv => ("/", PRODUCT),
+        BinaryOperator::IntDiv => ("//", PRODUCT),
+        BinaryOperator::Mod => ("%", PRODUCT),
+        BinaryOperator::Access => (".", POSTFIX),
+        BinaryOperator::OptionalAccess => ("?.", POSTFIX),
+    }
+}
+
+fn precedence(expression: &Expression) -> u8 {
+    match expression {
+        Expression::Binary { operator, .. } => binary_operator(operator).1,
+        Expression::Unary { .. } => UNARY,
+        Expression::Index { .. } | Expression::Call { .. } => POSTFIX,
+        Expression::Pipe { .. } => PIPE,
+        _ => VALUE,
+    }
+}
+
+// the reverse of the unescaping in the grammar
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    // starts a new line at the current depth unless nothing was printed yet
+    fn line(&mut self) {
+        if !self.out.is_empty() {
+            self.newline();
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        self.line();
+        self.statement_body(statement);
+    }
+
+    // a statement without the line break in front of it
+    fn statement_body(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Break => self.out.push_str("break;"),
+            Statement::Continue => self.out.push_str("continue;"),
+            Statement::Return { value } => {
+                self.out.push_str("return ");
+                self.expression(&value.inner, PIPE);
+                self.out.push(';');
+            }
+            Statement::Block { statements } => self.block(statements),
+            Statement::While { condition, block } => {
+                self.out.push_str("while (");
+                self.expression(&condition.inner, PIPE);
+                self.out.push_str(") ");
+                self.block(block);
+            }
+            Statement::If { .. } => self.if_statement(statement),
+            Statement::Assign { left, right } => {
+                self.expression(&left.inner, POSTFIX);
+                self.out.push_str(" = ");
+                self.expression(&right.inner, PIPE);
+                self.out.push(';');
+            }
+            Statement::Call { expression, args } => {
+                self.expression(&expression.inner, POSTFIX);
+                self.arguments(args);
+                self.out.push(';');
+            }
+            Statement::Defer { statement } => {
+                self.out.push_str("defer ");
+                self.statement_body(&statement.inner);
+            }
+            Statement::Pipe { pipe } => {
+                self.expression(&pipe.inner, PIPE);
+                self.out.push(';');
+            }
+        }
+    }
+
+    // "else { if ... }" and "else if ..." parse to the same ast, the latter
+    // is printed
+    fn if_statement(&mut self, statement: &Statement) {
+        if let Statement::If {
+            condition,
+            block,
+            elseblock,
+        } = statement
+        {
+            self.out.push_str("if (");
+            self.expression(&condition.inner, PIPE);
+            self.out.push_str(") ");
+            self.block(block);
+            match elseblock.as_deref() {
+                Some([chained]) if matches!(chained.inner, Statement::If { .. }) => {
+                    self.out.push_str(" else ");
+                    self.if_statement(&chained.inner);
+                }
+                Some(elseblock) => {
+                    self.out.push_str(" else ");
+                    self.block(elseblock);
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[Located<Statement>]) {
+        if statements.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push('{');
+        self.depth += 1;
+        for statement in statements {
+            self.statement(&statement.inner);
+        }
+        self.depth -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+
+    fn arguments(&mut self, args: &[Located<Expression>]) {
+        self.out.push('(');
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expression(&arg.inner, PIPE);
+        }
+        self.out.push(')');
+    }
+
+    // prints the expression so that it parses at the given grammar level,
+    // wrapping it in parentheses if it binds weaker than that
+    fn expression(&mut self, expression: &Expression, level: u8) {
+        let wrap = precedence(expression) < level;
+        if wrap {
+            self.out.push('(');
+        }
+        match expression {
+            Expression::List { elements } => {
+                self.out.push_str("new [");
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expression(&element.inner, PIPE);
+                }
+                self.out.push(']');
+            }
+            Expression::Bendy { elements } => {
+                if elements.is_empty() {
+                    self.out.push_str("new {}");
+                } else {
+                    self.out.push_str("new {");
+                    self.depth += 1;
+                    for (i, (key, value)) in elements.iter().enumerate() {
+                        if i > 0 {
+                            self.out.push(',');
+                        }
+                        self.newline();
+                        match key {
+                            Key::Name(name) => self.out.push_str(name.inner),
+                            Key::Computed(key) => {
+                                self.out.push('[');
+                                self.expression(&key.inner, PIPE);
+                                self.out.push(']');
+                            }
+                        }
+                        self.out.push_str(": ");
+                        self.expression(&value.inner, PIPE);
+                    }
+                    self.depth -= 1;
+                    self.newline();
+                    self.out.push('}');
+                }
+            }
+            Expression::Integer { value } | Expression::Float { value } => self.out.push_str(value),
+            Expression::String { value } => {
+                self.out.push('"');
+                self.out.push_str(&escape(value));
+                self.out.push('"');
+            }
+            Expression::Boolean { value } => {
+                self.out.push_str(if *value { "true" } else { "false" })
+            }
+            Expression::None => self.out.push_str("none"),
+            Expression::Variable { name } => self.out.push_str(name),
+            Expression::Binary {
+                left,
+                right,
+                operator: operator @ (BinaryOperator::Access | BinaryOperator::OptionalAccess),
+            } => {
+                self.expression(&left.inner, POSTFIX);
+                self.out.push_str(binary_operator(operator).0);
+                self.expression(&right.inner, VALUE);
+            }
+            Expression::Binary {
+                left,
+                right,
+                operator,
+            } => {
+                // all binary operators are left associative
+                let (symbol, level) = binary_operator(operator);
+                self.expression(&left.inner, level);
+                self.out.push(' ');
+                self.out.push_str(symbol);
+                self.out.push(' ');
+                self.expression(&right.inner, level + 1);
+            }
+            Expression::Unary {
+                expression,
+                operator,
+            } => {
+                self.out.push(match operator {
+                    UnaryOperator::Neg => '-',
+                    UnaryOperator::BoolNot => '!',
+                });
+                self.expression(&expression.inner, UNARY);
+            }
+            Expression::Index {
+                expression,
+                index,
+                optional,
+            } => {
+                self.expression(&expression.inner, POSTFIX);
+                self.out.push_str(if *optional { "?[" } else { "[" });
+                self.expression(&index.inner, PIPE);
+                self.out.push(']');
+            }
+            Expression::Call { expression, args } => {
+                self.expression(&expression.inner, POSTFIX);
+                self.arguments(args);
+            }
+            Expression::Function { parameters, block } => {
+                self.out.push_str("fun(");
+                for (i, parameter) in parameters.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str(parameter.name.inner);
+                    if let Some(type_name) = &parameter.type_name {
+                        self.out.push_str(": ");
+                        self.out.push_str(type_name.inner);
+                    }
+                }
+                self.out.push_str(") ");
+                self.block(block);
+            }
+            Expression::Pipe { value, function } => {
+                self.expression(&value.inner, PIPE);
+                self.out.push_str(" |> ");
+                self.expression(&function.inner, OR);
+            }
+        }
+        if wrap {
+            self.out.push(')');
+        }
+    }
+}
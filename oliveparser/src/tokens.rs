@@ -0,0 +1,195 @@
+// splits source into classified tokens for syntax highlighting. unlike the
+// parser this keeps comments and never fails: text that isn't a valid token is
+// returned as Invalid and scanning goes on after it
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Keyword,
+    Identifier,
+    Literal,
+    Operator,
+    Punctuation,
+    Comment,
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken<'a> {
+    pub start: usize,
+    pub end: usize,
+    pub text: &'a str,
+    pub category: Category,
+}
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "continue", "defer", "else", "fun", "if", "new", "or", "return", "while",
+];
+
+const LITERAL_KEYWORDS: &[&str] = &["true", "false", "none"];
+
+// longest first, so that e.g. "<<" wins over "<"
+const OPERATORS: &[&str] = &[
+    "!=", "==", "<=", ">=", "<<", ">>", "//", "|>", "?.", "?[", "!", "$", "%", "&", "*", "+", "-",
+    "/", "<", "=", ">", "^", "|", ".",
+];
+
+const PUNCTUATION: &[char] = &['(', ')', '[', ']', '{', '}', ',', ';', ':'];
+
+pub fn tokenize(source: &str) -> Vec<SpannedToken<'_>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    while start < source.len() {
+        let rest = &source[start..];
+        let first = rest.chars().next().unwrap();
+        if first.is_whitespace() {
+            start += first.len_utf8();
+            continue;
+        }
+        let (length, category) = if rest.starts_with("##") {
+            let length = block_comment_length(rest).unwrap_or(rest.len());
+            (length, Category::Comment)
+        } else if first == '#' {
+            (line_length(rest), Category::Comment)
+        } else if first == '"' {
+            string_length(rest)
+        } else if first.is_ascii_digit()
+            || (first == '.' && bytes.get(start + 1).is_some_and(u8::is_ascii_digit))
+        {
+            let length = number_length(rest);
+            match literal_digits(&rest[..length]) {
+                Some(_) => (length, Category::Literal),
+                None => (length, Category::Invalid),
+            }
+        } else if first.is_ascii_alphabetic() || first == '_' {
+            let length = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..length];
+            let category = if KEYWORDS.contains(&word) {
+                Category::Keyword
+            } else if LITERAL_KEYWORDS.contains(&word) {
+                Category::Literal
+            } else {
+                Category::Identifier
+            };
+            (length, category)
+        } else if let Some(operator) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            (operator.len(), Category::Operator)
+        } else if PUNCTUATION.contains(&first) {
+            (1, Category::Punctuation)
+        } else {
+            (first.len_utf8(), Category::Invalid)
+        };
+        tokens.push(SpannedToken {
+            start,
+            end: start + length,
+            text: &rest[..length],
+            category,
+        });
+        start += length;
+    }
+    tokens
+}
+
+// source with its comments replaced by spaces, line breaks and positions stay
+// the same. the grammar can't match nested comments, so the parser only gets
+// to see this. the error is the start of a block comment that isn't closed
+pub fn strip_comments(source: &str) -> Result<String, usize> {
+    let mut code = String::with_capacity(source.len());
+    let mut copied = 0;
+    for comment in tokenize(source) {
+        if comment.category != Category::Comment {
+            continue;
+        }
+        if comment.text.starts_with("##") && block_comment_length(comment.text).is_none() {
+            return Err(comment.start);
+        }
+        code.push_str(&source[copied..comment.start]);
+        for c in comment.text.chars() {
+            match c {
+                '\n' | '\r' => code.push(c),
+                _ => code.push_str(&" ".repeat(c.len_utf8())),
+            }
+        }
+        copied = comment.end;
+    }
+    code.push_str(&source[copied..]);
+    Ok(code)
+}
+
+// a block comment opened by a run of two or more hashes ends at the next run
+// of just as many, so one with more hashes can hold shorter ones, e.g.
+// "### x = 1; ## the answer ## ###". none if it isn't closed
+pub fn block_comment_length(text: &str) -> Option<usize> {
+    let run = |text: &str| text.find(|c| c != '#').unwrap_or(text.len());
+    let opening = run(text);
+    let mut end = opening;
+    while let Some(next) = text[end..].find('#') {
+        let length = run(&text[end + next..]);
+        end += next + length;
+        if length == opening {
+            return Some(end);
+        }
+    }
+    None
+}
+
+fn line_length(text: &str) -> usize {
+    text.find(['\n', '\r']).unwrap_or(text.len())
+}
+
+// strings can't span lines, an unclosed one is invalid up to the line end
+fn string_length(text: &str) -> (usize, Category) {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            '\n' | '\r' => return (i, Category::Invalid),
+            '"' if !escaped => return (i + 1, Category::Literal),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    (text.len(), Category::Invalid)
+}
+
+// digits and underscores with an optional fraction, a fraction alone or a hex
+// number like 0xff
+fn number_length(text: &str) -> usize {
+    let digits = |text: &str| {
+        text.find(|c: char| !(c.is_ascii_digit() || c == '_'))
+            .unwrap_or(text.len())
+    };
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        let length = hex
+            .find(|c: char| !(c.is_ascii_hexdigit() || c == '_'))
+            .unwrap_or(hex.len());
+        // without any digits the 0 is a number of its own
+        if length > 0 {
+            return 2 + length;
+        }
+    }
+    let integer = if text.starts_with('.') {
+        0
+    } else {
+        digits(text)
+    };
+    if text[integer..].starts_with('.') {
+        integer + 1 + digits(&text[integer + 1..])
+    } else {
+        integer
+    }
+}
+
+// a number literal with its underscores removed, e.g. 1000000 for 1_000_000
+// or 0xffff for 0xff_ff. none if an underscore doesn't stand between two digits
+pub fn literal_digits(literal: &str) -> Option<String> {
+    let bytes = literal.as_bytes();
+    let digit = |i: usize| bytes.get(i).is_some_and(u8::is_ascii_hexdigit);
+    for (i, byte) in bytes.iter().enumerate() {
+        if *byte == b'_' && !(i > 0 && digit(i - 1) && digit(i + 1)) {
+            return None;
+        }
+    }
+    Some(literal.replace('_', ""))
+}
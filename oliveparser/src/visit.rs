@@ -0,0 +1,121 @@
+// walks an ast in source order, a whole file being one block. the methods of
+// a visitor default to walking the children of the node, so an implementation
+// that overrides one calls the matching walk function to keep going below it.
+// the key of an access (the name in obj.name) is not an expression of its own
+// and isn't visited
+use crate::ast::{BinaryOperator, Expression, Key, Located, Statement};
+
+pub trait Visitor<'a> {
+    fn visit_block(&mut self, statements: &[Located<Statement<'a>>]) {
+        walk_block(self, statements);
+    }
+
+    fn visit_statement(&mut self, statement: &Located<Statement<'a>>) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Located<Expression<'a>>) {
+        walk_expression(self, expression);
+    }
+}
+
+pub fn walk_block<'a, V: Visitor<'a> + ?Sized>(
+    visitor: &mut V,
+    statements: &[Located<Statement<'a>>],
+) {
+    for statement in statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<'a, V: Visitor<'a> + ?Sized>(
+    visitor: &mut V,
+    statement: &Located<Statement<'a>>,
+) {
+    match &statement.inner {
+        Statement::Break | Statement::Continue => {}
+        Statement::Return { value } => visitor.visit_expression(value),
+        Statement::Block { statements } => visitor.visit_block(statements),
+        Statement::While { condition, block } => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(block);
+        }
+        Statement::If {
+            condition,
+            block,
+            elseblock,
+        } => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(block);
+            if let Some(elseblock) = elseblock {
+                visitor.visit_block(elseblock);
+            }
+        }
+        Statement::Assign { left, right } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Statement::Call { expression, args } => {
+            visitor.visit_expression(expression);
+            for arg in args {
+                visitor.visit_expression(arg);
+            }
+        }
+        Statement::Defer { statement } => visitor.visit_statement(statement),
+        Statement::Pipe { pipe } => visitor.visit_expression(pipe),
+    }
+}
+
+pub fn walk_expression<'a, V: Visitor<'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &Located<Expression<'a>>,
+) {
+    match &expression.inner {
+        Expression::Integer { .. }
+        | Expression::Float { .. }
+        | Expression::String { .. }
+        | Expression::Boolean { .. }
+        | Expression::None
+        | Expression::Variable { .. } => {}
+        Expression::List { elements } => {
+            for element in elements {
+                visitor.visit_expression(element);
+            }
+        }
+        Expression::Bendy { elements } => {
+            for (key, element) in elements {
+                if let Key::Computed(key) = key {
+                    visitor.visit_expression(key);
+                }
+                visitor.visit_expression(element);
+            }
+        }
+        Expression::Binary {
+            left,
+            operator: BinaryOperator::Access | BinaryOperator::OptionalAccess,
+            ..
+        } => visitor.visit_expression(left),
+        Expression::Binary { left, right, .. } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::Unary { expression, .. } => visitor.visit_expression(expression),
+        Expression::Index {
+            expression, index, ..
+        } => {
+            visitor.visit_expression(expression);
+            visitor.visit_expression(index);
+        }
+        Expression::Call { expression, args } => {
+            visitor.visit_expression(expression);
+            for arg in args {
+                visitor.visit_expression(arg);
+            }
+        }
+        Expression::Function { block, .. } => visitor.visit_block(block),
+        Expression::Pipe { value, function } => {
+            visitor.visit_expression(value);
+            visitor.visit_expression(function);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use oliveparser::ast::{Located, Statement};
+
+// the debug form of the statements without their positions, which move when
+// the source is printed in another layout
+pub fn shape(statements: &[Located<Statement>]) -> String {
+    let debug = format!("{:?}", statements);
+    let mut shape = String::new();
+    let mut rest = debug.as_str();
+    while let Some(i) = rest.find(|c: char| c.is_ascii_digit()) {
+        let (before, digits) = rest.split_at(i);
+        shape.push_str(before);
+        let length = digits
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(digits.len());
+        if !(before.ends_with("start: ") || before.ends_with("end: ")) {
+            shape.push_str(&digits[..length]);
+        }
+        rest = &digits[length..];
+    }
+    shape.push_str(rest);
+    shape
+}
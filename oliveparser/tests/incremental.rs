@@ -0,0 +1,97 @@
+mod common;
+
+use common::shape;
+use oliveparser::ast::{Located, Statement};
+use oliveparser::incremental::{reparse, statement_spans, Edit};
+use oliveparser::parse;
+use std::ops::Range;
+
+// replaces old[start..end] with text, reparses the result incrementally and
+// checks it against parsing the new source in full. gives the indices of the
+// replaced statements
+fn edit(old: &str, start: usize, end: usize, text: &str) -> Range<usize> {
+    let source = format!("{}{}{}", &old[..start], text, &old[end..]);
+    let previous = parse(old).unwrap();
+    let mut spans = statement_spans(&previous);
+    let edit = Edit {
+        start,
+        old_end: end,
+        new_end: start + text.len(),
+    };
+    let reparsed = reparse(&spans, &source, edit).unwrap();
+    let full = parse(&source).unwrap();
+
+    // the new statements are the ones of the full parse, positions included
+    let replaced = reparsed.replaced.clone();
+    let new_range = replaced.start..replaced.start + reparsed.statements.len();
+    assert_eq!(
+        format!("{:?}", reparsed.statements),
+        format!("{:?}", &full[new_range])
+    );
+
+    // spliced into the old statements they give the same ast
+    let mut spliced: Vec<&Located<Statement>> = previous[..replaced.start].iter().collect();
+    spliced.extend(reparsed.statements.iter());
+    spliced.extend(previous[replaced.end..].iter());
+    assert_eq!(spliced.len(), full.len());
+    for (spliced, full) in spliced.iter().zip(&full) {
+        assert_eq!(
+            shape(std::slice::from_ref(*spliced)),
+            shape(std::slice::from_ref(full))
+        );
+    }
+
+    // and the kept spans moved with the edit
+    reparsed.update_spans(&mut spans);
+    assert_eq!(spans, statement_spans(&full));
+    replaced
+}
+
+const SOURCE: &str = "a = 1;\nb = a + 2;\n# note\nprint(b);\n";
+
+#[test]
+fn an_edit_inside_a_statement_reparses_only_it() {
+    let at = SOURCE.find("2;").unwrap();
+    assert_eq!(edit(SOURCE, at, at + 1, "20 * 3"), 1..2);
+    let at = SOURCE.find("1;").unwrap();
+    assert_eq!(edit(SOURCE, at, at + 1, "new [1, 2]"), 0..1);
+}
+
+#[test]
+fn new_statements_between_others_are_inserted() {
+    let at = SOURCE.find("# note").unwrap();
+    assert_eq!(edit(SOURCE, at, at, "c = 3;\nd = 4;\n"), 2..2);
+}
+
+#[test]
+fn text_after_a_statement_can_continue_it() {
+    let source = "if (x) { y = 1; }\nz = 2;\n";
+    let at = source.find('\n').unwrap();
+    assert_eq!(edit(source, at, at, " else { y = 2; }"), 0..1);
+}
+
+#[test]
+fn edits_across_statements_replace_all_of_them() {
+    let start = SOURCE.find("1;").unwrap();
+    let end = SOURCE.find("+ 2").unwrap();
+    assert_eq!(edit(SOURCE, start, end, "1 - a "), 0..2);
+    let start = SOURCE.find("b = ").unwrap();
+    assert_eq!(edit(SOURCE, start, SOURCE.len(), ""), 1..3);
+}
+
+#[test]
+fn broken_neighbours_reparse_everything() {
+    // without the semicolon the next statement continues the edited one
+    let source = "a = b;\n(c)(d);\ne = 1;\n";
+    let at = source.find(';').unwrap();
+    assert_eq!(edit(source, at, at + 1, ""), 0..3);
+    // a line comment put in front of the line break hides the next statement
+    let at = SOURCE.find('\n').unwrap();
+    assert_eq!(edit(SOURCE, at, at + 1, " # "), 0..2);
+}
+
+#[test]
+fn comments_only_edits_change_no_statements() {
+    let at = SOURCE.find("note").unwrap();
+    assert_eq!(edit(SOURCE, at, at + 4, "longer comment"), 2..2);
+}
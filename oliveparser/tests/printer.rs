@@ -0,0 +1,76 @@
+mod common;
+
+use common::shape;
+use oliveparser::parse;
+use oliveparser::printer::print;
+
+// prints the source, checks that the printed source parses to the same ast
+// and is printed the same way again
+fn round_trip(source: &str) -> String {
+    let statements = parse(source).unwrap();
+    let printed = print(&statements);
+    let reparsed = parse(&printed).unwrap_or_else(|e| panic!("{:?} in\n{}", e, printed));
+    assert_eq!(shape(&statements), shape(&reparsed), "{}", printed);
+    assert_eq!(print(&reparsed), printed);
+    printed
+}
+
+#[test]
+fn operators_keep_their_spelling() {
+    assert_eq!(
+        round_trip("x = a+b-c*d/e//f%g;"),
+        "x = a + b - c * d / e // f % g;\n"
+    );
+    assert_eq!(
+        round_trip("x = a<<1|b>>2&c^d;"),
+        "x = a << 1 | b >> 2 & c ^ d;\n"
+    );
+    assert_eq!(
+        round_trip("x = a==b and c!=d or !e and -f<=g;"),
+        "x = a == b and c != d or !e and -f <= g;\n"
+    );
+    assert_eq!(round_trip("x = a $ b;"), "x = a $ b;\n");
+    assert_eq!(round_trip("x = a?.b?[c].d[e];"), "x = a?.b?[c].d[e];\n");
+    assert_eq!(round_trip("x |> f(1) |> g;"), "x |> f(1) |> g;\n");
+}
+
+#[test]
+fn parentheses_are_kept_only_where_needed() {
+    assert_eq!(round_trip("x = (a * b) + c;"), "x = a * b + c;\n");
+    assert_eq!(round_trip("x = (a + b) * c;"), "x = (a + b) * c;\n");
+    assert_eq!(round_trip("x = a - (b - c);"), "x = a - (b - c);\n");
+    assert_eq!(round_trip("x = (a - b) - c;"), "x = a - b - c;\n");
+    assert_eq!(round_trip("x = -(a + b);"), "x = -(a + b);\n");
+    assert_eq!(round_trip("x = !(a or b) and c;"), "x = !(a or b) and c;\n");
+    assert_eq!(round_trip("x = (a or b) and c;"), "x = (a or b) and c;\n");
+    assert_eq!(round_trip("x = ((f))(1)[2];"), "x = f(1)[2];\n");
+    assert_eq!(round_trip("x = (a + b).c;"), "x = (a + b).c;\n");
+}
+
+#[test]
+fn strings_are_escaped_again() {
+    assert_eq!(
+        round_trip(r#"print("a \"quoted\" \\ line\nand\r");"#),
+        "print(\"a \\\"quoted\\\" \\\\ line\\nand\\r\");\n"
+    );
+    assert_eq!(round_trip(r#"x = "";"#), "x = \"\";\n");
+}
+
+#[test]
+fn bendys_put_an_element_on_each_line() {
+    assert_eq!(round_trip("x = new {};"), "x = new {};\n");
+    assert_eq!(
+        round_trip("x = new {a: 1, [\"b\" $ c]: new [1, 2], d: new {e: none}};"),
+        "x = new {\n    a: 1,\n    [\"b\" $ c]: new [1, 2],\n    d: new {\n        e: none\n    }\n};\n"
+    );
+}
+
+#[test]
+fn statements_are_indented_by_depth() {
+    assert_eq!(
+        round_trip(
+            "f = fun(a: integer, b) { if (a) { return b; } else if (b) { while (true) { break; } } else { defer print(a); } };"
+        ),
+        "f = fun(a: integer, b) {\n    if (a) {\n        return b;\n    } else if (b) {\n        while (true) {\n            break;\n        }\n    } else {\n        defer print(a);\n    }\n};\n"
+    );
+}
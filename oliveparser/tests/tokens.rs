@@ -0,0 +1,125 @@
+use oliveparser::tokens::{literal_digits, strip_comments, tokenize, Category};
+
+// text and category of every token
+fn kinds(source: &str) -> Vec<(&str, Category)> {
+    tokenize(source)
+        .into_iter()
+        .map(|token| (token.text, token.category))
+        .collect()
+}
+
+#[test]
+fn tokens_have_categories() {
+    assert_eq!(
+        kinds("if (x != none) { return f(\"a\", 1.5); }"),
+        vec![
+            ("if", Category::Keyword),
+            ("(", Category::Punctuation),
+            ("x", Category::Identifier),
+            ("!=", Category::Operator),
+            ("none", Category::Literal),
+            (")", Category::Punctuation),
+            ("{", Category::Punctuation),
+            ("return", Category::Keyword),
+            ("f", Category::Identifier),
+            ("(", Category::Punctuation),
+            ("\"a\"", Category::Literal),
+            (",", Category::Punctuation),
+            ("1.5", Category::Literal),
+            (")", Category::Punctuation),
+            (";", Category::Punctuation),
+            ("}", Category::Punctuation),
+        ]
+    );
+}
+
+#[test]
+fn longer_operators_win() {
+    assert_eq!(
+        kinds("a<<b<=c|>d?.e?[f]//g"),
+        vec![
+            ("a", Category::Identifier),
+            ("<<", Category::Operator),
+            ("b", Category::Identifier),
+            ("<=", Category::Operator),
+            ("c", Category::Identifier),
+            ("|>", Category::Operator),
+            ("d", Category::Identifier),
+            ("?.", Category::Operator),
+            ("e", Category::Identifier),
+            ("?[", Category::Operator),
+            ("f", Category::Identifier),
+            ("]", Category::Punctuation),
+            ("//", Category::Operator),
+            ("g", Category::Identifier),
+        ]
+    );
+}
+
+#[test]
+fn spans_point_into_the_source() {
+    let source = "x = \"é\";\n  y";
+    let tokens = tokenize(source);
+    let spans: Vec<(usize, usize)> = tokens.iter().map(|t| (t.start, t.end)).collect();
+    assert_eq!(spans, vec![(0, 1), (2, 3), (4, 8), (8, 9), (12, 13)]);
+    for token in &tokens {
+        assert_eq!(&source[token.start..token.end], token.text);
+    }
+}
+
+#[test]
+fn numbers_with_underscores_and_hex() {
+    assert_eq!(
+        kinds("1_000 0xff_FF .5 2. 0x 1__0 1_"),
+        vec![
+            ("1_000", Category::Literal),
+            ("0xff_FF", Category::Literal),
+            (".5", Category::Literal),
+            ("2.", Category::Literal),
+            ("0", Category::Literal),
+            ("x", Category::Identifier),
+            ("1__0", Category::Invalid),
+            ("1_", Category::Invalid),
+        ]
+    );
+    assert_eq!(literal_digits("1_000_000"), Some(String::from("1000000")));
+    assert_eq!(literal_digits("0xff_ff"), Some(String::from("0xffff")));
+    assert_eq!(literal_digits("_1"), None);
+}
+
+#[test]
+fn comments_are_tokens() {
+    assert_eq!(
+        kinds("a # rest of line\n## block\n#  ## b ### nested ### ##c"),
+        vec![
+            ("a", Category::Identifier),
+            ("# rest of line", Category::Comment),
+            ("## block\n#  ##", Category::Comment),
+            ("b", Category::Identifier),
+            ("### nested ###", Category::Comment),
+            ("##c", Category::Comment),
+        ]
+    );
+}
+
+#[test]
+fn invalid_text_doesnt_stop_scanning() {
+    assert_eq!(
+        kinds("a @ \"open\nb"),
+        vec![
+            ("a", Category::Identifier),
+            ("@", Category::Invalid),
+            ("\"open", Category::Invalid),
+            ("b", Category::Identifier),
+        ]
+    );
+}
+
+#[test]
+fn stripped_comments_keep_positions() {
+    assert_eq!(
+        strip_comments("a # x\n## é\n ## b"),
+        Ok(String::from("a    \n     \n    b"))
+    );
+    assert_eq!(strip_comments("a ### x ## y"), Err(2));
+}
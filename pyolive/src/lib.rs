@@ -0,0 +1,111 @@
+use olive::{Engine, Mistake, OliveError, Value};
+use pyo3::exceptions::{PyRuntimeError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple};
+use std::collections::HashMap;
+
+fn to_py(py: Python, value: Value) -> PyObject {
+    match value {
+        Value::None | Value::Function => py.None(),
+        Value::Integer(value) => value.into_py(py),
+        Value::Float(value) => value.into_py(py),
+        Value::Boolean(value) => value.into_py(py),
+        Value::String(value) => value.into_py(py),
+        Value::List(data) => {
+            let items: Vec<PyObject> = data.into_iter().map(|value| to_py(py, value)).collect();
+            PyList::new(py, items).into_py(py)
+        }
+        Value::Bendy(data) => {
+            let dict = PyDict::new(py);
+            for (key, value) in data {
+                dict.set_item(key, to_py(py, value)).unwrap();
+            }
+            dict.into_py(py)
+        }
+    }
+}
+
+// bools are checked before ints since python bools are ints as well
+fn to_value(obj: &PyAny) -> PyResult<Value> {
+    if obj.is_none() {
+        Ok(Value::None)
+    } else if obj.is_instance_of::<PyBool>() {
+        Ok(Value::Boolean(obj.extract()?))
+    } else if obj.is_instance_of::<PyLong>() {
+        Ok(Value::Integer(obj.extract()?))
+    } else if obj.is_instance_of::<PyFloat>() {
+        Ok(Value::Float(obj.extract()?))
+    } else if obj.is_instance_of::<PyString>() {
+        Ok(Value::String(obj.extract()?))
+    } else if let Ok(list) = obj.downcast::<PyList>() {
+        list.iter().map(to_value).collect::<PyResult<Vec<Value>>>().map(Value::List)
+    } else if let Ok(tuple) = obj.downcast::<PyTuple>() {
+        tuple.iter().map(to_value).collect::<PyResult<Vec<Value>>>().map(Value::List)
+    } else if let Ok(dict) = obj.downcast::<PyDict>() {
+        let mut data = HashMap::new();
+        for (key, value) in dict {
+            data.insert(key.extract::<String>()?, to_value(value)?);
+        }
+        Ok(Value::Bendy(data))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "can't convert {} to an OliveScript value",
+            obj.get_type().name()?
+        )))
+    }
+}
+
+fn to_result(py: Python, result: Mistake<Value, OliveError>) -> PyResult<PyObject> {
+    match result {
+        Mistake::Fine(value, _) => Ok(to_py(py, value)),
+        Mistake::Fail(errors) => Err(PyRuntimeError::new_err(
+            errors
+                .iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )),
+    }
+}
+
+#[pyclass(name = "Engine", unsendable)]
+struct PyEngine {
+    engine: Engine,
+}
+
+#[pymethods]
+impl PyEngine {
+    #[new]
+    fn new() -> Self {
+        PyEngine {
+            engine: Engine::new(),
+        }
+    }
+
+    fn eval(&mut self, py: Python, source: &str) -> PyResult<PyObject> {
+        to_result(py, self.engine.eval_str(source))
+    }
+
+    fn run_file(&mut self, py: Python, path: &str) -> PyResult<PyObject> {
+        to_result(py, self.engine.run_file(path))
+    }
+
+    // makes a python callable available to scripts, exceptions fail the script
+    fn register(&mut self, name: &str, arg_count: u32, function: PyObject) {
+        self.engine.register_fn(name, arg_count, move |args| {
+            Python::with_gil(|py| {
+                let args: Vec<PyObject> = args.into_iter().map(|arg| to_py(py, arg)).collect();
+                function
+                    .call1(py, PyTuple::new(py, args))
+                    .and_then(|result| to_value(result.as_ref(py)))
+                    .map_err(|err| err.to_string())
+            })
+        });
+    }
+}
+
+#[pymodule]
+fn pyolive(_py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<PyEngine>()?;
+    Ok(())
+}
@@ -0,0 +1,410 @@
+use super::codegen::{Code, CodePosTable, FunctionInfo};
+use super::errors::OliveIoError;
+use spec::*;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+mod map;
+mod spec;
+
+pub use map::SourceMap;
+
+// functions and defer blocks nested deeper than this are taken for a broken
+// file, decoding them recurses and would overflow the stack otherwise
+const MAX_NESTING: usize = 256;
+
+// fails if the codes use more constants than fit the u16 indices or a
+// constant is longer than a u16 length allows. the source positions of the
+// codes are only kept if their table is passed, they are of no use without
+// the source next to the file
+pub fn encode(codes: &[Code], positions: Option<&CodePosTable>) -> Option<Vec<u8>> {
+    let mut encoder = Encoder {
+        constants: Vec::new(),
+        indices: HashMap::new(),
+        body: Vec::new(),
+        positions: positions.is_some(),
+    };
+    encoder.function(codes, positions.unwrap_or(&CodePosTable::default()))?;
+    let mut binary = Vec::with_capacity(encoder.body.len() + 64);
+    binary.extend_from_slice(MAGIC);
+    binary.push(VERSION);
+    binary.push(if encoder.positions { POSITIONS } else { 0 });
+    binary.extend_from_slice(&(encoder.constants.len() as u16).to_le_bytes());
+    for constant in &encoder.constants {
+        let length: u16 = constant.len().try_into().ok()?;
+        binary.extend_from_slice(&length.to_le_bytes());
+        binary.extend_from_slice(constant.as_bytes());
+    }
+    binary.extend_from_slice(&encoder.body);
+    Some(binary)
+}
+
+// fails on anything that wasn't written by encode with the same version.
+// files of another version and unknown opcodes are told apart from files that
+// are simply broken
+pub fn decode(binary: &[u8]) -> Result<(Vec<Code>, CodePosTable), OliveIoError> {
+    let mut decoder = Decoder {
+        binary,
+        offset: 0,
+        constants: Vec::new(),
+        positions: false,
+        unknown_opcode: None,
+        nesting: 0,
+    };
+    if decoder.take(MAGIC.len()) != Some(&MAGIC[..]) {
+        return Err(OliveIoError::Deserialize);
+    }
+    match decoder.u8() {
+        Some(VERSION) => {}
+        Some(found) => {
+            return Err(OliveIoError::Version {
+                found,
+                supported: VERSION,
+            })
+        }
+        None => return Err(OliveIoError::Deserialize),
+    }
+    match decoder.u8() {
+        Some(0) => {}
+        Some(POSITIONS) => decoder.positions = true,
+        _ => return Err(OliveIoError::Deserialize),
+    }
+    match (decoder.program(), decoder.unknown_opcode) {
+        (Some(codes), _) => Ok(codes),
+        (None, Some(opcode)) => Err(OliveIoError::Opcode { opcode }),
+        (None, None) => Err(OliveIoError::Deserialize),
+    }
+}
+
+struct Encoder {
+    constants: Vec<String>,
+    indices: HashMap<String, u16>,
+    body: Vec<u8>,
+    positions: bool,
+}
+
+impl Encoder {
+    fn constant(&mut self, value: &str) -> Option<()> {
+        let index = match self.indices.get(value) {
+            Some(index) => *index,
+            None => {
+                let index: u16 = self.constants.len().try_into().ok()?;
+                self.constants.push(String::from(value));
+                self.indices.insert(String::from(value), index);
+                index
+            }
+        };
+        self.body.extend_from_slice(&index.to_le_bytes());
+        Some(())
+    }
+
+    fn function(&mut self, codes: &[Code], code_pos_table: &CodePosTable) -> Option<()> {
+        let count: u32 = codes.len().try_into().ok()?;
+        self.body.extend_from_slice(&count.to_le_bytes());
+        for code in codes {
+            self.code(code)?;
+        }
+        if self.positions {
+            let entries = code_pos_table.entries();
+            let count: u32 = entries.len().try_into().ok()?;
+            self.body.extend_from_slice(&count.to_le_bytes());
+            for (code_index, source_index) in entries {
+                self.body.extend_from_slice(&code_index.to_le_bytes());
+                self.body.extend_from_slice(&source_index.to_le_bytes());
+            }
+        }
+        Some(())
+    }
+
+    fn code(&mut self, code: &Code) -> Option<()> {
+        match code {
+            Code::PushString(value) => {
+                self.body.push(PUSH_STRING);
+                self.constant(value)?;
+            }
+            Code::PushBoolean(value) => self.body.extend_from_slice(&[PUSH_BOOLEAN, *value as u8]),
+            Code::PushDouble(value) => {
+                self.body.push(PUSH_DOUBLE);
+                self.body.extend_from_slice(&value.to_le_bytes());
+            }
+            Code::PushLong(value) => {
+                self.body.push(PUSH_LONG);
+                self.body.extend_from_slice(&value.to_le_bytes());
+            }
+            Code::PushInt(value) => {
+                self.body.push(PUSH_INT);
+                self.body.extend_from_slice(&value.to_le_bytes());
+            }
+            Code::PushShort(value) => {
+                self.body.push(PUSH_SHORT);
+                self.body.extend_from_slice(&value.to_le_bytes());
+            }
+            Code::PushByte(value) => self.body.extend_from_slice(&[PUSH_BYTE, *value as u8]),
+            Code::PushFun(args, codes, code_pos_table, info) => {
+                let count: u16 = args.len().try_into().ok()?;
+                self.body.push(PUSH_FUN);
+                self.body.extend_from_slice(&count.to_le_bytes());
+                for arg in args {
+                    self.constant(arg)?;
+                }
+                match &info.name {
+                    Some(name) => {
+                        self.body.push(1);
+                        self.constant(name)?;
+                    }
+                    None => self.body.push(0),
+                }
+                for i in 0..args.len() {
+                    match info.parameter_types.get(i) {
+                        Some(Some(type_name)) => {
+                            self.body.push(1);
+                            self.constant(type_name)?;
+                        }
+                        _ => self.body.push(0),
+                    }
+                }
+                self.function(codes, code_pos_table)?;
+            }
+            Code::Defer(codes, code_pos_table) => {
+                self.body.push(DEFER);
+                self.function(codes, code_pos_table)?;
+            }
+            Code::Store(name) => {
+                self.body.push(STORE);
+                self.constant(name)?;
+            }
+            Code::Load(name) => {
+                self.body.push(LOAD);
+                self.constant(name)?;
+            }
+            Code::JumpNot(offset) => {
+                self.body.push(JUMP_NOT);
+                self.body.extend_from_slice(&offset.to_le_bytes());
+            }
+            Code::Jump(offset) => {
+                self.body.push(JUMP);
+                self.body.extend_from_slice(&offset.to_le_bytes());
+            }
+            Code::Goto(offset) => {
+                self.body.push(GOTO);
+                self.body.extend_from_slice(&offset.to_le_bytes());
+            }
+            Code::Call(arg_count) => {
+                let arg_count: u16 = (*arg_count).try_into().ok()?;
+                self.body.push(CALL);
+                self.body.extend_from_slice(&arg_count.to_le_bytes());
+            }
+            Code::PushBendy => self.body.push(PUSH_BENDY),
+            Code::PushList => self.body.push(PUSH_LIST),
+            Code::PushNone => self.body.push(PUSH_NONE),
+            Code::Pop => self.body.push(POP),
+            Code::Dup => self.body.push(DUP),
+            Code::Return => self.body.push(RETURN),
+            Code::Put => self.body.push(PUT),
+            Code::Get => self.body.push(GET),
+            Code::GetOptional => self.body.push(GET_OPTIONAL),
+            Code::Neg => self.body.push(NEG),
+            Code::Add => self.body.push(ADD),
+            Code::Sub => self.body.push(SUB),
+            Code::Mul => self.body.push(MUL),
+            Code::IntDiv => self.body.push(INT_DIV),
+            Code::FloatDiv => self.body.push(FLOAT_DIV),
+            Code::Mod => self.body.push(MOD),
+            Code::BitLsh => self.body.push(BIT_LSH),
+            Code::BitRsh => self.body.push(BIT_RSH),
+            Code::BitAnd => self.body.push(BIT_AND),
+            Code::BitOr => self.body.push(BIT_OR),
+            Code::BitXOr => self.body.push(BIT_XOR),
+            Code::BoolNot => self.body.push(BOOL_NOT),
+            Code::Concat => self.body.push(CONCAT),
+            Code::Equals => self.body.push(EQUALS),
+            Code::NotEquals => self.body.push(NOT_EQUALS),
+            Code::LessThan => self.body.push(LESS_THAN),
+            Code::LessEquals => self.body.push(LESS_EQUALS),
+            Code::GreaterThan => self.body.push(GREATER_THAN),
+            Code::GreaterEquals => self.body.push(GREATER_EQUALS),
+        }
+        Some(())
+    }
+}
+
+struct Decoder<'a> {
+    binary: &'a [u8],
+    offset: usize,
+    constants: Vec<String>,
+    positions: bool,
+    unknown_opcode: Option<u8>,
+    nesting: usize,
+}
+
+impl<'a> Decoder<'a> {
+    // the constants and the main function after the header
+    fn program(&mut self) -> Option<(Vec<Code>, CodePosTable)> {
+        let count = self.u16()?;
+        for _ in 0..count {
+            let length = self.u16()? as usize;
+            let constant = std::str::from_utf8(self.take(length)?).ok()?;
+            self.constants.push(String::from(constant));
+        }
+        let codes = self.function()?;
+        if self.offset != self.binary.len() {
+            return None;
+        }
+        Some(codes)
+    }
+
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self
+            .binary
+            .get(self.offset..self.offset.checked_add(length)?)?;
+        self.offset += length;
+        Some(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        Some(i32::from_le_bytes(self.array()?))
+    }
+
+    fn constant(&mut self) -> Option<String> {
+        let index = self.u16()? as usize;
+        self.constants.get(index).cloned()
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.array()?))
+    }
+
+    fn function(&mut self) -> Option<(Vec<Code>, CodePosTable)> {
+        if self.nesting == MAX_NESTING {
+            return None;
+        }
+        self.nesting += 1;
+        let function = self.function_body();
+        self.nesting -= 1;
+        function
+    }
+
+    fn function_body(&mut self) -> Option<(Vec<Code>, CodePosTable)> {
+        let count = self.u32()? as usize;
+        // every code takes at least a byte, so a bogus count fails here
+        // instead of reserving a huge vec
+        if count > self.binary.len() - self.offset {
+            return None;
+        }
+        let mut codes = Vec::with_capacity(count);
+        for _ in 0..count {
+            codes.push(self.code()?);
+        }
+        if !self.positions {
+            return Some((codes, CodePosTable::default()));
+        }
+        let count = self.u32()? as usize;
+        if count > self.binary.len() - self.offset {
+            return None;
+        }
+        let entries = (0..count)
+            .map(|_| Some((self.u32()?, self.u32()?)))
+            .collect::<Option<Vec<(u32, u32)>>>()?;
+        Some((codes, CodePosTable::from_entries(entries)))
+    }
+
+    fn code(&mut self) -> Option<Code> {
+        Some(match self.u8()? {
+            PUSH_STRING => Code::PushString(self.constant()?),
+            PUSH_BOOLEAN => match self.u8()? {
+                0 => Code::PushBoolean(false),
+                1 => Code::PushBoolean(true),
+                _ => return None,
+            },
+            PUSH_DOUBLE => Code::PushDouble(f64::from_le_bytes(self.array()?)),
+            PUSH_LONG => Code::PushLong(i64::from_le_bytes(self.array()?)),
+            PUSH_INT => Code::PushInt(self.i32()?),
+            PUSH_SHORT => Code::PushShort(i16::from_le_bytes(self.array()?)),
+            PUSH_BYTE => Code::PushByte(self.u8()? as i8),
+            PUSH_FUN => {
+                let count = self.u16()?;
+                let args = (0..count)
+                    .map(|_| self.constant())
+                    .collect::<Option<Vec<String>>>()?;
+                let name = match self.u8()? {
+                    0 => None,
+                    1 => Some(self.constant()?),
+                    _ => return None,
+                };
+                let mut parameter_types = (0..count)
+                    .map(|_| match self.u8()? {
+                        0 => Some(None),
+                        1 => Some(Some(self.constant()?)),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<Option<String>>>>()?;
+                // like the compiler, leave them out if no parameter has one
+                if parameter_types.iter().all(Option::is_none) {
+                    parameter_types.clear();
+                }
+                let info = FunctionInfo {
+                    name,
+                    span: None,
+                    parameter_types,
+                };
+                let (codes, code_pos_table) = self.function()?;
+                Code::PushFun(args, codes, code_pos_table, info)
+            }
+            DEFER => {
+                let (codes, code_pos_table) = self.function()?;
+                Code::Defer(codes, code_pos_table)
+            }
+            STORE => Code::Store(self.constant()?),
+            LOAD => Code::Load(self.constant()?),
+            JUMP_NOT => Code::JumpNot(self.i32()?),
+            JUMP => Code::Jump(self.i32()?),
+            GOTO => Code::Goto(self.i32()?),
+            CALL => Code::Call(self.u16()? as u32),
+            PUSH_BENDY => Code::PushBendy,
+            PUSH_LIST => Code::PushList,
+            PUSH_NONE => Code::PushNone,
+            POP => Code::Pop,
+            DUP => Code::Dup,
+            RETURN => Code::Return,
+            PUT => Code::Put,
+            GET => Code::Get,
+            GET_OPTIONAL => Code::GetOptional,
+            NEG => Code::Neg,
+            ADD => Code::Add,
+            SUB => Code::Sub,
+            MUL => Code::Mul,
+            INT_DIV => Code::IntDiv,
+            FLOAT_DIV => Code::FloatDiv,
+            MOD => Code::Mod,
+            BIT_LSH => Code::BitLsh,
+            BIT_RSH => Code::BitRsh,
+            BIT_AND => Code::BitAnd,
+            BIT_OR => Code::BitOr,
+            BIT_XOR => Code::BitXOr,
+            BOOL_NOT => Code::BoolNot,
+            CONCAT => Code::Concat,
+            EQUALS => Code::Equals,
+            NOT_EQUALS => Code::NotEquals,
+            LESS_THAN => Code::LessThan,
+            LESS_EQUALS => Code::LessEquals,
+            GREATER_THAN => Code::GreaterThan,
+            GREATER_EQUALS => Code::GreaterEquals,
+            opcode => {
+                self.unknown_opcode = Some(opcode);
+                return None;
+            }
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use crate::errors::Source;
+use serde::{Deserialize, Serialize};
+
+// written next to a compiled file as <file>.olvc.map, it lets errors in the
+// compiled code point at the script it was compiled from. the codes keep
+// their byte positions in that script, the map names it and tells where its
+// lines break, so the script itself isn't needed to report a line
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceMap {
+    pub file: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(file: &str, source: &Source) -> Self {
+        SourceMap {
+            file: String::from(file),
+            line_starts: source.line_starts().to_vec(),
+        }
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    // none for anything that isn't a map, such a map is ignored. the first
+    // line has to start at 0 and every other one behind the line before
+    pub fn from_json(json: &[u8]) -> Option<Self> {
+        let map: SourceMap = serde_json::from_slice(json).ok()?;
+        let ascending = map.line_starts.windows(2).all(|pair| pair[0] < pair[1]);
+        match map.line_starts.first() {
+            Some(0) if ascending => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn source(&self) -> Source<'static> {
+        Source::from_line_starts(self.line_starts.clone())
+    }
+}
@@ -0,0 +1,86 @@
+// the .olvc format. all numbers are little endian, a file is:
+//
+//   header      magic "OLVC", format version (u8), flags (u8)
+//   constants   count (u16), then every constant as length (u16) and utf-8
+//               bytes. names and strings in codes are indices (u16) into them
+//   main        a function: code count (u32), then every code as an opcode
+//               byte followed by its operands. if the POSITIONS flag is set,
+//               every function ends with its source positions: entry count
+//               (u32), then every entry as code index (u32) and source
+//               index (u32)
+//
+// operands of the opcodes that have any:
+//
+//   PUSH_STRING, STORE, LOAD    constant index (u16)
+//   PUSH_BOOLEAN                0 or 1 (u8)
+//   PUSH_DOUBLE                 f64
+//   PUSH_LONG, PUSH_INT,        i64, i32, i16 and i8
+//   PUSH_SHORT, PUSH_BYTE
+//   PUSH_FUN                    argument count (u16), the argument names as
+//                               constant indices, whether the function has a
+//                               name (u8) followed by its constant index if it
+//                               has one, for every argument whether it has a
+//                               type (u8) followed by the constant index of
+//                               the type name if it has one, then the body as
+//                               a function
+//   DEFER                       the deferred statement as a function
+//
+// functions inside PUSH_FUN and DEFER are nested at most 256 deep, counting
+// the main function, files with deeper ones are rejected as broken
+//   JUMP_NOT, JUMP, GOTO        offset from the jump in codes (i32)
+//   CALL                        number of arguments passed (u16)
+//
+// without the POSITIONS flag no source positions are stored, it is only set
+// for files compiled with a source map. VERSION goes up whenever the layout or
+// the meaning of an opcode changes, and opcodes are never reused for something
+// else, so a file is either read exactly as it was written or rejected
+pub const MAGIC: &[u8; 4] = b"OLVC";
+pub const VERSION: u8 = 6;
+
+// flags, no others may be set
+pub const POSITIONS: u8 = 0x01;
+
+pub const PUSH_STRING: u8 = 0x00;
+pub const PUSH_BOOLEAN: u8 = 0x01;
+pub const PUSH_DOUBLE: u8 = 0x02;
+pub const PUSH_LONG: u8 = 0x03;
+pub const PUSH_INT: u8 = 0x04;
+pub const PUSH_SHORT: u8 = 0x05;
+pub const PUSH_BYTE: u8 = 0x06;
+pub const PUSH_BENDY: u8 = 0x07;
+pub const PUSH_LIST: u8 = 0x08;
+pub const PUSH_NONE: u8 = 0x09;
+pub const PUSH_FUN: u8 = 0x0a;
+pub const POP: u8 = 0x10;
+pub const DUP: u8 = 0x11;
+pub const RETURN: u8 = 0x12;
+pub const CALL: u8 = 0x13;
+pub const PUT: u8 = 0x14;
+pub const GET: u8 = 0x15;
+pub const STORE: u8 = 0x16;
+pub const LOAD: u8 = 0x17;
+pub const JUMP_NOT: u8 = 0x18;
+pub const JUMP: u8 = 0x19;
+pub const GOTO: u8 = 0x1a;
+pub const DEFER: u8 = 0x1b;
+pub const GET_OPTIONAL: u8 = 0x1c;
+pub const NEG: u8 = 0x20;
+pub const ADD: u8 = 0x21;
+pub const SUB: u8 = 0x22;
+pub const MUL: u8 = 0x23;
+pub const INT_DIV: u8 = 0x24;
+pub const FLOAT_DIV: u8 = 0x25;
+pub const MOD: u8 = 0x26;
+pub const BIT_LSH: u8 = 0x27;
+pub const BIT_RSH: u8 = 0x28;
+pub const BIT_AND: u8 = 0x29;
+pub const BIT_OR: u8 = 0x2a;
+pub const BIT_XOR: u8 = 0x2b;
+pub const BOOL_NOT: u8 = 0x2c;
+pub const CONCAT: u8 = 0x2d;
+pub const EQUALS: u8 = 0x30;
+pub const NOT_EQUALS: u8 = 0x31;
+pub const LESS_THAN: u8 = 0x32;
+pub const LESS_EQUALS: u8 = 0x33;
+pub const GREATER_THAN: u8 = 0x34;
+pub const GREATER_EQUALS: u8 = 0x35;
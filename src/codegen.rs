@@ -1,10 +1,10 @@
-use super::errors::{OliveCodeError, OliveError};
+use super::errors::{OliveCodeError, OliveError, Source};
 use mistake::Mistake::{self, Fail, Fine};
-use oliveparser::ast::{BinaryOperator, Expression, Located, Statement, UnaryOperator};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use oliveparser::ast::{BinaryOperator, Expression, Key, Located, Statement, UnaryOperator};
+use oliveparser::tokens;
+use std::convert::TryFrom;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Code {
     PushString(String),
     PushBoolean(bool),
@@ -34,7 +34,9 @@ pub enum Code {
     Concat,
     Put,
     Get,
-    Call,
+    // like get, but none instead of failing for a none object or missing index
+    GetOptional,
+    Call(u32),
     Equals,
     NotEquals,
     LessThan,
@@ -47,24 +49,140 @@ pub enum Code {
     Goto(i32),
     Store(String),
     Load(String),
-    PushFun(Vec<String>, Vec<Code>),
+    PushFun(Vec<String>, Vec<Code>, CodePosTable, FunctionInfo),
+    // the code of a deferred statement, run when the current function returns
+    Defer(Vec<Code>, CodePosTable),
+}
+
+// what is known about a function literal besides its code. the name is the
+// one it was assigned to where it was written, the span is the source range
+// of the literal and is not kept in compiled files. parameter types has the
+// annotated type of every parameter, or is empty if none has one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionInfo {
+    pub name: Option<String>,
+    pub span: Option<(usize, usize)>,
+    pub parameter_types: Vec<Option<String>>,
+}
+
+// the types a parameter can be annotated with, as named by type errors
+pub const TYPE_NAMES: &[&str] = &[
+    "integer", "float", "boolean", "none", "string", "list", "bendy", "function", "native",
+];
+
+// source positions of the codes that can fail as (code index, source index)
+// pairs sorted by code index, an entry covers the codes up to the next one.
+// it is only searched when an error is reported
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CodePosTable {
+    entries: Vec<(u32, u32)>,
+}
+
+impl CodePosTable {
+    // codes are generated in order, so entries only ever get appended
+    fn insert(&mut self, code_index: usize, source_index: usize) {
+        let entry = (code_index as u32, source_index as u32);
+        match self.entries.last_mut() {
+            Some(last) if last.0 == entry.0 => *last = entry,
+            Some(last) if last.1 == entry.1 => {}
+            _ => self.entries.push(entry),
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub(crate) fn entries(&self) -> &[(u32, u32)] {
+        &self.entries
+    }
+
+    // entries read back from a file, sorted like insert keeps them
+    #[cfg(feature = "fs")]
+    pub(crate) fn from_entries(entries: Vec<(u32, u32)>) -> Self {
+        CodePosTable { entries }
+    }
+
+    pub fn get(&self, code_index: usize) -> Option<usize> {
+        let after = self
+            .entries
+            .partition_point(|&(index, _)| index as usize <= code_index);
+        after.checked_sub(1).map(|i| self.entries[i].1 as usize)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Label(usize);
+
+// the labels continue and break jump to in a while loop
+struct Loop {
+    start: Label,
+    end: Label,
+}
+
+// the codes of one function while they are generated. jumps point at labels
+// instead of offsets, finish turns them into offsets once every label is placed
+#[derive(Default)]
+struct CodeBuilder {
+    codes: Vec<Code>,
+    code_pos_table: CodePosTable,
+    labels: Vec<Option<usize>>,
+    jumps: Vec<(usize, Label)>,
+    loops: Vec<Loop>,
+}
+
+impl CodeBuilder {
+    fn push(&mut self, code: Code) {
+        self.codes.push(code);
+    }
+
+    // errors raised by the next code are reported at source_index
+    fn mark(&mut self, source_index: usize) {
+        self.code_pos_table.insert(self.codes.len(), source_index);
+    }
+
+    fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    // the label points at the next code
+    fn place(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.codes.len());
+    }
+
+    // jump is Code::Goto, Code::Jump or Code::JumpNot
+    fn jump(&mut self, jump: fn(i32) -> Code, label: Label) {
+        self.jumps.push((self.codes.len(), label));
+        self.codes.push(jump(0));
+    }
+
+    // fails with the index of a jump whose offset doesn't fit an i32
+    fn resolve_jumps(&mut self) -> Result<(), usize> {
+        for &(index, label) in &self.jumps {
+            let target = self.labels[label.0].expect("jump to a label that was never placed");
+            let offset = i32::try_from(target as i64 - index as i64).map_err(|_| index)?;
+            match &mut self.codes[index] {
+                Code::Goto(jump_offset) | Code::Jump(jump_offset) | Code::JumpNot(jump_offset) => {
+                    *jump_offset = offset
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(())
+    }
 }
 
 trait Generatable {
     fn generate(
         self,
-        codes: &mut Vec<Code>,
+        builder: &mut CodeBuilder,
         filename: &str,
-        source: &str,
-        code_pos_table: &mut HashMap<usize, usize>,
-    ) -> Mistake<(u32, Vec<usize>), OliveError>;
+        source: &Source,
+    ) -> Mistake<(), OliveError>;
     fn generate_lhs(
         self,
-        codes: &mut Vec<Code>,
+        builder: &mut CodeBuilder,
         filename: &str,
-        source: &str,
-        code_pos_table: &mut HashMap<usize, usize>,
-    ) -> Mistake<u32, OliveError>;
+        source: &Source,
+    ) -> Mistake<(), OliveError>;
 }
 
 fn push_integer(codes: &mut Vec<Code>, value: usize) -> bool {
@@ -74,7 +192,7 @@ fn push_integer(codes: &mut Vec<Code>, value: usize) -> bool {
         codes.push(Code::PushShort(value as i16));
     } else if value < 0x80000000 {
         codes.push(Code::PushInt(value as i32));
-    } else if value < 0x8000000000000000 {
+    } else if (value as u64) < 0x8000000000000000 {
         codes.push(Code::PushLong(value as i64));
     } else {
         return false;
@@ -82,666 +200,697 @@ fn push_integer(codes: &mut Vec<Code>, value: usize) -> bool {
     true
 }
 
+// generates every item even if some fail, so all their errors are reported
+fn generate_all<T: Generatable>(
+    items: Vec<T>,
+    builder: &mut CodeBuilder,
+    filename: &str,
+    source: &Source,
+) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    let mut fine = true;
+    for item in items {
+        if item
+            .generate(builder, filename, source)
+            .to_option(&mut errors)
+            .is_none()
+        {
+            fine = false;
+        }
+    }
+    if fine {
+        Fine((), errors)
+    } else {
+        Fail(errors)
+    }
+}
+
+// `value |> f(args)` calls f with the value in front of the args, and
+// `value |> f` calls f with the value alone
+fn generate_pipe(
+    value: Located<Expression>,
+    function: Located<Expression>,
+    builder: &mut CodeBuilder,
+    filename: &str,
+    source: &Source,
+) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    let value_opt = value
+        .generate(builder, filename, source)
+        .to_option(&mut errors);
+    let (callee, arg_count, args_opt) = match function.inner {
+        Expression::Call { expression, args } => {
+            let arg_count = args.len() as u32 + 1;
+            let args_opt = generate_all(args, builder, filename, source).to_option(&mut errors);
+            (*expression, arg_count, args_opt)
+        }
+        inner => (
+            Located {
+                inner,
+                start: function.start,
+                end: function.end,
+            },
+            1,
+            Some(()),
+        ),
+    };
+    let callee_opt = callee
+        .generate(builder, filename, source)
+        .to_option(&mut errors);
+    if value_opt.is_none() || args_opt.is_none() || callee_opt.is_none() {
+        return Fail(errors);
+    }
+    builder.mark(function.start);
+    builder.push(Code::Call(arg_count));
+    Fine((), errors)
+}
+
+// the digits of a number literal without underscores
+fn literal_digits(
+    literal: &str,
+    start: usize,
+    filename: &str,
+    source: &Source,
+) -> Result<String, OliveError> {
+    tokens::literal_digits(literal).ok_or_else(|| {
+        OliveError::new_code_error(
+            start,
+            filename,
+            source,
+            OliveCodeError::Underscore {
+                value: String::from(literal),
+            },
+        )
+    })
+}
+
+// pushes an integer literal with the smallest code that holds it
+fn generate_integer(
+    literal: &str,
+    negative: bool,
+    start: usize,
+    builder: &mut CodeBuilder,
+    filename: &str,
+    source: &Source,
+) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    let digits = attempt_res!(literal_digits(literal, start, filename, source), errors);
+    let hex = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"));
+    let magnitude = match hex {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => digits.parse::<u64>(),
+    };
+    // the magnitude of i64::MIN is one more than i64::MAX, so it is parsed
+    // unsigned and the sign applied after
+    let value = magnitude.ok().and_then(|magnitude| {
+        if negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    });
+    let value = match value {
+        Some(value) => value,
+        None => {
+            errors.push(OliveError::new_code_error(
+                start,
+                filename,
+                source,
+                OliveCodeError::ParseInteger {
+                    value: if negative {
+                        format!("-{}", literal)
+                    } else {
+                        String::from(literal)
+                    },
+                },
+            ));
+            return Fail(errors);
+        }
+    };
+    builder.push(if let Ok(value) = i8::try_from(value) {
+        Code::PushByte(value)
+    } else if let Ok(value) = i16::try_from(value) {
+        Code::PushShort(value)
+    } else if let Ok(value) = i32::try_from(value) {
+        Code::PushInt(value)
+    } else {
+        Code::PushLong(value)
+    });
+    Fine((), errors)
+}
+
+// the name a value assigned to left is known by, a variable or a bendy key
+fn assigned_name<'a>(left: &Located<Expression<'a>>) -> Option<&'a str> {
+    match &left.inner {
+        Expression::Variable { name } => Some(name),
+        Expression::Binary {
+            operator: BinaryOperator::Access,
+            right,
+            ..
+        } => match right.inner {
+            Expression::Variable { name } => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// generates a value, a function literal is given the name it is assigned to
+fn generate_value(
+    value: Located<Expression>,
+    name: Option<&str>,
+    builder: &mut CodeBuilder,
+    filename: &str,
+    source: &Source,
+) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    match value.inner {
+        Expression::Function { parameters, block } => {
+            let mut parameter_types = Vec::new();
+            let mut fine = true;
+            if parameters.iter().any(|p| p.type_name.is_some()) {
+                for parameter in &parameters {
+                    parameter_types.push(match &parameter.type_name {
+                        Some(type_name) if !TYPE_NAMES.contains(&type_name.inner) => {
+                            errors.push(OliveError::new_code_error(
+                                type_name.start,
+                                filename,
+                                source,
+                                OliveCodeError::UnknownType {
+                                    name: String::from(type_name.inner),
+                                },
+                            ));
+                            fine = false;
+                            None
+                        }
+                        type_name => type_name.as_ref().map(|t| String::from(t.inner)),
+                    });
+                }
+            }
+            let (inner_codes, inner_code_pos_table) =
+                attempt!(generate_codes(block, filename, source), errors);
+            if !fine {
+                return Fail(errors);
+            }
+            builder.push(Code::PushFun(
+                parameters
+                    .iter()
+                    .map(|p| String::from(p.name.inner))
+                    .collect(),
+                inner_codes,
+                inner_code_pos_table,
+                FunctionInfo {
+                    name: name.map(String::from),
+                    span: Some((value.start, value.end)),
+                    parameter_types,
+                },
+            ));
+            Fine((), errors)
+        }
+        _ => value.generate(builder, filename, source),
+    }
+}
+
 impl<'a> Generatable for Located<Expression<'a>> {
     fn generate(
         self,
-        codes: &mut Vec<Code>,
+        builder: &mut CodeBuilder,
         filename: &str,
-        source: &str,
-        code_pos_table: &mut HashMap<usize, usize>,
-    ) -> Mistake<(u32, Vec<usize>), OliveError> {
+        source: &Source,
+    ) -> Mistake<(), OliveError> {
         let mut errors = Vec::new();
 
-        Fine(
-            match self.inner {
-                Expression::Integer { value } => {
-                    codes.push(if let Ok(ival) = value.parse::<i8>() {
-                        Code::PushByte(ival)
-                    } else if let Ok(ival) = value.parse::<i16>() {
-                        Code::PushShort(ival)
-                    } else if let Ok(ival) = value.parse::<i32>() {
-                        Code::PushInt(ival)
-                    } else if let Ok(ival) = value.parse::<i64>() {
-                        Code::PushLong(ival)
-                    } else {
-                        errors.push(OliveError::new_code_error(
-                            self.start,
-                            filename,
-                            source,
-                            OliveCodeError::ParseInteger {
-                                value: String::from(value),
-                            },
-                        ));
-                        return Fail(errors);
-                    });
-                    (1, Vec::new())
-                }
-                Expression::Float { value } => {
-                    codes.push(if let Ok(ival) = value.parse::<f64>() {
-                        Code::PushDouble(ival)
-                    } else {
-                        errors.push(OliveError::new_code_error(
-                            self.start,
-                            filename,
-                            source,
-                            OliveCodeError::ParseFloat {
-                                value: String::from(value),
-                            },
-                        ));
-                        return Fail(errors);
-                    });
-                    (1, Vec::new())
-                }
-                Expression::Boolean { value } => {
-                    codes.push(Code::PushBoolean(value));
-                    (1, Vec::new())
-                }
-                Expression::None => {
-                    codes.push(Code::PushNone);
-                    (1, Vec::new())
-                }
-                Expression::Unary {
-                    expression,
-                    operator,
-                } => {
-                    let expression_size = attempt!(
-                        expression.generate(codes, filename, source, code_pos_table),
+        match self.inner {
+            Expression::Integer { value } => {
+                attempt!(
+                    generate_integer(value, false, self.start, builder, filename, source),
+                    errors
+                );
+            }
+            Expression::Float { value } => {
+                let digits =
+                    attempt_res!(literal_digits(value, self.start, filename, source), errors);
+                builder.push(if let Ok(ival) = digits.parse::<f64>() {
+                    Code::PushDouble(ival)
+                } else {
+                    errors.push(OliveError::new_code_error(
+                        self.start,
+                        filename,
+                        source,
+                        OliveCodeError::ParseFloat {
+                            value: String::from(value),
+                        },
+                    ));
+                    return Fail(errors);
+                });
+            }
+            Expression::Boolean { value } => builder.push(Code::PushBoolean(value)),
+            Expression::None => builder.push(Code::PushNone),
+            Expression::Unary {
+                expression,
+                operator,
+            } => {
+                // a minus in front of an integer literal is folded into it, so
+                // the smallest integer can be written although its digits alone
+                // are too large for one
+                if let (UnaryOperator::Neg, Expression::Integer { value }) =
+                    (&operator, &expression.inner)
+                {
+                    attempt!(
+                        generate_integer(value, true, self.start, builder, filename, source),
                         errors
                     );
-                    code_pos_table.insert(codes.len(), self.start);
-                    match operator {
-                        UnaryOperator::Neg => codes.push(Code::Neg),
-                        UnaryOperator::BoolNot => codes.push(Code::BoolNot),
-                    }
-                    (expression_size.0 + 1, Vec::new())
+                    return Fine((), errors);
                 }
-                Expression::Binary {
-                    left,
-                    right,
-                    operator,
-                } => match operator {
-                    BinaryOperator::BoolAnd => {
-                        let left_opt = left
-                            .generate(codes, filename, source, code_pos_table)
-                            .to_option(&mut errors);
-                        let first_jump_index = codes.len();
-                        codes.push(Code::JumpNot(0));
-                        let right_opt = right
-                            .generate(codes, filename, source, code_pos_table)
-                            .to_option(&mut errors);
-                        if let None = right_opt {
-                            return Fail(errors);
-                        }
-                        if let None = left_opt {
-                            return Fail(errors);
-                        }
-                        match &mut codes[first_jump_index] {
-                            Code::JumpNot(pos) => *pos = (right_opt.as_ref().unwrap().0 + 2) as i32,
-                            _ => panic!(),
-                        }
-                        codes.push(Code::Goto(2));
-                        codes.push(Code::PushBoolean(false));
-                        (3 + left_opt.unwrap().0 + right_opt.unwrap().0, Vec::new())
-                    }
-                    BinaryOperator::BoolOr => {
-                        let left_opt = left
-                            .generate(codes, filename, source, code_pos_table)
-                            .to_option(&mut errors);
-                        let first_jump_index = codes.len();
-                        codes.push(Code::Jump(0));
-                        let right_opt = right
-                            .generate(codes, filename, source, code_pos_table)
-                            .to_option(&mut errors);
-                        if let None = right_opt {
-                            return Fail(errors);
-                        }
-                        if let None = left_opt {
-                            return Fail(errors);
-                        }
-                        match &mut codes[first_jump_index] {
-                            Code::Jump(pos) => *pos = (right_opt.as_ref().unwrap().0 + 2) as i32,
-                            _ => panic!(),
-                        }
-                        codes.push(Code::Goto(2));
-                        codes.push(Code::PushBoolean(true));
-                        (3 + left_opt.unwrap().0 + right_opt.unwrap().0, Vec::new())
-                    }
-                    BinaryOperator::Access => {
-                        let left_opt = left
-                            .generate(codes, filename, source, code_pos_table)
-                            .to_option(&mut errors);
-                        let name = match right.inner {
-                            Expression::Variable { name } => name,
-                            _ => {
-                                errors.push(OliveError::new_code_error(
-                                    right.start,
-                                    filename,
-                                    source,
-                                    OliveCodeError::Access,
-                                ));
-                                return Fail(errors);
-                            }
-                        };
-                        if let None = left_opt {
-                            return Fail(errors);
-                        }
-                        codes.push(Code::PushString(String::from(name)));
-                        codes.push(Code::Get);
-                        (left_opt.unwrap().0 + 2, Vec::new())
-                    }
-                    _ => {
-                        let left_opt = left
-                            .generate(codes, filename, source, code_pos_table)
-                            .to_option(&mut errors);
-                        let right_opt = right
-                            .generate(codes, filename, source, code_pos_table)
-                            .to_option(&mut errors);
-                        if let None = left_opt {
-                            return Fail(errors);
-                        }
-                        if let None = right_opt {
-                            return Fail(errors);
-                        }
-                        code_pos_table.insert(codes.len(), self.start);
-                        match operator {
-                            BinaryOperator::Add => codes.push(Code::Add),
-                            BinaryOperator::Sub => codes.push(Code::Sub),
-                            BinaryOperator::Mul => codes.push(Code::Mul),
-                            BinaryOperator::Mod => codes.push(Code::Mod),
-                            BinaryOperator::FloatDiv => codes.push(Code::FloatDiv),
-                            BinaryOperator::IntDiv => codes.push(Code::IntDiv),
-                            BinaryOperator::BitAnd => codes.push(Code::BitAnd),
-                            BinaryOperator::BitOr => codes.push(Code::BitOr),
-                            BinaryOperator::BitXOr => codes.push(Code::BitXOr),
-                            BinaryOperator::BitLsh => codes.push(Code::BitLsh),
-                            BinaryOperator::BitRsh => codes.push(Code::BitRsh),
-                            BinaryOperator::Concat => codes.push(Code::Concat),
-                            BinaryOperator::Equals => codes.push(Code::Equals),
-                            BinaryOperator::NotEquals => codes.push(Code::NotEquals),
-                            BinaryOperator::LessEquals => codes.push(Code::LessEquals),
-                            BinaryOperator::LessThan => codes.push(Code::LessThan),
-                            BinaryOperator::GreaterEquals => codes.push(Code::GreaterEquals),
-                            BinaryOperator::GreaterThan => codes.push(Code::GreaterThan),
-                            _ => panic!(),
-                        }
-                        (left_opt.unwrap().0 + right_opt.unwrap().0 + 1, Vec::new())
-                    }
-                },
-                Expression::Index { expression, index } => {
-                    let left_opt = expression
-                        .generate(codes, filename, source, code_pos_table)
+                attempt!(expression.generate(builder, filename, source), errors);
+                builder.mark(self.start);
+                match operator {
+                    UnaryOperator::Neg => builder.push(Code::Neg),
+                    UnaryOperator::BoolNot => builder.push(Code::BoolNot),
+                }
+            }
+            Expression::Binary {
+                left,
+                right,
+                operator,
+            } => match operator {
+                // the right side only runs if the left one doesn't decide the
+                // result already, otherwise the result is pushed directly
+                BinaryOperator::BoolAnd | BinaryOperator::BoolOr => {
+                    let (jump, decided): (fn(i32) -> Code, bool) = match operator {
+                        BinaryOperator::BoolAnd => (Code::JumpNot, false),
+                        _ => (Code::Jump, true),
+                    };
+                    let short_circuit = builder.label();
+                    let end = builder.label();
+                    let left_opt = left
+                        .generate(builder, filename, source)
                         .to_option(&mut errors);
-                    let right_opt = index
-                        .generate(codes, filename, source, code_pos_table)
+                    builder.jump(jump, short_circuit);
+                    let right_opt = right
+                        .generate(builder, filename, source)
                         .to_option(&mut errors);
-                    if let None = left_opt {
-                        return Fail(errors);
-                    }
-                    if let None = right_opt {
+                    if left_opt.is_none() || right_opt.is_none() {
                         return Fail(errors);
                     }
-                    code_pos_table.insert(codes.len(), self.start);
-                    codes.push(Code::Get);
-                    (left_opt.unwrap().0 + right_opt.unwrap().0 + 1, Vec::new())
-                }
-                Expression::String { value } => {
-                    codes.push(Code::PushString(value));
-                    (1, Vec::new())
+                    builder.jump(Code::Goto, end);
+                    builder.place(short_circuit);
+                    builder.push(Code::PushBoolean(decided));
+                    builder.place(end);
                 }
-                Expression::Call { expression, args } => {
-                    let results: Vec<Option<u32>> = args
-                        .into_iter()
-                        .map(|arg| {
-                            match arg
-                                .generate(codes, filename, source, code_pos_table)
-                                .to_option(&mut errors)
-                            {
-                                Some((i, _)) => Some(i),
-                                None => None,
-                            }
-                        })
-                        .collect();
-                    let expression_opt = expression
-                        .generate(codes, filename, source, code_pos_table)
+                BinaryOperator::Access | BinaryOperator::OptionalAccess => {
+                    let left_opt = left
+                        .generate(builder, filename, source)
                         .to_option(&mut errors);
-                    let mut size = 0;
-                    for res in results {
-                        if let Some(l) = res {
-                            size += l;
-                        } else {
+                    let name = match right.inner {
+                        Expression::Variable { name } => name,
+                        _ => {
+                            errors.push(OliveError::new_code_error(
+                                right.start,
+                                filename,
+                                source,
+                                OliveCodeError::Access,
+                            ));
                             return Fail(errors);
                         }
+                    };
+                    if left_opt.is_none() {
+                        return Fail(errors);
                     }
-                    if let None = expression_opt {
+                    builder.push(Code::PushString(String::from(name)));
+                    builder.push(match operator {
+                        BinaryOperator::OptionalAccess => Code::GetOptional,
+                        _ => Code::Get,
+                    });
+                }
+                _ => {
+                    let left_opt = left
+                        .generate(builder, filename, source)
+                        .to_option(&mut errors);
+                    let right_opt = right
+                        .generate(builder, filename, source)
+                        .to_option(&mut errors);
+                    if left_opt.is_none() || right_opt.is_none() {
                         return Fail(errors);
                     }
-                    code_pos_table.insert(codes.len(), self.start);
-                    codes.push(Code::Call);
-                    (1 + expression_opt.unwrap().0 + size, Vec::new())
+                    builder.mark(self.start);
+                    builder.push(match operator {
+                        BinaryOperator::Add => Code::Add,
+                        BinaryOperator::Sub => Code::Sub,
+                        BinaryOperator::Mul => Code::Mul,
+                        BinaryOperator::Mod => Code::Mod,
+                        BinaryOperator::FloatDiv => Code::FloatDiv,
+                        BinaryOperator::IntDiv => Code::IntDiv,
+                        BinaryOperator::BitAnd => Code::BitAnd,
+                        BinaryOperator::BitOr => Code::BitOr,
+                        BinaryOperator::BitXOr => Code::BitXOr,
+                        BinaryOperator::BitLsh => Code::BitLsh,
+                        BinaryOperator::BitRsh => Code::BitRsh,
+                        BinaryOperator::Concat => Code::Concat,
+                        BinaryOperator::Equals => Code::Equals,
+                        BinaryOperator::NotEquals => Code::NotEquals,
+                        BinaryOperator::LessEquals => Code::LessEquals,
+                        BinaryOperator::LessThan => Code::LessThan,
+                        BinaryOperator::GreaterEquals => Code::GreaterEquals,
+                        BinaryOperator::GreaterThan => Code::GreaterThan,
+                        _ => panic!(),
+                    });
                 }
-                Expression::List { elements } => {
-                    codes.push(Code::PushList);
-                    if elements.len() > 0 {
-                        let results: Vec<Option<u32>> = elements
-                            .into_iter()
-                            .enumerate()
-                            .map(|(i, arg)| {
-                                codes.push(Code::Dup);
-                                push_integer(codes, i);
-                                let opt: Option<u32> = match arg
-                                    .generate(codes, filename, source, code_pos_table)
-                                    .to_option(&mut errors)
-                                {
-                                    Some((i, _)) => Some(i),
-                                    None => None,
-                                };
-                                codes.push(Code::Put);
-                                opt
-                            })
-                            .collect();
-                        for res in &results {
-                            if let None = res {
-                                return Fail(errors);
-                            }
-                        }
-                        (
-                            1 + results.into_iter().map(|x| x.unwrap() + 3).sum::<u32>(),
-                            Vec::new(),
-                        )
-                    } else {
-                        (1, Vec::new())
+            },
+            Expression::Index {
+                expression,
+                index,
+                optional,
+            } => {
+                let left_opt = expression
+                    .generate(builder, filename, source)
+                    .to_option(&mut errors);
+                let right_opt = index
+                    .generate(builder, filename, source)
+                    .to_option(&mut errors);
+                if left_opt.is_none() || right_opt.is_none() {
+                    return Fail(errors);
+                }
+                builder.mark(self.start);
+                builder.push(if optional {
+                    Code::GetOptional
+                } else {
+                    Code::Get
+                });
+            }
+            Expression::String { value } => builder.push(Code::PushString(value)),
+            Expression::Call { expression, args } => {
+                let arg_count = args.len() as u32;
+                let args_opt = generate_all(args, builder, filename, source).to_option(&mut errors);
+                let expression_opt = expression
+                    .generate(builder, filename, source)
+                    .to_option(&mut errors);
+                if args_opt.is_none() || expression_opt.is_none() {
+                    return Fail(errors);
+                }
+                builder.mark(self.start);
+                builder.push(Code::Call(arg_count));
+            }
+            Expression::Pipe { value, function } => {
+                attempt!(
+                    generate_pipe(*value, *function, builder, filename, source),
+                    errors
+                );
+            }
+            Expression::List { elements } => {
+                builder.push(Code::PushList);
+                let mut fine = true;
+                for (i, element) in elements.into_iter().enumerate() {
+                    builder.push(Code::Dup);
+                    push_integer(&mut builder.codes, i);
+                    if element
+                        .generate(builder, filename, source)
+                        .to_option(&mut errors)
+                        .is_none()
+                    {
+                        fine = false;
                     }
+                    builder.push(Code::Put);
+                }
+                if !fine {
+                    return Fail(errors);
                 }
-                Expression::Bendy { elements } => {
-                    codes.push(Code::PushBendy);
-                    if elements.len() > 0 {
-                        let results: Vec<Option<u32>> = elements
-                            .into_iter()
-                            .map(|(name, arg)| {
-                                codes.push(Code::Dup);
-                                codes.push(Code::PushString(String::from(name.inner)));
-                                let opt = match arg
-                                    .generate(codes, filename, source, code_pos_table)
-                                    .to_option(&mut errors)
-                                {
-                                    Some((i, _)) => Some(i),
-                                    None => None,
-                                };
-                                codes.push(Code::Put);
-                                opt
-                            })
-                            .collect();
-                        for res in &results {
-                            if let None = res {
-                                return Fail(errors);
+            }
+            Expression::Bendy { elements } => {
+                builder.push(Code::PushBendy);
+                let mut fine = true;
+                for (key, element) in elements {
+                    builder.push(Code::Dup);
+                    let (name, key_start) = match key {
+                        Key::Name(name) => {
+                            builder.push(Code::PushString(String::from(name.inner)));
+                            (Some(name.inner), None)
+                        }
+                        Key::Computed(key) => {
+                            let start = key.start;
+                            if key
+                                .generate(builder, filename, source)
+                                .to_option(&mut errors)
+                                .is_none()
+                            {
+                                fine = false;
                             }
+                            (None, Some(start))
                         }
-                        (
-                            1 + results.into_iter().map(|x| x.unwrap() + 3).sum::<u32>(),
-                            Vec::new(),
-                        )
-                    } else {
-                        (1, Vec::new())
+                    };
+                    if generate_value(element, name, builder, filename, source)
+                        .to_option(&mut errors)
+                        .is_none()
+                    {
+                        fine = false;
                     }
+                    // a computed key that isn't a string fails the put
+                    if let Some(start) = key_start {
+                        builder.mark(start);
+                    }
+                    builder.push(Code::Put);
                 }
-                Expression::Variable { name } => {
-                    code_pos_table.insert(codes.len(), self.start);
-                    codes.push(Code::Load(String::from(name)));
-                    (1, Vec::new())
-                }
-                Expression::Function { parameters, block } => {
-                    let (inner_codes, code_pos) =
-                        attempt!(generate_codes(block, filename, source), errors);
-                    code_pos_table.extend(code_pos);
-                    codes.push(Code::PushFun(
-                        parameters.iter().map(|s| String::from(s.inner)).collect(),
-                        inner_codes,
-                    ));
-                    (1, Vec::new())
+                if !fine {
+                    return Fail(errors);
                 }
-            },
-            errors,
-        )
+            }
+            Expression::Variable { name } => {
+                builder.mark(self.start);
+                builder.push(Code::Load(String::from(name)));
+            }
+            Expression::Function { .. } => {
+                attempt!(
+                    generate_value(self, None, builder, filename, source),
+                    errors
+                );
+            }
+        }
+        Fine((), errors)
     }
 
     fn generate_lhs(
         self,
-        codes: &mut Vec<Code>,
+        builder: &mut CodeBuilder,
         filename: &str,
-        source: &str,
-        code_pos_table: &mut HashMap<usize, usize>,
-    ) -> Mistake<u32, OliveError> {
+        source: &Source,
+    ) -> Mistake<(), OliveError> {
         let mut errors = Vec::new();
-        Fine(
-            match self.inner {
-                Expression::Binary {
-                    left,
-                    right,
-                    operator,
-                } => match operator {
-                    BinaryOperator::Access => {
-                        let left_opt = left
-                            .generate(codes, filename, source, code_pos_table)
-                            .to_option(&mut errors);
-                        let name = match right.inner {
-                            Expression::Variable { name } => name,
-                            _ => {
-                                errors.push(OliveError::new_code_error(
-                                    right.start,
-                                    filename,
-                                    source,
-                                    OliveCodeError::Access,
-                                ));
-                                return Fail(errors);
-                            }
-                        };
-                        if let None = left_opt {
-                            return Fail(errors);
-                        }
-                        codes.push(Code::PushString(String::from(name)));
-                        left_opt.unwrap().0 + 1
-                    }
+        match self.inner {
+            Expression::Binary {
+                left,
+                right,
+                operator: BinaryOperator::Access,
+            } => {
+                let left_opt = left
+                    .generate(builder, filename, source)
+                    .to_option(&mut errors);
+                let name = match right.inner {
+                    Expression::Variable { name } => name,
                     _ => {
                         errors.push(OliveError::new_code_error(
-                            self.start,
+                            right.start,
                             filename,
                             source,
-                            OliveCodeError::Assign {
-                                expression_type: String::from(&source[self.start..self.end]),
-                            },
+                            OliveCodeError::Access,
                         ));
                         return Fail(errors);
                     }
-                },
-                Expression::Index { expression, index } => {
-                    let left_opt = expression
-                        .generate(codes, filename, source, code_pos_table)
-                        .to_option(&mut errors);
-                    let right_opt = index
-                        .generate(codes, filename, source, code_pos_table)
-                        .to_option(&mut errors);
-                    if let None = left_opt {
-                        return Fail(errors);
-                    }
-                    if let None = right_opt {
-                        return Fail(errors);
-                    }
-                    left_opt.unwrap().0 + right_opt.unwrap().0
+                };
+                if left_opt.is_none() {
+                    return Fail(errors);
                 }
-                Expression::Variable { name: _ } => 0,
-                _ => {
-                    errors.push(OliveError::new_code_error(
-                        self.start,
-                        filename,
-                        source,
-                        OliveCodeError::Assign {
-                            expression_type: String::from(&source[self.start..self.end]),
-                        },
-                    ));
+                builder.push(Code::PushString(String::from(name)));
+            }
+            Expression::Index {
+                expression,
+                index,
+                optional: false,
+            } => {
+                let left_opt = expression
+                    .generate(builder, filename, source)
+                    .to_option(&mut errors);
+                let right_opt = index
+                    .generate(builder, filename, source)
+                    .to_option(&mut errors);
+                if left_opt.is_none() || right_opt.is_none() {
                     return Fail(errors);
                 }
-            },
-            errors,
-        )
+            }
+            Expression::Variable { .. } => {}
+            _ => {
+                errors.push(OliveError::new_code_error(
+                    self.start,
+                    filename,
+                    source,
+                    OliveCodeError::Assign {
+                        expression_type: String::from(&source.text[self.start..self.end]),
+                    },
+                ));
+                return Fail(errors);
+            }
+        }
+        Fine((), errors)
     }
 }
 
 impl<'a> Generatable for Located<Statement<'a>> {
     fn generate(
         self,
-        codes: &mut Vec<Code>,
+        builder: &mut CodeBuilder,
         filename: &str,
-        source: &str,
-        code_pos_table: &mut HashMap<usize, usize>,
-    ) -> Mistake<(u32, Vec<usize>), OliveError> {
+        source: &Source,
+    ) -> Mistake<(), OliveError> {
         let mut errors = Vec::new();
 
-        Fine(
-            match self.inner {
-                Statement::Return { value } => {
-                    let value_size = attempt!(
-                        value.generate(codes, filename, source, code_pos_table),
-                        errors
-                    )
-                    .0;
-                    codes.push(Code::Return);
-                    (value_size + 1, Vec::new())
+        match self.inner {
+            Statement::Return { value } => {
+                attempt!(value.generate(builder, filename, source), errors);
+                builder.push(Code::Return);
+            }
+            Statement::If {
+                condition,
+                block,
+                elseblock,
+            } => {
+                let else_start = builder.label();
+                let end = builder.label();
+                let condition_opt = condition
+                    .generate(builder, filename, source)
+                    .to_option(&mut errors);
+                builder.jump(Code::JumpNot, else_start);
+                let block_opt =
+                    generate_all(block, builder, filename, source).to_option(&mut errors);
+                let else_opt = if let Some(elseblock) = elseblock {
+                    builder.jump(Code::Goto, end);
+                    builder.place(else_start);
+                    generate_all(elseblock, builder, filename, source).to_option(&mut errors)
+                } else {
+                    builder.place(else_start);
+                    Some(())
+                };
+                builder.place(end);
+                if condition_opt.is_none() || block_opt.is_none() || else_opt.is_none() {
+                    return Fail(errors);
                 }
-                Statement::If {
-                    condition,
-                    block,
-                    elseblock,
-                } => {
-                    let mut break_positions = Vec::new();
-                    let condition_opt = condition
-                        .generate(codes, filename, source, code_pos_table)
-                        .to_option(&mut errors);
-                    let first_jump_index = codes.len();
-                    codes.push(Code::JumpNot(0));
-                    let block_opt = generate_block(block, codes, filename, source, code_pos_table)
-                        .to_option(&mut errors);
-                    let else_bonus = if elseblock.is_some() { 1 } else { 0 };
-                    let else_size = if let Some(elseblock) = elseblock {
-                        let second_jump_index = codes.len();
-                        codes.push(Code::Goto(0));
-                        let elseblock_opt =
-                            generate_block(elseblock, codes, filename, source, code_pos_table)
-                                .to_option(&mut errors);
-                        if let None = elseblock_opt {
-                            return Fail(errors);
-                        }
-                        break_positions.extend(&elseblock_opt.as_ref().unwrap().1);
-                        match &mut codes[second_jump_index] {
-                            Code::Goto(pos) => {
-                                *pos = (elseblock_opt.as_ref().unwrap().0 + 1) as i32
-                            }
-                            _ => panic!(),
-                        }
-                        elseblock_opt.unwrap().0 + 1
-                    } else {
-                        0
-                    };
-                    if let None = condition_opt {
-                        return Fail(errors);
-                    }
-                    if let None = block_opt {
-                        return Fail(errors);
-                    }
-                    break_positions.extend(&block_opt.as_ref().unwrap().1);
-                    match &mut codes[first_jump_index] {
-                        Code::JumpNot(pos) => {
-                            *pos = (block_opt.as_ref().unwrap().0 + 1 + else_bonus) as i32
-                        }
-                        _ => panic!(),
-                    }
-                    (
-                        1 + condition_opt.unwrap().0 + block_opt.as_ref().unwrap().0 + else_size,
-                        break_positions,
-                    )
+            }
+            Statement::Call { expression, args } => {
+                let arg_count = args.len() as u32;
+                let args_opt = generate_all(args, builder, filename, source).to_option(&mut errors);
+                let expression_opt = expression
+                    .generate(builder, filename, source)
+                    .to_option(&mut errors);
+                if args_opt.is_none() || expression_opt.is_none() {
+                    return Fail(errors);
                 }
-                Statement::Call { expression, args } => {
-                    let results: Vec<Option<u32>> = args
-                        .into_iter()
-                        .map(|arg| {
-                            match arg
-                                .generate(codes, filename, source, code_pos_table)
-                                .to_option(&mut errors)
-                            {
-                                Some((i, _)) => Some(i),
-                                None => None,
-                            }
-                        })
-                        .collect();
-                    let expression_opt = expression
-                        .generate(codes, filename, source, code_pos_table)
-                        .to_option(&mut errors);
-                    let mut size = 0;
-                    for res in results {
-                        if let Some(l) = res {
-                            size += l;
-                        } else {
-                            return Fail(errors);
-                        }
-                    }
-                    if let None = expression_opt {
-                        return Fail(errors);
-                    }
-                    code_pos_table.insert(codes.len(), self.start);
-                    codes.push(Code::Call);
-                    codes.push(Code::Pop);
-                    (2 + expression_opt.unwrap().0 + size, Vec::new())
+                builder.mark(self.start);
+                builder.push(Code::Call(arg_count));
+                builder.push(Code::Pop);
+            }
+            Statement::Pipe { pipe } => {
+                attempt!(pipe.generate(builder, filename, source), errors);
+                builder.push(Code::Pop);
+            }
+            Statement::Block { statements } => {
+                attempt!(generate_all(statements, builder, filename, source), errors);
+            }
+            Statement::Assign { left, right } => {
+                let var_name = match left.inner {
+                    Expression::Variable { name } => Some(String::from(name)),
+                    _ => None,
+                };
+                let assigned_name = assigned_name(&left);
+                let left_opt = left
+                    .generate_lhs(builder, filename, source)
+                    .to_option(&mut errors);
+                let right_opt = generate_value(*right, assigned_name, builder, filename, source)
+                    .to_option(&mut errors);
+                if left_opt.is_none() || right_opt.is_none() {
+                    return Fail(errors);
                 }
-                Statement::Block { statements } => attempt!(
-                    generate_block(statements, codes, filename, source, code_pos_table),
-                    errors
-                ),
-                Statement::Assign { left, right } => {
-                    let var_name = match left.inner {
-                        Expression::Variable { name } => Some(String::from(name)),
-                        _ => None,
-                    };
-                    let left_opt = left
-                        .generate_lhs(codes, filename, source, code_pos_table)
-                        .to_option(&mut errors);
-                    let right_opt = right
-                        .generate(codes, filename, source, code_pos_table)
-                        .to_option(&mut errors);
-                    if let None = left_opt {
-                        return Fail(errors);
-                    }
-                    if let None = right_opt {
-                        return Fail(errors);
-                    }
-                    if let Some(name) = var_name {
-                        codes.push(Code::Store(name));
-                    } else {
-                        codes.push(Code::Put);
-                    }
-                    (1 + left_opt.unwrap() + right_opt.unwrap().0, Vec::new())
+                if let Some(name) = var_name {
+                    builder.push(Code::Store(name));
+                } else {
+                    builder.push(Code::Put);
                 }
-                Statement::While { condition, block } => {
-                    let condition_opt = condition
-                        .generate(codes, filename, source, code_pos_table)
-                        .to_option(&mut errors);
-                    let first_jump_index = codes.len();
-                    codes.push(Code::JumpNot(0));
-                    let block_opt = generate_block(block, codes, filename, source, code_pos_table)
-                        .to_option(&mut errors);
-                    if let None = condition_opt {
-                        return Fail(errors);
-                    }
-                    if let None = block_opt {
+            }
+            Statement::While { condition, block } => {
+                let start = builder.label();
+                let end = builder.label();
+                builder.place(start);
+                let condition_opt = condition
+                    .generate(builder, filename, source)
+                    .to_option(&mut errors);
+                builder.jump(Code::JumpNot, end);
+                builder.loops.push(Loop { start, end });
+                let block_opt =
+                    generate_all(block, builder, filename, source).to_option(&mut errors);
+                builder.loops.pop();
+                builder.jump(Code::Goto, start);
+                builder.place(end);
+                if condition_opt.is_none() || block_opt.is_none() {
+                    return Fail(errors);
+                }
+            }
+            Statement::Break | Statement::Continue => {
+                let target = match (builder.loops.last(), &self.inner) {
+                    (Some(innermost), Statement::Break) => innermost.end,
+                    (Some(innermost), _) => innermost.start,
+                    (None, _) => {
+                        errors.push(OliveError::new_code_error(
+                            self.start,
+                            filename,
+                            source,
+                            OliveCodeError::BreakOutsideWhile,
+                        ));
                         return Fail(errors);
                     }
-                    codes.push(Code::Goto(
-                        -((block_opt.as_ref().unwrap().0 + condition_opt.as_ref().unwrap().0 + 1)
-                            as i32),
-                    ));
-                    match &mut codes[first_jump_index] {
-                        Code::JumpNot(pos) => *pos = (block_opt.as_ref().unwrap().0 + 2) as i32,
-                        _ => panic!(),
-                    }
-                    for position in &block_opt.as_ref().unwrap().1 {
-                        match &mut codes[*position] {
-                            Code::Goto(pos) if *pos == 0 => {
-                                *pos = block_opt.as_ref().unwrap().0 as i32
-                                    - (position - first_jump_index) as i32
-                                    + 2
-                            }
-                            Code::Goto(pos) if *pos == 1 => {
-                                *pos = -((position - first_jump_index) as i32
-                                    + condition_opt.as_ref().unwrap().0 as i32)
-                            }
-                            _ => panic!(),
-                        }
-                    }
-                    (
-                        2 + block_opt.unwrap().0 + condition_opt.unwrap().0,
-                        Vec::new(),
-                    )
-                }
-                Statement::Break => {
-                    let pos = codes.len();
-                    code_pos_table.insert(pos, self.start);
-                    codes.push(Code::Goto(0));
-                    (1, vec![pos])
-                }
-                Statement::Continue => {
-                    let pos = codes.len();
-                    code_pos_table.insert(pos, self.start);
-                    codes.push(Code::Goto(1));
-                    (1, vec![pos])
-                }
-            },
-            errors,
-        )
+                };
+                builder.mark(self.start);
+                builder.jump(Code::Goto, target);
+            }
+            Statement::Defer { statement } => {
+                // compiled on its own like a function body, so break, continue
+                // and return in it can't jump out of the deferred statement
+                let (codes, code_pos_table) =
+                    attempt!(generate_codes(vec![*statement], filename, source), errors);
+                builder.push(Code::Defer(codes, code_pos_table));
+            }
+        }
+        Fine((), errors)
     }
 
     fn generate_lhs(
         self,
-        _codes: &mut Vec<Code>,
+        _builder: &mut CodeBuilder,
         _filename: &str,
-        _source: &str,
-        _code_pos_table: &mut HashMap<usize, usize>,
-    ) -> Mistake<u32, OliveError> {
+        _source: &Source,
+    ) -> Mistake<(), OliveError> {
         panic!()
     }
 }
 
-fn generate_block(
-    block: Vec<Located<Statement>>,
-    codes: &mut Vec<Code>,
-    filename: &str,
-    source: &str,
-    code_pos_table: &mut HashMap<usize, usize>,
-) -> Mistake<(u32, Vec<usize>), OliveError> {
-    let mut break_positions = Vec::new();
-    let mut errors = Vec::new();
-    let mut fine = true;
-    let mut size = 0;
-    for st in block {
-        let st_opt = st
-            .generate(codes, filename, source, code_pos_table)
-            .to_option(&mut errors);
-        if let Some((l, break_pos)) = st_opt {
-            size += l;
-            break_positions.extend(break_pos);
-        } else {
-            fine = false;
-        }
-    }
-    if fine {
-        Fine((size, break_positions), errors)
-    } else {
-        Fail(errors)
-    }
-}
-
 pub fn generate_codes<'a>(
     tree: Vec<Located<Statement<'a>>>,
     filename: &str,
-    source: &str,
-) -> Mistake<(Vec<Code>, HashMap<usize, usize>), OliveError> {
-    let mut code_pos_table = HashMap::new();
+    source: &Source,
+) -> Mistake<(Vec<Code>, CodePosTable), OliveError> {
     let mut errors = Vec::new();
-    let mut codes: Vec<Code> = Vec::new();
-    let (total_len, break_positions) = attempt!(
-        generate_block(tree, &mut codes, filename, source, &mut code_pos_table),
-        errors
-    );
-    assert_eq!(codes.len() as u32, total_len);
-    codes.push(Code::PushNone);
-    codes.push(Code::Return);
-    for bp in &break_positions {
-        errors.push(OliveError::new_code_error(
-            *code_pos_table.get(bp).unwrap(),
-            filename,
-            source,
-            OliveCodeError::BreakOutsideWhile,
-        ));
-    }
-    if break_positions.len() != 0 {
-        return Fail(errors);
+    let mut builder = CodeBuilder::default();
+    attempt!(generate_all(tree, &mut builder, filename, source), errors);
+    builder.push(Code::PushNone);
+    builder.push(Code::Return);
+    match builder.resolve_jumps() {
+        Ok(()) => Fine((builder.codes, builder.code_pos_table), errors),
+        Err(index) => {
+            errors.push(OliveError::new_code_error(
+                builder.code_pos_table.get(index).unwrap_or(0),
+                filename,
+                source,
+                OliveCodeError::TooLarge,
+            ));
+            Fail(errors)
+        }
     }
-    Fine((codes, code_pos_table), errors)
 }
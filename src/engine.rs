@@ -0,0 +1,331 @@
+use super::codegen::{self, Code, CodePosTable};
+use super::errors::{OliveCodeError, OliveError, Source};
+use super::interpreter::{
+    self, HookList, Hooks, Log, LogLevel, Logger, NativeFn, Object, Output, Scope, SizeLimits,
+    Stats, BUILTIN_NAMES,
+};
+use super::value::Value;
+use mistake::Mistake::{self, Fine};
+use oliveparser::parse;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+#[cfg(feature = "fs")]
+mod cache;
+#[cfg(feature = "fs")]
+mod files;
+#[cfg(feature = "inspect")]
+mod inspect;
+mod plugin;
+#[cfg(feature = "fs")]
+pub use cache::CompileCache;
+#[cfg(feature = "fs")]
+pub use files::{compile_file, compile_file_with_source_map};
+pub use plugin::Plugin;
+
+// the builtins EnginePolicy::untrusted leaves out, besides on_signal
+const UNTRUSTED_DENIED: &[&str] = &["heapdump", "eval", "compile"];
+
+// decides which capabilities scripts in an engine get; the interpreter can only
+// reach the outside world through builtins, so those are what a policy restricts
+#[derive(Clone, Debug, Default)]
+pub struct EnginePolicy {
+    builtins: Option<HashSet<String>>,
+    signals: bool,
+    remote_inspector: bool,
+}
+
+impl EnginePolicy {
+    // every builtin except on_signal, see allow_signals. that includes
+    // heapdump, which writes files, so this is for scripts the host trusts
+    pub fn allow_all() -> Self {
+        EnginePolicy {
+            builtins: None,
+            signals: false,
+            remote_inspector: false,
+        }
+    }
+
+    // for scripts the host doesn't trust: every builtin but heapdump, which
+    // writes files wherever the script says, and eval and compile, which run
+    // code the script made up at runtime past whatever checked the source
+    pub fn untrusted() -> Self {
+        EnginePolicy::allow_builtins(
+            BUILTIN_NAMES
+                .iter()
+                .copied()
+                .filter(|name| !UNTRUSTED_DENIED.contains(name)),
+        )
+    }
+
+    // scripts only get the listed builtins, e.g. ["len"] to also take away print
+    pub fn allow_builtins<S: Into<String>, I: IntoIterator<Item = S>>(names: I) -> Self {
+        EnginePolicy {
+            builtins: Some(names.into_iter().map(Into::into).collect()),
+            signals: false,
+            remote_inspector: false,
+        }
+    }
+
+    // also gives scripts on_signal. a handler changes what the signal does to
+    // the whole process, it no longer terminates it while the engine is alive
+    pub fn allow_signals(mut self) -> Self {
+        self.signals = true;
+        self
+    }
+
+    // lets enable_inspector listen on addresses other machines can reach.
+    // anyone who can connect to it runs code in the engine, there is no login
+    pub fn allow_remote_inspector(mut self) -> Self {
+        self.remote_inspector = true;
+        self
+    }
+
+    pub fn allows_builtin(&self, name: &str) -> bool {
+        if name == "on_signal" && self.signals {
+            return true;
+        }
+        match &self.builtins {
+            Some(builtins) => builtins.contains(name),
+            None => name != "on_signal",
+        }
+    }
+}
+
+// an engine owns the globals, builtins, hooks and signal handlers of its
+// scripts, scripts in one engine never see the variables of another. what
+// engines share is the process: a signal handled by a script doesn't
+// terminate it for any engine until the engine with the handler is dropped.
+// engines can't be sent to another thread, a host keeps independent ones e.g.
+// one per thread
+pub struct Engine {
+    scope: Rc<RefCell<Scope>>,
+    output: Output,
+    log: Log,
+    hooks: HookList,
+    plugins: Vec<Rc<dyn Plugin>>,
+    stats: Option<Rc<Stats>>,
+    #[cfg(feature = "fs")]
+    compile_cache: Option<CompileCache>,
+    #[cfg(feature = "inspect")]
+    policy: EnginePolicy,
+    #[cfg(feature = "inspect")]
+    inspector: Option<inspect::Listener>,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine::with_policy(EnginePolicy::allow_all())
+    }
+
+    // functions registered by the host are not restricted by the policy
+    pub fn with_policy(policy: EnginePolicy) -> Self {
+        let output: Output = Rc::new(RefCell::new(Box::new(|line: &str| println!("{}", line))));
+        let log = Rc::new(Logger::new());
+        let hooks = HookList::default();
+        Engine {
+            scope: interpreter::global_scope(
+                |name| policy.allows_builtin(name),
+                output.clone(),
+                log.clone(),
+                hooks.clone(),
+            ),
+            output,
+            log,
+            hooks,
+            plugins: Vec::new(),
+            stats: None,
+            #[cfg(feature = "fs")]
+            compile_cache: None,
+            #[cfg(feature = "inspect")]
+            policy,
+            #[cfg(feature = "inspect")]
+            inspector: None,
+        }
+    }
+
+    // lines printed by scripts go to the callback instead of stdout
+    pub fn on_print<F: FnMut(&str) + 'static>(&mut self, callback: F) {
+        *self.output.borrow_mut() = Box::new(callback);
+    }
+
+    // collects everything printed from now on, read it with the returned buffer
+    pub fn capture_print(&mut self) -> Rc<RefCell<String>> {
+        let buffer = Rc::new(RefCell::new(String::new()));
+        let captured = buffer.clone();
+        self.on_print(move |line| {
+            let mut captured = captured.borrow_mut();
+            captured.push_str(line);
+            captured.push('\n');
+        });
+        buffer
+    }
+
+    // messages logged by scripts below the level are dropped, info by default
+    pub fn set_log_level(&mut self, level: LogLevel) {
+        self.log.set_level(level);
+    }
+
+    // caps the size single strings, lists and bendys of scripts may grow to
+    pub fn set_size_limits(&mut self, limits: SizeLimits) {
+        self.scope.borrow_mut().set_limits(limits);
+    }
+
+    // log lines, with time and level in front, go to the callback instead of
+    // stderr
+    pub fn on_log<F: FnMut(&str) + 'static>(&mut self, callback: F) {
+        self.log.on_line(callback);
+    }
+
+    // the hooks are called by every script run from now on, in the order they
+    // were added. the returned handle gives access to their state afterwards
+    pub fn add_hooks<H: Hooks + 'static>(&mut self, hooks: H) -> Rc<H> {
+        let hooks = Rc::new(hooks);
+        self.hooks.borrow_mut().push(hooks.clone());
+        hooks
+    }
+
+    // the plugin rewrites every script compiled from now on, after the plugins
+    // added before it. code given to eval and compile by scripts isn't
+    // rewritten
+    pub fn add_plugin<P: Plugin + 'static>(&mut self, plugin: P) {
+        self.plugins.push(Rc::new(plugin));
+    }
+
+    // counts executed instructions, created objects and calls from now on and
+    // gives scripts a vmstats() function returning the counters as a bendy
+    pub fn enable_stats(&mut self) {
+        let stats = self.add_hooks(Stats::new());
+        let counters = stats.clone();
+        let closure: NativeFn = Rc::new(move |_: &[Object]| Ok(counters.to_object()));
+        self.scope
+            .borrow_mut()
+            .store_native("vmstats", Object::new_native(0, closure));
+        self.stats = Some(stats);
+    }
+
+    // gives scripts an argv() function returning the arguments as a list of
+    // strings, e.g. the ones passed to olv after the script
+    pub fn set_args<S: Into<String>, I: IntoIterator<Item = S>>(&mut self, args: I) {
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+        let closure: NativeFn = Rc::new(move |_: &[Object]| {
+            let args = args.iter().cloned().map(Object::new_string).collect();
+            Ok(Object::new_filled_list(args))
+        });
+        self.scope
+            .borrow_mut()
+            .store_native("argv", Object::new_native(0, closure));
+    }
+
+    // the counters as readable text, if enable_stats was called
+    pub fn stats_report(&self) -> Option<String> {
+        self.stats.as_ref().map(|stats| stats.report())
+    }
+
+    // the objects reachable from the globals of this engine as json, see the
+    // heapdump builtin
+    pub fn heap_snapshot(&self) -> String {
+        interpreter::heap_snapshot(&self.scope.borrow())
+    }
+
+    // names of all global variables, builtins included, in sorted order
+    pub fn global_names(&self) -> Vec<String> {
+        let mut names = self.scope.borrow().names();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // names of the builtins scripts in this engine can call and of the
+    // functions registered by the host, in sorted order. e.g. a policy for
+    // another engine can be made from a part of them
+    pub fn builtin_names(&self) -> Vec<String> {
+        self.scope.borrow().builtin_names()
+    }
+
+    // makes a rust closure callable from scripts as a global function taking
+    // arg_count arguments; an Err fails the script with that message
+    pub fn register_fn<F>(&mut self, name: &str, arg_count: u32, function: F)
+    where
+        F: Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        let closure: NativeFn = Rc::new(move |args: &[Object]| {
+            function(args.iter().map(Value::from).collect()).map(Object::from)
+        });
+        self.scope
+            .borrow_mut()
+            .store_native(name, Object::new_native(arg_count, closure));
+    }
+
+    // like register_fn, but the function is called as module.name, e.g.
+    // sqlite.open, so the functions of different modules can share names
+    pub fn register_module_fn<F>(&mut self, module: &str, name: &str, arg_count: u32, function: F)
+    where
+        F: Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        let closure: NativeFn = Rc::new(move |args: &[Object]| {
+            function(args.iter().map(Value::from).collect()).map(Object::from)
+        });
+        self.scope.borrow_mut().store_module_native(
+            module,
+            name,
+            Object::new_native(arg_count, closure),
+        );
+    }
+
+    // globals assigned by one call stay visible to the following ones; the
+    // result is the value of a top level return statement, or none
+    pub fn eval_str(&mut self, source: &str) -> Mistake<Value, OliveError> {
+        let mut errors = Vec::new();
+        let source = Source::new(source);
+        let (codes, code_pos_table) = attempt!(get_codes(&source, "<eval>", &self.plugins), errors);
+        let result = attempt!(
+            interpreter::run(
+                &codes,
+                &code_pos_table,
+                "<eval>",
+                Some(&source),
+                self.scope.clone(),
+                &self.hooks.borrow()
+            ),
+            errors
+        );
+        Fine(Value::from(&result), errors)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+fn get_codes(
+    source: &Source,
+    in_path_str: &str,
+    plugins: &[Rc<dyn Plugin>],
+) -> Mistake<(Vec<Code>, CodePosTable), OliveError> {
+    let mut errors = Vec::new();
+    let mut ast = attempt_res!(
+        parse(source.text).map_err(|err| OliveError::from_parse_err(err, in_path_str, source)),
+        errors
+    );
+    for plugin in plugins {
+        attempt_res!(
+            plugin
+                .rewrite(&mut ast)
+                .map_err(|message| OliveError::new_code_error(
+                    message.start,
+                    in_path_str,
+                    source,
+                    OliveCodeError::Plugin {
+                        message: message.inner
+                    },
+                )),
+            errors
+        );
+    }
+    let codes = attempt!(codegen::generate_codes(ast, in_path_str, source), errors);
+    Fine(codes, errors)
+}
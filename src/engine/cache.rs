@@ -0,0 +1,91 @@
+use crate::bytecode;
+use crate::codegen::{Code, CodePosTable};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// where the compiled codes of .olv files are kept between runs. a cache file
+// is the magic "OLVK", the olv version as length (u8) and bytes, length (u64)
+// and hash (u64) of the source it was compiled from and then the codes in the
+// .olvc format, with their source positions so errors still point at lines
+#[derive(Clone, Debug)]
+pub enum CompileCache {
+    // a .olvcache directory next to every script
+    NextToScript,
+    Dir(PathBuf),
+}
+
+const MAGIC: &[u8; 4] = b"OLVK";
+
+impl CompileCache {
+    // the file the codes of the script at path are cached in. scripts with
+    // the same name in different directories get different files
+    fn file(&self, path: &Path) -> Option<PathBuf> {
+        let path = path.canonicalize().ok()?;
+        let dir = match self {
+            CompileCache::NextToScript => path.parent()?.join(".olvcache"),
+            CompileCache::Dir(dir) => dir.clone(),
+        };
+        let name = format!(
+            "{}-{:016x}.olvk",
+            path.file_name()?.to_string_lossy(),
+            hash(path.to_string_lossy().as_bytes())
+        );
+        Some(dir.join(name))
+    }
+
+    // the cached codes, if the script didn't change since they were compiled
+    // by this version of olv
+    pub(super) fn load(&self, path: &Path, text: &str) -> Option<(Vec<Code>, CodePosTable)> {
+        let binary = fs::read(self.file(path)?).ok()?;
+        let codes = binary.strip_prefix(&header(text)[..])?;
+        bytecode::decode(codes).ok()
+    }
+
+    // a cache that can't be written only makes the next run slower, so
+    // failures are ignored
+    pub(super) fn store(
+        &self,
+        path: &Path,
+        text: &str,
+        codes: &[Code],
+        code_pos_table: &CodePosTable,
+    ) {
+        let (file, codes) = match (
+            self.file(path),
+            bytecode::encode(codes, Some(code_pos_table)),
+        ) {
+            (Some(file), Some(codes)) => (file, codes),
+            _ => return,
+        };
+        let mut binary = header(text);
+        binary.extend_from_slice(&codes);
+        if let Some(dir) = file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        // written under another name first, so another run reading the cache
+        // at the same time never sees half a file
+        let temporary = file.with_extension(format!("{}.tmp", std::process::id()));
+        if fs::write(&temporary, binary).is_err() || fs::rename(&temporary, &file).is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+    }
+}
+
+fn header(text: &str) -> Vec<u8> {
+    let version = env!("CARGO_PKG_VERSION");
+    let mut header = Vec::with_capacity(32);
+    header.extend_from_slice(MAGIC);
+    header.push(version.len() as u8);
+    header.extend_from_slice(version.as_bytes());
+    header.extend_from_slice(&(text.len() as u64).to_le_bytes());
+    header.extend_from_slice(&hash(text.as_bytes()).to_le_bytes());
+    header
+}
+
+// 64 bit fnv-1a. the hasher of the standard library may change between rust
+// versions, this one gives the same hash for the same bytes in every build
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,176 @@
+use super::{get_codes, CompileCache, Engine};
+use crate::bytecode::{self, SourceMap};
+use crate::errors::{OliveError, OliveIoError, Source};
+use crate::interpreter;
+use crate::value::Value;
+use mistake::Mistake::{self, Fail, Fine};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+impl Engine {
+    // run_file keeps the codes of .olv files in the cache from now on and
+    // reuses them while the source stays the same
+    pub fn enable_compile_cache(&mut self, cache: CompileCache) {
+        self.compile_cache = Some(cache);
+    }
+
+    // runs either source (.olv) or compiled (.olvc) files
+    pub fn run_file<P: AsRef<Path>>(&mut self, path: P) -> Mistake<Value, OliveError> {
+        let mut errors = Vec::new();
+        let in_path_str = &path.as_ref().to_string_lossy().into_owned();
+        let contents = attempt!(read_file(in_path_str), errors);
+        let text = match path.as_ref().extension() {
+            Some(x) if x == "olv" => Some(attempt!(to_source(&contents, in_path_str), errors)),
+            Some(x) if x == "olvc" => None,
+            _ => {
+                errors.push(OliveError::Io {
+                    file: String::from(in_path_str),
+                    kind: OliveIoError::Extension,
+                });
+                return Fail(errors);
+            }
+        };
+        let source = text.map(Source::new);
+        // the cache can't tell which plugins the codes were made with
+        let cache = self
+            .compile_cache
+            .as_ref()
+            .filter(|_| self.plugins.is_empty());
+        let cached = match (cache, &source) {
+            (Some(cache), Some(source)) => cache.load(path.as_ref(), source.text),
+            _ => None,
+        };
+        let (codes, code_pos_table) = match (&source, cached) {
+            (_, Some(codes)) => codes,
+            (Some(source), None) => {
+                let (codes, code_pos_table) =
+                    attempt!(get_codes(source, in_path_str, &self.plugins), errors);
+                if let Some(cache) = cache {
+                    cache.store(path.as_ref(), source.text, &codes, &code_pos_table);
+                }
+                (codes, code_pos_table)
+            }
+            (None, None) => attempt_res!(
+                bytecode::decode(&contents).map_err(|kind| OliveError::Io {
+                    file: String::from(in_path_str),
+                    kind,
+                }),
+                errors
+            ),
+        };
+        // errors in a compiled file point at the script it was compiled from
+        // if a source map was written next to it
+        let map = match &source {
+            Some(_) => None,
+            None => std::fs::read(format!("{}.map", in_path_str))
+                .ok()
+                .and_then(|json| SourceMap::from_json(&json)),
+        };
+        let mapped_source = map.as_ref().map(SourceMap::source);
+        let (filename, source) = match &map {
+            Some(map) => (&map.file, mapped_source.as_ref()),
+            None => (in_path_str, source.as_ref()),
+        };
+        let result = attempt!(
+            interpreter::run(
+                &codes,
+                &code_pos_table,
+                filename,
+                source,
+                self.scope.clone(),
+                &self.hooks.borrow()
+            ),
+            errors
+        );
+        Fine(Value::from(&result), errors)
+    }
+}
+
+// compiles a .olv file to the binary code format stored in .olvc files
+pub fn compile_file<P: AsRef<Path>>(path: P) -> Mistake<Vec<u8>, OliveError> {
+    let mut errors = Vec::new();
+    let (binary, _) = attempt!(compile(path.as_ref(), false), errors);
+    Fine(binary, errors)
+}
+
+// like compile_file, but the codes keep their source positions and the
+// second result is the source map to write next to the .olvc file, with the
+// name of the compiled file and .map appended
+pub fn compile_file_with_source_map<P: AsRef<Path>>(
+    path: P,
+) -> Mistake<(Vec<u8>, Vec<u8>), OliveError> {
+    let mut errors = Vec::new();
+    let (binary, map) = attempt!(compile(path.as_ref(), true), errors);
+    Fine((binary, map.to_json()), errors)
+}
+
+// the positions are only of use together with the source map
+fn compile(path: &Path, keep_positions: bool) -> Mistake<(Vec<u8>, SourceMap), OliveError> {
+    let mut errors = Vec::new();
+    let in_path_str = &path.to_string_lossy().into_owned();
+    match path.extension() {
+        Some(x) if x == "olv" => {}
+        Some(x) if x == "olvc" => {
+            errors.push(OliveError::Io {
+                file: String::from(in_path_str),
+                kind: OliveIoError::CompileCompiled,
+            });
+            return Fail(errors);
+        }
+        _ => {
+            errors.push(OliveError::Io {
+                file: String::from(in_path_str),
+                kind: OliveIoError::Extension,
+            });
+            return Fail(errors);
+        }
+    }
+    let contents = attempt!(read_file(in_path_str), errors);
+    let text = attempt!(to_source(&contents, in_path_str), errors);
+    let source = Source::new(text);
+    let (codes, code_pos_table) = attempt!(get_codes(&source, in_path_str, &[]), errors);
+    // the codes run in the global scope like those of a .olv file
+    let positions = if keep_positions {
+        Some(&code_pos_table)
+    } else {
+        None
+    };
+    let binary = attempt_res!(
+        bytecode::encode(&codes, positions).ok_or_else(|| OliveError::Io {
+            file: String::from(in_path_str),
+            kind: OliveIoError::Serialize,
+        }),
+        errors
+    );
+    Fine((binary, SourceMap::new(in_path_str, &source)), errors)
+}
+
+fn read_file(in_path_str: &str) -> Mistake<Vec<u8>, OliveError> {
+    let mut errors = Vec::new();
+    let mut file = attempt_res!(
+        File::open(in_path_str).map_err(|_| OliveError::Io {
+            file: String::from(in_path_str),
+            kind: OliveIoError::OpenRead,
+        }),
+        errors
+    );
+    let mut contents: Vec<u8> = Vec::new();
+    attempt_res!(
+        file.read_to_end(&mut contents).map_err(|_| OliveError::Io {
+            file: String::from(in_path_str),
+            kind: OliveIoError::Read,
+        }),
+        errors
+    );
+    Fine(contents, errors)
+}
+
+fn to_source<'a>(contents: &'a [u8], in_path_str: &str) -> Mistake<&'a str, OliveError> {
+    std::str::from_utf8(contents)
+        .map_err(|_| OliveError::Io {
+            file: String::from(in_path_str),
+            kind: OliveIoError::UTF,
+        })
+        .into()
+}
@@ -0,0 +1,235 @@
+use super::{get_codes, Engine};
+use crate::codegen::Code;
+use crate::errors::{OliveError, OliveIoError, Source};
+use crate::interpreter::{self, Hooks, Scope, BUILTINS};
+use mistake::Mistake::{Fail, Fine};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::rc::{Rc, Weak};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+const FILENAME: &str = "<inspect>";
+
+// a line sent by a client, with where its answer goes
+type Request = (String, Sender<String>);
+
+impl Engine {
+    // serves a line protocol on the address, e.g. "127.0.0.1:9229", to look
+    // into scripts while they run. every line a client sends gets one line
+    // back: ":vars" lists the global variables with their types, anything
+    // else is evaluated in the global scope, as an expression if it is one.
+    // answers start with "ok" or "error". lines are only answered while the
+    // engine runs a script, between two of its instructions. the code runs
+    // with the builtins of the engine, and only if its policy allows eval.
+    // the address has to be a loopback one unless the policy allows a remote
+    // inspector. the port is closed and clients are disconnected when the
+    // engine is dropped or the inspector is enabled again. returns the
+    // address listened on, which has the port if 0 was given
+    pub fn enable_inspector(&mut self, address: &str) -> Result<SocketAddr, OliveError> {
+        let io_error = |kind| OliveError::Io {
+            file: String::from(address),
+            kind,
+        };
+        if !self.policy.allows_builtin("eval") {
+            return Err(io_error(OliveIoError::InspectNotAllowed));
+        }
+        let addresses: Vec<SocketAddr> = address
+            .to_socket_addrs()
+            .map_err(|_| io_error(OliveIoError::Listen))?
+            .collect();
+        if !self.policy.remote_inspector && !addresses.iter().all(|a| a.ip().is_loopback()) {
+            return Err(io_error(OliveIoError::InspectNotLocal));
+        }
+        let listener =
+            TcpListener::bind(&addresses[..]).map_err(|_| io_error(OliveIoError::Listen))?;
+        let local_address = listener
+            .local_addr()
+            .map_err(|_| io_error(OliveIoError::Listen))?;
+        let (requests, received) = mpsc::channel();
+        let clients = Arc::new(Mutex::new(Clients::default()));
+        let accepting = clients.clone();
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let id = match accepting.lock() {
+                    Ok(mut clients) if !clients.closed => clients.add(&stream),
+                    _ => break,
+                };
+                // a client that couldn't be disconnected isn't served
+                let id = match id {
+                    Some(id) => id,
+                    None => continue,
+                };
+                let requests = requests.clone();
+                let clients = accepting.clone();
+                thread::spawn(move || {
+                    let _ = serve(stream, requests);
+                    if let Ok(mut clients) = clients.lock() {
+                        clients.streams.remove(&id);
+                    }
+                });
+            }
+        });
+        self.add_hooks(Inspector {
+            scope: Rc::downgrade(&self.scope),
+            requests: received,
+        });
+        self.inspector = Some(Listener {
+            address: local_address,
+            clients,
+            thread: Some(thread),
+        });
+        Ok(local_address)
+    }
+}
+
+// the connections of the clients being served, so they can be closed
+#[derive(Default)]
+struct Clients {
+    closed: bool,
+    next_id: usize,
+    streams: HashMap<usize, TcpStream>,
+}
+
+impl Clients {
+    fn add(&mut self, stream: &TcpStream) -> Option<usize> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.streams.insert(id, stream.try_clone().ok()?);
+        Some(id)
+    }
+}
+
+// owned by the engine, stops the accepting thread and disconnects the
+// clients when dropped
+pub struct Listener {
+    address: SocketAddr,
+    clients: Arc<Mutex<Clients>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.closed = true;
+            for stream in clients.streams.values() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            clients.streams.clear();
+        }
+        // the accepting thread only sees the flag once a connection comes in
+        let mut address = self.address;
+        if address.ip().is_unspecified() {
+            address.set_ip(match address {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        if TcpStream::connect(address).is_ok() {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+// answers the lines of one client until it leaves, sends ":quit" or the
+// engine is dropped
+fn serve(stream: TcpStream, requests: Sender<Request>) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+        if command == ":quit" {
+            break;
+        }
+        let (reply, answer) = mpsc::channel();
+        if requests.send((line, reply)).is_err() {
+            break;
+        }
+        // the sender is dropped unanswered when the engine goes away
+        match answer.recv() {
+            Ok(answer) => writeln!(writer, "{}", answer)?,
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+struct Inspector {
+    scope: Weak<RefCell<Scope>>,
+    requests: Receiver<Request>,
+}
+
+impl Hooks for Inspector {
+    fn on_instruction(&self, _ip: usize, _code: &Code) {
+        while let Ok((line, reply)) = self.requests.try_recv() {
+            let _ = reply.send(self.answer(&line));
+        }
+    }
+}
+
+impl Inspector {
+    fn answer(&self, line: &str) -> String {
+        let scope = match self.scope.upgrade() {
+            Some(scope) => scope,
+            None => return String::from("error the engine was dropped"),
+        };
+        if line.trim() == ":vars" {
+            return format!("ok {}", variables(&scope.borrow()));
+        }
+        // an expression gives its value, statements give none
+        let expression = format!("return {};", line);
+        let expression = Source::new(&expression);
+        let statements = Source::new(line);
+        let (source, (codes, code_pos_table)) = match get_codes(&expression, FILENAME, &[]) {
+            Fine(codes, _) => (expression, codes),
+            Fail(_) => match get_codes(&statements, FILENAME, &[]) {
+                Fine(codes, _) => (statements, codes),
+                Fail(errors) => return error_line(&errors[0]),
+            },
+        };
+        // no hooks, so the inspector isn't asked again from inside
+        match interpreter::run(&codes, &code_pos_table, FILENAME, Some(&source), scope, &[]) {
+            Fine(value, _) => format!("ok {}", value.repr()),
+            Fail(errors) => error_line(&errors[0]),
+        }
+    }
+}
+
+// the global variables the script made, in sorted order, e.g. "x: integer"
+fn variables(scope: &Scope) -> String {
+    let builtins = scope.builtin_names();
+    let mut names: Vec<String> = scope
+        .names()
+        .into_iter()
+        .filter(|name| name != BUILTINS && !builtins.contains(name))
+        .collect();
+    names.sort();
+    names
+        .iter()
+        .filter_map(|name| {
+            scope
+                .load(name)
+                .map(|value| format!("{}: {}", name, value.get_type_name()))
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// positions are left out, a line given as an expression is shifted by the
+// return in front of it
+fn error_line(error: &OliveError) -> String {
+    let message = match error {
+        OliveError::Code { data, .. } => data.message(),
+        OliveError::Runtime { data, .. } => data.message(),
+        OliveError::Io { .. } => error.to_string(),
+    };
+    format!("error {}", message)
+}
@@ -0,0 +1,13 @@
+use oliveparser::ast::{Located, Statement};
+
+// a compiler extension for domain specific sugar, e.g. turning calls of a
+// made up function into route tables. rewrite gets the statements of every
+// script the engine compiles right after parsing and may change them freely
+// before code is generated from them. an error stops the compilation and is
+// reported at the start of the located message
+pub trait Plugin {
+    fn rewrite<'a>(
+        &self,
+        statements: &mut Vec<Located<Statement<'a>>>,
+    ) -> Result<(), Located<String>>;
+}
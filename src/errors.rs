@@ -1,5 +1,9 @@
+use super::codegen::TYPE_NAMES;
+use super::value::Value;
 use colored::Colorize;
-use oliveparser::{ParseError, Token};
+use oliveparser::{ParseError, SyntaxError, Token};
+use std::cell::OnceCell;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
 #[derive(Debug)]
@@ -13,6 +17,13 @@ pub enum OliveIoError {
     Deserialize,
     Extension,
     CompileCompiled,
+    // a compiled file of another bytecode format version
+    Version { found: u8, supported: u8 },
+    Opcode { opcode: u8 },
+    // for these three the file is the address the inspector was to listen on
+    Listen,
+    InspectNotAllowed,
+    InspectNotLocal,
 }
 
 #[derive(Debug)]
@@ -22,26 +33,88 @@ pub enum OliveCodeError {
         expected: Vec<String>,
     },
     InvalidToken,
+    // the source ended in the middle of something, e.g. an open block
+    UnexpectedEnd {
+        expected: Vec<String>,
+    },
+    // where the comment started, the error is at the end of the source
+    UnterminatedComment {
+        line: usize,
+        col: usize,
+    },
     ParseInteger {
         value: String,
     },
     ParseFloat {
         value: String,
     },
+    Underscore {
+        value: String,
+    },
     Assign {
         expression_type: String,
     },
     Access,
     BreakOutsideWhile,
+    TooLarge,
+    UnknownType {
+        name: String,
+    },
+    // rejected by a plugin of the engine
+    Plugin {
+        message: String,
+    },
 }
 
 #[derive(Debug)]
 pub enum OliveRuntimeError {
-    IncorrectType { got: String, expected: Vec<String> },
-    UnmatchingTypes { left: String, right: String },
+    IncorrectType {
+        got: String,
+        expected: Vec<String>,
+    },
+    UnmatchingTypes {
+        left: String,
+        right: String,
+    },
     IndexOutOfBounds,
-    CallArgs { expected: usize, got: usize },
-    VariableNotFound { name: String },
+    CallArgs {
+        function: Option<String>,
+        expected: usize,
+        got: usize,
+    },
+    DivisionByZero,
+    // a put on a list or bendy passed to freeze
+    Frozen {
+        type_name: String,
+    },
+    // a value grew past the size limits of its engine
+    SizeLimit {
+        type_name: String,
+        limit: usize,
+    },
+    // functions called each other deeper than the limit of the interpreter
+    CallDepth {
+        limit: usize,
+    },
+    // the code itself is broken, e.g. a hand made .olvc file
+    Internal {
+        ip: usize,
+        message: String,
+    },
+    VariableNotFound {
+        name: String,
+    },
+    Native {
+        message: String,
+    },
+}
+
+// a function call a runtime error passed through on its way out
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub function: String,
+    // the line the call was made on, if the source is known
+    pub line: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -61,9 +134,153 @@ pub enum OliveError {
         line: Option<usize>,
         col: Option<usize>,
         data: OliveRuntimeError,
+        // the calls the error left, innermost first. behind a box to keep
+        // results carrying errors small
+        trace: Box<Vec<TraceFrame>>,
     },
 }
 
+// the tokens the parser expected, as they are written in the grammar
+fn expected_tokens(expected: &[String]) -> String {
+    expected
+        .iter()
+        .map(|s| format!("'{}'", &s[1..s.len() - 1]))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl OliveCodeError {
+    // the error without the file and position it happened at
+    pub fn message(&self) -> String {
+        match self {
+            OliveCodeError::Parse { found, expected } => format!(
+                "got unexpected token '{}', expected one of [{}]",
+                found,
+                expected_tokens(expected)
+            ),
+            OliveCodeError::InvalidToken => String::from("invalid token"),
+            OliveCodeError::UnexpectedEnd { expected } => format!(
+                "got unexpected end of source, expected one of [{}]",
+                expected_tokens(expected)
+            ),
+            OliveCodeError::UnterminatedComment { line, col } => format!(
+                "unterminated comment started at ln {} col {}",
+                line, col
+            ),
+            OliveCodeError::ParseInteger { value } => format!(
+                "couldn't convert literal '{}' to integer (might be too large)",
+                value
+            ),
+            OliveCodeError::ParseFloat { value } => format!(
+                "couldn't convert literal '{}' to float (might be too large)",
+                value
+            ),
+            OliveCodeError::Underscore { value } => format!(
+                "misplaced underscore in literal '{}', underscores have to stand between two digits",
+                value
+            ),
+            OliveCodeError::Access => String::from(
+                "can't use access operator with any right hand expression (must be identifier)",
+            ),
+            OliveCodeError::Assign { expression_type } => {
+                format!("can't use '{}' as left hand of assignment", expression_type)
+            }
+            OliveCodeError::BreakOutsideWhile => {
+                String::from("tried to break or continue outside of a while loop")
+            }
+            OliveCodeError::TooLarge => {
+                String::from("function is too large, a jump in it can't reach its target")
+            }
+            OliveCodeError::UnknownType { name } => format!(
+                "unknown parameter type '{}', expected one of [{}]",
+                name,
+                TYPE_NAMES.join(", ")
+            ),
+            OliveCodeError::Plugin { message } => message.clone(),
+        }
+    }
+}
+
+impl OliveRuntimeError {
+    // a name for the kind of error that scripts can compare against
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OliveRuntimeError::IncorrectType { .. } => "incorrect_type",
+            OliveRuntimeError::UnmatchingTypes { .. } => "unmatching_types",
+            OliveRuntimeError::IndexOutOfBounds => "index_out_of_bounds",
+            OliveRuntimeError::CallArgs { .. } => "call_args",
+            OliveRuntimeError::DivisionByZero => "division_by_zero",
+            OliveRuntimeError::Frozen { .. } => "frozen",
+            OliveRuntimeError::SizeLimit { .. } => "size_limit",
+            OliveRuntimeError::CallDepth { .. } => "call_depth",
+            OliveRuntimeError::Internal { .. } => "internal",
+            OliveRuntimeError::VariableNotFound { .. } => "variable_not_found",
+            OliveRuntimeError::Native { .. } => "native",
+        }
+    }
+
+    // the error without the file and position it happened at
+    pub fn message(&self) -> String {
+        match self {
+            OliveRuntimeError::IncorrectType { expected, got } => {
+                if expected.len() == 1 {
+                    format!("expected type {}, got type {}", &expected[0], got)
+                } else {
+                    format!(
+                        "expected one of types [{}], got type {}",
+                        expected.join(", "),
+                        got
+                    )
+                }
+            }
+            OliveRuntimeError::UnmatchingTypes { left, right } => format!(
+                "operation not supported for type {} and type {}",
+                left, right
+            ),
+            OliveRuntimeError::IndexOutOfBounds => String::from("index not found in object"),
+            OliveRuntimeError::VariableNotFound { name } => {
+                format!("couldn't find variable '{}' in scope", name)
+            }
+            OliveRuntimeError::CallArgs {
+                function,
+                expected,
+                got,
+            } => match function {
+                Some(function) => format!(
+                    "expected {} arguments to call of '{}', got {}",
+                    expected, function, got
+                ),
+                None => format!(
+                    "expected {} arguments to function call, got {}",
+                    expected, got
+                ),
+            },
+            OliveRuntimeError::DivisionByZero => String::from("division by zero"),
+            OliveRuntimeError::Frozen { type_name } => {
+                format!("can't change a frozen {}", type_name)
+            }
+            OliveRuntimeError::SizeLimit { type_name, limit } => {
+                let unit = if type_name == "string" {
+                    "bytes"
+                } else {
+                    "elements"
+                };
+                format!(
+                    "{} would grow past the limit of {} {}",
+                    type_name, limit, unit
+                )
+            }
+            OliveRuntimeError::CallDepth { limit } => {
+                format!("function calls nested deeper than the limit of {}", limit)
+            }
+            OliveRuntimeError::Internal { ip, message } => {
+                format!("internal error at instruction {}: {}", ip, message)
+            }
+            OliveRuntimeError::Native { message } => message.clone(),
+        }
+    }
+}
+
 impl Display for OliveError {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match self {
@@ -73,39 +290,15 @@ impl Display for OliveError {
                 file,
                 data,
             } => {
-                let message = match data {
-                    OliveCodeError::Parse { found, expected } => format!(
-                        "got unexpected token '{}', expected one of [{}]",
-                        found,
-                        expected
-                            .iter()
-                            .map(|s| format!("'{}'", &s[1..s.len() - 1]))
-                            .collect::<Vec<String>>()
-                            .join(", ")
-                    ),
-                    OliveCodeError::InvalidToken => format!("invalid token (probalby unclosed multi-line comment"),
-                    OliveCodeError::ParseInteger { value } => format!(
-                        "couldn't convert literal '{}' to integer (might be too large)",
-                        value
-                    ),
-                    OliveCodeError::ParseFloat { value } => format!(
-                        "couldn't convert literal '{}' to float (might be too large)",
-                        value
-                    ),
-                    OliveCodeError::Access => {
-                        String::from("can't use access operator with any right hand expression (must be identifier)")
-                    }
-                    OliveCodeError::Assign {expression_type} => {
-                        format!("can't use '{}' as left hand of assignment", expression_type)
-                    }
-                    OliveCodeError::BreakOutsideWhile => String::from("tried to break or continue outside of a while loop")
-                };
+                let message = data.message();
                 write!(
                     f,
-                    "{} {} {}",
+                    "{} {} at ln {} col {}: {}",
                     "error".red().bold(),
                     format!("(in '{}'):", file).bold(),
-                    format!("at ln {} col {}: {}", line, col, message)
+                    line,
+                    col,
+                    message
                 )
             }
             OliveError::Runtime {
@@ -113,34 +306,9 @@ impl Display for OliveError {
                 col,
                 file,
                 data,
+                ..
             } => {
-                let message = match data {
-                    OliveRuntimeError::IncorrectType { expected, got } => {
-                        if expected.len() == 1 {
-                            format!("expected type {}, got type {}", &expected[0], got)
-                        } else {
-                            format!(
-                                "expected one of types [{}], got type {}",
-                                expected.join(", "),
-                                got
-                            )
-                        }
-                    }
-                    OliveRuntimeError::UnmatchingTypes { left, right } => format!(
-                        "operation not supported for type {} and type {}",
-                        left, right
-                    ),
-                    OliveRuntimeError::IndexOutOfBounds => {
-                        String::from("index not found in object")
-                    }
-                    OliveRuntimeError::VariableNotFound { name } => {
-                        format!("couldn't find variable '{}' in scope", name)
-                    }
-                    OliveRuntimeError::CallArgs { expected, got } => format!(
-                        "expected {} arguments to function call, got {}",
-                        expected, got
-                    ),
-                };
+                let message = data.message();
                 write!(
                     f,
                     "{} {} {}",
@@ -154,6 +322,7 @@ impl Display for OliveError {
                 )
             }
             OliveError::Io { kind, file } => {
+                let detail;
                 let message: &str = match kind {
                     OliveIoError::OpenRead => {
                         "failed to open file for reading (file might not exist)"
@@ -166,6 +335,31 @@ impl Display for OliveError {
                     OliveIoError::Deserialize => "failed to deserialize file",
                     OliveIoError::Extension => "unrecognized file extension",
                     OliveIoError::CompileCompiled => "tried to compile binary file (.olvc)",
+                    OliveIoError::Listen => "failed to listen for inspector connections",
+                    OliveIoError::InspectNotAllowed => {
+                        "the engine policy doesn't allow eval, which the inspector needs"
+                    }
+                    OliveIoError::InspectNotLocal => {
+                        "the engine policy only lets the inspector listen on a loopback address"
+                    }
+                    OliveIoError::Version { found, supported } => {
+                        detail = if found > supported {
+                            format!(
+                                "compiled for bytecode version {}, but this interpreter only reads version {}",
+                                found, supported
+                            )
+                        } else {
+                            format!(
+                                "compiled for the old bytecode version {}, recompile it for version {}",
+                                found, supported
+                            )
+                        };
+                        &detail
+                    }
+                    OliveIoError::Opcode { opcode } => {
+                        detail = format!("unknown opcode 0x{:02x} in compiled file", opcode);
+                        &detail
+                    }
                 };
                 write!(
                     f,
@@ -179,29 +373,57 @@ impl Display for OliveError {
     }
 }
 
-impl OliveError {
-    //TODO
-    fn get_line_and_column(start: usize, source: &str) -> (usize, usize) {
-        let line_starts: Vec<usize> = std::iter::once(0)
-            .chain(
-                source
-                    .char_indices()
-                    .take(start)
-                    .filter(|(_, ch)| *ch == '\n')
-                    .map(|t| t.0),
-            )
-            .collect();
-        let line_start = line_starts.last().unwrap();
-        (line_starts.len(), 1 + start - line_start)
+// the text of a script together with where its lines start. the lines are
+// only looked for when the first error is reported and then reused for every
+// other error from the same source
+pub struct Source<'a> {
+    pub text: &'a str,
+    line_starts: OnceCell<Vec<usize>>,
+}
+
+impl<'a> Source<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Source {
+            text,
+            line_starts: OnceCell::new(),
+        }
     }
 
+    // a source whose text is gone, only the line starts are left to report
+    // positions with
+    #[cfg(feature = "fs")]
+    pub(crate) fn from_line_starts(line_starts: Vec<usize>) -> Source<'static> {
+        Source {
+            text: "",
+            line_starts: OnceCell::from(line_starts),
+        }
+    }
+
+    // the index of the first char of every line, a line starts after a '\n'
+    pub(crate) fn line_starts(&self) -> &[usize] {
+        self.line_starts.get_or_init(|| {
+            std::iter::once(0)
+                .chain(self.text.match_indices('\n').map(|(i, _)| i + 1))
+                .collect()
+        })
+    }
+
+    // lines and columns count from 1, the '\n' ending a line belongs to it
+    pub(crate) fn get_line_and_column(&self, start: usize) -> (usize, usize) {
+        let line_starts = self.line_starts();
+        let line = line_starts.partition_point(|&line_start| line_start <= start);
+        (line, 1 + start - line_starts[line - 1])
+    }
+}
+
+impl OliveError {
     pub fn new_code_error(
         start: usize,
         filename: &str,
-        source: &str,
+        source: &Source,
         data: OliveCodeError,
     ) -> Self {
-        let (line, col) = OliveError::get_line_and_column(start, source);
+        let (line, col) = source.get_line_and_column(start);
         OliveError::Code {
             line,
             col,
@@ -213,16 +435,17 @@ impl OliveError {
     pub fn new_runtime_error(
         start: Option<usize>,
         filename: &str,
-        source: &str,
+        source: Option<&Source>,
         data: OliveRuntimeError,
     ) -> Self {
-        if let Some(start) = start {
-            let (line, col) = OliveError::get_line_and_column(start, source);
+        if let (Some(start), Some(source)) = (start, source) {
+            let (line, col) = source.get_line_and_column(start);
             OliveError::Runtime {
                 line: Some(line),
                 col: Some(col),
                 file: String::from(filename),
                 data,
+                trace: Box::default(),
             }
         } else {
             OliveError::Runtime {
@@ -230,14 +453,68 @@ impl OliveError {
                 col: None,
                 file: String::from(filename),
                 data,
+                trace: Box::default(),
             }
         }
     }
 
+    // adds a call to the trace of a runtime error leaving that call
+    pub(crate) fn push_trace(&mut self, function: &str, line: Option<usize>) {
+        if let OliveError::Runtime { trace, .. } = self {
+            trace.push(TraceFrame {
+                function: String::from(function),
+                line,
+            });
+        }
+    }
+
+    // a runtime error as a bendy with the keys type, message, file, line and
+    // trace, a list of bendies with the keys function and line. this is the
+    // value scripts will get to inspect, other errors can't happen at run time
+    pub fn to_value(&self) -> Option<Value> {
+        let line = |line: &Option<usize>| match line {
+            Some(line) => Value::Integer(*line as i64),
+            None => Value::None,
+        };
+        match self {
+            OliveError::Runtime {
+                file,
+                line: error_line,
+                data,
+                trace,
+                ..
+            } => {
+                let trace = trace
+                    .iter()
+                    .map(|frame| {
+                        let mut data = HashMap::new();
+                        data.insert(
+                            String::from("function"),
+                            Value::String(frame.function.clone()),
+                        );
+                        data.insert(String::from("line"), line(&frame.line));
+                        Value::Bendy(data)
+                    })
+                    .collect();
+                let mut value = HashMap::new();
+                value.insert(
+                    String::from("type"),
+                    Value::String(String::from(data.kind())),
+                );
+                value.insert(String::from("message"), Value::String(data.message()));
+                value.insert(String::from("file"), Value::String(file.clone()));
+                value.insert(String::from("line"), line(error_line));
+                value.insert(String::from("trace"), Value::List(trace));
+                Some(Value::Bendy(value))
+            }
+            _ => None,
+        }
+    }
+
     pub fn from_parse_err(
-        err: ParseError<usize, Token<'_>, &str>,
+        err: ParseError<usize, Token<'_>, SyntaxError>,
         file: &str,
-        source: &str,
+        source: &Source,
     ) -> Self {
         match err {
             ParseError::UnrecognizedToken { token, expected } => OliveError::new_code_error(
@@ -252,6 +529,23 @@ impl OliveError {
             ParseError::InvalidToken { location } => {
                 OliveError::new_code_error(location, file, source, OliveCodeError::InvalidToken)
             }
+            ParseError::UnrecognizedEOF { location, expected } => OliveError::new_code_error(
+                location,
+                file,
+                source,
+                OliveCodeError::UnexpectedEnd { expected },
+            ),
+            ParseError::User {
+                error: SyntaxError::UnterminatedComment { start },
+            } => {
+                let (line, col) = source.get_line_and_column(start);
+                OliveError::new_code_error(
+                    source.text.len(),
+                    file,
+                    source,
+                    OliveCodeError::UnterminatedComment { line, col },
+                )
+            }
             _ => unimplemented!("{:?}", err),
         }
     }
@@ -0,0 +1,469 @@
+use super::errors::{OliveError, Source};
+use mistake::Mistake::{self, Fail, Fine};
+use oliveparser::ast::{Expression, Key, Located, Statement};
+use oliveparser::printer::print_expression;
+use oliveparser::tokens::{tokenize, Category};
+use oliveparser::visit::{walk_block, walk_expression, walk_statement, Visitor};
+use oliveparser::{parse, ParseError, SyntaxError, Token};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::mem::discriminant;
+
+// metavariables are parsed as variables with this prefix, a name no script
+// would use
+const META_PREFIX: &str = "__olv_meta_";
+const WRAP_PREFIX: &str = "__olv_pattern = ";
+
+// a piece of code to search scripts for by the shape of its ast rather than
+// its text. $ followed by an uppercase name, as in print($X $ $Y), matches
+// any expression, and every use of the same name has to match the same code.
+// $_ matches any expression without binding. a pattern is an expression if it
+// parses as one, otherwise a sequence of statements, which matches that run of
+// statements in a block
+#[derive(Debug)]
+pub struct AstPattern {
+    text: String,
+    statements: bool,
+}
+
+#[derive(Debug)]
+pub struct AstMatch {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+    // the first line of the matching code
+    pub text: String,
+}
+
+impl Display for AstMatch {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.col, self.text)
+    }
+}
+
+enum Pattern<'p> {
+    Expression(Located<Expression<'p>>),
+    Statements(Vec<Located<Statement<'p>>>),
+}
+
+impl AstPattern {
+    // fails if the pattern doesn't parse, with the error pointing into the
+    // pattern as it was written
+    pub fn new(pattern: &str) -> Mistake<AstPattern, OliveError> {
+        let (translated, shifts) = translate(pattern);
+        let wrapped = format!("{}{};", WRAP_PREFIX, translated);
+        let expression_err = match parse(&wrapped) {
+            Ok(ast) if ast.len() == 1 => {
+                return Fine(
+                    AstPattern {
+                        text: wrapped,
+                        statements: false,
+                    },
+                    Vec::new(),
+                )
+            }
+            Ok(_) => None,
+            Err(err) => Some(err.map_location(|location| {
+                original_location(location.saturating_sub(WRAP_PREFIX.len()), &shifts)
+            })),
+        };
+        let statement_err = match parse(&translated) {
+            Ok(_) => {
+                return Fine(
+                    AstPattern {
+                        text: translated,
+                        statements: true,
+                    },
+                    Vec::new(),
+                )
+            }
+            Err(err) => err.map_location(|location| original_location(location, &shifts)),
+        };
+        // a pattern ending like a statement was probably meant as one
+        let err = match expression_err {
+            Some(err) if !pattern.trim_end().ends_with(';') => err,
+            _ => statement_err,
+        };
+        Fail(vec![from_pattern_err(err, pattern)])
+    }
+
+    // finds the code in the text that matches, in source order. a match
+    // containing others is listed before them. fails only if the text doesn't
+    // parse
+    pub fn search(&self, text: &str, filename: &str) -> Mistake<Vec<AstMatch>, OliveError> {
+        let mut errors = Vec::new();
+        let source = Source::new(text);
+        let ast = attempt_res!(
+            parse(text).map_err(|err| OliveError::from_parse_err(err, filename, &source)),
+            errors
+        );
+        // new made sure the pattern parses
+        let mut pattern_ast = parse(&self.text).unwrap();
+        let pattern = if self.statements {
+            Pattern::Statements(pattern_ast)
+        } else {
+            match pattern_ast.pop().unwrap().inner {
+                Statement::Assign { right, .. } => Pattern::Expression(*right),
+                _ => unreachable!(),
+            }
+        };
+        let mut search = Search {
+            pattern: &pattern,
+            found: Vec::new(),
+        };
+        search.visit_block(&ast);
+        let matches = search
+            .found
+            .into_iter()
+            .map(|(start, end)| {
+                let (line, col) = source.get_line_and_column(start);
+                AstMatch {
+                    file: String::from(filename),
+                    line,
+                    col,
+                    text: String::from(text[start..end].lines().next().unwrap_or("").trim_end()),
+                }
+            })
+            .collect();
+        Fine(matches, errors)
+    }
+}
+
+// replaces the metavariables with variables the parser accepts. the shifts
+// are where the translated text lines up with the pattern again, as pairs of
+// positions in the translation and in the pattern
+fn translate(pattern: &str) -> (String, Vec<(usize, usize)>) {
+    let mut translated = String::new();
+    let mut shifts = vec![(0, 0)];
+    let mut copied = 0;
+    let tokens = tokenize(pattern);
+    for pair in tokens.windows(2) {
+        let (dollar, name) = (&pair[0], &pair[1]);
+        let is_meta = dollar.text == "$"
+            && name.category == Category::Identifier
+            && name.start == dollar.end
+            && name
+                .text
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            && !name.text.starts_with(|c: char| c.is_ascii_digit());
+        if is_meta {
+            translated.push_str(&pattern[copied..dollar.start]);
+            translated.push_str(META_PREFIX);
+            translated.push_str(name.text);
+            copied = name.end;
+            shifts.push((translated.len(), copied));
+        }
+    }
+    translated.push_str(&pattern[copied..]);
+    (translated, shifts)
+}
+
+fn original_location(location: usize, shifts: &[(usize, usize)]) -> usize {
+    let (translated, original) = shifts
+        .iter()
+        .rev()
+        .find(|(translated, _)| *translated <= location)
+        .unwrap();
+    original + (location - translated)
+}
+
+fn from_pattern_err(err: ParseError<usize, Token<'_>, SyntaxError>, pattern: &str) -> OliveError {
+    // positions past the end come from the semicolon that was added
+    let source = Source::new(pattern);
+    OliveError::from_parse_err(
+        err.map_location(|location| location.min(pattern.len())),
+        "<pattern>",
+        &source,
+    )
+}
+
+struct Search<'s, 'p> {
+    pattern: &'s Pattern<'p>,
+    found: Vec<(usize, usize)>,
+}
+
+impl<'a> Visitor<'a> for Search<'_, '_> {
+    fn visit_block(&mut self, statements: &[Located<Statement<'a>>]) {
+        if let Pattern::Statements(pattern) = self.pattern {
+            if !pattern.is_empty() {
+                for window in statements.windows(pattern.len()) {
+                    let mut matcher = Matcher::default();
+                    if matcher.block(pattern, window) {
+                        let last = &window[window.len() - 1];
+                        self.found.push((window[0].start, last.end));
+                    }
+                }
+            }
+        }
+        walk_block(self, statements);
+    }
+
+    fn visit_statement(&mut self, statement: &Located<Statement<'a>>) {
+        // a call on its own is a statement, but matches call patterns too
+        if let (
+            Pattern::Expression(Located {
+                inner:
+                    Expression::Call {
+                        expression: pattern_expression,
+                        args: pattern_args,
+                    },
+                ..
+            }),
+            Statement::Call { expression, args },
+        ) = (self.pattern, &statement.inner)
+        {
+            let mut matcher = Matcher::default();
+            if matcher.expression(&pattern_expression.inner, &expression.inner)
+                && matcher.expressions(pattern_args, args)
+            {
+                self.found.push((statement.start, statement.end));
+            }
+        }
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Located<Expression<'a>>) {
+        if let Pattern::Expression(pattern) = self.pattern {
+            let mut matcher = Matcher::default();
+            if matcher.expression(&pattern.inner, &expression.inner) {
+                self.found.push((expression.start, expression.end));
+            }
+        }
+        walk_expression(self, expression);
+    }
+}
+
+// compares a pattern to code, ignoring where either is. the bindings hold the
+// printed code each metavariable matched so far
+#[derive(Default)]
+struct Matcher {
+    bindings: HashMap<String, String>,
+}
+
+impl Matcher {
+    fn block(&mut self, pattern: &[Located<Statement>], code: &[Located<Statement>]) -> bool {
+        pattern.len() == code.len()
+            && pattern
+                .iter()
+                .zip(code)
+                .all(|(pattern, code)| self.statement(&pattern.inner, &code.inner))
+    }
+
+    fn expressions(
+        &mut self,
+        pattern: &[Located<Expression>],
+        code: &[Located<Expression>],
+    ) -> bool {
+        pattern.len() == code.len()
+            && pattern
+                .iter()
+                .zip(code)
+                .all(|(pattern, code)| self.expression(&pattern.inner, &code.inner))
+    }
+
+    fn statement(&mut self, pattern: &Statement, code: &Statement) -> bool {
+        match (pattern, code) {
+            (Statement::Break, Statement::Break) | (Statement::Continue, Statement::Continue) => {
+                true
+            }
+            (Statement::Return { value: pattern }, Statement::Return { value: code }) => {
+                self.expression(&pattern.inner, &code.inner)
+            }
+            (
+                Statement::Block {
+                    statements: pattern,
+                },
+                Statement::Block { statements: code },
+            ) => self.block(pattern, code),
+            (
+                Statement::While {
+                    condition: pattern_condition,
+                    block: pattern_block,
+                },
+                Statement::While { condition, block },
+            ) => {
+                self.expression(&pattern_condition.inner, &condition.inner)
+                    && self.block(pattern_block, block)
+            }
+            (
+                Statement::If {
+                    condition: pattern_condition,
+                    block: pattern_block,
+                    elseblock: pattern_elseblock,
+                },
+                Statement::If {
+                    condition,
+                    block,
+                    elseblock,
+                },
+            ) => {
+                self.expression(&pattern_condition.inner, &condition.inner)
+                    && self.block(pattern_block, block)
+                    && match (pattern_elseblock, elseblock) {
+                        (Some(pattern), Some(code)) => self.block(pattern, code),
+                        (None, None) => true,
+                        _ => false,
+                    }
+            }
+            (
+                Statement::Assign {
+                    left: pattern_left,
+                    right: pattern_right,
+                },
+                Statement::Assign { left, right },
+            ) => {
+                self.expression(&pattern_left.inner, &left.inner)
+                    && self.expression(&pattern_right.inner, &right.inner)
+            }
+            (
+                Statement::Call {
+                    expression: pattern_expression,
+                    args: pattern_args,
+                },
+                Statement::Call { expression, args },
+            ) => {
+                self.expression(&pattern_expression.inner, &expression.inner)
+                    && self.expressions(pattern_args, args)
+            }
+            (Statement::Defer { statement: pattern }, Statement::Defer { statement: code }) => {
+                self.statement(&pattern.inner, &code.inner)
+            }
+            (Statement::Pipe { pipe: pattern }, Statement::Pipe { pipe: code }) => {
+                self.expression(&pattern.inner, &code.inner)
+            }
+            _ => false,
+        }
+    }
+
+    fn expression(&mut self, pattern: &Expression, code: &Expression) -> bool {
+        match (pattern, code) {
+            (Expression::Variable { name }, _) if name.starts_with(META_PREFIX) => {
+                let name = &name[META_PREFIX.len()..];
+                if name == "_" {
+                    return true;
+                }
+                let printed = print_expression(code);
+                match self.bindings.get(name) {
+                    Some(bound) => *bound == printed,
+                    None => {
+                        self.bindings.insert(String::from(name), printed);
+                        true
+                    }
+                }
+            }
+            (Expression::List { elements: pattern }, Expression::List { elements: code }) => {
+                self.expressions(pattern, code)
+            }
+            (Expression::Bendy { elements: pattern }, Expression::Bendy { elements: code }) => {
+                pattern.len() == code.len()
+                    && pattern.iter().zip(code).all(|(pattern, code)| {
+                        let same_key = match (&pattern.0, &code.0) {
+                            (Key::Name(pattern), Key::Name(code)) => pattern.inner == code.inner,
+                            (Key::Computed(pattern), Key::Computed(code)) => {
+                                self.expression(&pattern.inner, &code.inner)
+                            }
+                            _ => false,
+                        };
+                        same_key && self.expression(&pattern.1.inner, &code.1.inner)
+                    })
+            }
+            (Expression::Integer { value: pattern }, Expression::Integer { value: code })
+            | (Expression::Float { value: pattern }, Expression::Float { value: code })
+            | (Expression::Variable { name: pattern }, Expression::Variable { name: code }) => {
+                pattern == code
+            }
+            (Expression::String { value: pattern }, Expression::String { value: code }) => {
+                pattern == code
+            }
+            (Expression::Boolean { value: pattern }, Expression::Boolean { value: code }) => {
+                pattern == code
+            }
+            (Expression::None, Expression::None) => true,
+            (
+                Expression::Binary {
+                    left: pattern_left,
+                    right: pattern_right,
+                    operator: pattern_operator,
+                },
+                Expression::Binary {
+                    left,
+                    right,
+                    operator,
+                },
+            ) => {
+                discriminant(pattern_operator) == discriminant(operator)
+                    && self.expression(&pattern_left.inner, &left.inner)
+                    && self.expression(&pattern_right.inner, &right.inner)
+            }
+            (
+                Expression::Unary {
+                    expression: pattern_expression,
+                    operator: pattern_operator,
+                },
+                Expression::Unary {
+                    expression,
+                    operator,
+                },
+            ) => {
+                discriminant(pattern_operator) == discriminant(operator)
+                    && self.expression(&pattern_expression.inner, &expression.inner)
+            }
+            (
+                Expression::Index {
+                    expression: pattern_expression,
+                    index: pattern_index,
+                    optional: pattern_optional,
+                },
+                Expression::Index {
+                    expression,
+                    index,
+                    optional,
+                },
+            ) => {
+                pattern_optional == optional
+                    && self.expression(&pattern_expression.inner, &expression.inner)
+                    && self.expression(&pattern_index.inner, &index.inner)
+            }
+            (
+                Expression::Call {
+                    expression: pattern_expression,
+                    args: pattern_args,
+                },
+                Expression::Call { expression, args },
+            ) => {
+                self.expression(&pattern_expression.inner, &expression.inner)
+                    && self.expressions(pattern_args, args)
+            }
+            (
+                Expression::Function {
+                    parameters: pattern_parameters,
+                    block: pattern_block,
+                },
+                Expression::Function { parameters, block },
+            ) => {
+                pattern_parameters.len() == parameters.len()
+                    && pattern_parameters
+                        .iter()
+                        .zip(parameters)
+                        .all(|(pattern, code)| {
+                            pattern.name.inner == code.name.inner
+                                && pattern.type_name.as_ref().map(|name| name.inner)
+                                    == code.type_name.as_ref().map(|name| name.inner)
+                        })
+                    && self.block(pattern_block, block)
+            }
+            (
+                Expression::Pipe {
+                    value: pattern_value,
+                    function: pattern_function,
+                },
+                Expression::Pipe { value, function },
+            ) => {
+                self.expression(&pattern_value.inner, &value.inner)
+                    && self.expression(&pattern_function.inner, &function.inner)
+            }
+            _ => false,
+        }
+    }
+}
@@ -1,38 +1,323 @@
-use super::object::{Object, RefObject};
+use super::log::{Log, LogLevel};
+use super::object::{format_float, Object, RefObject};
+use super::Output;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-fn native_print(args: Vec<Object>) -> Object {
-    println!(
-        "{}",
-        args.iter()
-            .map(|o| o.to_string())
-            .collect::<Vec<String>>()
-            .join(", ")
-    );
-    Object::new_none()
+const PPRINT_INDENT: usize = 4;
+
+fn native_print(output: &Output, args: &[Object]) -> Result<Object, String> {
+    let line = args
+        .iter()
+        .map(|o| o.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+    (output.borrow_mut())(&line);
+    Ok(Object::new_none())
+}
+
+fn native_log(log: &Log, level: LogLevel, args: &[Object]) -> Result<Object, String> {
+    log.log(level, &args[0].to_string());
+    Ok(Object::new_none())
 }
 
-fn native_len(args: Vec<Object>) -> Object {
-    Object::new_integer(match &args[0] {
+fn native_len(args: &[Object]) -> Result<Object, String> {
+    Ok(Object::new_integer(match &args[0] {
         Object::Pointer { value: v } => match &**v {
-            RefObject::Bendy { data } => data.len() as i64,
-            RefObject::List { data } => data.len() as i64,
+            RefObject::Bendy { data, .. } => data.len() as i64,
+            RefObject::List { data, .. } => data.len() as i64,
             RefObject::String { value } => value.len() as i64,
-            _ => return Object::None,
+            _ => return Ok(Object::None),
+        },
+        _ => return Ok(Object::None),
+    }))
+}
+
+// the keys of a bendy in sorted order
+fn native_keys(args: &[Object]) -> Result<Object, String> {
+    Ok(match &args[0] {
+        Object::Pointer { value: v } => match &**v {
+            RefObject::Bendy { data, .. } => {
+                let mut keys: Vec<&String> = data.keys().collect();
+                keys.sort();
+                let keys = keys.into_iter().cloned().map(Object::new_string);
+                Object::new_filled_list(keys.collect())
+            }
+            _ => Object::None,
+        },
+        _ => Object::None,
+    })
+}
+
+fn native_arity(args: &[Object]) -> Result<Object, String> {
+    Ok(Object::new_integer(match &args[0] {
+        Object::Pointer { value: v } => match &**v {
+            RefObject::Function { args, .. } => args.len() as i64,
+            RefObject::Native { arg_count, .. } => *arg_count as i64,
+            _ => return Ok(Object::None),
+        },
+        _ => return Ok(Object::None),
+    }))
+}
+
+// natives have no name of their own, only the one they are stored under
+fn native_fn_name(args: &[Object]) -> Result<Object, String> {
+    Ok(match &args[0] {
+        Object::Pointer { value: v } => match &**v {
+            RefObject::Function { info, .. } => match &info.name {
+                Some(name) => Object::new_string(name.clone()),
+                None => Object::None,
+            },
+            _ => Object::None,
+        },
+        _ => Object::None,
+    })
+}
+
+fn native_repr(args: &[Object]) -> Result<Object, String> {
+    Ok(Object::new_string(args[0].repr()))
+}
+
+// the value itself, so that it can be frozen where it is created
+fn native_freeze(args: &[Object]) -> Result<Object, String> {
+    args[0].freeze();
+    Ok(args[0].clone())
+}
+
+// the most digits format and format_number put after the point, enough for
+// every digit of the smallest float
+const MAX_DIGITS: i64 = 1074;
+
+// a number with a fixed count of digits after the point
+fn native_format(args: &[Object]) -> Result<Object, String> {
+    let value = match &args[0] {
+        Object::Integer { value } => *value as f64,
+        Object::Float { value } => *value,
+        _ => return Ok(Object::None),
+    };
+    match &args[1] {
+        Object::Integer { value: digits } if *digits > MAX_DIGITS => {
+            Err(format!("format expects at most {} digits", MAX_DIGITS))
+        }
+        Object::Integer { value: digits } if *digits >= 0 => Ok(Object::new_string(format!(
+            "{:.*}",
+            *digits as usize, value
+        ))),
+        _ => Ok(Object::None),
+    }
+}
+
+// format_number(x, options) with options a bendy of thousands, the separator
+// put between groups of three digits (none by default), point, the decimal
+// separator ("." by default), and decimals, a fixed count of digits after it.
+// none for anything but a number and options of those types
+fn native_format_number(args: &[Object]) -> Result<Object, String> {
+    let options = match &args[1] {
+        Object::Pointer { value } => match &**value {
+            RefObject::Bendy { data, .. } => data,
+            _ => return Ok(Object::None),
+        },
+        _ => return Ok(Object::None),
+    };
+    let text_option = |name: &str, default: &'static str| match options.get(name) {
+        None => Some(String::from(default)),
+        Some(Object::Pointer { value }) => match &**value {
+            RefObject::String { value } => Some(value.clone()),
+            _ => None,
+        },
+        Some(_) => None,
+    };
+    let (thousands, point) = match (text_option("thousands", ""), text_option("point", ".")) {
+        (Some(thousands), Some(point)) => (thousands, point),
+        _ => return Ok(Object::None),
+    };
+    let decimals = match options.get("decimals") {
+        None => None,
+        Some(Object::Integer { value }) if *value > MAX_DIGITS => {
+            return Err(format!(
+                "format_number expects decimals to be at most {}",
+                MAX_DIGITS
+            ))
+        }
+        Some(Object::Integer { value }) if *value >= 0 => Some(*value as usize),
+        Some(_) => return Ok(Object::None),
+    };
+    let text = match (&args[0], decimals) {
+        (Object::Integer { value }, None) | (Object::Integer { value }, Some(0)) => {
+            value.to_string()
+        }
+        // integers are exact, converting them to floats could round them
+        (Object::Integer { value }, Some(decimals)) => {
+            format!("{}.{}", value, "0".repeat(decimals))
+        }
+        // nan and inf are left as they print
+        (Object::Float { value }, _) if !value.is_finite() => {
+            return Ok(Object::new_string(format_float(*value)))
+        }
+        // all the digits instead of an exponent, so large and small values are
+        // grouped as well
+        (Object::Float { value }, None) => {
+            let text = value.to_string();
+            if text.contains('.') {
+                text
+            } else {
+                text + ".0"
+            }
+        }
+        (Object::Float { value }, Some(decimals)) => format!("{:.*}", decimals, value),
+        _ => return Ok(Object::None),
+    };
+    let (sign, digits) = match text.strip_prefix('-') {
+        // -0.0, or a negative value rounded to zero, is shown as plain zero
+        Some(digits) if digits.chars().all(|c| c == '0' || c == '.') => ("", digits),
+        Some(digits) => ("-", digits),
+        None => ("", text.as_str()),
+    };
+    let (integer, fraction) = match digits.find('.') {
+        Some(end) => (&digits[..end], Some(&digits[end + 1..])),
+        None => (digits, None),
+    };
+    let mut result = String::from(sign);
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            result.push_str(&thousands);
+        }
+        result.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        result.push_str(&point);
+        result.push_str(fraction);
+    }
+    Ok(Object::new_string(result))
+}
+
+// upper and lower case of a string by the unicode rules, so e.g. "straße"
+// turns into "STRASSE"
+fn native_upper(args: &[Object]) -> Result<Object, String> {
+    Ok(match &args[0] {
+        Object::Pointer { value } => match &**value {
+            RefObject::String { value } => Object::new_string(value.to_uppercase()),
+            _ => Object::None,
         },
-        _ => return Object::None,
+        _ => Object::None,
     })
 }
 
-pub fn get_functions() -> HashMap<String, Object> {
+fn native_lower(args: &[Object]) -> Result<Object, String> {
+    Ok(match &args[0] {
+        Object::Pointer { value } => match &**value {
+            RefObject::String { value } => Object::new_string(value.to_lowercase()),
+            _ => Object::None,
+        },
+        _ => Object::None,
+    })
+}
+
+// prints one line after the other, so a captured output gets them all
+fn native_pprint(output: &Output, args: &[Object]) -> Result<Object, String> {
+    for line in args[0].to_pretty_string(PPRINT_INDENT).lines() {
+        (output.borrow_mut())(line);
+    }
+    Ok(Object::new_none())
+}
+
+fn native_to_pretty_string(args: &[Object]) -> Result<Object, String> {
+    match &args[1] {
+        Object::Integer { value: indent } if *indent >= 0 => Ok(Object::new_string(
+            args[0].to_pretty_string(*indent as usize),
+        )),
+        _ => Ok(Object::None),
+    }
+}
+
+// the names global_scope defines, par_map, on_signal and heapdump only exist
+// with the parallel, signals and fs features
+pub const BUILTIN_NAMES: &[&str] = &[
+    "print",
+    "len",
+    "keys",
+    "arity",
+    "fn_name",
+    "repr",
+    "format",
+    "format_number",
+    "upper",
+    "lower",
+    "pprint",
+    "to_pretty_string",
+    "par_map",
+    "on_signal",
+    "log_debug",
+    "log_info",
+    "log_warn",
+    "log_error",
+    "eval",
+    "compile",
+    "freeze",
+    "heapdump",
+];
+
+pub fn get_functions(output: Output, log: Log) -> HashMap<String, Object> {
     let mut functions = HashMap::new();
+    for level in LogLevel::ALL.iter().copied() {
+        let log = log.clone();
+        functions.insert(
+            format!("log_{}", level.name()),
+            Object::new_native(1, Rc::new(move |args| native_log(&log, level, args))),
+        );
+    }
+    let pprint_output = output.clone();
     functions.insert(
         String::from("print"),
-        Object::new_native(1, native_print as fn(Vec<Object>) -> Object),
+        Object::new_native(1, Rc::new(move |args| native_print(&output, args))),
+    );
+    functions.insert(
+        String::from("pprint"),
+        Object::new_native(1, Rc::new(move |args| native_pprint(&pprint_output, args))),
     );
     functions.insert(
         String::from("len"),
-        Object::new_native(1, native_len as fn(Vec<Object>) -> Object),
+        Object::new_native(1, Rc::new(native_len)),
+    );
+    functions.insert(
+        String::from("keys"),
+        Object::new_native(1, Rc::new(native_keys)),
+    );
+    functions.insert(
+        String::from("arity"),
+        Object::new_native(1, Rc::new(native_arity)),
+    );
+    functions.insert(
+        String::from("fn_name"),
+        Object::new_native(1, Rc::new(native_fn_name)),
+    );
+    functions.insert(
+        String::from("repr"),
+        Object::new_native(1, Rc::new(native_repr)),
+    );
+    functions.insert(
+        String::from("freeze"),
+        Object::new_native(1, Rc::new(native_freeze)),
+    );
+    functions.insert(
+        String::from("format"),
+        Object::new_native(2, Rc::new(native_format)),
+    );
+    functions.insert(
+        String::from("format_number"),
+        Object::new_native(2, Rc::new(native_format_number)),
+    );
+    functions.insert(
+        String::from("upper"),
+        Object::new_native(1, Rc::new(native_upper)),
+    );
+    functions.insert(
+        String::from("lower"),
+        Object::new_native(1, Rc::new(native_lower)),
+    );
+    functions.insert(
+        String::from("to_pretty_string"),
+        Object::new_native(2, Rc::new(native_to_pretty_string)),
     );
     functions
 }
@@ -1,30 +1,21 @@
-use super::super::errors::{OliveError, OliveRuntimeError};
-use std::collections::HashMap;
+use super::super::codegen::CodePosTable;
+use super::super::errors::{OliveError, OliveRuntimeError, Source};
 
 pub fn create_runtime_error(
     position: usize,
-    code_pos_table: &HashMap<usize, usize>,
+    code_pos_table: &CodePosTable,
     filename: &str,
-    source: Option<&str>,
+    source: Option<&Source>,
     data: OliveRuntimeError,
 ) -> OliveError {
-    if let Some(source) = source {
-        OliveError::new_runtime_error(
-            Some(*code_pos_table.get(&position).unwrap()),
-            filename,
-            source,
-            data,
-        )
-    } else {
-        OliveError::new_runtime_error(None, filename, "", data)
-    }
+    OliveError::new_runtime_error(code_pos_table.get(position), filename, source, data)
 }
 
 pub fn create_type_error(
     position: usize,
-    code_pos_table: &HashMap<usize, usize>,
+    code_pos_table: &CodePosTable,
     filename: &str,
-    source: Option<&str>,
+    source: Option<&Source>,
     expected: Vec<&str>,
     got: &str,
 ) -> OliveError {
@@ -34,7 +25,7 @@ pub fn create_type_error(
         filename,
         source,
         OliveRuntimeError::IncorrectType {
-            expected: expected.into_iter().map(|s| String::from(s)).collect(),
+            expected: expected.into_iter().map(String::from).collect(),
             got: String::from(got),
         },
     )
@@ -42,9 +33,9 @@ pub fn create_type_error(
 
 pub fn create_variable_error(
     position: usize,
-    code_pos_table: &HashMap<usize, usize>,
+    code_pos_table: &CodePosTable,
     filename: &str,
-    source: Option<&str>,
+    source: Option<&Source>,
     name: &str,
 ) -> OliveError {
     create_runtime_error(
@@ -60,9 +51,9 @@ pub fn create_variable_error(
 
 pub fn create_binop_type_error(
     position: usize,
-    code_pos_table: &HashMap<usize, usize>,
+    code_pos_table: &CodePosTable,
     filename: &str,
-    source: Option<&str>,
+    source: Option<&Source>,
     left: &str,
     right: &str,
 ) -> OliveError {
@@ -78,22 +69,21 @@ pub fn create_binop_type_error(
     )
 }
 
-pub fn create_call_error(
+pub fn create_internal_error(
     position: usize,
-    code_pos_table: &HashMap<usize, usize>,
+    code_pos_table: &CodePosTable,
     filename: &str,
-    source: Option<&str>,
-    got: usize,
-    expected: usize,
+    source: Option<&Source>,
+    message: &str,
 ) -> OliveError {
     create_runtime_error(
         position,
         code_pos_table,
         filename,
         source,
-        OliveRuntimeError::CallArgs {
-            expected: expected,
-            got: got,
+        OliveRuntimeError::Internal {
+            ip: position,
+            message: String::from(message),
         },
     )
 }
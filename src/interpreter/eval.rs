@@ -0,0 +1,113 @@
+use super::super::codegen::{self, Code, CodePosTable, FunctionInfo};
+use super::super::errors::{OliveError, Source};
+use super::object::{NativeFn, Object, RefObject};
+use super::{execute, HookList, Scope};
+use mistake::Mistake::{Fail, Fine};
+use oliveparser::parse;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+const FILENAME: &str = "<eval>";
+
+// eval(code) runs a string of source in the global scope, so variables it
+// assigns stay defined, and returns the value of its top level return or none.
+// the code calls the hooks of the engine, its errors are reported to them as
+// the error of the call of eval
+pub fn eval_native(globals: Weak<RefCell<Scope>>, hooks: HookList) -> Object {
+    let closure: NativeFn = Rc::new(move |args: &[Object]| {
+        let source = Source::new(source_text("eval", &args[0])?);
+        let (mut codes, code_pos_table) = generate(&source).map_err(|e| describe("eval", &e))?;
+        forget_positions(&mut codes, true);
+        let scope = match globals.upgrade() {
+            Some(scope) => scope,
+            None => return Err(String::from("eval called after its engine was dropped")),
+        };
+        let hooks = hooks.borrow().clone();
+        match execute(
+            &codes,
+            &code_pos_table,
+            FILENAME,
+            Some(&source),
+            scope,
+            &hooks,
+        ) {
+            Fine(value, _) => Ok(value),
+            Fail(errors) => Err(describe("eval", &errors[0])),
+        }
+    });
+    Object::new_native(1, closure)
+}
+
+// compile(code) turns a string of source into a function without parameters,
+// calling it runs the code like the body of a function literal
+pub fn compile_native() -> Object {
+    let closure: NativeFn = Rc::new(|args: &[Object]| {
+        let source = Source::new(source_text("compile", &args[0])?);
+        let (mut codes, _) = generate(&source).map_err(|e| describe("compile", &e))?;
+        forget_positions(&mut codes, false);
+        Ok(Object::new_function(
+            Vec::new(),
+            codes,
+            CodePosTable::default(),
+            FunctionInfo::default(),
+        ))
+    });
+    Object::new_native(1, closure)
+}
+
+fn source_text<'a>(builtin: &str, arg: &'a Object) -> Result<&'a str, String> {
+    if let Object::Pointer { value } = arg {
+        if let RefObject::String { value } = &**value {
+            return Ok(value);
+        }
+    }
+    Err(format!("{} expects a string of source", builtin))
+}
+
+// the first error of parsing or generating the codes
+fn generate(source: &Source) -> Result<(Vec<Code>, CodePosTable), OliveError> {
+    let ast =
+        parse(source.text).map_err(|err| OliveError::from_parse_err(err, FILENAME, source))?;
+    match codegen::generate_codes(ast, FILENAME, source) {
+        Fine(codes, _) => Ok(codes),
+        Fail(mut errors) => Err(errors.remove(0)),
+    }
+}
+
+// errors are reported against the source of the code that runs, which for a
+// function is whoever calls it. codes that can run outside of the call of eval
+// or compile drop their positions, so their errors have none instead of
+// pointing into the wrong source
+fn forget_positions(codes: &mut [Code], keep: bool) {
+    for code in codes {
+        match code {
+            Code::PushFun(_, codes, code_pos_table, _) => {
+                *code_pos_table = CodePosTable::default();
+                forget_positions(codes, false);
+            }
+            Code::Defer(codes, code_pos_table) => {
+                if !keep {
+                    *code_pos_table = CodePosTable::default();
+                }
+                forget_positions(codes, keep);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn describe(builtin: &str, error: &OliveError) -> String {
+    let (position, message) = match error {
+        OliveError::Code {
+            line, col, data, ..
+        } => (Some((*line, *col)), data.message()),
+        OliveError::Runtime {
+            line, col, data, ..
+        } => (line.zip(*col), data.message()),
+        OliveError::Io { .. } => return error.to_string(),
+    };
+    match position {
+        Some((line, col)) => format!("{}: at ln {} col {}: {}", builtin, line, col, message),
+        None => format!("{}: {}", builtin, message),
+    }
+}
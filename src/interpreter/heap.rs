@@ -0,0 +1,135 @@
+use super::super::codegen::FunctionInfo;
+#[cfg(feature = "fs")]
+use super::object::NativeFn;
+use super::object::{Garbage, Object, RefObject};
+use super::Scope;
+use serde_json::{json, Value as Json};
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+// strings are shown up to this many chars, enough to tell them apart
+const PREVIEW_CHARS: usize = 40;
+
+// a json snapshot of the objects reachable from the global variables and the
+// variables of the running functions: their type, size, reference count and
+// the references they hold to other objects, plus the variables as roots.
+// values a function only has on its stack in the middle of an expression
+// aren't in it, and neither are the objects of other engines on the thread
+pub fn snapshot(globals: &Scope) -> String {
+    let frames: Vec<Rc<RefCell<Scope>>> = globals
+        .frames
+        .borrow()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    let frames: Vec<Ref<Scope>> = frames.iter().map(|frame| frame.borrow()).collect();
+    // the global scope is among the frames while a script runs, and a frame
+    // running deferred statements is in there twice
+    let mut scopes: Vec<&Scope> = vec![globals];
+    for frame in &frames {
+        if !scopes.iter().any(|scope| std::ptr::eq(*scope, &**frame)) {
+            scopes.push(frame);
+        }
+    }
+    let mut variables: Vec<(Option<usize>, &String, &Object)> = Vec::new();
+    for (index, scope) in scopes.iter().enumerate() {
+        let mut names: Vec<&String> = scope.variables.keys().collect();
+        names.sort();
+        let frame = index.checked_sub(1);
+        variables.extend(
+            names
+                .into_iter()
+                .map(|name| (frame, name, &scope.variables[name])),
+        );
+    }
+    // ids are given in the order objects are first reached
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    let mut objects: Vec<Garbage<RefObject>> = Vec::new();
+    let mut reach = |object: &Object, objects: &mut Vec<Garbage<RefObject>>| match object {
+        Object::Pointer { value } => Some(*ids.entry(value.address()).or_insert_with(|| {
+            objects.push(value.clone());
+            objects.len() - 1
+        })),
+        _ => None,
+    };
+    let roots: Vec<Json> = variables
+        .into_iter()
+        .filter_map(|(frame, name, value)| {
+            let to = reach(value, &mut objects)?;
+            Some(match frame {
+                Some(frame) => json!({ "frame": frame, "name": name, "to": to }),
+                None => json!({ "name": name, "to": to }),
+            })
+        })
+        .collect();
+    let mut entries: Vec<Json> = Vec::new();
+    while entries.len() < objects.len() {
+        let id = entries.len();
+        let object = objects[id].clone();
+        let edges: Vec<Json> = match &*object {
+            RefObject::List { data, .. } => data
+                .iter()
+                .enumerate()
+                .filter_map(|(index, element)| {
+                    reach(element, &mut objects).map(|to| json!({ "index": index, "to": to }))
+                })
+                .collect(),
+            RefObject::Bendy { data, .. } => {
+                let mut keys: Vec<&String> = data.keys().collect();
+                keys.sort();
+                keys.into_iter()
+                    .filter_map(|key| {
+                        reach(&data[key], &mut objects).map(|to| json!({ "key": key, "to": to }))
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        // the two handles taken for the snapshot aren't counted
+        let mut entry = json!({
+            "id": id,
+            "type": object.get_type_name(),
+            "size": object.shallow_size(),
+            "refcount": object.refcount() - 2,
+            "edges": edges,
+        });
+        match &*object {
+            RefObject::String { value } => {
+                entry["preview"] = json!(value.chars().take(PREVIEW_CHARS).collect::<String>());
+            }
+            RefObject::Function {
+                info: FunctionInfo {
+                    name: Some(name), ..
+                },
+                ..
+            } => entry["name"] = json!(name),
+            _ => {}
+        }
+        entries.push(entry);
+    }
+    json!({ "roots": roots, "objects": entries }).to_string()
+}
+
+// heapdump(path) writes a snapshot of the heap to the file
+#[cfg(feature = "fs")]
+pub fn native(globals: Weak<RefCell<Scope>>) -> Object {
+    let closure: NativeFn = Rc::new(move |args: &[Object]| {
+        let path = match &args[0] {
+            Object::Pointer { value } => match &**value {
+                RefObject::String { value } => value.clone(),
+                _ => return Err(String::from("heapdump expects a path")),
+            },
+            _ => return Err(String::from("heapdump expects a path")),
+        };
+        let globals = match globals.upgrade() {
+            Some(globals) => globals,
+            None => return Err(String::from("heapdump called after its engine was dropped")),
+        };
+        let snapshot = snapshot(&globals.borrow());
+        std::fs::write(&path, snapshot)
+            .map_err(|error| format!("couldn't write heap dump to '{}': {}", path, error))?;
+        Ok(Object::new_none())
+    });
+    Object::new_native(1, closure)
+}
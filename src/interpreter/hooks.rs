@@ -0,0 +1,23 @@
+use super::super::codegen::Code;
+use super::super::errors::OliveError;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// instrumentation called from the interpreter loop, e.g. for tracing or
+// profiling. every method does nothing by default, so an implementation only
+// overrides what it needs. calls are reported under the function's own name,
+// or the variable it was loaded from right before the call, or "<anonymous>"
+pub trait Hooks {
+    // before the code at ip of the running function is executed
+    fn on_instruction(&self, _ip: usize, _code: &Code) {}
+    // before a function or native is entered, once the call was checked, so
+    // a call that fails its arity or type checks is never reported
+    fn on_call(&self, _name: &str, _arg_count: usize) {}
+    // after a function or native returned without an error
+    fn on_return(&self, _name: &str) {}
+    // once for every error a script fails with
+    fn on_error(&self, _error: &OliveError) {}
+}
+
+// the hooks of an engine, shared with the builtins that run code themselves
+pub type HookList = Rc<RefCell<Vec<Rc<dyn Hooks>>>>;
@@ -0,0 +1,62 @@
+use super::super::errors::OliveRuntimeError;
+use super::object::{Object, RefObject};
+
+// how deep script functions may call each other. each call takes native
+// stack, a script recursing without end would overflow it and abort the
+// whole process. scripts at the limit need well under 1 MiB of stack in
+// release builds, but several MiB in debug builds
+pub const MAX_CALL_DEPTH: usize = 256;
+
+// caps on how large a single value may grow and how deep calls may nest, for
+// hosts running untrusted scripts. growing a value past them fails the script
+// with a size_limit error. none means no cap, which is the default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeLimits {
+    // in bytes
+    pub max_string_length: Option<usize>,
+    // elements of a list or keys of a bendy
+    pub max_elements: Option<usize>,
+    // can only lower MAX_CALL_DEPTH, deeper calls fail with a call_depth error
+    pub max_call_depth: Option<usize>,
+}
+
+impl SizeLimits {
+    pub fn check(&self, object: &Object) -> Result<(), OliveRuntimeError> {
+        match object {
+            Object::Pointer { value } => match &**value {
+                RefObject::String { value } => self.check_string(value.len()),
+                RefObject::List { data, .. } => self.check_elements("list", data.len()),
+                RefObject::Bendy { data, .. } => self.check_elements("bendy", data.len()),
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // how deep calls may nest with these limits
+    pub fn call_depth(&self) -> usize {
+        self.max_call_depth
+            .map_or(MAX_CALL_DEPTH, |limit| limit.min(MAX_CALL_DEPTH))
+    }
+
+    fn check_string(&self, length: usize) -> Result<(), OliveRuntimeError> {
+        match self.max_string_length {
+            Some(limit) if length > limit => Err(OliveRuntimeError::SizeLimit {
+                type_name: String::from("string"),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // called before a list or bendy grows to the length
+    pub fn check_elements(&self, type_name: &str, length: usize) -> Result<(), OliveRuntimeError> {
+        match self.max_elements {
+            Some(limit) if length > limit => Err(OliveRuntimeError::SizeLimit {
+                type_name: String::from(type_name),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use super::Output;
+use std::cell::{Cell, RefCell};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::rc::Rc;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        LogLevel::ALL
+            .iter()
+            .find(|level| level.name().eq_ignore_ascii_case(name))
+            .copied()
+            .ok_or_else(|| format!("unknown log level '{}'", name))
+    }
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.name())
+    }
+}
+
+// where the log_debug, log_info, log_warn and log_error builtins write to.
+// messages below the level are dropped, the others are written as one line
+// with the time and level in front
+pub struct Logger {
+    level: Cell<LogLevel>,
+    output: Output,
+}
+
+pub type Log = Rc<Logger>;
+
+impl Logger {
+    pub fn new() -> Self {
+        Logger::with_level(LogLevel::Info)
+    }
+
+    pub fn with_level(level: LogLevel) -> Self {
+        Logger {
+            level: Cell::new(level),
+            output: Rc::new(RefCell::new(Box::new(|line: &str| eprintln!("{}", line)))),
+        }
+    }
+
+    // workers of par_map start out with the level of the calling thread
+    #[cfg(feature = "parallel")]
+    pub fn level(&self) -> LogLevel {
+        self.level.get()
+    }
+
+    pub fn set_level(&self, level: LogLevel) {
+        self.level.set(level);
+    }
+
+    pub fn on_line<F: FnMut(&str) + 'static>(&self, callback: F) {
+        *self.output.borrow_mut() = Box::new(callback);
+    }
+
+    pub fn log(&self, level: LogLevel, message: &str) {
+        if level >= self.level.get() {
+            let line = match timestamp() {
+                Some(time) => format!("{} {:<5} {}", time, level.name(), message),
+                None => format!("{:<5} {}", level.name(), message),
+            };
+            self.write_line(&line);
+        }
+    }
+
+    // a line that was already formatted, e.g. by a worker thread
+    pub fn write_line(&self, line: &str) {
+        (self.output.borrow_mut())(line);
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger::new()
+    }
+}
+
+// the current utc time like 2020-01-31T12:34:56.789Z, browsers have no clock
+// the standard library can read
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn timestamp() -> Option<String> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    let seconds = now.as_secs();
+    let (year, month, day) = civil_date((seconds / 86400) as i64);
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        now.subsec_millis()
+    ))
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn timestamp() -> Option<String> {
+    None
+}
+
+// year, month and day of a count of days since 1970-01-01, see
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn civil_date(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -1,49 +1,90 @@
-use super::codegen::Code;
-use super::errors::{OliveError, OliveRuntimeError};
+use super::codegen::{Code, CodePosTable};
+use super::errors::{OliveError, OliveRuntimeError, Source};
 use mistake::Mistake::{self, Fail, Fine};
 use std::collections::HashMap;
 
 mod builtins;
 mod error;
+mod eval;
+mod heap;
+mod hooks;
+mod limits;
+mod log;
 mod object;
-use object::{Object, RefObject};
+#[cfg(feature = "parallel")]
+mod parallel;
+mod pool;
+mod serialize;
+#[cfg(feature = "signals")]
+mod signals;
+mod stats;
+pub use builtins::BUILTIN_NAMES;
+pub use heap::snapshot as heap_snapshot;
+pub use hooks::{HookList, Hooks};
+pub use limits::SizeLimits;
+pub use log::{Log, LogLevel, Logger};
+pub use object::{format_float, NativeFn, Object, RefObject};
+pub use stats::Stats;
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
+
+// receives every line printed by a script
+pub type Output = Rc<RefCell<Box<dyn FnMut(&str)>>>;
+
+// the global bendy holding every builtin and every native the host added,
+// under the names scripts call them by
+pub const BUILTINS: &str = "builtins";
 
 pub struct Scope {
     variables: HashMap<String, Object>,
     parent: Option<Rc<RefCell<Scope>>>,
+    // taken over from the parent, so code running in a scope can read them
+    // without walking up to the global one
+    limits: SizeLimits,
+    #[cfg(feature = "signals")]
+    signals: Option<Rc<signals::Handlers>>,
+    // the scopes of the code running in this engine, outermost first, shared
+    // by all scopes made from the global one. heap snapshots start from them
+    frames: Frames,
 }
 
+type Frames = Rc<RefCell<Vec<Weak<RefCell<Scope>>>>>;
+
 impl Scope {
     pub fn new() -> Self {
         Scope {
             variables: HashMap::new(),
             parent: None,
+            limits: SizeLimits::default(),
+            #[cfg(feature = "signals")]
+            signals: None,
+            frames: Frames::default(),
         }
     }
 
     fn from_parent(parent: Rc<RefCell<Scope>>) -> Self {
+        let limits = parent.borrow().limits;
+        #[cfg(feature = "signals")]
+        let signals = parent.borrow().signals.clone();
+        let frames = parent.borrow().frames.clone();
         Scope {
             variables: HashMap::new(),
             parent: Some(parent),
+            limits,
+            #[cfg(feature = "signals")]
+            signals,
+            frames,
         }
     }
 
-    fn has(&self, name: String) -> bool {
-        let keys: Vec<&String> = self.variables.keys().collect();
-        if keys.contains(&&name) {
-            true
-        } else {
-            if let Some(parent_scope) = &self.parent {
-                parent_scope.borrow().has(name)
-            } else {
-                false
-            }
-        }
+    // scopes made from this one later on get the limits too
+    pub fn set_limits(&mut self, limits: SizeLimits) {
+        self.limits = limits;
     }
 
-    fn load(&self, varname: &String) -> Option<Object> {
+    // objects are either primitives or counted pointers to the heap, so
+    // loading one copies a handle and never the data behind it
+    pub fn load(&self, varname: &str) -> Option<Object> {
         if let Some(result) = self.variables.get(varname) {
             Some(result.clone())
         } else {
@@ -55,77 +96,413 @@ impl Scope {
         }
     }
 
-    fn store(&mut self, name: String, val: Object) {
-        if self.has(name.clone()) {
-            self.variables.insert(name, val); // write in this
+    // assignments bind in the innermost scope, a variable that already
+    // exists there is overwritten in place so only new names allocate a key
+    pub fn store(&mut self, name: &str, val: Object) {
+        if let Some(slot) = self.variables.get_mut(name) {
+            *slot = val;
         } else {
-            if let Some(parent_scope) = &mut self.parent {
-                if parent_scope.borrow().has(name.clone()) {
-                    parent_scope.borrow_mut().store(name, val); // write in parent
-                } else {
-                    self.variables.insert(name, val); // write in this
-                }
-            } else {
-                self.variables.insert(name, val); // write in this
+            self.variables.insert(String::from(name), val);
+        }
+    }
+
+    // stores a native and lists it in the builtins bendy. that one is frozen
+    // for scripts, but not for the host
+    pub fn store_native(&mut self, name: &str, native: Object) {
+        if let Some(Object::Pointer { value }) = self.variables.get(BUILTINS) {
+            let mut value = value.clone();
+            if let RefObject::Bendy { data, .. } = &mut *value {
+                data.insert(String::from(name), native.clone());
             }
         }
+        self.store(name, native);
+    }
+
+    // stores a native in the bendy of a module, e.g. open as sqlite.open. the
+    // bendy is made by the first native of the module and frozen for scripts
+    // like the builtins one
+    pub fn store_module_native(&mut self, module: &str, name: &str, native: Object) {
+        let is_bendy = match self.variables.get(module) {
+            Some(Object::Pointer { value }) => matches!(&**value, RefObject::Bendy { .. }),
+            _ => false,
+        };
+        if !is_bendy {
+            let bendy = Object::new_bendy();
+            bendy.freeze();
+            self.store(module, bendy);
+        }
+        if let Some(Object::Pointer { value }) = self.variables.get(module) {
+            let mut value = value.clone();
+            if let RefObject::Bendy { data, .. } = &mut *value {
+                data.insert(String::from(name), native);
+            }
+        }
+    }
+
+    // names of the natives in the builtins bendy, in sorted order
+    pub fn builtin_names(&self) -> Vec<String> {
+        let mut names = match self.variables.get(BUILTINS) {
+            Some(Object::Pointer { value }) => match &**value {
+                RefObject::Bendy { data, .. } => data.keys().cloned().collect(),
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+        names.sort();
+        names
+    }
+
+    // every name visible from this scope, including the ones of its parents
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.variables.keys().cloned().collect();
+        if let Some(parent) = &self.parent {
+            names.extend(parent.borrow().names());
+        }
+        names
     }
 }
 
 pub fn run(
-    codes: &Vec<Code>,
-    code_pos_table: &HashMap<usize, usize>,
+    codes: &[Code],
+    code_pos_table: &CodePosTable,
+    filename: &str,
+    source: Option<&Source>,
+    scope: Rc<RefCell<Scope>>,
+    hooks: &[Rc<dyn Hooks>],
+) -> Mistake<Object, OliveError> {
+    let result = execute(codes, code_pos_table, filename, source, scope, hooks);
+    // errors are passed up through every function call, so they are only
+    // reported to the hooks once they leave the outermost one
+    let errors = match &result {
+        Fine(_, errors) | Fail(errors) => errors,
+    };
+    for error in errors {
+        for hook in hooks {
+            hook.on_error(error);
+        }
+    }
+    result
+}
+
+// the name a call is reported to hooks under
+fn call_name<'a>(function: &'a Object, previous: Option<&'a Code>) -> &'a str {
+    if let Object::Pointer { value } = function {
+        if let RefObject::Function { info, .. } = &**value {
+            if let Some(name) = &info.name {
+                return name;
+            }
+        }
+    }
+    match previous {
+        Some(Code::Load(name)) => name,
+        _ => "<anonymous>",
+    }
+}
+
+// why the function can't be called with the arguments, if it can't: every
+// call passes exactly as many arguments as the function takes, anything else
+// would leave values on the stack or take ones that belong to the caller, and
+// annotated parameter types are checked before the function runs, so the
+// error points at the call that passed the wrong argument
+pub(crate) fn check_call(function: &RefObject, args: &[Object]) -> Option<OliveRuntimeError> {
+    let (expected, name, parameter_types) = match function {
+        RefObject::Function {
+            args: parameters,
+            info,
+            ..
+        } => (
+            parameters.len(),
+            info.name.as_deref(),
+            &info.parameter_types[..],
+        ),
+        RefObject::Native { arg_count, .. } => (*arg_count as usize, None, &[][..]),
+        _ => return None,
+    };
+    if args.len() != expected {
+        return Some(OliveRuntimeError::CallArgs {
+            function: name.map(String::from),
+            expected,
+            got: args.len(),
+        });
+    }
+    parameter_types
+        .iter()
+        .zip(args)
+        .find_map(|(expected, value)| match expected {
+            Some(expected) if value.get_type_name() != expected => {
+                Some(OliveRuntimeError::IncorrectType {
+                    expected: vec![expected.clone()],
+                    got: String::from(value.get_type_name()),
+                })
+            }
+            _ => None,
+        })
+}
+
+fn execute(
+    codes: &[Code],
+    code_pos_table: &CodePosTable,
+    filename: &str,
+    source: Option<&Source>,
+    scope: Rc<RefCell<Scope>>,
+    hooks: &[Rc<dyn Hooks>],
+) -> Mistake<Object, OliveError> {
+    let frames = scope.borrow().frames.clone();
+    frames.borrow_mut().push(Rc::downgrade(&scope));
+    let result = execute_deferring(codes, code_pos_table, filename, source, scope, hooks);
+    frames.borrow_mut().pop();
+    result
+}
+
+fn execute_deferring(
+    codes: &[Code],
+    code_pos_table: &CodePosTable,
+    filename: &str,
+    source: Option<&Source>,
+    scope: Rc<RefCell<Scope>>,
+    hooks: &[Rc<dyn Hooks>],
+) -> Mistake<Object, OliveError> {
+    let mut deferred = Vec::new();
+    let result = execute_frame(
+        codes,
+        code_pos_table,
+        filename,
+        source,
+        scope.clone(),
+        hooks,
+        &mut deferred,
+    );
+    if deferred.is_empty() {
+        return result;
+    }
+    // deferred statements run last to first in the scope of the frame however
+    // it was left. their errors are added to the ones of the frame
+    let (mut value, mut errors) = match result {
+        Fine(value, errors) => (Some(value), errors),
+        Fail(errors) => (None, errors),
+    };
+    for (codes, code_pos_table) in deferred.into_iter().rev() {
+        match execute(
+            codes,
+            code_pos_table,
+            filename,
+            source,
+            scope.clone(),
+            hooks,
+        ) {
+            Fine(_, mut deferred_errors) => errors.append(&mut deferred_errors),
+            Fail(mut deferred_errors) => {
+                errors.append(&mut deferred_errors);
+                value = None;
+            }
+        }
+    }
+    match value {
+        Some(value) => Fine(value, errors),
+        None => Fail(errors),
+    }
+}
+
+fn execute_frame<'a>(
+    codes: &'a [Code],
+    code_pos_table: &CodePosTable,
     filename: &str,
-    source: Option<&str>,
+    source: Option<&Source>,
     scope: Rc<RefCell<Scope>>,
+    hooks: &[Rc<dyn Hooks>],
+    deferred: &mut Vec<(&'a [Code], &'a CodePosTable)>,
 ) -> Mistake<Object, OliveError> {
     let mut errors = Vec::new();
     let mut stack = Vec::new();
+    let limits = scope.borrow().limits;
+    #[cfg(feature = "signals")]
+    let signals = scope.borrow().signals.clone();
 
     let mut ip = 0;
+    // malformed code, e.g. from a broken .olvc file, fails the script with an
+    // internal error instead of panicking
+    macro_rules! internal_error {
+        ($message:expr) => {{
+            errors.push(error::create_internal_error(
+                ip,
+                code_pos_table,
+                filename,
+                source,
+                $message,
+            ));
+            return Fail(errors);
+        }};
+    }
+    macro_rules! pop {
+        () => {
+            match stack.pop() {
+                Some(value) => value,
+                None => internal_error!("pop from an empty stack"),
+            }
+        };
+    }
+    macro_rules! within_limits {
+        ($check:expr) => {
+            attempt_res!(
+                $check.map_err(|data| {
+                    error::create_runtime_error(ip, code_pos_table, filename, source, data)
+                }),
+                errors
+            )
+        };
+    }
+    macro_rules! jump {
+        ($offset:expr) => {
+            match jump_target(ip, $offset) {
+                Some(target) => ip = target,
+                None => internal_error!("jump before the first instruction"),
+            }
+        };
+    }
     loop {
-        let code = &codes[ip];
+        let code = match codes.get(ip) {
+            Some(code) => code,
+            None => internal_error!("ran past the last instruction"),
+        };
+        #[cfg(feature = "signals")]
+        if let Some(signals) = signals.as_ref().filter(|signals| signals.pending()) {
+            // handlers see the variables of the code they interrupted
+            for handler in signals.take() {
+                if let Object::Pointer { value } = &handler {
+                    if let RefObject::Function {
+                        codes,
+                        code_pos_table: handler_code_pos_table,
+                        ..
+                    } = &**value
+                    {
+                        let handler_scope =
+                            Rc::new(RefCell::new(Scope::from_parent(scope.clone())));
+                        attempt!(
+                            execute(
+                                codes,
+                                handler_code_pos_table,
+                                filename,
+                                source,
+                                handler_scope,
+                                hooks
+                            ),
+                            errors
+                        );
+                    }
+                }
+            }
+        }
+        for hook in hooks {
+            hook.on_instruction(ip, code);
+        }
         match code {
-            Code::PushFun(args, codes) => {
-                let fun_obj = Object::new_function(args.clone(), codes.clone());
+            Code::PushFun(args, codes, code_pos_table, info) => {
+                let fun_obj = Object::new_function(
+                    args.clone(),
+                    codes.clone(),
+                    code_pos_table.clone(),
+                    info.clone(),
+                );
                 stack.push(fun_obj);
             }
-            Code::Call => {
-                let function = stack.pop().unwrap();
-                match function {
-                    Object::Pointer { value } => match &*value {
-                        RefObject::Function { args, codes } => {
+            Code::Call(arg_count) => {
+                let arg_count = *arg_count as usize;
+                let function = pop!();
+                let name = call_name(&function, ip.checked_sub(1).map(|i| &codes[i]));
+                if stack.len() < arg_count {
+                    internal_error!("call with fewer arguments on the stack than passed");
+                }
+                if let Object::Pointer { value } = &function {
+                    if let Some(error) = check_call(value, &stack[stack.len() - arg_count..]) {
+                        errors.push(error::create_runtime_error(
+                            ip,
+                            code_pos_table,
+                            filename,
+                            source,
+                            error,
+                        ));
+                        return Fail(errors);
+                    }
+                }
+                match &function {
+                    Object::Pointer { value } => match &**value {
+                        RefObject::Function {
+                            args,
+                            codes,
+                            code_pos_table: function_code_pos_table,
+                            ..
+                        } => {
+                            let limit = limits.call_depth();
+                            if scope.borrow().frames.borrow().len() >= limit {
+                                errors.push(error::create_runtime_error(
+                                    ip,
+                                    code_pos_table,
+                                    filename,
+                                    source,
+                                    OliveRuntimeError::CallDepth { limit },
+                                ));
+                                return Fail(errors);
+                            }
+                            for hook in hooks {
+                                hook.on_call(name, arg_count);
+                            }
+                            let args_start = stack.len() - arg_count;
                             let new_scope =
                                 Rc::new(RefCell::new(Scope::from_parent(scope.clone())));
-                            for (i, arg) in args.iter().rev().enumerate() {
-                                if let Some(value) = stack.pop() {
-                                    new_scope.borrow_mut().store(arg.clone(), value);
-                                } else {
-                                    println!("{}, {:?}", ip, code_pos_table);
-                                    errors.push(error::create_call_error(
-                                        ip,
-                                        code_pos_table,
-                                        filename,
-                                        source,
-                                        i,
-                                        args.len(),
-                                    ));
+                            for (arg, value) in args.iter().zip(stack.drain(args_start..)) {
+                                new_scope.borrow_mut().store(arg, value);
+                            }
+                            let return_val = match execute(
+                                codes,
+                                function_code_pos_table,
+                                filename,
+                                source,
+                                new_scope,
+                                hooks,
+                            ) {
+                                Fine(value, mut function_errors) => {
+                                    errors.append(&mut function_errors);
+                                    value
+                                }
+                                Fail(mut function_errors) => {
+                                    let line = code_pos_table.get(ip).and_then(|start| {
+                                        source.map(|source| source.get_line_and_column(start).0)
+                                    });
+                                    for error in &mut function_errors {
+                                        error.push_trace(name, line);
+                                    }
+                                    errors.append(&mut function_errors);
                                     return Fail(errors);
                                 }
+                            };
+                            for hook in hooks {
+                                hook.on_return(name);
                             }
-                            let return_val = attempt!(
-                                run(&codes, &code_pos_table, filename, source, new_scope,),
-                                errors
-                            );
                             stack.push(return_val);
                         }
-                        RefObject::Native { arg_count, closure } => {
-                            let mut args = Vec::new();
-                            for _ in 0..*arg_count {
-                                let value = stack.pop().unwrap();
-                                args.push(value);
+                        RefObject::Native { closure, .. } => {
+                            // the arguments are the top of the stack in call order,
+                            // natives borrow them from there without a copy
+                            for hook in hooks {
+                                hook.on_call(name, arg_count);
+                            }
+                            let args_start = stack.len() - arg_count;
+                            let result = closure(&stack[args_start..]);
+                            stack.truncate(args_start);
+                            let return_val = attempt_res!(
+                                result.map_err(|message| error::create_runtime_error(
+                                    ip,
+                                    code_pos_table,
+                                    filename,
+                                    source,
+                                    OliveRuntimeError::Native { message },
+                                )),
+                                errors
+                            );
+                            within_limits!(limits.check(&return_val));
+                            for hook in hooks {
+                                hook.on_return(name);
                             }
-                            let return_val = closure(args);
                             stack.push(return_val);
                         }
                         t => {
@@ -180,45 +557,39 @@ pub fn run(
                 stack.push(Object::new_none());
             }
             Code::Return => {
-                return Fine(stack.pop().unwrap(), errors);
+                return Fine(pop!(), errors);
+            }
+            Code::Defer(codes, code_pos_table) => {
+                deferred.push((codes, code_pos_table));
             }
             Code::Dup => {
-                let val = stack.last().unwrap().clone();
+                let val = match stack.last() {
+                    Some(val) => val.clone(),
+                    None => internal_error!("dup on an empty stack"),
+                };
                 stack.push(val);
             }
             Code::Pop => {
                 stack.pop();
             }
             Code::Goto(offset) => {
-                if *offset > 0 {
-                    ip += *offset as usize;
-                } else {
-                    ip -= (-*offset) as usize;
-                }
+                jump!(*offset);
                 continue;
             }
             Code::JumpNot(offset) => {
-                if !stack.pop().unwrap().truthy() {
-                    if *offset > 0 {
-                        ip += *offset as usize;
-                    } else {
-                        ip -= (-*offset) as usize;
-                    }
+                if !pop!().truthy() {
+                    jump!(*offset);
                     continue;
                 }
             }
             Code::Jump(offset) => {
-                if stack.pop().unwrap().truthy() {
-                    if *offset > 0 {
-                        ip += *offset as usize;
-                    } else {
-                        ip -= (-*offset) as usize;
-                    }
+                if pop!().truthy() {
+                    jump!(*offset);
                     continue;
                 }
             }
-            Code::Neg => match stack.pop().unwrap() {
-                Object::Integer { value } => stack.push(Object::new_integer(-value)),
+            Code::Neg => match pop!() {
+                Object::Integer { value } => stack.push(Object::new_integer(value.wrapping_neg())),
                 Object::Float { value } => stack.push(Object::new_float(-value)),
                 t => {
                     errors.push(error::create_type_error(
@@ -233,7 +604,7 @@ pub fn run(
                 }
             },
             Code::BoolNot => {
-                let value = !stack.pop().unwrap().truthy();
+                let value = !pop!().truthy();
                 stack.push(Object::new_boolean(value))
             }
             Code::Add
@@ -254,53 +625,109 @@ pub fn run(
             | Code::LessEquals
             | Code::GreaterThan
             | Code::GreaterEquals => {
-                let b = &stack.pop().unwrap();
-                let a = &stack.pop().unwrap();
-                stack.push(attempt_res!(
+                let b = &pop!();
+                let a = &pop!();
+                let result = attempt_res!(
                     a.operate(b, ip, code_pos_table, filename, source, code),
                     errors
-                ));
+                );
+                within_limits!(limits.check(&result));
+                stack.push(result);
             }
             Code::Put => {
-                let value = stack.pop().unwrap();
-                let index = stack.pop().unwrap();
-                let object = stack.pop().unwrap();
+                let value = pop!();
+                let index = pop!();
+                let object = pop!();
                 match object {
                     Object::Pointer { value: mut v } => match &mut *v {
-                        RefObject::List { data } => {
+                        t @ RefObject::List { frozen: true, .. }
+                        | t @ RefObject::Bendy { frozen: true, .. } => {
+                            errors.push(error::create_runtime_error(
+                                ip,
+                                code_pos_table,
+                                filename,
+                                source,
+                                OliveRuntimeError::Frozen {
+                                    type_name: String::from(t.get_type_name()),
+                                },
+                            ));
+                            return Fail(errors);
+                        }
+                        RefObject::List { data, .. } => {
                             let int_index: i64 = attempt_res!(
                                 index.as_integer(ip, code_pos_table, filename, source),
                                 errors
                             );
+                            if int_index < 0 {
+                                errors.push(error::create_runtime_error(
+                                    ip,
+                                    code_pos_table,
+                                    filename,
+                                    source,
+                                    OliveRuntimeError::IndexOutOfBounds,
+                                ));
+                                return Fail(errors);
+                            }
+                            let length = data.len().max(int_index as usize + 1);
+                            within_limits!(limits.check_elements("list", length));
                             while data.len() < int_index as usize + 1 {
                                 data.push(Object::new_none());
                             }
-                            data[int_index as usize] = Object::from(value);
+                            data[int_index as usize] = value;
                         }
-                        RefObject::Bendy { data } => {
+                        RefObject::Bendy { data, .. } => {
                             let str_index: &str = attempt_res!(
                                 index.as_string(ip, code_pos_table, filename, source),
                                 errors
                             );
-                            data.insert(String::from(str_index), Object::from(value));
+                            if !data.contains_key(str_index) {
+                                within_limits!(limits.check_elements("bendy", data.len() + 1));
+                            }
+                            data.insert(String::from(str_index), value);
+                        }
+                        t => {
+                            errors.push(error::create_type_error(
+                                ip,
+                                code_pos_table,
+                                filename,
+                                source,
+                                vec!["list", "bendy"],
+                                t.get_type_name(),
+                            ));
+                            return Fail(errors);
                         }
-                        _ => unimplemented!(),
                     },
-                    _ => unimplemented!(),
+                    t => {
+                        errors.push(error::create_type_error(
+                            ip,
+                            code_pos_table,
+                            filename,
+                            source,
+                            vec!["list", "bendy"],
+                            t.get_type_name(),
+                        ));
+                        return Fail(errors);
+                    }
                 }
             }
-            Code::Get => {
-                let index = stack.pop().unwrap();
-                let object = stack.pop().unwrap();
+            Code::Get | Code::GetOptional => {
+                // the optional get gives none for a none object or a missing
+                // index instead of failing
+                let optional = matches!(code, Code::GetOptional);
+                let index = pop!();
+                let object = pop!();
                 match object {
+                    Object::None if optional => stack.push(Object::None),
                     Object::Pointer { value: mut v } => match &mut *v {
-                        RefObject::List { data } => {
+                        RefObject::List { data, .. } => {
                             let int_index: i64 = attempt_res!(
                                 index.as_integer(ip, code_pos_table, filename, source),
                                 errors
                             );
                             if let Some(v) = data.get(int_index as usize) {
                                 stack.push(v.clone());
+                            } else if optional {
+                                stack.push(Object::None);
                             } else {
                                 errors.push(error::create_runtime_error(
                                     ip,
@@ -317,8 +744,15 @@ pub fn run(
                                 index.as_integer(ip, code_pos_table, filename, source),
                                 errors
                             );
-                            if let Some(v) = value.chars().skip(int_index as usize).next() {
+                            // negative indices are missing without walking the string
+                            let char = match int_index {
+                                index if index < 0 => None,
+                                index => value.chars().nth(index as usize),
+                            };
+                            if let Some(v) = char {
                                 stack.push(Object::new_string(v.to_string()));
+                            } else if optional {
+                                stack.push(Object::None);
                             } else {
                                 errors.push(error::create_runtime_error(
                                     ip,
@@ -330,13 +764,15 @@ pub fn run(
                                 return Fail(errors);
                             }
                         }
-                        RefObject::Bendy { data } => {
+                        RefObject::Bendy { data, .. } => {
                             let str_index: &str = attempt_res!(
                                 index.as_string(ip, code_pos_table, filename, source),
                                 errors
                             );
                             if let Some(v) = data.get(str_index) {
                                 stack.push(v.clone());
+                            } else if optional {
+                                stack.push(Object::None);
                             } else {
                                 errors.push(error::create_runtime_error(
                                     ip,
@@ -348,9 +784,29 @@ pub fn run(
                                 return Fail(errors);
                             }
                         }
-                        _ => unimplemented!(),
+                        t => {
+                            errors.push(error::create_type_error(
+                                ip,
+                                code_pos_table,
+                                filename,
+                                source,
+                                vec!["list", "string", "bendy"],
+                                t.get_type_name(),
+                            ));
+                            return Fail(errors);
+                        }
                     },
-                    _ => unimplemented!(),
+                    t => {
+                        errors.push(error::create_type_error(
+                            ip,
+                            code_pos_table,
+                            filename,
+                            source,
+                            vec!["list", "string", "bendy"],
+                            t.get_type_name(),
+                        ));
+                        return Fail(errors);
+                    }
                 }
             }
             Code::Load(varname) => {
@@ -368,28 +824,65 @@ pub fn run(
                 }
             }
             Code::Store(varname) => {
-                let value = stack.pop().unwrap();
-                scope.borrow_mut().store(varname.clone(), value.clone());
+                let value = pop!();
+                scope.borrow_mut().store(varname, value);
             }
         }
         ip += 1;
     }
 }
 
-pub fn start(
-    codes: &Vec<Code>,
-    code_pos_table: &HashMap<usize, usize>,
-    filename: &str,
-    source: Option<&str>,
-) -> Mistake<(), OliveError> {
-    let mut errors = Vec::new();
+fn jump_target(ip: usize, offset: i32) -> Option<usize> {
+    if offset < 0 {
+        ip.checked_sub(offset.unsigned_abs() as usize)
+    } else {
+        ip.checked_add(offset as usize)
+    }
+}
+
+// only the builtins accepted by allow are made available. code run by eval
+// calls the hooks
+pub fn global_scope<F: Fn(&str) -> bool>(
+    allow: F,
+    output: Output,
+    log: Log,
+    hooks: HookList,
+) -> Rc<RefCell<Scope>> {
     let global_scope = Rc::new(RefCell::new(Scope::new()));
-    for (name, function) in builtins::get_functions() {
-        global_scope.borrow_mut().store(name, function);
+    #[cfg(feature = "parallel")]
+    if allow("par_map") {
+        let par_map = parallel::native(Rc::downgrade(&global_scope), output.clone(), log.clone());
+        global_scope.borrow_mut().store("par_map", par_map);
     }
-    attempt!(
-        run(codes, code_pos_table, filename, source, global_scope),
-        errors
-    );
-    return Fine((), errors);
+    if allow("eval") {
+        let eval = eval::eval_native(Rc::downgrade(&global_scope), hooks);
+        global_scope.borrow_mut().store("eval", eval);
+    }
+    #[cfg(feature = "fs")]
+    if allow("heapdump") {
+        let heapdump = heap::native(Rc::downgrade(&global_scope));
+        global_scope.borrow_mut().store("heapdump", heapdump);
+    }
+    if allow("compile") {
+        global_scope
+            .borrow_mut()
+            .store("compile", eval::compile_native());
+    }
+    #[cfg(feature = "signals")]
+    if allow("on_signal") {
+        let handlers = Rc::new(signals::Handlers::default());
+        global_scope.borrow_mut().signals = Some(handlers.clone());
+        global_scope
+            .borrow_mut()
+            .store("on_signal", signals::native(handlers));
+    }
+    for (name, function) in builtins::get_functions(output, log) {
+        if allow(&name) {
+            global_scope.borrow_mut().store(&name, function);
+        }
+    }
+    let natives = Object::new_filled_bendy(global_scope.borrow().variables.clone());
+    natives.freeze();
+    global_scope.borrow_mut().store(BUILTINS, natives);
+    global_scope
 }
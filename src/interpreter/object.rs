@@ -1,27 +1,39 @@
-use super::super::codegen::Code;
-use super::super::errors::OliveError;
+use super::super::codegen::{Code, CodePosTable, FunctionInfo};
+use super::super::errors::{OliveError, OliveRuntimeError, Source};
 use super::error;
-use std::alloc::{alloc, dealloc, Layout};
+use super::pool::Pool;
+use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+// natives get their arguments in call order, an Err fails the script with that message
+pub type NativeFn = Rc<dyn Fn(&[Object]) -> Result<Object, String>>;
 
 pub enum RefObject {
     Function {
         args: Vec<String>,
         codes: Vec<Code>,
+        code_pos_table: CodePosTable,
+        info: FunctionInfo,
     },
     String {
         value: String,
     },
+    // frozen lists and bendys can't be changed, see the freeze builtin
     List {
         data: Vec<Object>,
+        frozen: bool,
     },
     Bendy {
         data: HashMap<String, Object>,
+        frozen: bool,
     },
     Native {
         arg_count: u32,
-        closure: fn(Vec<Object>) -> Object,
+        closure: NativeFn,
     },
 }
 
@@ -43,48 +55,196 @@ impl From<Garbage<RefObject>> for Object {
 impl RefObject {
     pub fn get_type_name(&self) -> &str {
         match self {
-            RefObject::Function { args: _, codes: _ } => "function",
+            RefObject::Function { .. } => "function",
             RefObject::String { value: _ } => "string",
-            RefObject::List { data: _ } => "list",
-            RefObject::Bendy { data: _ } => "bendy",
+            RefObject::List { .. } => "list",
+            RefObject::Bendy { .. } => "bendy",
             RefObject::Native {
                 arg_count: _,
                 closure: _,
             } => "native",
         }
     }
+
+    // bytes taken by the object itself and the buffers it owns, but not by
+    // the objects it points to. buffers are counted by capacity, so this is
+    // close to what the allocator handed out
+    pub fn shallow_size(&self) -> usize {
+        let own = std::mem::size_of::<GarbageBlock<RefObject>>();
+        own + match self {
+            RefObject::Function { args, codes, .. } => {
+                args.iter().map(String::capacity).sum::<usize>()
+                    + codes.capacity() * std::mem::size_of::<Code>()
+            }
+            RefObject::String { value } => value.capacity(),
+            RefObject::List { data, .. } => data.capacity() * std::mem::size_of::<Object>(),
+            RefObject::Bendy { data, .. } => {
+                data.capacity() * std::mem::size_of::<(String, Object)>()
+                    + data.keys().map(String::capacity).sum::<usize>()
+            }
+            RefObject::Native { .. } => 0,
+        }
+    }
 }
 
-impl ToString for Object {
-    fn to_string(&self) -> String {
-        match self {
-            Object::Integer { value } => format!("{}", value),
-            Object::Boolean { value } => format!("{}", value),
-            Object::Float { value } => format!("{}", value),
-            Object::None => String::from("none"),
-            Object::Pointer { value: v } => match &**v {
-                RefObject::String { value } => value.clone(),
-                RefObject::List { data } => format!(
-                    "[{}]",
-                    data.iter()
-                        .map(|e| e.to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                ),
-                RefObject::Bendy { data } => format!(
-                    "{{{}}}",
-                    data.iter()
-                        .map(|(k, v)| format!("{}: {}", k.to_string(), v.to_string()))
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                ),
-                RefObject::Function { args, codes: _ } => format!("function({})", args.join(", ")),
-                RefObject::Native {
-                    arg_count: _,
-                    closure,
-                } => format!("native({:?})", closure),
+// the shortest text that reads back as the same float. it always has a
+// fraction or an exponent, so it can't be mistaken for an integer
+pub fn format_float(value: f64) -> String {
+    if value.is_nan() {
+        return String::from("nan");
+    }
+    if value.is_infinite() {
+        return String::from(if value < 0.0 { "-inf" } else { "inf" });
+    }
+    let magnitude = value.abs();
+    if magnitude != 0.0 && !(1e-4..1e16).contains(&magnitude) {
+        return format!("{:e}", value);
+    }
+    let text = format!("{}", value);
+    if text.contains('.') {
+        text
+    } else {
+        text + ".0"
+    }
+}
+
+// lines of pretty printed objects are only broken up beyond this many chars
+const PRETTY_WIDTH: usize = 80;
+
+// a string literal reading as value, with the escapes the lexer knows
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// written for a list or bendy inside itself, in place of writing it again
+const CYCLE: &str = "<cycle>";
+
+impl Object {
+    // like to_string, but strings are quoted so they can be told apart from
+    // the other types, also inside lists and bendys
+    pub fn repr(&self) -> String {
+        self.write(true, &mut Vec::new())
+    }
+
+    // on_stack has the addresses of the objects being written further up, an
+    // object that contains itself is written as <cycle> the second time
+    fn write(&self, quoted: bool, on_stack: &mut Vec<usize>) -> String {
+        let v = match self {
+            Object::Integer { value } => return format!("{}", value),
+            Object::Boolean { value } => return format!("{}", value),
+            Object::Float { value } => return format_float(*value),
+            Object::None => return String::from("none"),
+            Object::Pointer { value } => value,
+        };
+        if on_stack.contains(&v.address()) {
+            return String::from(CYCLE);
+        }
+        on_stack.push(v.address());
+        let text = match &**v {
+            RefObject::String { value } if quoted => quote(value),
+            RefObject::String { value } => value.clone(),
+            RefObject::List { data, .. } => format!(
+                "[{}]",
+                data.iter()
+                    .map(|e| e.write(quoted, on_stack))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            RefObject::Bendy { data, .. } => format!(
+                "{{{}}}",
+                data.iter()
+                    .map(|(k, v)| format!("{}: {}", k, v.write(quoted, on_stack)))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            RefObject::Function { args, info, .. } => match &info.name {
+                Some(name) => format!("function {}({})", name, args.join(", ")),
+                None => format!("function({})", args.join(", ")),
             },
+            RefObject::Native {
+                arg_count,
+                closure: _,
+            } => format!("native({})", arg_count),
+        };
+        on_stack.pop();
+        text
+    }
+
+    // repr over several lines: a list or bendy that doesn't fit in the line
+    // width puts every element on its own line, indented by indent spaces
+    // per level of nesting
+    pub fn to_pretty_string(&self, indent: usize) -> String {
+        let mut text = String::new();
+        self.write_pretty(&mut text, indent, 0, &mut Vec::new());
+        text
+    }
+
+    fn write_pretty(
+        &self,
+        text: &mut String,
+        indent: usize,
+        depth: usize,
+        on_stack: &mut Vec<usize>,
+    ) {
+        let flat = self.write(true, on_stack);
+        let line_start = text.rfind('\n').map_or(0, |i| i + 1);
+        let column = text[line_start..].chars().count();
+        let v = match self {
+            Object::Pointer { value } if !on_stack.contains(&value.address()) => value,
+            _ => {
+                text.push_str(&flat);
+                return;
+            }
+        };
+        if column + flat.chars().count() <= PRETTY_WIDTH {
+            text.push_str(&flat);
+            return;
+        }
+        let inner = " ".repeat(indent * (depth + 1));
+        on_stack.push(v.address());
+        match &**v {
+            RefObject::List { data, .. } if !data.is_empty() => {
+                text.push_str("[\n");
+                for (i, element) in data.iter().enumerate() {
+                    text.push_str(&inner);
+                    element.write_pretty(text, indent, depth + 1, on_stack);
+                    text.push_str(if i + 1 < data.len() { ",\n" } else { "\n" });
+                }
+                text.push_str(&" ".repeat(indent * depth));
+                text.push(']');
+            }
+            RefObject::Bendy { data, .. } if !data.is_empty() => {
+                text.push_str("{\n");
+                for (i, (key, element)) in data.iter().enumerate() {
+                    text.push_str(&inner);
+                    text.push_str(key);
+                    text.push_str(": ");
+                    element.write_pretty(text, indent, depth + 1, on_stack);
+                    text.push_str(if i + 1 < data.len() { ",\n" } else { "\n" });
+                }
+                text.push_str(&" ".repeat(indent * depth));
+                text.push('}');
+            }
+            _ => text.push_str(&flat),
         }
+        on_stack.pop();
+    }
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.write(false, &mut Vec::new()))
     }
 }
 
@@ -103,10 +263,7 @@ impl PartialEq for Object {
                 Object::Boolean { value: v2 } => v1 == v2,
                 _ => false,
             },
-            Object::None => match other {
-                Object::None => true,
-                _ => false,
-            },
+            Object::None => matches!(other, Object::None),
             Object::Pointer { value: v } => match &**v {
                 RefObject::String { value: v1 } => match other {
                     Object::Pointer { value: v } => match &**v {
@@ -115,16 +272,16 @@ impl PartialEq for Object {
                     },
                     _ => false,
                 },
-                RefObject::List { data: v1 } => match other {
+                RefObject::List { data: v1, .. } => match other {
                     Object::Pointer { value: v } => match &**v {
-                        RefObject::List { data: v2 } => v1 == v2,
+                        RefObject::List { data: v2, .. } => v1 == v2,
                         _ => false,
                     },
                     _ => false,
                 },
-                RefObject::Bendy { data: v1 } => match other {
+                RefObject::Bendy { data: v1, .. } => match other {
                     Object::Pointer { value: v } => match &**v {
-                        RefObject::Bendy { data: v2 } => v1 == v2,
+                        RefObject::Bendy { data: v2, .. } => v1 == v2,
                         _ => false,
                     },
                     _ => false,
@@ -132,11 +289,13 @@ impl PartialEq for Object {
                 RefObject::Function {
                     args: args1,
                     codes: codes1,
+                    ..
                 } => match other {
                     Object::Pointer { value: v } => match &**v {
                         RefObject::Function {
                             args: args2,
                             codes: codes2,
+                            ..
                         } => args1 == args2 && codes1 == codes2,
                         _ => false,
                     },
@@ -150,7 +309,7 @@ impl PartialEq for Object {
                         RefObject::Native {
                             arg_count: a2,
                             closure: c2,
-                        } => a1 == a2 && c1 == c2,
+                        } => a1 == a2 && Rc::ptr_eq(c1, c2),
                         _ => false,
                     },
                     _ => false,
@@ -173,12 +332,22 @@ impl Object {
     pub fn new_boolean(value: bool) -> Self {
         Object::Boolean { value }
     }
-    pub fn new_function(args: Vec<String>, codes: Vec<Code>) -> Self {
+    pub fn new_function(
+        args: Vec<String>,
+        codes: Vec<Code>,
+        code_pos_table: CodePosTable,
+        info: FunctionInfo,
+    ) -> Self {
         Object::Pointer {
-            value: Garbage::new(RefObject::Function { args, codes }),
+            value: Garbage::new(RefObject::Function {
+                args,
+                codes,
+                code_pos_table,
+                info,
+            }),
         }
     }
-    pub fn new_native(arg_count: u32, closure: fn(Vec<Object>) -> Object) -> Self {
+    pub fn new_native(arg_count: u32, closure: NativeFn) -> Self {
         Object::Pointer {
             value: Garbage::new(RefObject::Native { arg_count, closure }),
         }
@@ -187,22 +356,32 @@ impl Object {
         Object::Pointer {
             value: Garbage::new(RefObject::Bendy {
                 data: HashMap::new(),
+                frozen: false,
             }),
         }
     }
     pub fn new_list() -> Self {
         Object::Pointer {
-            value: Garbage::new(RefObject::List { data: Vec::new() }),
+            value: Garbage::new(RefObject::List {
+                data: Vec::new(),
+                frozen: false,
+            }),
         }
     }
     pub fn new_filled_list(data: Vec<Object>) -> Self {
         Object::Pointer {
-            value: Garbage::new(RefObject::List { data }),
+            value: Garbage::new(RefObject::List {
+                data,
+                frozen: false,
+            }),
         }
     }
     pub fn new_filled_bendy(data: HashMap<String, Object>) -> Self {
         Object::Pointer {
-            value: Garbage::new(RefObject::Bendy { data }),
+            value: Garbage::new(RefObject::Bendy {
+                data,
+                frozen: false,
+            }),
         }
     }
     pub fn new_string(value: String) -> Self {
@@ -227,10 +406,10 @@ impl Object {
             Object::Float { value } => *value != 0.0,
             Object::None => false,
             Object::Pointer { value } => match &**value {
-                RefObject::String { value } => value.len() > 0,
-                RefObject::List { data } => data.len() > 0,
-                RefObject::Bendy { data } => data.len() > 0,
-                RefObject::Function { args: _, codes: _ } => true,
+                RefObject::String { value } => !value.is_empty(),
+                RefObject::List { data, .. } => !data.is_empty(),
+                RefObject::Bendy { data, .. } => !data.is_empty(),
+                RefObject::Function { .. } => true,
                 RefObject::Native {
                     arg_count: _,
                     closure: _,
@@ -238,12 +417,34 @@ impl Object {
             },
         }
     }
+    // makes lists and bendys and everything in them unchangeable. ones that
+    // are frozen already are skipped, so a list holding itself is no problem
+    pub fn freeze(&self) {
+        let mut value = match self {
+            Object::Pointer { value } => value.clone(),
+            _ => return,
+        };
+        let children: Vec<Object> = match &mut *value {
+            RefObject::List { data, frozen } if !*frozen => {
+                *frozen = true;
+                data.clone()
+            }
+            RefObject::Bendy { data, frozen } if !*frozen => {
+                *frozen = true;
+                data.values().cloned().collect()
+            }
+            _ => return,
+        };
+        for child in &children {
+            child.freeze();
+        }
+    }
     pub fn as_integer(
         &self,
         position: usize,
-        code_pos_table: &HashMap<usize, usize>,
+        code_pos_table: &CodePosTable,
         filename: &str,
-        source: Option<&str>,
+        source: Option<&Source>,
     ) -> Result<i64, OliveError> {
         match self {
             Object::Integer { value } => Ok(*value),
@@ -260,9 +461,9 @@ impl Object {
     pub fn as_string(
         &self,
         position: usize,
-        code_pos_table: &HashMap<usize, usize>,
+        code_pos_table: &CodePosTable,
         filename: &str,
-        source: Option<&str>,
+        source: Option<&Source>,
     ) -> Result<&str, OliveError> {
         match self {
             Object::Pointer { value } => match &**value {
@@ -287,12 +488,13 @@ impl Object {
         }
     }
 
+    // integers wrap around when they overflow, like negation and -2^63 // -1
+    // do, instead of failing
     fn operate_int(a: i64, b: i64, operation: &Code) -> i64 {
         match operation {
-            Code::Add => a + b,
-            Code::Sub => a - b,
-            Code::Mod => a % b,
-            Code::Mul => a * b,
+            Code::Add => a.wrapping_add(b),
+            Code::Sub => a.wrapping_sub(b),
+            Code::Mul => a.wrapping_mul(b),
             Code::BitAnd => a & b,
             Code::BitOr => a | b,
             Code::BitXOr => a ^ b,
@@ -326,22 +528,67 @@ impl Object {
         match operation {
             Code::Add => a + b,
             Code::Sub => a - b,
-            Code::Mod => a % b,
             Code::Mul => a * b,
             _ => panic!(),
         }
     }
+
+    // // and % round the quotient down and give the remainder the sign of the
+    // divisor, so that a == (a // b) * b + a % b. the divisor is never 0, and
+    // the only overflow, -2^63 // -1, wraps around to -2^63
+    fn divide_int(a: i64, b: i64, operation: &Code) -> i64 {
+        let quotient = a.wrapping_div(b);
+        let remainder = a.wrapping_rem(b);
+        let round_down = remainder != 0 && (remainder < 0) != (b < 0);
+        match operation {
+            Code::IntDiv if round_down => quotient - 1,
+            Code::IntDiv => quotient,
+            _ if round_down => remainder + b,
+            _ => remainder,
+        }
+    }
+
+    // the same rules for floats, the quotient is a whole number stored as a
+    // float. it is computed from the remainder, because flooring a / b can be
+    // off by one when a / b rounds up to a whole number
+    fn divide_float(a: f64, b: f64, operation: &Code) -> f64 {
+        let mut remainder = a % b;
+        let mut quotient = (a - remainder) / b;
+        if remainder != 0.0 && (remainder < 0.0) != (b < 0.0) {
+            remainder += b;
+            quotient -= 1.0;
+        }
+        match operation {
+            Code::IntDiv => {
+                let floor = quotient.floor();
+                if quotient - floor > 0.5 {
+                    floor + 1.0
+                } else {
+                    floor
+                }
+            }
+            _ => remainder,
+        }
+    }
+
+    fn to_float(&self) -> Option<f64> {
+        match self {
+            Object::Integer { value } => Some(*value as f64),
+            Object::Float { value } => Some(*value),
+            _ => None,
+        }
+    }
     pub fn operate(
         &self,
         other: &Self,
         position: usize,
-        code_pos_table: &HashMap<usize, usize>,
+        code_pos_table: &CodePosTable,
         filename: &str,
-        source: Option<&str>,
+        source: Option<&Source>,
         operation: &Code,
     ) -> Result<Self, OliveError> {
         match operation {
-            Code::Add | Code::Sub | Code::Mod | Code::Mul => match self {
+            Code::Add | Code::Sub | Code::Mul => match self {
                 Object::Integer { value: v1 } => match other {
                     Object::Integer { value: v2 } => {
                         return Ok(Object::Integer {
@@ -371,68 +618,57 @@ impl Object {
                 _ => {}
             },
             Code::LessEquals | Code::LessThan | Code::GreaterEquals | Code::GreaterThan => {
-                match self {
-                    Object::Integer { value: v1 } => match other {
-                        Object::Integer { value: v2 } => {
-                            return Ok(Object::Boolean {
-                                value: Object::compare_int(*v1, *v2, operation),
-                            })
-                        }
-                        _ => {}
-                    },
-                    Object::Float { value: v1 } => match other {
-                        Object::Float { value: v2 } => {
-                            return Ok(Object::Boolean {
-                                value: Object::compare_float(*v1, *v2, operation),
-                            })
-                        }
-                        _ => {}
-                    },
-                    _ => {}
-                }
-            }
-            Code::BitAnd | Code::BitOr | Code::BitXOr | Code::BitLsh | Code::BitRsh => match self {
-                Object::Integer { value: v1 } => match other {
-                    Object::Integer { value: v2 } => {
-                        return Ok(Object::Integer {
-                            value: Object::operate_int(*v1, *v2, operation),
+                match (self, other) {
+                    (Object::Integer { value: v1 }, Object::Integer { value: v2 }) => {
+                        return Ok(Object::Boolean {
+                            value: Object::compare_int(*v1, *v2, operation),
+                        })
+                    }
+                    (Object::Float { value: v1 }, Object::Float { value: v2 }) => {
+                        return Ok(Object::Boolean {
+                            value: Object::compare_float(*v1, *v2, operation),
                         })
                     }
                     _ => {}
-                },
-                _ => {}
-            },
-            Code::Concat => match self {
-                Object::Pointer { value: v } => match &**v {
-                    RefObject::String { value: v1 } => {
-                        return Ok(Object::new_string(format!("{}{}", v1, other.to_string())))
+                }
+            }
+            Code::BitAnd | Code::BitOr | Code::BitXOr | Code::BitLsh | Code::BitRsh => {
+                if let (Object::Integer { value: v1 }, Object::Integer { value: v2 }) =
+                    (self, other)
+                {
+                    return Ok(Object::Integer {
+                        value: Object::operate_int(*v1, *v2, operation),
+                    });
+                }
+            }
+            Code::Concat => {
+                if let Object::Pointer { value: v } = self {
+                    if let RefObject::String { value: v1 } = &**v {
+                        return Ok(Object::new_string(format!("{}{}", v1, other)));
                     }
-                    RefObject::List { data: d1 } => match other {
-                        Object::Pointer { value: v } => match &**v {
-                            RefObject::List { data: d2 } => {
+                    if let Object::Pointer { value: w } = other {
+                        match (&**v, &**w) {
+                            (
+                                RefObject::List { data: d1, .. },
+                                RefObject::List { data: d2, .. },
+                            ) => {
                                 let mut result = d1.clone();
                                 result.extend(d2.clone());
                                 return Ok(Object::new_filled_list(result));
                             }
-                            _ => {}
-                        },
-                        _ => {}
-                    },
-                    RefObject::Bendy { data: d1 } => match other {
-                        Object::Pointer { value: v } => match &**v {
-                            RefObject::Bendy { data: d2 } => {
+                            (
+                                RefObject::Bendy { data: d1, .. },
+                                RefObject::Bendy { data: d2, .. },
+                            ) => {
                                 let mut result = d1.clone();
                                 result.extend(d2.clone());
                                 return Ok(Object::new_filled_bendy(result));
                             }
                             _ => {}
-                        },
-                        _ => {}
-                    },
-                    _ => {}
-                },
-                _ => {}
-            },
+                        }
+                    }
+                }
+            }
             Code::FloatDiv => {
                 let a: f64 = match self {
                     Object::Float { value } => *value,
@@ -464,38 +700,38 @@ impl Object {
                 };
                 return Ok(Object::Float { value: a / b });
             }
-            Code::IntDiv => {
-                let a: f64 = match self {
-                    Object::Float { value } => *value,
-                    Object::Integer { value } => *value as f64,
-                    _ => {
-                        return Err(error::create_binop_type_error(
+            // integers stay integers, with a float involved the result is a
+            // float. a zero divisor is an error for both
+            Code::IntDiv | Code::Mod => {
+                if let (Object::Integer { value: a }, Object::Integer { value: b }) = (self, other)
+                {
+                    if *b == 0 {
+                        return Err(error::create_runtime_error(
                             position,
                             code_pos_table,
                             filename,
                             source,
-                            self.get_type_name(),
-                            other.get_type_name(),
-                        ))
+                            OliveRuntimeError::DivisionByZero,
+                        ));
                     }
-                };
-                let b: f64 = match other {
-                    Object::Float { value } => *value,
-                    Object::Integer { value } => *value as f64,
-                    _ => {
-                        return Err(error::create_binop_type_error(
+                    return Ok(Object::Integer {
+                        value: Object::divide_int(*a, *b, operation),
+                    });
+                }
+                if let (Some(a), Some(b)) = (self.to_float(), other.to_float()) {
+                    if b == 0.0 {
+                        return Err(error::create_runtime_error(
                             position,
                             code_pos_table,
                             filename,
                             source,
-                            self.get_type_name(),
-                            other.get_type_name(),
-                        ))
+                            OliveRuntimeError::DivisionByZero,
+                        ));
                     }
-                };
-                return Ok(Object::Integer {
-                    value: (a / b) as i64,
-                });
+                    return Ok(Object::Float {
+                        value: Object::divide_float(a, b, operation),
+                    });
+                }
             }
             Code::Equals => {
                 return Ok(Object::Boolean {
@@ -509,44 +745,99 @@ impl Object {
             }
             _ => {}
         }
-        return Err(error::create_binop_type_error(
+        Err(error::create_binop_type_error(
             position,
             code_pos_table,
             filename,
             source,
             self.get_type_name(),
             other.get_type_name(),
-        ));
+        ))
     }
 }
 
+// the reference count lives in the same block as the value, so every
+// object is a single allocation
+struct GarbageBlock<T> {
+    refcount: usize,
+    value: T,
+}
+
+thread_local! {
+    static BLOCKS: RefCell<Pool> = RefCell::new(Pool::new(Layout::new::<GarbageBlock<RefObject>>()));
+    static ALLOCATED: Cell<u64> = const { Cell::new(0) };
+}
+
+// how many objects were ever created on this thread
+pub fn allocated_objects() -> u64 {
+    ALLOCATED.try_with(Cell::get).unwrap_or(0)
+}
+
 pub struct Garbage<T> {
-    data: *mut T,
-    refcount: *mut usize,
+    block: *mut GarbageBlock<T>,
 }
 
 impl<T: Sized> Garbage<T> {
     pub fn new(value: T) -> Self {
-        let layout = Layout::new::<T>();
-        let data;
+        let layout = Layout::new::<GarbageBlock<T>>();
+        let pooled = BLOCKS.try_with(|blocks| {
+            let mut blocks = blocks.borrow_mut();
+            if blocks.fits(layout) {
+                Some(blocks.take())
+            } else {
+                None
+            }
+        });
+        let block = match pooled {
+            Ok(Some(block)) => block,
+            _ => unsafe {
+                let block = alloc(layout);
+                if block.is_null() {
+                    handle_alloc_error(layout);
+                }
+                block
+            },
+        } as *mut GarbageBlock<T>;
         unsafe {
-            data = alloc(layout) as *mut T;
-            *data = value;
-        }
-        Garbage {
-            data,
-            refcount: Box::into_raw(Box::new(1)),
+            block.write(GarbageBlock { refcount: 1, value });
         }
+        let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + 1));
+        Garbage { block }
+    }
+}
+
+impl<T> Garbage<T> {
+    // the same for every handle to one object
+    pub fn address(&self) -> usize {
+        self.block as usize
+    }
+
+    pub fn refcount(&self) -> usize {
+        unsafe { (*self.block).refcount }
     }
 }
 
 impl<T> Drop for Garbage<T> {
     fn drop(&mut self) {
         unsafe {
-            *self.refcount -= 1;
-            if *self.refcount == 0 {
-                let layout = Layout::new::<T>();
-                dealloc(self.data as *mut u8, layout);
+            (*self.block).refcount -= 1;
+            if (*self.block).refcount == 0 {
+                std::ptr::drop_in_place(self.block);
+                let block = self.block as *mut u8;
+                let layout = Layout::new::<GarbageBlock<T>>();
+                // blocks freed while the thread shuts down are leaked
+                let pooled = BLOCKS.try_with(|blocks| {
+                    let mut blocks = blocks.borrow_mut();
+                    if blocks.fits(layout) {
+                        blocks.give(block);
+                        true
+                    } else {
+                        false
+                    }
+                });
+                if let Ok(false) = pooled {
+                    dealloc(block, layout);
+                }
             }
         }
     }
@@ -555,24 +846,21 @@ impl<T> Drop for Garbage<T> {
 impl<T> Deref for Garbage<T> {
     type Target = T;
     fn deref(&self) -> &T {
-        unsafe { self.data.as_ref().unwrap() }
+        unsafe { &(*self.block).value }
     }
 }
 
 impl<T> DerefMut for Garbage<T> {
     fn deref_mut(&mut self) -> &mut T {
-        unsafe { self.data.as_mut().unwrap() }
+        unsafe { &mut (*self.block).value }
     }
 }
 
 impl<T> Clone for Garbage<T> {
     fn clone(&self) -> Self {
         unsafe {
-            *self.refcount += 1;
-        }
-        Garbage {
-            data: self.data,
-            refcount: self.refcount,
+            (*self.block).refcount += 1;
         }
+        Garbage { block: self.block }
     }
 }
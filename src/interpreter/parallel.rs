@@ -0,0 +1,329 @@
+use super::super::codegen::{Code, CodePosTable, FunctionInfo};
+use super::super::errors::OliveError;
+use super::builtins::BUILTIN_NAMES;
+use super::object::{NativeFn, Object, RefObject};
+use super::{
+    check_call, global_scope, run, Log, LogLevel, Logger, Output, Scope, SizeLimits, BUILTINS,
+};
+use mistake::Mistake::{Fail, Fine};
+use rayon::prelude::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+// a copy of an object that can be sent to another thread. natives are
+// closures bound to their thread, they turn into none. lists and bendys keep
+// whether they are frozen
+#[derive(Clone)]
+enum Shared {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    None,
+    String(String),
+    List(Vec<Shared>, bool),
+    Bendy(HashMap<String, Shared>, bool),
+    Function {
+        args: Vec<String>,
+        codes: Vec<Code>,
+        code_pos_table: CodePosTable,
+        info: FunctionInfo,
+    },
+}
+
+impl Shared {
+    // copies the object, or gives none if it contains itself. containers on
+    // the way down are on the stack, the same one reached twice without a
+    // cycle is copied twice. names the copied functions load are added to
+    // loads
+    fn copy(
+        object: &Object,
+        on_stack: &mut Vec<usize>,
+        loads: &mut HashSet<String>,
+    ) -> Option<Self> {
+        let value = match object {
+            Object::Integer { value } => return Some(Shared::Integer(*value)),
+            Object::Float { value } => return Some(Shared::Float(*value)),
+            Object::Boolean { value } => return Some(Shared::Boolean(*value)),
+            Object::None => return Some(Shared::None),
+            Object::Pointer { value } => value,
+        };
+        if on_stack.contains(&value.address()) {
+            return None;
+        }
+        on_stack.push(value.address());
+        let shared = match &**value {
+            RefObject::String { value } => Some(Shared::String(value.clone())),
+            RefObject::List { data, frozen } => data
+                .iter()
+                .map(|element| Shared::copy(element, on_stack, loads))
+                .collect::<Option<Vec<Shared>>>()
+                .map(|data| Shared::List(data, *frozen)),
+            RefObject::Bendy { data, frozen } => data
+                .iter()
+                .map(|(key, value)| Some((key.clone(), Shared::copy(value, on_stack, loads)?)))
+                .collect::<Option<HashMap<String, Shared>>>()
+                .map(|data| Shared::Bendy(data, *frozen)),
+            RefObject::Function {
+                args,
+                codes,
+                code_pos_table,
+                info,
+            } => {
+                loaded_names(codes, loads);
+                Some(Shared::Function {
+                    args: args.clone(),
+                    codes: codes.clone(),
+                    code_pos_table: code_pos_table.clone(),
+                    info: info.clone(),
+                })
+            }
+            RefObject::Native { .. } => Some(Shared::None),
+        };
+        on_stack.pop();
+        shared
+    }
+}
+
+// the names of the variables the codes load, including those of the functions
+// they make
+fn loaded_names(codes: &[Code], loads: &mut HashSet<String>) {
+    for code in codes {
+        match code {
+            Code::Load(name) => {
+                loads.insert(name.clone());
+            }
+            Code::PushFun(_, codes, _, _) | Code::Defer(codes, _) => loaded_names(codes, loads),
+            _ => {}
+        }
+    }
+}
+
+const CONTAINS_ITSELF: &str = "par_map can't copy a value that contains itself";
+
+impl From<Shared> for Object {
+    fn from(shared: Shared) -> Self {
+        match shared {
+            Shared::Integer(value) => Object::new_integer(value),
+            Shared::Float(value) => Object::new_float(value),
+            Shared::Boolean(value) => Object::new_boolean(value),
+            Shared::None => Object::new_none(),
+            Shared::String(value) => Object::new_string(value),
+            Shared::List(data, frozen) => {
+                let list = Object::new_filled_list(data.into_iter().map(Object::from).collect());
+                if frozen {
+                    list.freeze();
+                }
+                list
+            }
+            Shared::Bendy(data, frozen) => {
+                let bendy = Object::new_filled_bendy(
+                    data.into_iter()
+                        .map(|(key, value)| (key, Object::from(value)))
+                        .collect(),
+                );
+                if frozen {
+                    bendy.freeze();
+                }
+                bendy
+            }
+            Shared::Function {
+                args,
+                codes,
+                code_pos_table,
+                info,
+            } => Object::new_function(args, codes, code_pos_table, info),
+        }
+    }
+}
+
+// what a worker starts with: the builtins the calling script has, copies of
+// the global variables the mapped function loads, directly or through the
+// functions it loads, its log level and size limits. code passed to eval
+// only sees the copied ones. the builtins bendy is made anew, copied natives
+// would turn into none
+struct Globals {
+    builtins: HashSet<String>,
+    variables: Vec<(String, Shared)>,
+    log_level: LogLevel,
+    limits: SizeLimits,
+}
+
+impl Globals {
+    fn copy(
+        scope: &Scope,
+        log_level: LogLevel,
+        mut loads: HashSet<String>,
+    ) -> Result<Self, String> {
+        let mut builtins = HashSet::new();
+        for (name, value) in &scope.variables {
+            if let Object::Pointer { value } = value {
+                if matches!(&**value, RefObject::Native { .. })
+                    && BUILTIN_NAMES.contains(&name.as_str())
+                {
+                    builtins.insert(name.clone());
+                }
+            }
+        }
+        let mut copied = HashSet::new();
+        let mut variables = Vec::new();
+        while let Some(name) = loads.iter().find(|name| !copied.contains(*name)).cloned() {
+            copied.insert(name.clone());
+            let value = match scope.variables.get(&name) {
+                Some(value) if name != BUILTINS && !builtins.contains(&name) => value,
+                _ => continue,
+            };
+            match Shared::copy(value, &mut Vec::new(), &mut loads) {
+                Some(shared) => variables.push((name, shared)),
+                None => return Err(format!("{}, global '{}' does", CONTAINS_ITSELF, name)),
+            }
+        }
+        Ok(Globals {
+            builtins,
+            variables,
+            log_level,
+            limits: scope.limits,
+        })
+    }
+}
+
+// lines printed and logged on a worker thread, kept until the calling thread
+// writes them
+#[derive(Default)]
+struct Lines {
+    printed: Vec<String>,
+    logged: Vec<String>,
+}
+
+// a global scope on a worker thread
+struct Worker {
+    scope: Rc<RefCell<Scope>>,
+    lines: Rc<RefCell<Lines>>,
+}
+
+impl Worker {
+    fn new(globals: &Globals) -> Self {
+        let lines = Rc::new(RefCell::new(Lines::default()));
+        let printed = lines.clone();
+        let output: Output = Rc::new(RefCell::new(Box::new(move |line: &str| {
+            printed.borrow_mut().printed.push(String::from(line))
+        })));
+        let logged = lines.clone();
+        let log = Rc::new(Logger::with_level(globals.log_level));
+        log.on_line(move |line| logged.borrow_mut().logged.push(String::from(line)));
+        let scope = global_scope(
+            |name| globals.builtins.contains(name),
+            output,
+            log,
+            Rc::default(),
+        );
+        scope.borrow_mut().set_limits(globals.limits);
+        for (name, value) in &globals.variables {
+            scope.borrow_mut().store(name, Object::from(value.clone()));
+        }
+        Worker { scope, lines }
+    }
+
+    fn call(
+        &self,
+        arg: &str,
+        codes: &[Code],
+        code_pos_table: &CodePosTable,
+        item: Shared,
+    ) -> (Result<Shared, String>, Lines) {
+        let scope = Rc::new(RefCell::new(Scope::from_parent(self.scope.clone())));
+        scope.borrow_mut().store(arg, Object::from(item));
+        let result = match run(codes, code_pos_table, "<par_map>", None, scope, &[]) {
+            Fine(value, _) => Shared::copy(&value, &mut Vec::new(), &mut HashSet::new())
+                .ok_or_else(|| format!("{}, a result does", CONTAINS_ITSELF)),
+            Fail(mut errors) => match errors.remove(0) {
+                OliveError::Runtime { data, .. } => Err(data.message()),
+                error => Err(error.to_string()),
+            },
+        };
+        let lines = std::mem::take(&mut *self.lines.borrow_mut());
+        (result, lines)
+    }
+}
+
+// par_map(list, function) calls the function with every element on a pool of
+// worker threads and returns the results in order. workers see copies of the
+// global variables, so changes they make are lost; lines they print or log
+// show up in order once all of them are done
+pub fn native(globals: Weak<RefCell<Scope>>, output: Output, log: Log) -> Object {
+    let closure: NativeFn = Rc::new(move |args: &[Object]| par_map(&globals, &output, &log, args));
+    Object::new_native(2, closure)
+}
+
+const EXPECTS_FUNCTION: &str = "par_map expects a function taking one argument";
+
+fn par_map(
+    globals: &Weak<RefCell<Scope>>,
+    output: &Output,
+    log: &Log,
+    args: &[Object],
+) -> Result<Object, String> {
+    let items = match &args[0] {
+        Object::Pointer { value } => match &**value {
+            RefObject::List { data, .. } => data,
+            _ => return Err(String::from("par_map expects a list")),
+        },
+        _ => return Err(String::from("par_map expects a list")),
+    };
+    let (function, arg, codes, code_pos_table) = match &args[1] {
+        Object::Pointer { value } => match &**value {
+            RefObject::Function {
+                args,
+                codes,
+                code_pos_table,
+                ..
+            } if args.len() == 1 => (value, &args[0], codes, code_pos_table),
+            // natives can't leave this thread, they are called one by one
+            RefObject::Native { arg_count, closure } if *arg_count == 1 => {
+                let results = items
+                    .iter()
+                    .map(|item| closure(std::slice::from_ref(item)))
+                    .collect::<Result<Vec<Object>, String>>()?;
+                return Ok(Object::new_filled_list(results));
+            }
+            _ => return Err(String::from(EXPECTS_FUNCTION)),
+        },
+        _ => return Err(String::from(EXPECTS_FUNCTION)),
+    };
+    // checked like a call here, workers only get the code of the function
+    for item in items {
+        if let Some(error) = check_call(function, std::slice::from_ref(item)) {
+            return Err(error.message());
+        }
+    }
+    // functions among the elements may load globals too
+    let mut loads = HashSet::new();
+    loaded_names(codes, &mut loads);
+    let items: Vec<Shared> = items
+        .iter()
+        .map(|item| Shared::copy(item, &mut Vec::new(), &mut loads))
+        .collect::<Option<Vec<Shared>>>()
+        .ok_or_else(|| format!("{}, an element does", CONTAINS_ITSELF))?;
+    let globals = match globals.upgrade() {
+        Some(scope) => Globals::copy(&scope.borrow(), log.level(), loads)?,
+        None => return Err(String::from("par_map called after its engine was dropped")),
+    };
+    let results: Vec<(Result<Shared, String>, Lines)> = items
+        .into_par_iter()
+        .map_init(
+            || Worker::new(&globals),
+            |worker, item| worker.call(arg, codes, code_pos_table, item),
+        )
+        .collect();
+    let mut values = Vec::new();
+    for (result, lines) in results {
+        for line in lines.printed {
+            (output.borrow_mut())(&line);
+        }
+        for line in lines.logged {
+            log.write_line(&line);
+        }
+        values.push(Object::from(result?));
+    }
+    Ok(Object::new_filled_list(values))
+}
@@ -0,0 +1,68 @@
+use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+
+// how many blocks are allocated at once
+const CHUNK_BLOCKS: usize = 256;
+
+// hands out memory blocks of a single layout. blocks come from chunks of many
+// blocks at once and freed blocks are kept for reuse instead of going back to
+// the allocator, so scripts that create lots of short lived objects mostly
+// recycle the same memory
+pub struct Pool {
+    layout: Layout,
+    chunks: Vec<*mut u8>,
+    free: Vec<*mut u8>,
+}
+
+impl Pool {
+    pub fn new(layout: Layout) -> Self {
+        Pool {
+            layout: layout.pad_to_align(),
+            chunks: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn fits(&self, layout: Layout) -> bool {
+        layout.pad_to_align() == self.layout
+    }
+
+    pub fn take(&mut self) -> *mut u8 {
+        if let Some(block) = self.free.pop() {
+            return block;
+        }
+        let layout = self.chunk_layout();
+        let chunk = unsafe { alloc(layout) };
+        if chunk.is_null() {
+            handle_alloc_error(layout);
+        }
+        self.chunks.push(chunk);
+        let size = self.layout.size();
+        self.free.extend(
+            (1..CHUNK_BLOCKS)
+                .rev()
+                .map(|i| unsafe { chunk.add(i * size) }),
+        );
+        chunk
+    }
+
+    pub fn give(&mut self, block: *mut u8) {
+        self.free.push(block);
+    }
+
+    fn chunk_layout(&self) -> Layout {
+        Layout::from_size_align(self.layout.size() * CHUNK_BLOCKS, self.layout.align()).unwrap()
+    }
+}
+
+// chunks can only be freed when no object lives in them anymore, otherwise
+// they are left to the process
+impl Drop for Pool {
+    fn drop(&mut self) {
+        if self.free.len() == self.chunks.len() * CHUNK_BLOCKS {
+            let layout = self.chunk_layout();
+            for chunk in &self.chunks {
+                unsafe { dealloc(*chunk, layout) };
+            }
+        }
+    }
+}
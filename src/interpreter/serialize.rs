@@ -0,0 +1,159 @@
+use super::object::{Object, RefObject};
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Formatter, Result as FmtResult};
+
+// objects serialize as plain data: lists become sequences and bendys maps.
+// functions and natives have no data representation and a list or bendy that
+// contains itself would never end, both are serialization errors
+impl Serialize for Object {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let path = RefCell::new(Vec::new());
+        Tracked {
+            object: self,
+            path: &path,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Serialize for RefObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let path = RefCell::new(vec![self as *const RefObject]);
+        serialize_ref(self, &path, serializer)
+    }
+}
+
+// remembers the containers on the way down to the current object
+struct Tracked<'a> {
+    object: &'a Object,
+    path: &'a RefCell<Vec<*const RefObject>>,
+}
+
+impl<'a> Serialize for Tracked<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.object {
+            Object::None => serializer.serialize_unit(),
+            Object::Integer { value } => serializer.serialize_i64(*value),
+            Object::Float { value } => serializer.serialize_f64(*value),
+            Object::Boolean { value } => serializer.serialize_bool(*value),
+            Object::Pointer { value } => {
+                let pointer = &**value as *const RefObject;
+                if self.path.borrow().contains(&pointer) {
+                    return Err(ser::Error::custom(
+                        "can't serialize a value that contains itself",
+                    ));
+                }
+                self.path.borrow_mut().push(pointer);
+                let result = serialize_ref(value, self.path, serializer);
+                self.path.borrow_mut().pop();
+                result
+            }
+        }
+    }
+}
+
+fn serialize_ref<S: Serializer>(
+    object: &RefObject,
+    path: &RefCell<Vec<*const RefObject>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match object {
+        RefObject::String { value } => serializer.serialize_str(value),
+        RefObject::List { data, .. } => {
+            let mut seq = serializer.serialize_seq(Some(data.len()))?;
+            for object in data {
+                seq.serialize_element(&Tracked { object, path })?;
+            }
+            seq.end()
+        }
+        RefObject::Bendy { data, .. } => {
+            let mut map = serializer.serialize_map(Some(data.len()))?;
+            for (key, object) in data {
+                map.serialize_entry(key, &Tracked { object, path })?;
+            }
+            map.end()
+        }
+        RefObject::Function { .. }
+        | RefObject::Native {
+            arg_count: _,
+            closure: _,
+        } => Err(ser::Error::custom(format!(
+            "can't serialize a {}",
+            object.get_type_name()
+        ))),
+    }
+}
+
+impl<'de> Deserialize<'de> for Object {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ObjectVisitor)
+    }
+}
+
+struct ObjectVisitor;
+
+impl<'de> Visitor<'de> for ObjectVisitor {
+    type Value = Object;
+
+    fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
+        formatter.write_str("none, a number, a boolean, a string, a list or a bendy")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Object, E> {
+        Ok(Object::new_none())
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Object, E> {
+        Ok(Object::new_none())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Object, D::Error> {
+        Object::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Object, E> {
+        Ok(Object::new_boolean(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Object, E> {
+        Ok(Object::new_integer(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Object, E> {
+        if value > i64::MAX as u64 {
+            return Err(E::custom(format!("integer {} is too large", value)));
+        }
+        Ok(Object::new_integer(value as i64))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Object, E> {
+        Ok(Object::new_float(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Object, E> {
+        Ok(Object::new_string(String::from(value)))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Object, E> {
+        Ok(Object::new_string(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Object, A::Error> {
+        let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(object) = seq.next_element()? {
+            data.push(object);
+        }
+        Ok(Object::new_filled_list(data))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Object, A::Error> {
+        let mut data = HashMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((key, object)) = map.next_entry::<String, Object>()? {
+            data.insert(key, object);
+        }
+        Ok(Object::new_filled_bendy(data))
+    }
+}
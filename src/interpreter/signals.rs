@@ -0,0 +1,160 @@
+use super::object::{NativeFn, Object, RefObject};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::SigId;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// the signals scripts can handle, with the names on_signal takes
+const SIGNALS: [(&str, i32); 2] = [("INT", SIGINT), ("TERM", SIGTERM)];
+
+// counted up by the signal handlers, which may only touch atomics. every
+// engine remembers the counts it has seen and runs its script handlers when
+// they change
+static ARRIVED: AtomicUsize = AtomicUsize::new(0);
+static RECEIVED: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+// set while no engine handles the signal, it then does what it did before
+static UNOWNED: [AtomicBool; 2] = [AtomicBool::new(true), AtomicBool::new(true)];
+
+// the engines with a handler for a signal own it. the action counting the
+// signal is registered while it has owners, and unregistered again when the
+// last one is dropped
+struct Owners {
+    count: usize,
+    action: Option<SigId>,
+    // signal-hook keeps its handler installed after the action is gone, this
+    // one runs the default action of the signal instead while it is unowned
+    fallback: bool,
+}
+
+static OWNERS: Mutex<[Owners; 2]> = Mutex::new([Owners::NONE, Owners::NONE]);
+
+impl Owners {
+    const NONE: Owners = Owners {
+        count: 0,
+        action: None,
+        fallback: false,
+    };
+}
+
+fn own(index: usize) -> Result<(), String> {
+    let mut owners = OWNERS.lock().unwrap_or_else(|e| e.into_inner());
+    let owners = &mut owners[index];
+    let signal = SIGNALS[index].1;
+    if !owners.fallback {
+        unsafe {
+            signal_hook::low_level::register(signal, move || {
+                if UNOWNED[index].load(Ordering::SeqCst) {
+                    let _ = signal_hook::low_level::emulate_default_handler(signal);
+                }
+            })
+        }
+        .map_err(|error| format!("couldn't handle signal {}: {}", SIGNALS[index].0, error))?;
+        owners.fallback = true;
+    }
+    if owners.count == 0 {
+        let action = unsafe {
+            signal_hook::low_level::register(signal, move || {
+                RECEIVED[index].fetch_add(1, Ordering::SeqCst);
+                ARRIVED.fetch_add(1, Ordering::SeqCst);
+            })
+        }
+        .map_err(|error| format!("couldn't handle signal {}: {}", SIGNALS[index].0, error))?;
+        owners.action = Some(action);
+        UNOWNED[index].store(false, Ordering::SeqCst);
+    }
+    owners.count += 1;
+    Ok(())
+}
+
+fn release(index: usize) {
+    let mut owners = OWNERS.lock().unwrap_or_else(|e| e.into_inner());
+    let owners = &mut owners[index];
+    owners.count -= 1;
+    if owners.count == 0 {
+        UNOWNED[index].store(true, Ordering::SeqCst);
+        if let Some(action) = owners.action.take() {
+            signal_hook::low_level::unregister(action);
+        }
+    }
+}
+
+// the script handlers of one engine, kept with its global scope. objects
+// can't leave their thread, so only that engine runs them, and every engine
+// with a handler for a signal runs it once the signal arrives. the engine
+// owns the signals it has handlers for until it is dropped
+#[derive(Default)]
+pub struct Handlers {
+    handlers: RefCell<[Option<Object>; 2]>,
+    received: [Cell<usize>; 2],
+    arrived: Cell<usize>,
+}
+
+impl Handlers {
+    // checked before every instruction
+    pub fn pending(&self) -> bool {
+        ARRIVED.load(Ordering::Relaxed) != self.arrived.get()
+    }
+
+    // the handlers of the signals that arrived since the last call
+    pub fn take(&self) -> Vec<Object> {
+        self.arrived.set(ARRIVED.load(Ordering::SeqCst));
+        let handlers = self.handlers.borrow();
+        let mut arrived = Vec::new();
+        for (i, handler) in handlers.iter().enumerate() {
+            let received = RECEIVED[i].load(Ordering::SeqCst);
+            if self.received[i].replace(received) != received {
+                if let Some(handler) = handler {
+                    arrived.push(handler.clone());
+                }
+            }
+        }
+        arrived
+    }
+}
+
+impl Drop for Handlers {
+    fn drop(&mut self) {
+        for (index, handler) in self.handlers.borrow().iter().enumerate() {
+            if handler.is_some() {
+                release(index);
+            }
+        }
+    }
+}
+
+const EXPECTS_FUNCTION: &str = "on_signal expects a function without parameters";
+
+// on_signal(name, handler) runs the handler, a function without parameters,
+// between two instructions once the signal arrives instead of terminating
+pub fn native(handlers: Rc<Handlers>) -> Object {
+    let closure: NativeFn = Rc::new(move |args: &[Object]| {
+        let name = match &args[0] {
+            Object::Pointer { value } => match &**value {
+                RefObject::String { value } => value.as_str(),
+                _ => "",
+            },
+            _ => "",
+        };
+        let index = match SIGNALS.iter().position(|(signal, _)| *signal == name) {
+            Some(index) => index,
+            None => return Err(String::from("on_signal expects \"INT\" or \"TERM\"")),
+        };
+        match &args[1] {
+            Object::Pointer { value } => match &**value {
+                RefObject::Function { args, .. } if args.is_empty() => {}
+                _ => return Err(String::from(EXPECTS_FUNCTION)),
+            },
+            _ => return Err(String::from(EXPECTS_FUNCTION)),
+        }
+        if handlers.handlers.borrow()[index].is_none() {
+            own(index)?;
+        }
+        // signals that arrived before aren't the new handler's
+        handlers.received[index].set(RECEIVED[index].load(Ordering::SeqCst));
+        handlers.handlers.borrow_mut()[index] = Some(args[1].clone());
+        Ok(Object::new_none())
+    });
+    Object::new_native(2, closure)
+}
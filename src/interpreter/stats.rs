@@ -0,0 +1,80 @@
+use super::super::codegen::Code;
+use super::hooks::Hooks;
+use super::object::{self, Object};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+// counters collected while scripts run. objects are freed by reference
+// counting as soon as they are unused, so there are no garbage collector runs
+// to count and gc_runs always stays 0
+pub struct Stats {
+    instructions: Cell<u64>,
+    allocations_before: u64,
+    calls: RefCell<HashMap<String, u64>>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            instructions: Cell::new(0),
+            allocations_before: object::allocated_objects(),
+            calls: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // counts every object created on this thread since the stats were made
+    fn allocations(&self) -> u64 {
+        object::allocated_objects() - self.allocations_before
+    }
+
+    pub fn to_object(&self) -> Object {
+        let calls = self
+            .calls
+            .borrow()
+            .iter()
+            .map(|(name, count)| (name.clone(), Object::new_integer(*count as i64)))
+            .collect();
+        let mut data = HashMap::new();
+        data.insert(
+            String::from("instructions"),
+            Object::new_integer(self.instructions.get() as i64),
+        );
+        data.insert(
+            String::from("allocations"),
+            Object::new_integer(self.allocations() as i64),
+        );
+        data.insert(String::from("gc_runs"), Object::new_integer(0));
+        data.insert(String::from("calls"), Object::new_filled_bendy(calls));
+        Object::new_filled_bendy(data)
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "instructions: {}\nallocations: {}\ngc runs: 0\ncalls:\n",
+            self.instructions.get(),
+            self.allocations()
+        );
+        let calls = self.calls.borrow();
+        let mut calls: Vec<(&String, &u64)> = calls.iter().collect();
+        calls.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (name, count) in calls {
+            report.push_str(&format!("    {}: {}\n", name, count));
+        }
+        report
+    }
+}
+
+impl Hooks for Stats {
+    fn on_instruction(&self, _ip: usize, _code: &Code) {
+        self.instructions.set(self.instructions.get() + 1);
+    }
+
+    fn on_call(&self, name: &str, _arg_count: usize) {
+        let mut calls = self.calls.borrow_mut();
+        if let Some(count) = calls.get_mut(name) {
+            *count += 1;
+        } else {
+            calls.insert(String::from(name), 1);
+        }
+    }
+}
@@ -1,6 +1,28 @@
-//pub mod codegen;
-// pub mod parser;
-//pub mod interpreter;
+#[macro_use]
+extern crate mistake;
+pub use mistake::Mistake;
+pub use oliveparser::ast;
 
-//pub use crate::interpreter::data::Object;
-//pub use crate::interpreter::data::RuntimeError;
+#[cfg(feature = "fs")]
+mod bytecode;
+mod codegen;
+mod engine;
+mod errors;
+mod grep;
+mod interpreter;
+mod lint;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod typecheck;
+mod value;
+
+pub use codegen::Code;
+#[cfg(feature = "fs")]
+pub use engine::{compile_file, compile_file_with_source_map, CompileCache};
+pub use engine::{Engine, EnginePolicy, Plugin};
+pub use errors::{OliveCodeError, OliveError, OliveIoError, OliveRuntimeError, TraceFrame};
+pub use grep::{AstMatch, AstPattern};
+pub use interpreter::{Hooks, LogLevel, SizeLimits};
+pub use lint::{lint, LintOptions, LintRule, LintWarning};
+pub use typecheck::{typecheck, TypeWarning};
+pub use value::{from_value, to_value, Value};
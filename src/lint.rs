@@ -0,0 +1,393 @@
+use super::errors::{OliveError, Source};
+use super::interpreter::{BUILTINS, BUILTIN_NAMES};
+use mistake::Mistake::{self, Fine};
+use oliveparser::ast::{BinaryOperator, Expression, Key, Located, Parameter, Statement};
+use oliveparser::parse;
+use oliveparser::tokens::{self, Category};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+// comments starting with this turn rules off for their own and the next line,
+// e.g. "# olv-lint: allow empty-block, unused-parameter"
+const SUPPRESS_PREFIX: &str = "olv-lint: allow";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    ShadowedBuiltin,
+    CompareToBoolean,
+    EmptyBlock,
+    UnusedParameter,
+    DeepNesting,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 5] = [
+        LintRule::ShadowedBuiltin,
+        LintRule::CompareToBoolean,
+        LintRule::EmptyBlock,
+        LintRule::UnusedParameter,
+        LintRule::DeepNesting,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LintRule::ShadowedBuiltin => "shadowed-builtin",
+            LintRule::CompareToBoolean => "compare-to-boolean",
+            LintRule::EmptyBlock => "empty-block",
+            LintRule::UnusedParameter => "unused-parameter",
+            LintRule::DeepNesting => "deep-nesting",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        LintRule::ALL
+            .iter()
+            .copied()
+            .find(|rule| rule.name() == name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LintOptions {
+    allowed: HashSet<LintRule>,
+    max_depth: usize,
+}
+
+impl LintOptions {
+    pub fn allow(&mut self, rule: LintRule) {
+        self.allowed.insert(rule);
+    }
+
+    // blocks nested deeper than this are reported by deep-nesting
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        LintOptions {
+            allowed: HashSet::new(),
+            max_depth: 4,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LintWarning {
+    pub rule: LintRule,
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl Display for LintWarning {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "warning (in '{}'): at ln {} col {}: {} [{}]",
+            self.file,
+            self.line,
+            self.col,
+            self.message,
+            self.rule.name()
+        )
+    }
+}
+
+// fails only if the source doesn't parse
+pub fn lint(
+    text: &str,
+    filename: &str,
+    options: &LintOptions,
+) -> Mistake<Vec<LintWarning>, OliveError> {
+    let mut errors = Vec::new();
+    let source = Source::new(text);
+    let ast = attempt_res!(
+        parse(text).map_err(|err| OliveError::from_parse_err(err, filename, &source)),
+        errors
+    );
+    let mut linter = Linter {
+        text,
+        options,
+        found: Vec::new(),
+    };
+    linter.block(&ast, 0);
+    let suppressed = suppressed_lines(&source);
+    let mut warnings: Vec<LintWarning> = linter
+        .found
+        .into_iter()
+        .filter(|(rule, _, _)| !options.allowed.contains(rule))
+        .map(|(rule, start, message)| {
+            let (line, col) = source.get_line_and_column(start);
+            LintWarning {
+                rule,
+                file: String::from(filename),
+                line,
+                col,
+                message,
+            }
+        })
+        .filter(|warning| !suppressed.contains(&(warning.line, warning.rule)))
+        .collect();
+    warnings.sort_by_key(|warning| (warning.line, warning.col));
+    Fine(warnings, errors)
+}
+
+// every (line, rule) pair switched off by a comment
+fn suppressed_lines(source: &Source) -> HashSet<(usize, LintRule)> {
+    let mut suppressed = HashSet::new();
+    for token in tokens::tokenize(source.text) {
+        if token.category != Category::Comment {
+            continue;
+        }
+        let comment = token.text.trim_start_matches('#').trim();
+        if let Some(rules) = comment.strip_prefix(SUPPRESS_PREFIX) {
+            let (line, _) = source.get_line_and_column(token.start);
+            for rule in rules
+                .split(',')
+                .filter_map(|name| LintRule::from_name(name.trim()))
+            {
+                suppressed.insert((line, rule));
+                suppressed.insert((line + 1, rule));
+            }
+        }
+    }
+    suppressed
+}
+
+struct Linter<'o> {
+    text: &'o str,
+    options: &'o LintOptions,
+    found: Vec<(LintRule, usize, String)>,
+}
+
+impl<'o> Linter<'o> {
+    fn warn(&mut self, rule: LintRule, start: usize, message: String) {
+        self.found.push((rule, start, message));
+    }
+
+    fn block(&mut self, statements: &[Located<Statement>], depth: usize) {
+        for statement in statements {
+            self.statement(statement, depth);
+        }
+    }
+
+    // the block of an if, while, function or a bare block, owned by the
+    // statement or expression starting at start
+    fn nested(&mut self, statements: &[Located<Statement>], depth: usize, start: usize) {
+        if statements.is_empty() {
+            self.warn(LintRule::EmptyBlock, start, String::from("empty block"));
+        }
+        if depth == self.options.max_depth + 1 {
+            self.warn(
+                LintRule::DeepNesting,
+                start,
+                format!(
+                    "block is nested {} levels deep (at most {} allowed)",
+                    depth, self.options.max_depth
+                ),
+            );
+        }
+        self.block(statements, depth);
+    }
+
+    fn statement(&mut self, statement: &Located<Statement>, depth: usize) {
+        match &statement.inner {
+            Statement::Break | Statement::Continue => {}
+            Statement::Return { value } => self.expression(value, depth),
+            Statement::Block { statements } => self.nested(statements, depth + 1, statement.start),
+            Statement::While { condition, block } => {
+                self.expression(condition, depth);
+                self.nested(block, depth + 1, statement.start);
+            }
+            Statement::If {
+                condition,
+                block,
+                elseblock,
+            } => {
+                self.expression(condition, depth);
+                self.nested(block, depth + 1, statement.start);
+                match elseblock.as_deref() {
+                    // the parser turns "else if" into an else block holding
+                    // just the chained if, which stays on the same level
+                    Some([chained]) if self.text[..chained.start].trim_end().ends_with("else") => {
+                        self.statement(chained, depth)
+                    }
+                    Some(elseblock) => self.nested(elseblock, depth + 1, statement.start),
+                    None => {}
+                }
+            }
+            Statement::Assign { left, right } => {
+                if let Expression::Variable { name } = left.inner {
+                    if BUILTIN_NAMES.contains(&name) || name == BUILTINS {
+                        self.warn(
+                            LintRule::ShadowedBuiltin,
+                            left.start,
+                            format!("assignment shadows the builtin '{}'", name),
+                        );
+                    }
+                } else {
+                    self.expression(left, depth);
+                }
+                self.expression(right, depth);
+            }
+            Statement::Call { expression, args } => {
+                self.expression(expression, depth);
+                for arg in args {
+                    self.expression(arg, depth);
+                }
+            }
+            Statement::Pipe { pipe } => self.expression(pipe, depth),
+            Statement::Defer { statement } => self.statement(statement, depth),
+        }
+    }
+
+    fn expression(&mut self, expression: &Located<Expression>, depth: usize) {
+        match &expression.inner {
+            Expression::List { elements } => {
+                for element in elements {
+                    self.expression(element, depth);
+                }
+            }
+            Expression::Bendy { elements } => {
+                for (key, element) in elements {
+                    if let Key::Computed(key) = key {
+                        self.expression(key, depth);
+                    }
+                    self.expression(element, depth);
+                }
+            }
+            Expression::Binary {
+                left,
+                right,
+                operator,
+            } => {
+                if let BinaryOperator::Equals | BinaryOperator::NotEquals = operator {
+                    for side in [left, right].iter() {
+                        if let Expression::Boolean { value } = side.inner {
+                            self.warn(
+                                LintRule::CompareToBoolean,
+                                expression.start,
+                                format!("comparison to {}, use the condition itself", value),
+                            );
+                        }
+                    }
+                }
+                self.expression(left, depth);
+                if let BinaryOperator::Access | BinaryOperator::OptionalAccess = operator {
+                } else {
+                    self.expression(right, depth);
+                }
+            }
+            Expression::Unary { expression, .. } => self.expression(expression, depth),
+            Expression::Index {
+                expression, index, ..
+            } => {
+                self.expression(expression, depth);
+                self.expression(index, depth);
+            }
+            Expression::Call { expression, args } => {
+                self.expression(expression, depth);
+                for arg in args {
+                    self.expression(arg, depth);
+                }
+            }
+            Expression::Pipe { value, function } => {
+                self.expression(value, depth);
+                self.expression(function, depth);
+            }
+            Expression::Function { parameters, block } => {
+                for Parameter { name, .. } in parameters {
+                    if !name.inner.starts_with('_') && !block_reads(block, name.inner) {
+                        self.warn(
+                            LintRule::UnusedParameter,
+                            name.start,
+                            format!("parameter '{}' is never used", name.inner),
+                        );
+                    }
+                }
+                self.nested(block, depth + 1, expression.start);
+            }
+            Expression::Integer { .. }
+            | Expression::Float { .. }
+            | Expression::String { .. }
+            | Expression::Boolean { .. }
+            | Expression::None
+            | Expression::Variable { .. } => {}
+        }
+    }
+}
+
+fn block_reads(statements: &[Located<Statement>], name: &str) -> bool {
+    statements
+        .iter()
+        .any(|statement| statement_reads(statement, name))
+}
+
+fn statement_reads(statement: &Located<Statement>, name: &str) -> bool {
+    match &statement.inner {
+        Statement::Break | Statement::Continue => false,
+        Statement::Return { value } => reads(value, name),
+        Statement::Block { statements } => block_reads(statements, name),
+        Statement::While { condition, block } => reads(condition, name) || block_reads(block, name),
+        Statement::If {
+            condition,
+            block,
+            elseblock,
+        } => {
+            reads(condition, name)
+                || block_reads(block, name)
+                || elseblock
+                    .as_ref()
+                    .is_some_and(|elseblock| block_reads(elseblock, name))
+        }
+        // assigning to a variable doesn't read it, assigning into it does
+        Statement::Assign { left, right } => {
+            let left_reads = match left.inner {
+                Expression::Variable { .. } => false,
+                _ => reads(left, name),
+            };
+            left_reads || reads(right, name)
+        }
+        Statement::Call { expression, args } => {
+            reads(expression, name) || args.iter().any(|arg| reads(arg, name))
+        }
+        Statement::Pipe { pipe } => reads(pipe, name),
+        Statement::Defer { statement } => statement_reads(statement, name),
+    }
+}
+
+fn reads(expression: &Located<Expression>, name: &str) -> bool {
+    match &expression.inner {
+        Expression::Variable { name: variable } => *variable == name,
+        Expression::List { elements } => elements.iter().any(|element| reads(element, name)),
+        Expression::Bendy { elements } => elements.iter().any(|(key, element)| {
+            matches!(key, Key::Computed(key) if reads(key, name)) || reads(element, name)
+        }),
+        Expression::Binary {
+            left,
+            right,
+            operator: BinaryOperator::Access | BinaryOperator::OptionalAccess,
+        } => {
+            reads(left, name)
+                || !matches!(right.inner, Expression::Variable { .. }) && reads(right, name)
+        }
+        Expression::Binary { left, right, .. } => reads(left, name) || reads(right, name),
+        Expression::Unary { expression, .. } => reads(expression, name),
+        Expression::Index {
+            expression, index, ..
+        } => reads(expression, name) || reads(index, name),
+        Expression::Call { expression, args } => {
+            reads(expression, name) || args.iter().any(|arg| reads(arg, name))
+        }
+        Expression::Pipe { value, function } => reads(value, name) || reads(function, name),
+        Expression::Function { block, .. } => block_reads(block, name),
+        Expression::Integer { .. }
+        | Expression::Float { .. }
+        | Expression::String { .. }
+        | Expression::Boolean { .. }
+        | Expression::None => false,
+    }
+}
@@ -1,183 +1,455 @@
-use clap::{App, Arg};
-use oliveparser::parse;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 #[macro_use]
 extern crate mistake;
 use mistake::Mistake::{self, Fail, Fine};
 
-mod codegen;
-mod errors;
-mod interpreter;
-use errors::{OliveError, OliveIoError};
+use olive::{
+    compile_file, compile_file_with_source_map, lint, typecheck, AstPattern, CompileCache, Engine,
+    EnginePolicy, LintOptions, LintRule, LogLevel, OliveError, OliveIoError, Value,
+};
 
-fn get_codes(
-    contents: Vec<u8>,
-    compile: bool,
-    in_path_str: &str,
-) -> Mistake<
-    (
-        bool,
-        Vec<codegen::Code>,
-        HashMap<usize, usize>,
-        Option<String>,
-    ),
-    OliveError,
-> {
-    let mut errors = Vec::new();
-    let in_path = Path::new(in_path_str);
-    match in_path.extension() {
-        Some(x) if x == "olv" => {
-            let str_contents: &str = attempt_res!(
-                std::str::from_utf8(&contents).map_err(|_| OliveError::Io {
-                    file: String::from(in_path_str),
-                    kind: OliveIoError::UTF,
-                }),
-                errors
-            );
-            let ast = parse(str_contents);
-            let (codes, code_pos) = attempt!(
-                codegen::generate_codes(
-                    attempt_res!(
-                        ast.map_err(|err| OliveError::from_parse_err(
-                            err,
-                            in_path_str,
-                            str_contents
-                        )),
-                        errors
-                    ),
-                    in_path_str,
-                    str_contents
-                ),
-                errors
-            );
-            Fine(
-                (
-                    !compile,
-                    vec![
-                        codegen::Code::PushFun(Vec::new(), codes),
-                        codegen::Code::Call,
-                        codegen::Code::Return,
-                    ],
-                    code_pos,
-                    Some(String::from(str_contents)),
-                ),
-                errors,
-            )
-        }
-        Some(x) if x == "olvc" => {
-            if !compile {
-                let codes = attempt_res!(
-                    bincode::deserialize(&contents).map_err(|_| {
-                        OliveError::Io {
-                            file: String::from(in_path_str),
-                            kind: OliveIoError::Deserialize,
-                        }
-                    }),
-                    errors
-                );
-                Fine((true, codes, HashMap::new(), None), errors)
-            } else {
-                errors.push(OliveError::Io {
-                    kind: OliveIoError::CompileCompiled,
-                    file: String::from(in_path_str),
-                });
-                Fail(errors)
-            }
-        }
-        _ => {
-            errors.push(OliveError::Io {
-                file: String::from(in_path_str),
-                kind: OliveIoError::Extension,
-            });
-            Fail(errors)
-        }
-    }
-}
+mod repl;
 
-fn run<'a>() -> Mistake<(), OliveError> {
+fn run() -> Mistake<(), OliveError> {
     let mut errors = Vec::new();
     let matches = App::new("olv")
         .about("OliveScript interpreter and compiler")
         .author("Ian Rehwinkel")
         .version("0.2.0")
-        .arg(Arg::with_name("INPUT").required(true))
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::TrailingVarArg)
+        .arg(Arg::with_name("INPUT").help("script to run, starts a repl if left out"))
+        .arg(
+            Arg::with_name("ARGS")
+                .multiple(true)
+                .allow_hyphen_values(true)
+                .requires("INPUT")
+                .help("arguments for the script, it gets them from argv()"),
+        )
         .arg(
             Arg::with_name("compile")
                 .short("c")
                 .long("compile")
-                .help("produce binary instead of running file"),
+                .requires("INPUT")
+                .help("produce binary instead of running file, or one for every script in a directory"),
+        )
+        .arg(
+            Arg::with_name("source-map")
+                .long("source-map")
+                .requires("compile")
+                .help("also write a .olvc.map file so errors in the binary point at the source"),
         )
         .arg(
             Arg::with_name("output")
                 .short("o")
                 .value_name("output")
                 .long("output")
-                .help("output file path"),
+                .help("output file path, or directory when compiling a directory"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .help("discard everything the script prints"),
+        )
+        .arg(
+            Arg::with_name("capture")
+                .long("capture")
+                .value_name("file")
+                .conflicts_with("quiet")
+                .help("write everything the script prints to a file"),
+        )
+        .arg(
+            Arg::with_name("compile-cache")
+                .long("compile-cache")
+                .conflicts_with("compile")
+                .help("reuse the compiled script from a .olvcache directory next to it"),
+        )
+        .arg(
+            Arg::with_name("cache-dir")
+                .long("cache-dir")
+                .value_name("dir")
+                .conflicts_with("compile")
+                .help("like --compile-cache, but keep compiled scripts in dir"),
+        )
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .help("print instruction, allocation and call counts when done"),
+        )
+        .arg(
+            Arg::with_name("inspect")
+                .long("inspect")
+                .value_name("address")
+                .help("answer lines of code sent to the address, e.g. 127.0.0.1:9229, while the script runs"),
+        )
+        .arg(
+            Arg::with_name("inspect-remote")
+                .long("inspect-remote")
+                .requires("inspect")
+                .help("let --inspect listen on an address other machines can reach, anyone connecting can run code"),
+        )
+        .arg(
+            Arg::with_name("heap-dump-on-exit")
+                .long("heap-dump-on-exit")
+                .value_name("file")
+                .help("write the objects still reachable when the script ends to a json file"),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .long("log-level")
+                .value_name("level")
+                .env("OLV_LOG_LEVEL")
+                .possible_values(
+                    &LogLevel::ALL
+                        .iter()
+                        .map(|level| level.name())
+                        .collect::<Vec<_>>(),
+                )
+                .case_insensitive(true)
+                .help("drop messages the script logs below this level (default info)"),
+        )
+        .arg(
+            Arg::with_name("log-file")
+                .long("log-file")
+                .value_name("file")
+                .env("OLV_LOG_FILE")
+                .help("append messages the script logs to a file instead of stderr"),
+        )
+        .subcommand(
+            SubCommand::with_name("lint")
+                .about("check a file for suspicious code")
+                .arg(Arg::with_name("INPUT").required(true))
+                .arg(
+                    Arg::with_name("allow")
+                        .long("allow")
+                        .value_name("rule")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .possible_values(
+                            &LintRule::ALL
+                                .iter()
+                                .map(|rule| rule.name())
+                                .collect::<Vec<_>>(),
+                        )
+                        .help("turn off a rule"),
+                )
+                .arg(
+                    Arg::with_name("max-depth")
+                        .long("max-depth")
+                        .value_name("depth")
+                        .validator(|depth| {
+                            depth
+                                .parse::<usize>()
+                                .map(|_| ())
+                                .map_err(|_| String::from("must be a number"))
+                        })
+                        .help("how deeply blocks may be nested (default 4)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("typecheck")
+                .about("check a file for operations on values of the wrong type")
+                .arg(Arg::with_name("INPUT").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("ast-grep")
+                .about("search scripts for code by its structure, e.g. 'print($X $ $Y)'")
+                .arg(
+                    Arg::with_name("PATTERN")
+                        .required(true)
+                        .help("code to find, $ and an uppercase name matches any expression"),
+                )
+                .arg(
+                    Arg::with_name("INPUT")
+                        .required(true)
+                        .multiple(true)
+                        .help("scripts, or directories to search every .olv file below"),
+                ),
         )
         .get_matches();
+    if let Some(matches) = matches.subcommand_matches("lint") {
+        return run_lint(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("typecheck") {
+        return run_typecheck(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("ast-grep") {
+        return run_ast_grep(matches);
+    }
+    let in_path = matches.value_of("INPUT");
+    if !matches.is_present("compile") {
+        // scripts run by olv own the process, so they may handle signals
+        let mut policy = EnginePolicy::allow_all().allow_signals();
+        if matches.is_present("inspect-remote") {
+            policy = policy.allow_remote_inspector();
+        }
+        let mut engine = Engine::with_policy(policy);
+        if matches.is_present("quiet") {
+            engine.on_print(|_| {});
+        } else if let Some(capture_path) = matches.value_of("capture") {
+            let mut capture_file = attempt_res!(
+                File::create(capture_path).map_err(|_| OliveError::Io {
+                    file: String::from(capture_path),
+                    kind: OliveIoError::OpenWrite,
+                }),
+                errors
+            );
+            engine.on_print(move |line| {
+                let _ = writeln!(capture_file, "{}", line);
+            });
+        }
+        if let Some(level) = matches.value_of("log-level") {
+            // clap only accepts the names of the levels
+            engine.set_log_level(level.parse().unwrap());
+        }
+        if let Some(log_path) = matches.value_of("log-file") {
+            let mut log_file = attempt_res!(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(log_path)
+                    .map_err(|_| OliveError::Io {
+                        file: String::from(log_path),
+                        kind: OliveIoError::OpenWrite,
+                    }),
+                errors
+            );
+            engine.on_log(move |line| {
+                let _ = writeln!(log_file, "{}", line);
+            });
+        }
+        engine.set_args(matches.values_of("ARGS").into_iter().flatten());
+        if let Some(cache_dir) = matches.value_of("cache-dir") {
+            engine.enable_compile_cache(CompileCache::Dir(PathBuf::from(cache_dir)));
+        } else if matches.is_present("compile-cache") {
+            engine.enable_compile_cache(CompileCache::NextToScript);
+        }
+        if matches.is_present("stats") {
+            engine.enable_stats();
+        }
+        if let Some(address) = matches.value_of("inspect") {
+            attempt_res!(engine.enable_inspector(address), errors);
+        }
+        let result = match in_path {
+            Some(in_path_str) => engine.run_file(in_path_str),
+            None => {
+                repl::run(&mut engine);
+                Fine(Value::None, Vec::new())
+            }
+        };
+        if let Some(report) = engine.stats_report() {
+            eprint!("{}", report);
+        }
+        // written whether the script failed or not, leaks matter either way
+        if let Some(dump_path) = matches.value_of("heap-dump-on-exit") {
+            attempt_res!(
+                std::fs::write(dump_path, engine.heap_snapshot()).map_err(|_| OliveError::Io {
+                    file: String::from(dump_path),
+                    kind: OliveIoError::Write,
+                }),
+                errors
+            );
+        }
+        attempt!(result, errors);
+    } else {
+        let in_path_str = in_path.unwrap();
+        let source_map = matches.is_present("source-map");
+        if Path::new(in_path_str).is_dir() {
+            // the .olvc files mirror the directory, they are written next to
+            // the sources unless an output directory is given
+            let out_dir = matches.value_of("output").unwrap_or(in_path_str);
+            attempt!(
+                compile_dir(Path::new(in_path_str), Path::new(out_dir), source_map),
+                errors
+            );
+        } else {
+            let out_path = match matches.value_of("output") {
+                Some(val) => PathBuf::from(val),
+                None => PathBuf::from(format!(
+                    "{}c",
+                    Path::new(in_path_str)
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                )),
+            };
+            attempt!(write_compiled(in_path_str, &out_path, source_map), errors);
+        }
+    }
+    Fine((), errors)
+}
+
+// writes the source map next to the binary as <binary>.map if asked to
+fn write_compiled<P: AsRef<Path>>(
+    in_path: P,
+    out_path: &Path,
+    source_map: bool,
+) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    if source_map {
+        let (binary, map) = attempt!(compile_file_with_source_map(in_path), errors);
+        attempt!(write_binary(out_path, &binary), errors);
+        let mut map_path = out_path.as_os_str().to_owned();
+        map_path.push(".map");
+        attempt!(write_binary(Path::new(&map_path), &map), errors);
+    } else {
+        let binary = attempt!(compile_file(in_path), errors);
+        attempt!(write_binary(out_path, &binary), errors);
+    }
+    Fine((), errors)
+}
+
+// compiles every .olv file in the directory and the ones below it, a file
+// that fails doesn't stop the others from being compiled
+fn compile_dir(dir: &Path, out_dir: &Path, source_map: bool) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    let read_error = || OliveError::Io {
+        file: dir.to_string_lossy().into_owned(),
+        kind: OliveIoError::OpenRead,
+    };
+    let entries = attempt_res!(std::fs::read_dir(dir).map_err(|_| read_error()), errors);
+    let mut paths: Vec<PathBuf> = attempt_res!(
+        entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()
+            .map_err(|_| read_error()),
+        errors
+    );
+    // sorted so errors are reported in the same order on every system
+    paths.sort();
+    let mut fine = true;
+    for path in paths {
+        let out_path = out_dir.join(path.file_name().unwrap());
+        let compiled = if path.is_dir() {
+            compile_dir(&path, &out_path, source_map)
+        } else if path.extension().is_some_and(|extension| extension == "olv") {
+            write_compiled(&path, &out_path.with_extension("olvc"), source_map)
+        } else {
+            continue;
+        };
+        if compiled.to_option(&mut errors).is_none() {
+            fine = false;
+        }
+    }
+    if fine {
+        Fine((), errors)
+    } else {
+        Fail(errors)
+    }
+}
+
+fn write_binary(out_path: &Path, binary: &[u8]) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    let io_error = |kind| OliveError::Io {
+        file: out_path.to_string_lossy().into_owned(),
+        kind,
+    };
+    if let Some(dir) = out_path.parent() {
+        attempt_res!(
+            std::fs::create_dir_all(dir).map_err(|_| io_error(OliveIoError::OpenWrite)),
+            errors
+        );
+    }
+    let mut out_file = attempt_res!(
+        File::create(out_path).map_err(|_| io_error(OliveIoError::OpenWrite)),
+        errors
+    );
+    attempt_res!(
+        out_file
+            .write_all(binary)
+            .map_err(|_| io_error(OliveIoError::Write)),
+        errors
+    );
+    Fine((), errors)
+}
+
+fn run_lint(matches: &ArgMatches) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
     let in_path_str: &str = matches.value_of("INPUT").unwrap();
-    let in_path = Path::new(in_path_str);
-    let mut file = attempt_res!(
-        File::open(in_path).map_err(|_| OliveError::Io {
+    let mut options = LintOptions::default();
+    for name in matches.values_of("allow").into_iter().flatten() {
+        options.allow(LintRule::from_name(name).unwrap());
+    }
+    if let Some(depth) = matches.value_of("max-depth") {
+        options.set_max_depth(depth.parse().unwrap());
+    }
+    let text = attempt_res!(
+        std::fs::read_to_string(in_path_str).map_err(|_| OliveError::Io {
             file: String::from(in_path_str),
             kind: OliveIoError::OpenRead,
         }),
         errors
     );
-    let mut contents: Vec<u8> = Vec::new();
-    attempt_res!(
-        file.read_to_end(&mut contents).map_err(|_| OliveError::Io {
+    for warning in attempt!(lint(&text, in_path_str, &options), errors) {
+        println!("{}", warning);
+    }
+    Fine((), errors)
+}
+
+fn run_typecheck(matches: &ArgMatches) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    let in_path_str: &str = matches.value_of("INPUT").unwrap();
+    let text = attempt_res!(
+        std::fs::read_to_string(in_path_str).map_err(|_| OliveError::Io {
             file: String::from(in_path_str),
-            kind: OliveIoError::Read,
+            kind: OliveIoError::OpenRead,
         }),
         errors
     );
-    let (should_run, codes, code_pos_table, source) = attempt!(
-        get_codes(contents, matches.is_present("compile"), in_path_str),
+    for warning in attempt!(typecheck(&text, in_path_str), errors) {
+        println!("{}", warning);
+    }
+    Fine((), errors)
+}
+
+fn run_ast_grep(matches: &ArgMatches) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    let pattern = attempt!(
+        AstPattern::new(matches.value_of("PATTERN").unwrap()),
         errors
     );
-    if should_run {
-        attempt!(
-            interpreter::start(&codes, &code_pos_table, in_path_str, source.as_deref()),
-            errors
-        );
-    } else {
-        let out_path = match matches.value_of("output") {
-            Some(val) => val.to_string(),
-            None => format!(
-                "{}c",
-                Path::new(in_path_str)
-                    .file_name()
-                    .unwrap()
-                    .to_string_lossy()
-            ),
-        };
-        let mut out_file = attempt_res!(
-            File::create(&out_path).map_err(|_| OliveError::Io {
-                file: String::from(&out_path),
-                kind: OliveIoError::OpenWrite,
-            }),
-            errors
-        );
-        attempt_res!(
-            out_file
-                .write(&attempt_res!(
-                    bincode::serialize(&codes).map_err(|_| OliveError::Io {
-                        file: String::from(&out_path),
-                        kind: OliveIoError::Serialize,
-                    }),
-                    errors
-                ))
-                .map_err(|_| OliveError::Io {
-                    file: String::from(&out_path),
-                    kind: OliveIoError::Write,
-                }),
+    for in_path_str in matches.values_of("INPUT").unwrap() {
+        // a script that can't be searched doesn't stop the others
+        let _ = ast_grep_path(&pattern, Path::new(in_path_str)).to_option(&mut errors);
+    }
+    Fine((), errors)
+}
+
+fn ast_grep_path(pattern: &AstPattern, path: &Path) -> Mistake<(), OliveError> {
+    let mut errors = Vec::new();
+    let read_error = || OliveError::Io {
+        file: path.to_string_lossy().into_owned(),
+        kind: OliveIoError::OpenRead,
+    };
+    if !path.is_dir() {
+        let text = attempt_res!(
+            std::fs::read_to_string(path).map_err(|_| read_error()),
             errors
         );
+        let file = path.to_string_lossy();
+        for found in attempt!(pattern.search(&text, &file), errors) {
+            println!("{}", found);
+        }
+        return Fine((), errors);
+    }
+    let entries = attempt_res!(std::fs::read_dir(path).map_err(|_| read_error()), errors);
+    let mut paths: Vec<PathBuf> = attempt_res!(
+        entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()
+            .map_err(|_| read_error()),
+        errors
+    );
+    paths.sort();
+    for path in paths {
+        if path.is_dir() || path.extension().is_some_and(|extension| extension == "olv") {
+            let _ = ast_grep_path(pattern, &path).to_option(&mut errors);
+        }
     }
     Fine((), errors)
 }
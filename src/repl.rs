@@ -0,0 +1,116 @@
+use mistake::Mistake::{Fail, Fine};
+use olive::{Engine, Value};
+use oliveparser::{ParseError, SyntaxError};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
+use std::path::PathBuf;
+
+const PROMPT: &str = ">> ";
+
+// completes variable and builtin names, the names are taken from the global
+// scope after every evaluated entry
+struct ReplHelper {
+    names: Vec<String>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        // names behind a dot are bendy keys, not variables
+        if line[..start].ends_with('.') {
+            return Ok((pos, Vec::new()));
+        }
+        let prefix = &line[start..pos];
+        let candidates = self
+            .names
+            .iter()
+            .filter(|name| name.starts_with(prefix))
+            .cloned()
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+// an entry that ends too early, e.g. inside a block, goes on in the next line
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(match oliveparser::parse(ctx.input()) {
+            Err(ParseError::UnrecognizedEOF { .. })
+            | Err(ParseError::User {
+                error: SyntaxError::UnterminatedComment { .. },
+            }) => ValidationResult::Incomplete,
+            _ => ValidationResult::Valid(None),
+        })
+    }
+}
+
+impl Helper for ReplHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".olv_history"))
+}
+
+// reads entries until ctrl-d, globals stay defined between entries. the value
+// of a top level return is printed unless it is none
+pub fn run(engine: &mut Engine) {
+    let mut editor = Editor::<ReplHelper>::new();
+    editor.set_helper(Some(ReplHelper {
+        names: engine.global_names(),
+    }));
+    let history = history_path();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+    loop {
+        let entry = match editor.readline(PROMPT) {
+            Ok(entry) => entry,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(_) => break,
+        };
+        if entry.trim().is_empty() {
+            continue;
+        }
+        editor.add_history_entry(entry.as_str());
+        // saved after every entry so nothing is lost however the repl exits
+        if let Some(history) = &history {
+            let _ = editor.save_history(history);
+        }
+        match engine.eval_str(&entry) {
+            Fine(value, errors) => {
+                for err in errors {
+                    println!("{}", err);
+                }
+                if value != Value::None {
+                    println!("{}", value);
+                }
+            }
+            Fail(errors) => {
+                for err in errors {
+                    println!("{}", err);
+                }
+            }
+        }
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = engine.global_names();
+        }
+    }
+}
@@ -0,0 +1,27 @@
+// helpers for the tests of this crate and of the native modules, which run
+// scripts in an engine and only care about the result or the first error
+use super::engine::Engine;
+use super::value::Value;
+use mistake::Mistake::{Fail, Fine};
+
+// the value the script returned, panics on any error
+pub fn eval(engine: &mut Engine, source: &str) -> Value {
+    match engine.eval_str(source) {
+        Fine(value, errors) if errors.is_empty() => value,
+        Fine(_, errors) | Fail(errors) => panic!("{}", errors[0]),
+    }
+}
+
+// the message of the first error, panics if the script ran without one
+pub fn error(engine: &mut Engine, source: &str) -> String {
+    match engine.eval_str(source) {
+        Fine(_, errors) | Fail(errors) => errors
+            .first()
+            .map(|error| error.to_string())
+            .expect("the script ran without errors"),
+    }
+}
+
+pub fn string(value: &str) -> Value {
+    Value::String(String::from(value))
+}
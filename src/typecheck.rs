@@ -0,0 +1,742 @@
+use super::errors::{OliveError, OliveRuntimeError, Source};
+use super::interpreter::{BUILTINS, BUILTIN_NAMES};
+use mistake::Mistake::{self, Fine};
+use oliveparser::ast::{
+    BinaryOperator, Expression, Key, Located, Parameter, Statement, UnaryOperator,
+};
+use oliveparser::parse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::rc::Rc;
+
+// what the checker knows about a value. anything it can't follow, like the
+// result of a native or a variable from the caller of a function, is Any and
+// never warned about
+#[derive(Clone, Debug, PartialEq)]
+enum Type {
+    Any,
+    Integer,
+    Float,
+    Boolean,
+    None,
+    String,
+    List,
+    // the keys are known for bendys made by a literal that weren't passed
+    // anywhere they could have been changed since
+    Bendy(Option<BTreeMap<String, Type>>),
+    Function(Option<Rc<FunctionType>>),
+    Native,
+}
+
+#[derive(Debug, PartialEq)]
+struct FunctionType {
+    name: Option<String>,
+    parameters: Vec<Type>,
+    returns: Type,
+}
+
+impl Type {
+    // the name the interpreter gives values of the type
+    fn name(&self) -> Option<&'static str> {
+        Some(match self {
+            Type::Any => return None,
+            Type::Integer => "integer",
+            Type::Float => "float",
+            Type::Boolean => "boolean",
+            Type::None => "none",
+            Type::String => "string",
+            Type::List => "list",
+            Type::Bendy(_) => "bendy",
+            Type::Function(_) => "function",
+            Type::Native => "native",
+        })
+    }
+
+    fn from_name(name: &str) -> Type {
+        match name {
+            "integer" => Type::Integer,
+            "float" => Type::Float,
+            "boolean" => Type::Boolean,
+            "none" => Type::None,
+            "string" => Type::String,
+            "list" => Type::List,
+            "bendy" => Type::Bendy(None),
+            "function" => Type::Function(None),
+            "native" => Type::Native,
+            _ => Type::Any,
+        }
+    }
+
+    // a value that is one of the two
+    fn join(self, other: Type) -> Type {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Type::Bendy(_), Type::Bendy(_)) => Type::Bendy(None),
+            (Type::Function(_), Type::Function(_)) => Type::Function(None),
+            _ => Type::Any,
+        }
+    }
+}
+
+// the result of a binary operator, or none if the interpreter would fail
+// for the operands
+fn binary(operator: &BinaryOperator, left: &Type, right: &Type) -> Option<Type> {
+    use Type::*;
+    Some(match operator {
+        BinaryOperator::Equals | BinaryOperator::NotEquals => Boolean,
+        BinaryOperator::Add | BinaryOperator::Sub | BinaryOperator::Mul => match (left, right) {
+            (Integer, Integer) => Integer,
+            (Integer | Float, Integer | Float) => Float,
+            (Integer | Float | Any, Integer | Float | Any) => Any,
+            _ => return Option::None,
+        },
+        BinaryOperator::LessThan
+        | BinaryOperator::LessEquals
+        | BinaryOperator::GreaterThan
+        | BinaryOperator::GreaterEquals => match (left, right) {
+            (Integer, Integer) | (Float, Float) => Boolean,
+            (Any, Integer | Float | Any) | (Integer | Float, Any) => Boolean,
+            _ => return Option::None,
+        },
+        BinaryOperator::BitAnd
+        | BinaryOperator::BitOr
+        | BinaryOperator::BitXOr
+        | BinaryOperator::BitLsh
+        | BinaryOperator::BitRsh => match (left, right) {
+            (Integer | Any, Integer | Any) => Integer,
+            _ => return Option::None,
+        },
+        BinaryOperator::FloatDiv => match (left, right) {
+            (Integer | Float | Any, Integer | Float | Any) => Float,
+            _ => return Option::None,
+        },
+        BinaryOperator::IntDiv | BinaryOperator::Mod => match (left, right) {
+            (Integer, Integer) => Integer,
+            (Integer | Float, Integer | Float) => Float,
+            (Integer | Float | Any, Integer | Float | Any) => Any,
+            _ => return Option::None,
+        },
+        BinaryOperator::Concat => match (left, right) {
+            (String, _) => String,
+            (List, List | Any) => List,
+            (Bendy(Some(a)), Bendy(Some(b))) => {
+                let mut keys = a.clone();
+                keys.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+                Bendy(Some(keys))
+            }
+            (Bendy(_), Bendy(_) | Any) => Bendy(Option::None),
+            (Any, _) => Any,
+            _ => return Option::None,
+        },
+        // handled where they are checked
+        BinaryOperator::BoolAnd
+        | BinaryOperator::BoolOr
+        | BinaryOperator::Access
+        | BinaryOperator::OptionalAccess => Any,
+    })
+}
+
+#[derive(Debug)]
+pub struct TypeWarning {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl Display for TypeWarning {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "warning (in '{}'): at ln {} col {}: {}",
+            self.file, self.line, self.col, self.message
+        )
+    }
+}
+
+// reports operations that will probably fail when the code runs. it follows
+// the types of variables through the code and only warns where it knows the
+// types involved, so it misses errors rather than reporting working code.
+// fails only if the source doesn't parse
+pub fn typecheck(text: &str, filename: &str) -> Mistake<Vec<TypeWarning>, OliveError> {
+    let mut errors = Vec::new();
+    let source = Source::new(text);
+    let ast = attempt_res!(
+        parse(text).map_err(|err| OliveError::from_parse_err(err, filename, &source)),
+        errors
+    );
+    let mut checker = Checker {
+        state: State::default(),
+        loops: Vec::new(),
+        returns: Vec::new(),
+        quiet: 0,
+        found: Vec::new(),
+    };
+    checker.block(&ast);
+    let mut found = checker.found;
+    found.sort();
+    found.dedup();
+    let warnings = found
+        .into_iter()
+        .map(|(start, message)| {
+            let (line, col) = source.get_line_and_column(start);
+            TypeWarning {
+                file: String::from(filename),
+                line,
+                col,
+                message,
+            }
+        })
+        .collect();
+    Fine(warnings, errors)
+}
+
+// the variables at a point in the code. after a return, break or continue
+// the point can't be reached and its variables don't count
+#[derive(Clone, Debug)]
+struct State<'a> {
+    vars: HashMap<&'a str, Type>,
+    reachable: bool,
+}
+
+impl<'a> Default for State<'a> {
+    fn default() -> Self {
+        State {
+            vars: HashMap::new(),
+            reachable: true,
+        }
+    }
+}
+
+impl<'a> State<'a> {
+    // the state after either of two paths, a variable only one of them
+    // assigned may not exist
+    fn join(self, other: State<'a>) -> State<'a> {
+        if !self.reachable {
+            return other;
+        }
+        if !other.reachable {
+            return self;
+        }
+        let mut vars = HashMap::new();
+        for (name, t) in self.vars {
+            let joined = match other.vars.get(name) {
+                Some(other) => t.join(other.clone()),
+                None => Type::Any,
+            };
+            vars.insert(name, joined);
+        }
+        for name in other.vars.keys() {
+            vars.entry(*name).or_insert(Type::Any);
+        }
+        State {
+            vars,
+            reachable: true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Loop<'a> {
+    breaks: Vec<State<'a>>,
+    continues: Vec<State<'a>>,
+}
+
+struct Checker<'a> {
+    state: State<'a>,
+    loops: Vec<Loop<'a>>,
+    // the returned types of every function being checked, innermost last
+    returns: Vec<Vec<Type>>,
+    // above zero while a loop body is checked the first time
+    quiet: usize,
+    found: Vec<(usize, String)>,
+}
+
+impl<'a> Checker<'a> {
+    fn warn(&mut self, start: usize, message: String) {
+        if self.quiet == 0 {
+            self.found.push((start, message));
+        }
+    }
+
+    fn warn_type(&mut self, start: usize, expected: &[&str], got: &Type) {
+        if let Some(got) = got.name() {
+            let message = OliveRuntimeError::IncorrectType {
+                expected: expected.iter().map(|s| String::from(*s)).collect(),
+                got: String::from(got),
+            }
+            .message();
+            self.warn(start, message);
+        }
+    }
+
+    fn load(&self, name: &str) -> Type {
+        match self.state.vars.get(name) {
+            Some(t) => t.clone(),
+            None if name == BUILTINS => Type::Bendy(None),
+            None if BUILTIN_NAMES.contains(&name) => Type::Native,
+            None => Type::Any,
+        }
+    }
+
+    // a bendy that is passed on may get keys where the checker can't see it
+    fn escape(&mut self, expression: &Located<Expression<'a>>) {
+        if let Expression::Variable { name } = expression.inner {
+            if let Some(Type::Bendy(keys)) = self.state.vars.get_mut(name) {
+                *keys = None;
+            }
+        }
+    }
+
+    // a function may change any bendy it can reach
+    fn forget_keys(&mut self) {
+        for t in self.state.vars.values_mut() {
+            if let Type::Bendy(keys) = t {
+                *keys = None;
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[Located<Statement<'a>>]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Located<Statement<'a>>) {
+        match &statement.inner {
+            Statement::Break => {
+                if let Some(current) = self.loops.last_mut() {
+                    current.breaks.push(self.state.clone());
+                }
+                self.state.reachable = false;
+            }
+            Statement::Continue => {
+                if let Some(current) = self.loops.last_mut() {
+                    current.continues.push(self.state.clone());
+                }
+                self.state.reachable = false;
+            }
+            Statement::Return { value } => {
+                let t = self.expression(value);
+                if let Some(returns) = self.returns.last_mut() {
+                    returns.push(t);
+                }
+                self.state.reachable = false;
+            }
+            Statement::Block { statements } => self.block(statements),
+            Statement::While { condition, block } => self.loop_statement(condition, block),
+            Statement::If {
+                condition,
+                block,
+                elseblock,
+            } => {
+                self.expression(condition);
+                let before = self.state.clone();
+                self.block(block);
+                let after_block = std::mem::replace(&mut self.state, before);
+                if let Some(elseblock) = elseblock {
+                    self.block(elseblock);
+                }
+                let after_else = std::mem::take(&mut self.state);
+                self.state = after_block.join(after_else);
+            }
+            Statement::Assign { left, right } => self.assign(left, right),
+            Statement::Call { expression, args } => {
+                self.call(expression, statement.start, None, args);
+            }
+            Statement::Defer { statement } => {
+                // runs later, so it doesn't change what follows
+                let before = self.state.clone();
+                self.statement(statement);
+                self.state = before;
+            }
+            Statement::Pipe { pipe } => {
+                self.expression(pipe);
+            }
+        }
+    }
+
+    // the body is checked quietly once to find what the variables may be at
+    // the start of later iterations, then again with those to report
+    fn loop_statement(
+        &mut self,
+        condition: &Located<Expression<'a>>,
+        block: &[Located<Statement<'a>>],
+    ) {
+        let entry = self.state.clone();
+        self.quiet += 1;
+        self.expression(condition);
+        self.loops.push(Loop::default());
+        self.block(block);
+        let first = self.loops.pop().unwrap_or_default();
+        self.quiet -= 1;
+        let mut head = entry.join(std::mem::take(&mut self.state));
+        for state in first.continues {
+            head = head.join(state);
+        }
+        self.state = head;
+        self.expression(condition);
+        let after_condition = self.state.clone();
+        self.loops.push(Loop::default());
+        self.block(block);
+        let second = self.loops.pop().unwrap_or_default();
+        let mut exit = after_condition.join(std::mem::take(&mut self.state));
+        for state in second.breaks {
+            exit = exit.join(state);
+        }
+        self.state = exit;
+    }
+
+    fn assign(&mut self, left: &Located<Expression<'a>>, right: &Located<Expression<'a>>) {
+        // both names refer to the same bendy afterwards
+        self.escape(right);
+        let t = match &right.inner {
+            Expression::Function { parameters, block } => {
+                self.function(assigned_name(left), parameters, block)
+            }
+            _ => self.expression(right),
+        };
+        match &left.inner {
+            Expression::Variable { name } => {
+                self.state.vars.insert(name, t);
+            }
+            Expression::Binary {
+                left: object,
+                right: key,
+                operator: BinaryOperator::Access,
+            } => {
+                let object_type = self.expression(object);
+                match (&object_type, &key.inner) {
+                    (Type::Bendy(Some(_)), Expression::Variable { name: key }) => {
+                        self.add_key(object, key, t)
+                    }
+                    (Type::Bendy(_), _) | (Type::Any, _) => {}
+                    (other, _) => self.warn_type(object.start, &["bendy"], other),
+                }
+            }
+            Expression::Index {
+                expression: object,
+                index,
+                ..
+            } => {
+                let object_type = self.expression(object);
+                let index_type = self.expression(index);
+                match &object_type {
+                    Type::List => self.check_index(index.start, "integer", &index_type),
+                    Type::Bendy(keys) => {
+                        self.check_index(index.start, "string", &index_type);
+                        match &index.inner {
+                            Expression::String { value } if keys.is_some() => {
+                                self.add_key(object, value, t)
+                            }
+                            _ => self.escape(object),
+                        }
+                    }
+                    Type::Any => {}
+                    other => self.warn_type(object.start, &["list", "bendy"], other),
+                }
+            }
+            // the compiler reports anything else
+            _ => {}
+        }
+    }
+
+    fn add_key(&mut self, object: &Located<Expression<'a>>, key: &str, t: Type) {
+        if let Expression::Variable { name } = object.inner {
+            if let Some(Type::Bendy(Some(keys))) = self.state.vars.get_mut(name) {
+                keys.insert(String::from(key), t);
+            }
+        }
+    }
+
+    fn check_index(&mut self, start: usize, expected: &str, got: &Type) {
+        if got.name().is_some_and(|name| name != expected) {
+            self.warn_type(start, &[expected], got);
+        }
+    }
+
+    fn function(
+        &mut self,
+        name: Option<&str>,
+        parameters: &[Parameter<'a>],
+        block: &[Located<Statement<'a>>],
+    ) -> Type {
+        let parameter_types: Vec<Type> = parameters
+            .iter()
+            .map(|parameter| match &parameter.type_name {
+                Some(type_name) => Type::from_name(type_name.inner),
+                None => Type::Any,
+            })
+            .collect();
+        // the body sees the variables of whoever calls it, which can't be
+        // known here, so it starts out with only the parameters
+        let mut inner = State::default();
+        for (parameter, t) in parameters.iter().zip(&parameter_types) {
+            inner.vars.insert(parameter.name.inner, t.clone());
+        }
+        let outer = std::mem::replace(&mut self.state, inner);
+        let loops = std::mem::take(&mut self.loops);
+        self.returns.push(Vec::new());
+        self.block(block);
+        let mut returns = self.returns.pop().unwrap_or_default();
+        if self.state.reachable {
+            returns.push(Type::None);
+        }
+        self.state = outer;
+        self.loops = loops;
+        let returns = returns.into_iter().reduce(Type::join).unwrap_or(Type::Any);
+        Type::Function(Some(Rc::new(FunctionType {
+            name: name.map(String::from),
+            parameters: parameter_types,
+            returns,
+        })))
+    }
+
+    // a call of the function with the arguments, the piped value in front
+    // of them if there is one
+    fn call(
+        &mut self,
+        function: &Located<Expression<'a>>,
+        start: usize,
+        piped: Option<(Type, &Located<Expression<'a>>)>,
+        args: &[Located<Expression<'a>>],
+    ) -> Type {
+        let mut arg_types = Vec::new();
+        if let Some((t, value)) = piped {
+            arg_types.push((t, value.start));
+            self.escape(value);
+        }
+        for arg in args {
+            let t = self.expression(arg);
+            arg_types.push((t, arg.start));
+            self.escape(arg);
+        }
+        match self.expression(function) {
+            Type::Function(Some(function)) => {
+                self.forget_keys();
+                if function.parameters.len() != arg_types.len() {
+                    let message = OliveRuntimeError::CallArgs {
+                        function: function.name.clone(),
+                        expected: function.parameters.len(),
+                        got: arg_types.len(),
+                    }
+                    .message();
+                    self.warn(start, message);
+                    return Type::Any;
+                }
+                for (expected, (got, _)) in function.parameters.iter().zip(&arg_types) {
+                    if let (Some(expected), Some(got_name)) = (expected.name(), got.name()) {
+                        if expected != got_name {
+                            self.warn_type(start, &[expected], got);
+                        }
+                    }
+                }
+                function.returns.clone()
+            }
+            Type::Native => Type::Any,
+            Type::Function(None) | Type::Any => {
+                self.forget_keys();
+                Type::Any
+            }
+            other => {
+                self.warn_type(start, &["function", "native"], &other);
+                Type::Any
+            }
+        }
+    }
+
+    // reading a key or an element, optional for ?. and ?[
+    fn get(
+        &mut self,
+        start: usize,
+        object: &Type,
+        key: Option<&str>,
+        index: &Type,
+        optional: bool,
+    ) -> Type {
+        match object {
+            Type::Any => Type::Any,
+            Type::None if optional => Type::None,
+            Type::List => {
+                self.check_index(start, "integer", index);
+                Type::Any
+            }
+            Type::String => {
+                self.check_index(start, "integer", index);
+                if optional {
+                    Type::Any
+                } else {
+                    Type::String
+                }
+            }
+            Type::Bendy(keys) => {
+                self.check_index(start, "string", index);
+                match (keys, key) {
+                    (Some(keys), Some(key)) => match keys.get(key) {
+                        Some(t) => t.clone(),
+                        None if optional => Type::None,
+                        None => {
+                            self.warn(start, format!("bendy has no key '{}'", key));
+                            Type::Any
+                        }
+                    },
+                    _ => Type::Any,
+                }
+            }
+            other => {
+                self.warn_type(start, &["list", "string", "bendy"], other);
+                Type::Any
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Located<Expression<'a>>) -> Type {
+        match &expression.inner {
+            Expression::Integer { .. } => Type::Integer,
+            Expression::Float { .. } => Type::Float,
+            Expression::String { .. } => Type::String,
+            Expression::Boolean { .. } => Type::Boolean,
+            Expression::None => Type::None,
+            Expression::Variable { name } => self.load(name),
+            Expression::List { elements } => {
+                for element in elements {
+                    self.expression(element);
+                    self.escape(element);
+                }
+                Type::List
+            }
+            Expression::Bendy { elements } => {
+                let mut keys = Some(BTreeMap::new());
+                for (key, element) in elements {
+                    let name = match key {
+                        Key::Name(name) => Some(String::from(name.inner)),
+                        Key::Computed(key) => {
+                            self.expression(key);
+                            match &key.inner {
+                                Expression::String { value } => Some(value.clone()),
+                                _ => None,
+                            }
+                        }
+                    };
+                    let t = self.expression(element);
+                    self.escape(element);
+                    match (&mut keys, name) {
+                        (Some(known), Some(name)) => {
+                            known.insert(name, t);
+                        }
+                        _ => keys = None,
+                    }
+                }
+                Type::Bendy(keys)
+            }
+            Expression::Binary {
+                left,
+                right,
+                operator: operator @ (BinaryOperator::Access | BinaryOperator::OptionalAccess),
+            } => {
+                let object = self.expression(left);
+                let key = match right.inner {
+                    Expression::Variable { name } => Some(name),
+                    _ => None,
+                };
+                let optional = matches!(operator, BinaryOperator::OptionalAccess);
+                self.get(expression.start, &object, key, &Type::String, optional)
+            }
+            Expression::Binary {
+                left,
+                right,
+                operator: BinaryOperator::BoolAnd | BinaryOperator::BoolOr,
+            } => {
+                self.expression(left);
+                // the right side only runs sometimes
+                let before = self.state.clone();
+                let t = self.expression(right);
+                self.state = std::mem::take(&mut self.state).join(before);
+                Type::Boolean.join(t)
+            }
+            Expression::Binary {
+                left,
+                right,
+                operator,
+            } => {
+                let left_type = self.expression(left);
+                let right_type = self.expression(right);
+                match binary(operator, &left_type, &right_type) {
+                    Some(t) => t,
+                    None => {
+                        if let (Some(left), Some(right)) = (left_type.name(), right_type.name()) {
+                            let message = OliveRuntimeError::UnmatchingTypes {
+                                left: String::from(left),
+                                right: String::from(right),
+                            }
+                            .message();
+                            self.warn(expression.start, message);
+                        }
+                        Type::Any
+                    }
+                }
+            }
+            Expression::Unary {
+                expression: operand,
+                operator,
+            } => {
+                let t = self.expression(operand);
+                match operator {
+                    UnaryOperator::BoolNot => Type::Boolean,
+                    UnaryOperator::Neg => match t {
+                        Type::Integer | Type::Float | Type::Any => t,
+                        other => {
+                            self.warn_type(expression.start, &["integer", "float"], &other);
+                            Type::Any
+                        }
+                    },
+                }
+            }
+            Expression::Index {
+                expression: object,
+                index,
+                optional,
+            } => {
+                let object_type = self.expression(object);
+                let index_type = self.expression(index);
+                let key = match &index.inner {
+                    Expression::String { value } => Some(value.as_str()),
+                    _ => None,
+                };
+                self.get(expression.start, &object_type, key, &index_type, *optional)
+            }
+            Expression::Call {
+                expression: function,
+                args,
+            } => self.call(function, expression.start, None, args),
+            Expression::Pipe { value, function } => {
+                let t = self.expression(value);
+                match &function.inner {
+                    Expression::Call {
+                        expression: callee,
+                        args,
+                    } => self.call(callee, function.start, Some((t, value)), args),
+                    _ => self.call(function, function.start, Some((t, value)), &[]),
+                }
+            }
+            Expression::Function { parameters, block } => self.function(None, parameters, block),
+        }
+    }
+}
+
+// the name a function assigned to left is called by in errors
+fn assigned_name<'a>(left: &Located<Expression<'a>>) -> Option<&'a str> {
+    match &left.inner {
+        Expression::Variable { name } => Some(name),
+        Expression::Binary {
+            operator: BinaryOperator::Access,
+            right,
+            ..
+        } => match right.inner {
+            Expression::Variable { name } => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
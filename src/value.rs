@@ -0,0 +1,374 @@
+use super::interpreter::{format_float, Object, RefObject};
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::ser::{self, SerializeMap, SerializeSeq, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value as Json};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+// the result of running a script, copied out of the interpreter's objects
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    None,
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    List(Vec<Value>),
+    Bendy(HashMap<String, Value>),
+    Function,
+}
+
+// a list or bendy inside itself is copied as none in that place, a value
+// can't contain itself
+impl From<&Object> for Value {
+    fn from(object: &Object) -> Self {
+        copy_object(object, &mut Vec::new())
+    }
+}
+
+// on_stack has the addresses of the containers being copied further up
+fn copy_object(object: &Object, on_stack: &mut Vec<usize>) -> Value {
+    let value = match object {
+        Object::None => return Value::None,
+        Object::Integer { value } => return Value::Integer(*value),
+        Object::Float { value } => return Value::Float(*value),
+        Object::Boolean { value } => return Value::Boolean(*value),
+        Object::Pointer { value } => value,
+    };
+    if on_stack.contains(&value.address()) {
+        return Value::None;
+    }
+    on_stack.push(value.address());
+    let copied = match &**value {
+        RefObject::String { value } => Value::String(value.clone()),
+        RefObject::List { data, .. } => Value::List(
+            data.iter()
+                .map(|element| copy_object(element, on_stack))
+                .collect(),
+        ),
+        RefObject::Bendy { data, .. } => Value::Bendy(
+            data.iter()
+                .map(|(key, value)| (key.clone(), copy_object(value, on_stack)))
+                .collect(),
+        ),
+        RefObject::Function { .. }
+        | RefObject::Native {
+            arg_count: _,
+            closure: _,
+        } => Value::Function,
+    };
+    on_stack.pop();
+    copied
+}
+
+// functions can't be passed back into a script, they turn into none
+impl From<Value> for Object {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::None | Value::Function => Object::new_none(),
+            Value::Integer(value) => Object::new_integer(value),
+            Value::Float(value) => Object::new_float(value),
+            Value::Boolean(value) => Object::new_boolean(value),
+            Value::String(value) => Object::new_string(value),
+            Value::List(data) => {
+                Object::new_filled_list(data.into_iter().map(Object::from).collect())
+            }
+            Value::Bendy(data) => Object::new_filled_bendy(
+                data.into_iter()
+                    .map(|(key, value)| (key, Object::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Value {
+    pub fn get_type_name(&self) -> &'static str {
+        match self {
+            Value::None => "none",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Boolean(_) => "boolean",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Bendy(_) => "bendy",
+            Value::Function => "function",
+        }
+    }
+}
+
+// formatted the same way print formats objects
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Value::None => write!(f, "none"),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", format_float(*value)),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::List(data) => write!(
+                f,
+                "[{}]",
+                data.iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Value::Bendy(data) => write!(
+                f,
+                "{{{}}}",
+                data.iter()
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Value::Function => write!(f, "function"),
+        }
+    }
+}
+
+fn type_error(expected: &str, got: &Value) -> String {
+    format!(
+        "expected type {}, got type {}",
+        expected,
+        got.get_type_name()
+    )
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::None
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Integer(value as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(String::from(value))
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => value.into(),
+            None => Value::None,
+        }
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(data: Vec<T>) -> Self {
+        Value::List(data.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+    fn from(data: HashMap<String, T>) -> Self {
+        Value::Bendy(
+            data.into_iter()
+                .map(|(key, value)| (key, value.into()))
+                .collect(),
+        )
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Integer(value) => Ok(value),
+            other => Err(type_error("integer", &other)),
+        }
+    }
+}
+
+// integers are accepted as well, like in arithmetic inside scripts
+impl TryFrom<Value> for f64 {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Float(value) => Ok(value),
+            Value::Integer(value) => Ok(value as f64),
+            other => Err(type_error("float", &other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Boolean(value) => Ok(value),
+            other => Err(type_error("boolean", &other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::String(value) => Ok(value),
+            other => Err(type_error("string", &other)),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = String>> TryFrom<Value> for Option<T> {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::None => Ok(None),
+            other => T::try_from(other).map(Some),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = String>> TryFrom<Value> for Vec<T> {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::List(data) => data.into_iter().map(T::try_from).collect(),
+            other => Err(type_error("list", &other)),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = String>> TryFrom<Value> for HashMap<String, T> {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Bendy(data) => data
+                .into_iter()
+                .map(|(key, value)| Ok((key, T::try_from(value)?)))
+                .collect(),
+            other => Err(type_error("bendy", &other)),
+        }
+    }
+}
+
+// values serialize as plain data like the objects they were copied from
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::None => serializer.serialize_unit(),
+            Value::Integer(value) => serializer.serialize_i64(*value),
+            Value::Float(value) => serializer.serialize_f64(*value),
+            Value::Boolean(value) => serializer.serialize_bool(*value),
+            Value::String(value) => serializer.serialize_str(value),
+            Value::List(data) => {
+                let mut seq = serializer.serialize_seq(Some(data.len()))?;
+                for value in data {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Value::Bendy(data) => {
+                let mut map = serializer.serialize_map(Some(data.len()))?;
+                for (key, value) in data {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Value::Function => Err(ser::Error::custom("can't serialize a function")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Object::deserialize(deserializer).map(|object| Value::from(&object))
+    }
+}
+
+impl From<Json> for Value {
+    fn from(json: Json) -> Self {
+        match json {
+            Json::Null => Value::None,
+            Json::Bool(value) => Value::Boolean(value),
+            Json::Number(number) => match number.as_i64() {
+                Some(value) => Value::Integer(value),
+                None => Value::Float(number.as_f64().unwrap_or(f64::NAN)),
+            },
+            Json::String(value) => Value::String(value),
+            Json::Array(data) => Value::List(data.into_iter().map(Value::from).collect()),
+            Json::Object(data) => Value::Bendy(
+                data.into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl TryFrom<Value> for Json {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, String> {
+        Ok(match value {
+            Value::None => Json::Null,
+            Value::Integer(value) => Json::Number(Number::from(value)),
+            Value::Float(value) => match Number::from_f64(value) {
+                Some(number) => Json::Number(number),
+                None => return Err(format!("can't convert float {}", value)),
+            },
+            Value::Boolean(value) => Json::Bool(value),
+            Value::String(value) => Json::String(value),
+            Value::List(data) => Json::Array(
+                data.into_iter()
+                    .map(Json::try_from)
+                    .collect::<Result<Vec<Json>, String>>()?,
+            ),
+            Value::Bendy(data) => {
+                let mut map = Map::new();
+                for (key, value) in data {
+                    map.insert(key, Json::try_from(value)?);
+                }
+                Json::Object(map)
+            }
+            Value::Function => return Err(String::from("can't convert a function")),
+        })
+    }
+}
+
+// converts any serializable rust value, structs become bendies
+pub fn to_value<T: Serialize>(value: &T) -> Result<Value, String> {
+    serde_json::to_value(value)
+        .map(Value::from)
+        .map_err(|e| format!("{}", e))
+}
+
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, String> {
+    serde_json::from_value(Json::try_from(value)?).map_err(|e| format!("{}", e))
+}
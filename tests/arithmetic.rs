@@ -0,0 +1,246 @@
+use mistake::Mistake::{Fail, Fine};
+use olive::{Engine, Value};
+
+fn eval(expression: &str) -> Result<Value, String> {
+    let mut engine = Engine::new();
+    match engine.eval_str(&format!("return {};", expression)) {
+        Fine(value, errors) if errors.is_empty() => Ok(value),
+        Fine(_, errors) | Fail(errors) => Err(errors[0].to_string()),
+    }
+}
+
+fn check(cases: &[(&str, Value)]) {
+    for (expression, expected) in cases {
+        assert_eq!(eval(expression).as_ref(), Ok(expected), "{}", expression);
+    }
+}
+
+#[test]
+fn int_floor_division() {
+    check(&[
+        ("7 // 2", Value::Integer(3)),
+        ("-7 // 2", Value::Integer(-4)),
+        ("7 // -2", Value::Integer(-4)),
+        ("-7 // -2", Value::Integer(3)),
+        ("6 // 3", Value::Integer(2)),
+        ("-6 // 3", Value::Integer(-2)),
+        ("0 // 5", Value::Integer(0)),
+        // exact for values a float can't hold
+        ("9007199254740993 // 1", Value::Integer(9007199254740993)),
+        (
+            "(0 - 9223372036854775807 - 1) // -1",
+            Value::Integer(i64::MIN),
+        ),
+    ]);
+}
+
+#[test]
+fn int_overflow_wraps() {
+    check(&[
+        ("9223372036854775807 + 1", Value::Integer(i64::MIN)),
+        ("-9223372036854775808 - 1", Value::Integer(i64::MAX)),
+        ("9223372036854775807 * 2", Value::Integer(-2)),
+        ("-9223372036854775808 * -1", Value::Integer(i64::MIN)),
+        ("-(-9223372036854775808)", Value::Integer(i64::MIN)),
+        ("-9223372036854775808 // -1", Value::Integer(i64::MIN)),
+        ("-9223372036854775808 % -1", Value::Integer(0)),
+    ]);
+}
+
+#[test]
+fn int_modulo() {
+    check(&[
+        ("7 % 3", Value::Integer(1)),
+        ("-7 % 3", Value::Integer(2)),
+        ("7 % -3", Value::Integer(-2)),
+        ("-7 % -3", Value::Integer(-1)),
+        ("6 % 3", Value::Integer(0)),
+        ("-6 % 3", Value::Integer(0)),
+        ("(0 - 9223372036854775807 - 1) % -1", Value::Integer(0)),
+    ]);
+}
+
+#[test]
+fn float_floor_division() {
+    check(&[
+        ("7.5 // 2.", Value::Float(3.0)),
+        ("-7.5 // 2.", Value::Float(-4.0)),
+        ("7.5 // -2.", Value::Float(-4.0)),
+        ("-7.5 // -2.", Value::Float(3.0)),
+        ("7 // 2.", Value::Float(3.0)),
+        ("7.5 // 2", Value::Float(3.0)),
+        ("-7 // 2.", Value::Float(-4.0)),
+        // 1 / 0.1 rounds up to 10, but 0.1 is slightly above one tenth
+        ("1 // 0.1", Value::Float(9.0)),
+    ]);
+}
+
+#[test]
+fn float_modulo() {
+    check(&[
+        ("7.5 % 2.", Value::Float(1.5)),
+        ("-7.5 % 2.", Value::Float(0.5)),
+        ("7.5 % -2.", Value::Float(-0.5)),
+        ("-7.5 % -2.", Value::Float(-1.5)),
+        ("7 % 2.5", Value::Float(2.0)),
+        ("-7.5 % 2", Value::Float(0.5)),
+    ]);
+}
+
+#[test]
+fn division_identity() {
+    for a in -9..=9 {
+        for b in [-4, -3, -1, 1, 2, 5].iter() {
+            let expression = format!("({a} // {b}) * {b} + {a} % {b}", a = a, b = b);
+            assert_eq!(eval(&expression), Ok(Value::Integer(a)), "{}", expression);
+        }
+    }
+}
+
+#[test]
+fn division_by_zero() {
+    for expression in [
+        "1 // 0",
+        "1 % 0",
+        "1. // 0",
+        "1 % 0.",
+        "1.5 // 0.",
+        "-1 % 0.",
+    ]
+    .iter()
+    {
+        let error = eval(expression).unwrap_err();
+        assert!(
+            error.contains("division by zero"),
+            "{}: {}",
+            expression,
+            error
+        );
+    }
+    // true division stays ieee, only // and % fail
+    assert_eq!(eval("1 / 0"), Ok(Value::Float(f64::INFINITY)));
+}
+
+#[test]
+fn non_numbers() {
+    for expression in ["\"a\" // 2", "2 % none", "true // 1"].iter() {
+        let error = eval(expression).unwrap_err();
+        assert!(
+            error.contains("operation not supported"),
+            "{}: {}",
+            expression,
+            error
+        );
+    }
+}
+
+#[test]
+fn integer_literal_range() {
+    check(&[
+        ("9223372036854775807", Value::Integer(i64::MAX)),
+        ("-9223372036854775808", Value::Integer(i64::MIN)),
+        ("-(9223372036854775807)", Value::Integer(-i64::MAX)),
+        ("-128", Value::Integer(-128)),
+        ("--129", Value::Integer(129)),
+        ("00000000000000000000042", Value::Integer(42)),
+    ]);
+    for expression in ["9223372036854775808", "-9223372036854775809"].iter() {
+        let error = eval(expression).unwrap_err();
+        assert!(error.contains("to integer"), "{}: {}", expression, error);
+    }
+}
+
+#[test]
+fn number_literals() {
+    check(&[
+        ("1_000_000", Value::Integer(1000000)),
+        ("0xFF_FF", Value::Integer(65535)),
+        ("0Xab", Value::Integer(171)),
+        ("-0x8000_0000_0000_0000", Value::Integer(i64::MIN)),
+        ("1_0.2_5", Value::Float(10.25)),
+    ]);
+    for expression in ["1__0", "10_", "1_.5", "0x_1"].iter() {
+        let error = eval(expression).unwrap_err();
+        assert!(
+            error.contains("misplaced underscore"),
+            "{}: {}",
+            expression,
+            error
+        );
+    }
+}
+
+#[test]
+fn format_number_and_case() {
+    let string = |text: &str| Value::String(String::from(text));
+    check(&[
+        (
+            "format_number(1234567, new {thousands: \",\"})",
+            string("1,234,567"),
+        ),
+        (
+            "format_number(-1234567.891, new {thousands: \".\", point: \",\", decimals: 2})",
+            string("-1.234.567,89"),
+        ),
+        ("format_number(999, new {thousands: \",\"})", string("999")),
+        (
+            "format_number(9007199254740993, new {thousands: \" \", decimals: 1})",
+            string("9 007 199 254 740 993.0"),
+        ),
+        (
+            "format_number(1.0 / 0, new {thousands: \",\"})",
+            string("inf"),
+        ),
+        ("format_number(1, new {decimals: 3})", string("1.000")),
+        (
+            "format_number(100000000000000000000000000000.0, new {thousands: \",\"})",
+            string("100,000,000,000,000,000,000,000,000,000.0"),
+        ),
+        ("format_number(0.0000001, new {})", string("0.0000001")),
+        ("format_number(-0.5, new {decimals: 0})", string("0")),
+        ("format_number(-0.0, new {})", string("0.0")),
+        ("format_number(\"1\", new {})", Value::None),
+        ("format_number(1, none)", Value::None),
+        ("format_number(1, new {thousands: 1})", Value::None),
+        ("format_number(1, new {decimals: -1})", Value::None),
+        ("upper(\"straße\")", string("STRASSE")),
+        ("lower(\"ÀÉÎ\")", string("àéî")),
+    ]);
+    for expression in [
+        "format_number(1, new {decimals: 100000000000})",
+        "format_number(1.5, new {decimals: 1075})",
+    ]
+    .iter()
+    {
+        let error = eval(expression).unwrap_err();
+        assert!(
+            error.contains("format_number expects decimals to be at most 1074"),
+            "{}: {}",
+            expression,
+            error
+        );
+    }
+}
+
+#[test]
+fn format_digits() {
+    let string = |text: &str| Value::String(String::from(text));
+    check(&[
+        ("format(3.14159, 2)", string("3.14")),
+        ("format(2, 1)", string("2.0")),
+        ("format(1.0, -1)", Value::None),
+    ]);
+    assert_eq!(
+        eval("format(1.0, 1074)").map(|value| match value {
+            Value::String(text) => text.len(),
+            _ => 0,
+        }),
+        Ok(1076)
+    );
+    let error = eval("format(1.0, 100000000000)").unwrap_err();
+    assert!(
+        error.contains("format expects at most 1074 digits"),
+        "{}",
+        error
+    );
+}
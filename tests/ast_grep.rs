@@ -0,0 +1,75 @@
+use mistake::Mistake::{Fail, Fine};
+use olive::AstPattern;
+
+fn search(pattern: &str, source: &str) -> Vec<String> {
+    let pattern = match AstPattern::new(pattern) {
+        Fine(pattern, _) => pattern,
+        Fail(errors) => panic!("{:?}", errors),
+    };
+    match pattern.search(source, "search.olv") {
+        Fine(matches, _) => matches
+            .into_iter()
+            .map(|found| format!("{}: {}", found.line, found.text))
+            .collect(),
+        Fail(errors) => panic!("{:?}", errors),
+    }
+}
+
+#[test]
+fn patterns_match_expressions_by_structure() {
+    let source = "name = \"olive\";\n\
+                  print(\"hi \"   $ name);\n\
+                  show = fun(a) { print(a $ a); return a $ name; };\n\
+                  x = show(1) + show(1) + show(2);";
+    assert_eq!(
+        search("print($X $ $Y)", source),
+        vec!["2: print(\"hi \"   $ name);", "3: print(a $ a);"]
+    );
+    assert_eq!(search("$A $ $A", source), vec!["3: a $ a"]);
+    assert_eq!(
+        search("show($_) + show(1)", source),
+        vec!["4: show(1) + show(1)"]
+    );
+}
+
+#[test]
+fn statement_patterns_match_runs_of_statements() {
+    let source = "f = fun() {\n\
+                  result = none;\n\
+                  return result;\n\
+                  };\n\
+                  g = fun() { y = none; return 1; };";
+    assert_eq!(
+        search("$X = none; return $X;", source),
+        vec!["2: result = none;"]
+    );
+}
+
+#[test]
+fn pattern_errors_point_into_the_pattern() {
+    match AstPattern::new("print($X +)") {
+        Fail(errors) => assert_eq!(
+            format!("{}", errors[0]).split(": got").next().unwrap(),
+            "error (in '<pattern>'): at ln 1 col 11"
+        ),
+        Fine(..) => panic!("pattern parsed"),
+    }
+}
+
+#[test]
+fn columns_count_from_the_start_of_the_line() {
+    let pattern = match AstPattern::new("print($X)") {
+        Fine(pattern, _) => pattern,
+        Fail(errors) => panic!("{:?}", errors),
+    };
+    match pattern.search("x = 1;\n  print(x);", "search.olv") {
+        Fine(matches, _) => assert_eq!(
+            matches
+                .iter()
+                .map(|found| (found.line, found.col))
+                .collect::<Vec<_>>(),
+            vec![(2, 3)]
+        ),
+        Fail(errors) => panic!("{:?}", errors),
+    }
+}
@@ -0,0 +1,158 @@
+use mistake::Mistake::{Fail, Fine};
+use olive::{
+    compile_file, compile_file_with_source_map, CompileCache, Engine, OliveError, OliveIoError,
+};
+use std::fs;
+use std::path::PathBuf;
+
+// compiles the source and returns the binary together with a path to write
+// changed copies of it to
+fn compile(name: &str, source: &str) -> (Vec<u8>, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("olv-bytecode-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}.olv", name));
+    fs::write(&path, source).unwrap();
+    let binary = match compile_file(path.to_str().unwrap()) {
+        Fine(binary, _) => binary,
+        Fail(errors) => panic!("{}", errors[0]),
+    };
+    (binary, path.with_extension("olvc"))
+}
+
+fn run(binary: &[u8], path: &PathBuf) -> Result<String, OliveError> {
+    fs::write(path, binary).unwrap();
+    let mut engine = Engine::new();
+    let printed = engine.capture_print();
+    match engine.run_file(path) {
+        Fine(_, _) => Ok(printed.borrow().clone()),
+        Fail(mut errors) => Err(errors.remove(0)),
+    }
+}
+
+#[test]
+fn round_trip() {
+    let (binary, path) = compile("round_trip", "f = fun(a) { defer print(a); };\nf(\"hi\");");
+    assert_eq!(run(&binary, &path).unwrap(), "hi\n");
+}
+
+#[test]
+fn compiled_scripts_run_in_the_global_scope() {
+    let (binary, path) = compile("global", "x = 41;\nprint(eval(\"return x + 1;\"));");
+    assert_eq!(run(&binary, &path).unwrap(), "42\n");
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    let (mut binary, path) = compile("versions", "print(1);");
+    binary[4] += 1;
+    match run(&binary, &path) {
+        Err(OliveError::Io {
+            kind: OliveIoError::Version { found, supported },
+            ..
+        }) => assert_eq!(found, supported + 1),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn unknown_opcodes_are_rejected() {
+    let (mut binary, path) = compile("opcodes", "print(1);");
+    *binary.last_mut().unwrap() = 0xff;
+    match run(&binary, &path) {
+        Err(OliveError::Io {
+            kind: OliveIoError::Opcode { opcode },
+            ..
+        }) => assert_eq!(opcode, 0xff),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn cached_codes_keep_error_lines() {
+    let (_, path) = compile("cached", "print(1);\nx = 1 // 0;");
+    let source = path.with_extension("olv");
+    let cache = path.with_extension("cache");
+    for _ in 0..2 {
+        let mut engine = Engine::new();
+        engine.capture_print();
+        engine.enable_compile_cache(CompileCache::Dir(cache.clone()));
+        match engine.run_file(&source) {
+            Fail(errors) => match &errors[0] {
+                OliveError::Runtime { line, .. } => assert_eq!(*line, Some(2)),
+                other => panic!("{:?}", other),
+            },
+            Fine(_, _) => panic!("division by zero succeeded"),
+        }
+        assert_eq!(fs::read_dir(&cache).unwrap().count(), 1);
+    }
+}
+
+#[test]
+fn source_maps_point_errors_at_the_source() {
+    let (_, path) = compile("mapped", "print(1);\nx = 1 // 0;");
+    let source = path.with_extension("olv");
+    let (binary, map) = match compile_file_with_source_map(&source) {
+        Fine(compiled, _) => compiled,
+        Fail(errors) => panic!("{}", errors[0]),
+    };
+    fs::write(path.with_extension("olvc.map"), map).unwrap();
+    match run(&binary, &path) {
+        Err(OliveError::Runtime { file, line, .. }) => {
+            assert_eq!(file, source.to_string_lossy());
+            assert_eq!(line, Some(2));
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn broken_source_maps_are_ignored() {
+    let (binary, path) = compile("broken_map", "print(1);\nx = 1 // 0;");
+    for line_starts in &["[]", "[5, 12]", "[0, 12, 4]", "[0, 10, 10]"] {
+        fs::write(
+            path.with_extension("olvc.map"),
+            format!(
+                "{{\"file\": \"other.olv\", \"line_starts\": {}}}",
+                line_starts
+            ),
+        )
+        .unwrap();
+        match run(&binary, &path) {
+            Err(OliveError::Runtime { file, .. }) => {
+                assert_eq!(file, path.to_string_lossy(), "{}", line_starts)
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
+
+#[test]
+fn deeply_nested_functions_are_rejected() {
+    let (header, path) = compile("nesting", "print(1);");
+    // the header without any constants, then defer blocks nested into each
+    // other, every one a function of a single code
+    let mut binary = header[..5].to_vec();
+    binary.extend_from_slice(&[0, 0, 0]);
+    for _ in 0..200_000 {
+        binary.extend_from_slice(&[1, 0, 0, 0, 0x1b]);
+    }
+    binary.extend_from_slice(&[0, 0, 0, 0]);
+    match run(&binary, &path) {
+        Err(OliveError::Io {
+            kind: OliveIoError::Deserialize,
+            ..
+        }) => {}
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn nested_functions_below_the_limit_round_trip() {
+    let mut body = String::from("return 7;");
+    for _ in 0..40 {
+        body = format!("return fun() {{ {} }};", body);
+    }
+    let source = format!("f = fun() {{ {} }};\nprint(f(){});", body, "()".repeat(40));
+    let (binary, path) = compile("shallow_nesting", &source);
+    assert_eq!(run(&binary, &path).unwrap(), "7\n");
+}
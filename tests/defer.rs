@@ -0,0 +1,44 @@
+use mistake::Mistake::{Fail, Fine};
+use olive::Engine;
+
+// everything the script printed and whether it ran without errors
+fn run(source: &str) -> (String, bool) {
+    let mut engine = Engine::new();
+    let printed = engine.capture_print();
+    let fine = match engine.eval_str(source) {
+        Fine(_, errors) => errors.is_empty(),
+        Fail(_) => false,
+    };
+    let printed = printed.borrow().clone();
+    (printed, fine)
+}
+
+#[test]
+fn deferred_statements_run_last_to_first() {
+    let source = "f = fun() {
+        defer print(1);
+        defer { print(2); }
+        print(3);
+    };
+    f();
+    print(4);";
+    assert_eq!(run(source), (String::from("3\n2\n1\n4\n"), true));
+}
+
+#[test]
+fn deferred_statements_run_on_return_and_error() {
+    let returned = "f = fun() {
+        defer print(\"cleanup\");
+        return 1;
+        print(\"unreachable\");
+    };
+    print(f());";
+    assert_eq!(run(returned), (String::from("cleanup\n1\n"), true));
+    let failed = "f = fun() {
+        defer print(\"cleanup\");
+        return 1 // 0;
+    };
+    f();
+    print(\"unreachable\");";
+    assert_eq!(run(failed), (String::from("cleanup\n"), false));
+}
@@ -0,0 +1,353 @@
+use olive::testing::{error, eval};
+use olive::{from_value, to_value, Code, Engine, EnginePolicy, Hooks, LogLevel, Value};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+#[test]
+fn registered_functions_convert_their_values() {
+    let mut engine = Engine::new();
+    engine.register_fn("total", 1, |args| {
+        let numbers = Vec::<i64>::try_from(args[0].clone())?;
+        Ok(Value::from(numbers.iter().sum::<i64>()))
+    });
+    assert_eq!(
+        eval(&mut engine, "return total(new [1, 2, 3]);"),
+        Value::Integer(6)
+    );
+    assert!(error(&mut engine, "total(new [1, \"a\"]);")
+        .contains("expected type integer, got type string"));
+    let names = HashMap::<String, Option<String>>::try_from(eval(
+        &mut engine,
+        "return new {a: \"x\", b: none};",
+    ))
+    .unwrap();
+    assert_eq!(names["a"], Some(String::from("x")));
+    assert_eq!(names["b"], None);
+}
+
+#[test]
+fn module_functions_are_called_through_their_bendy() {
+    let mut engine = Engine::new();
+    engine.register_module_fn("first", "name", 0, |_| Ok(Value::from("first")));
+    engine.register_module_fn("second", "name", 0, |_| Ok(Value::from("second")));
+    engine.register_module_fn("first", "twice", 1, |args| match &args[0] {
+        Value::Integer(value) => Ok(Value::Integer(value * 2)),
+        _ => Err(String::from("twice expects an integer")),
+    });
+    assert_eq!(
+        eval(&mut engine, "return first.name() $ second.name();"),
+        Value::from("firstsecond")
+    );
+    assert_eq!(
+        eval(&mut engine, "return first.twice(4);"),
+        Value::Integer(8)
+    );
+    assert!(error(&mut engine, "first.name = 1;").contains("frozen"));
+}
+
+#[test]
+fn engines_keep_their_globals_apart() {
+    let mut first = Engine::new();
+    let mut second = Engine::new();
+    eval(&mut first, "x = 1;");
+    eval(&mut second, "x = 2;");
+    assert_eq!(eval(&mut first, "return x;"), Value::Integer(1));
+    assert_eq!(eval(&mut second, "return x;"), Value::Integer(2));
+    assert!(error(&mut Engine::new(), "return x;").contains("'x'"));
+}
+
+#[test]
+fn policies_hide_the_other_builtins() {
+    let mut engine = Engine::with_policy(EnginePolicy::allow_builtins(vec!["len"]));
+    assert_eq!(eval(&mut engine, "return len(\"abc\");"), Value::Integer(3));
+    assert!(error(&mut engine, "print(1);").contains("'print'"));
+    assert!(error(&mut engine, "eval(\"x = 1;\");").contains("'eval'"));
+}
+
+#[test]
+fn untrusted_policies_leave_out_eval_and_heapdump() {
+    let mut engine = Engine::with_policy(EnginePolicy::untrusted());
+    assert_eq!(eval(&mut engine, "return len(\"abc\");"), Value::Integer(3));
+    for call in &[
+        "eval(\"x = 1;\");",
+        "compile(\"x = 1;\");",
+        "heapdump(\"dump.json\");",
+    ] {
+        assert!(error(&mut engine, call).contains("couldn't find variable"));
+    }
+}
+
+#[test]
+fn print_goes_to_the_host() {
+    let mut engine = Engine::new();
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let sink = lines.clone();
+    engine.on_print(move |line| sink.borrow_mut().push(String::from(line)));
+    eval(&mut engine, "print(1); print(\"two\");");
+    assert_eq!(*lines.borrow(), vec!["1", "two"]);
+    let printed = engine.capture_print();
+    eval(&mut engine, "print(new [1.5, none]);");
+    assert_eq!(*printed.borrow(), "[1.5, none]\n");
+    assert_eq!(lines.borrow().len(), 2);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct User {
+    name: String,
+    tags: Vec<String>,
+    age: Option<i64>,
+}
+
+#[test]
+fn serde_values_go_in_and_out_of_scripts() {
+    let user = User {
+        name: String::from("ada"),
+        tags: vec![String::from("admin")],
+        age: None,
+    };
+    let mut engine = Engine::new();
+    engine.register_fn("user", 0, |_| {
+        to_value(&User {
+            name: String::from("ada"),
+            tags: vec![String::from("admin")],
+            age: None,
+        })
+    });
+    let value = eval(&mut engine, "u = user(); u.age = 36; return u;");
+    let returned: User = from_value(value).unwrap();
+    assert_eq!(returned.name, user.name);
+    assert_eq!(returned.tags, user.tags);
+    assert_eq!(returned.age, Some(36));
+    assert!(from_value::<User>(Value::Integer(1)).is_err());
+}
+
+#[test]
+fn errors_in_functions_point_at_their_line() {
+    let mut engine = Engine::new();
+    let message = error(
+        &mut engine,
+        "f = fun(a) {\n    b = a + 1;\n    return b + \"x\";\n};\nf(1);",
+    );
+    assert!(message.contains("at ln 3"), "{}", message);
+}
+
+#[test]
+fn calls_check_the_argument_count() {
+    let mut engine = Engine::new();
+    let message = error(&mut engine, "f = fun(a, b) { return a; };\nf(1);");
+    assert!(
+        message.contains("expected 2 arguments to call of 'f', got 1"),
+        "{}",
+        message
+    );
+    let message = error(&mut engine, "len(1, 2);");
+    assert!(
+        message.contains("expected 1 arguments to function call, got 2"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn jumps_reach_over_long_blocks() {
+    // far more codes than fit in a one byte offset
+    let body = "x = x + 1;\n".repeat(2000);
+    let source = format!(
+        "x = 0; i = 0;\nwhile (i < 3) {{\n{}i = i + 1;\n}}\nif (x > 1) {{\n{}}}\nreturn x;",
+        body, body
+    );
+    assert_eq!(eval(&mut Engine::new(), &source), Value::Integer(8000));
+}
+
+#[test]
+fn functions_know_their_name_and_arity() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "add = fun(a, b) { return a + b; };\n\
+             return new [arity(add), fn_name(add), arity(len), fn_name(len), fn_name(fun() {})];"
+        ),
+        Value::List(vec![
+            Value::Integer(2),
+            Value::String(String::from("add")),
+            Value::Integer(1),
+            Value::None,
+            Value::None,
+        ])
+    );
+}
+
+#[test]
+fn stats_count_instructions_and_calls() {
+    let mut engine = Engine::new();
+    engine.enable_stats();
+    let stats = eval(&mut engine, "f = fun() {};\nf(); f();\nreturn vmstats();");
+    let stats = match stats {
+        Value::Bendy(data) => data,
+        other => panic!("vmstats returned {}", other),
+    };
+    assert_eq!(stats["gc_runs"], Value::Integer(0));
+    match &stats["instructions"] {
+        Value::Integer(count) => assert!(*count > 0),
+        other => panic!("instructions is {}", other),
+    }
+    let calls = HashMap::<String, i64>::try_from(stats["calls"].clone()).unwrap();
+    assert_eq!(calls["f"], 2);
+    assert!(engine.stats_report().unwrap().contains("    f: 2\n"));
+}
+
+#[test]
+fn hooks_see_every_instruction() {
+    #[derive(Default)]
+    struct Count(Cell<usize>);
+    impl Hooks for Count {
+        fn on_instruction(&self, _ip: usize, _code: &Code) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+    let mut engine = Engine::new();
+    let count = engine.add_hooks(Count::default());
+    eval(&mut engine, "x = 1;");
+    let once = count.0.get();
+    assert!(once > 0);
+    eval(&mut engine, "i = 0;\nwhile (i < 10) { i = i + 1; }");
+    assert!(count.0.get() > once + 10);
+}
+
+#[test]
+fn calls_failing_their_checks_are_not_reported() {
+    #[derive(Default)]
+    struct Calls(RefCell<Vec<String>>);
+    impl Hooks for Calls {
+        fn on_call(&self, name: &str, _arg_count: usize) {
+            self.0.borrow_mut().push(String::from(name));
+        }
+    }
+    let mut engine = Engine::new();
+    let calls = engine.add_hooks(Calls::default());
+    error(&mut engine, "f = fun(a) {};\nf(1, 2);");
+    error(&mut engine, "g = fun(a: int) {};\ng(\"s\");");
+    error(&mut engine, "h = 1;\nh();");
+    assert!(calls.0.borrow().is_empty());
+}
+
+#[test]
+fn unused_objects_are_freed() {
+    let mut engine = Engine::new();
+    let objects = |engine: &Engine| {
+        let snapshot: serde_json::Value = serde_json::from_str(&engine.heap_snapshot()).unwrap();
+        snapshot["objects"].as_array().unwrap().len()
+    };
+    eval(&mut engine, "i = 0;");
+    let before = objects(&engine);
+    eval(
+        &mut engine,
+        "while (i < 1000) { l = new [new {a: i}, \"s\" $ i]; i = i + 1; }",
+    );
+    // only the last list and what it holds are still around
+    assert!(objects(&engine) <= before + 4);
+}
+
+#[test]
+fn floats_print_and_repr_the_shortest_way() {
+    let mut engine = Engine::new();
+    let printed = engine.capture_print();
+    eval(
+        &mut engine,
+        "print(1.0); print(0.1 + 0.2); print(1.0 / 0.0); print(10000000000000000.0);\n\
+         print(repr(new [\"a\\n\", 2.0, none]));",
+    );
+    assert_eq!(
+        *printed.borrow(),
+        "1.0\n0.30000000000000004\ninf\n1e16\n[\"a\\n\", 2.0, none]\n"
+    );
+}
+
+#[test]
+fn pretty_strings_break_long_structures() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(&mut engine, "return to_pretty_string(new [1, 2], 2);"),
+        Value::String(String::from("[1, 2]"))
+    );
+    let long = "x".repeat(40);
+    assert_eq!(
+        eval(
+            &mut engine,
+            &format!("return to_pretty_string(new [\"{0}\", \"{0}\"], 2);", long)
+        ),
+        Value::String(format!("[\n  \"{0}\",\n  \"{0}\"\n]", long))
+    );
+}
+
+#[test]
+fn par_map_keeps_the_order() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "return par_map(new [1, 2, 3, 4, 5], fun(v) { return v * v; });"
+        ),
+        Value::List(vec![
+            Value::Integer(1),
+            Value::Integer(4),
+            Value::Integer(9),
+            Value::Integer(16),
+            Value::Integer(25),
+        ])
+    );
+}
+
+#[test]
+fn logs_below_the_level_are_dropped() {
+    let mut engine = Engine::new();
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let sink = lines.clone();
+    engine.on_log(move |line| sink.borrow_mut().push(String::from(line)));
+    engine.set_log_level(LogLevel::Warn);
+    eval(
+        &mut engine,
+        "log_debug(\"a\"); log_info(\"b\"); log_warn(\"c\"); log_error(new [1]);",
+    );
+    let lines = lines.borrow();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("warn  c"), "{}", lines[0]);
+    assert!(lines[1].ends_with("error [1]"), "{}", lines[1]);
+}
+
+#[test]
+fn frozen_values_can_still_be_read() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "table = freeze(new {a: new [1, 2]});\ncopy = table.a[1];\nreturn copy + len(table.a);"
+        ),
+        Value::Integer(4)
+    );
+    assert!(error(&mut engine, "table.b = 1;").contains("can't change a frozen bendy"));
+}
+
+#[test]
+fn values_containing_themselves_copy_as_none_there() {
+    let mut engine = Engine::new();
+    let mut inner = HashMap::new();
+    inner.insert(String::from("a"), Value::Integer(1));
+    inner.insert(String::from("self"), Value::None);
+    assert_eq!(
+        eval(&mut engine, "b = new {a: 1}; b.self = b; return b;"),
+        Value::Bendy(inner)
+    );
+    engine.register_fn("count", 1, |args| match &args[0] {
+        Value::List(data) => Ok(Value::from(data.len() as i64)),
+        other => Err(format!("not a list: {}", other)),
+    });
+    assert_eq!(
+        eval(&mut engine, "l = new [1, 2]; l[1] = l; return count(l);"),
+        Value::Integer(2)
+    );
+}
@@ -0,0 +1,240 @@
+use mistake::Mistake::{Fail, Fine};
+use olive::testing::eval;
+use olive::{Engine, OliveError, SizeLimits, Value};
+use std::collections::HashMap;
+
+fn error_value(source: &str) -> Value {
+    let mut engine = Engine::new();
+    match engine.eval_str(source) {
+        Fine(_, errors) | Fail(errors) => errors[0].to_value().expect("a runtime error"),
+    }
+}
+
+fn bendy(entries: &[(&str, Value)]) -> Value {
+    let data: HashMap<String, Value> = entries
+        .iter()
+        .map(|(key, value)| (String::from(*key), value.clone()))
+        .collect();
+    Value::Bendy(data)
+}
+
+#[test]
+fn runtime_error_value() {
+    assert_eq!(
+        error_value("x = 1;\nreturn x // 0;"),
+        bendy(&[
+            ("type", Value::String(String::from("division_by_zero"))),
+            ("message", Value::String(String::from("division by zero"))),
+            ("file", Value::String(String::from("<eval>"))),
+            ("line", Value::Integer(2)),
+            ("trace", Value::List(Vec::new())),
+        ])
+    );
+}
+
+#[test]
+fn runtime_error_trace() {
+    let source = "inner = fun() { return missing; };\n\
+                  outer = fun() {\n\
+                  return inner();\n\
+                  };\n\
+                  outer();";
+    let frame = |function: &str, line: i64| {
+        bendy(&[
+            ("function", Value::String(String::from(function))),
+            ("line", Value::Integer(line)),
+        ])
+    };
+    assert_eq!(
+        error_value(source),
+        bendy(&[
+            ("type", Value::String(String::from("variable_not_found"))),
+            (
+                "message",
+                Value::String(String::from("couldn't find variable 'missing' in scope")),
+            ),
+            ("file", Value::String(String::from("<eval>"))),
+            ("line", Value::Integer(1)),
+            (
+                "trace",
+                Value::List(vec![frame("inner", 3), frame("outer", 5)]),
+            ),
+        ])
+    );
+}
+
+#[test]
+fn only_runtime_errors_have_values() {
+    let mut engine = Engine::new();
+    match engine.eval_str("x = ;") {
+        Fine(_, errors) | Fail(errors) => assert_eq!(errors[0].to_value(), None),
+    }
+}
+
+#[test]
+fn unterminated_comment() {
+    let mut engine = Engine::new();
+    match engine.eval_str("x = 1; ### x ## y\nreturn x;") {
+        Fine(_, errors) | Fail(errors) => assert_eq!(
+            errors[0].to_string(),
+            "error (in '<eval>'): at ln 2 col 10: unterminated comment started at ln 1 col 8"
+        ),
+    }
+}
+
+#[test]
+fn frozen_error_value() {
+    assert_eq!(
+        error_value("table = freeze(new {a: new [1]});\ntable.a[0] = 2;"),
+        bendy(&[
+            ("type", Value::String(String::from("frozen"))),
+            (
+                "message",
+                Value::String(String::from("can't change a frozen list")),
+            ),
+            ("file", Value::String(String::from("<eval>"))),
+            ("line", Value::Integer(2)),
+            ("trace", Value::List(Vec::new())),
+        ])
+    );
+}
+
+#[test]
+fn size_limits() {
+    let mut engine = Engine::new();
+    engine.set_size_limits(SizeLimits {
+        max_string_length: Some(8),
+        max_elements: Some(3),
+        ..SizeLimits::default()
+    });
+    let cases = [
+        (
+            "s = \"\";\nwhile (true) { s = s $ \"ab\"; }",
+            "string",
+            8,
+            "bytes",
+        ),
+        ("l = new [];\nl[3] = 1;", "list", 3, "elements"),
+        (
+            "b = new {a: 1, b: 2, c: 3};\nb.c = 4;\nb.d = 5;",
+            "bendy",
+            3,
+            "elements",
+        ),
+        ("l = new [1, 2];\nl = l $ l;", "list", 3, "elements"),
+    ];
+    for (source, type_name, limit, unit) in cases.iter() {
+        let error = match engine.eval_str(source) {
+            Fine(_, errors) | Fail(errors) => errors[0].to_value().expect("a runtime error"),
+        };
+        let message = format!(
+            "{} would grow past the limit of {} {}",
+            type_name, limit, unit
+        );
+        match error {
+            Value::Bendy(data) => {
+                assert_eq!(data["type"], Value::String(String::from("size_limit")));
+                assert_eq!(data["message"], Value::String(message));
+            }
+            _ => panic!("{}", source),
+        }
+    }
+}
+
+#[test]
+fn endless_recursion_fails_at_the_call_depth_limit() {
+    // debug builds take several MiB of stack to reach the limit
+    let error = std::thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(|| error_value("f = fun() {\nreturn f();\n};\nf();"))
+        .unwrap()
+        .join()
+        .unwrap();
+    match error {
+        Value::Bendy(data) => {
+            assert_eq!(data["type"], Value::String(String::from("call_depth")));
+            assert_eq!(
+                data["message"],
+                Value::String(String::from(
+                    "function calls nested deeper than the limit of 256"
+                ))
+            );
+        }
+        _ => panic!("not an error value"),
+    }
+}
+
+#[test]
+fn size_limits_lower_the_call_depth() {
+    let mut engine = Engine::new();
+    engine.set_size_limits(SizeLimits {
+        max_call_depth: Some(10),
+        ..SizeLimits::default()
+    });
+    assert_eq!(
+        eval(
+            &mut engine,
+            "f = fun(n) {\nif (n == 0) { return 0; }\nreturn f(n - 1);\n};\nreturn f(8);"
+        ),
+        Value::Integer(0)
+    );
+    let error = match engine.eval_str("f(20);") {
+        Fine(_, errors) | Fail(errors) => errors[0].to_value().expect("a runtime error"),
+    };
+    match error {
+        Value::Bendy(data) => assert_eq!(
+            data["message"],
+            Value::String(String::from(
+                "function calls nested deeper than the limit of 10"
+            ))
+        ),
+        _ => panic!("not an error value"),
+    }
+}
+
+#[test]
+fn parameter_types_are_checked_at_the_call() {
+    assert_eq!(
+        error_value("add = fun(a: integer, b) {\nreturn a + b;\n};\nadd(\"1\", 2);"),
+        bendy(&[
+            ("type", Value::String(String::from("incorrect_type"))),
+            (
+                "message",
+                Value::String(String::from("expected type integer, got type string")),
+            ),
+            ("file", Value::String(String::from("<eval>"))),
+            ("line", Value::Integer(4)),
+            ("trace", Value::List(Vec::new())),
+        ])
+    );
+    let mut engine = Engine::new();
+    match engine.eval_str("f = fun(x: int) { return x; };") {
+        Fine(_, errors) | Fail(errors) => assert_eq!(
+            errors[0].to_string(),
+            "error (in '<eval>'): at ln 1 col 12: unknown parameter type 'int', expected one of \
+             [integer, float, boolean, none, string, list, bendy, function, native]"
+        ),
+    }
+}
+
+#[test]
+fn par_map_checks_parameter_types() {
+    match error_value("par_map(new [\"a\"], fun(v: integer) { return v; });") {
+        Value::Bendy(data) => assert_eq!(
+            data["message"],
+            Value::String(String::from("expected type integer, got type string"))
+        ),
+        error => panic!("{:?}", error),
+    }
+}
+
+#[test]
+fn runtime_errors_on_later_lines_have_columns() {
+    let mut engine = Engine::new();
+    match engine.eval_str("x = 1;\ny = x // 0;") {
+        Fine(_, errors) | Fail(errors) => match &errors[0] {
+            OliveError::Runtime { line, col, .. } => assert_eq!((*line, *col), (Some(2), Some(5))),
+            other => panic!("{:?}", other),
+        },
+    }
+}
@@ -0,0 +1,110 @@
+use mistake::Mistake::{Fail, Fine};
+use olive::{lint, LintOptions, LintRule};
+
+fn warnings_with(source: &str, options: &LintOptions) -> Vec<String> {
+    match lint(source, "lint.olv", options) {
+        Fine(warnings, _) => warnings
+            .into_iter()
+            .map(|warning| {
+                format!(
+                    "{}: {} [{}]",
+                    warning.line,
+                    warning.message,
+                    warning.rule.name()
+                )
+            })
+            .collect(),
+        Fail(errors) => panic!("{:?}", errors),
+    }
+}
+
+fn warnings(source: &str) -> Vec<String> {
+    warnings_with(source, &LintOptions::default())
+}
+
+#[test]
+fn assigning_to_a_builtin_shadows_it() {
+    assert_eq!(
+        warnings("len = 3;\nprint = fun(x) { return x; };"),
+        vec![
+            "1: assignment shadows the builtin 'len' [shadowed-builtin]",
+            "2: assignment shadows the builtin 'print' [shadowed-builtin]",
+        ]
+    );
+    assert!(warnings("length = len(\"ab\");\nb = new {};\nb.len = 2;").is_empty());
+}
+
+#[test]
+fn comparing_to_a_boolean_is_reported() {
+    assert_eq!(
+        warnings("x = 1 == 1;\nif (x == true) { print(x); }\ny = false != x;"),
+        vec![
+            "2: comparison to true, use the condition itself [compare-to-boolean]",
+            "3: comparison to false, use the condition itself [compare-to-boolean]",
+        ]
+    );
+    assert!(warnings("x = 1;\nif (x == 1) { print(x); }\ny = !x;").is_empty());
+}
+
+#[test]
+fn empty_blocks_are_reported() {
+    assert_eq!(
+        warnings("x = 1;\nif (x) {}\nwhile (false) {\n}\nf = fun() {};"),
+        vec![
+            "2: empty block [empty-block]",
+            "3: empty block [empty-block]",
+            "5: empty block [empty-block]",
+        ]
+    );
+    assert!(warnings("x = 1;\nif (x) { x = 2; } else { x = 3; }").is_empty());
+}
+
+#[test]
+fn unused_parameters_are_reported() {
+    assert_eq!(
+        warnings("f = fun(a, b) {\n    b = 1;\n    return a.b;\n};"),
+        vec!["1: parameter 'b' is never used [unused-parameter]"]
+    );
+    assert!(warnings(
+        "f = fun(a, _b, c) { a.x = fun() { return c; }; return a; };\nf(new {}, 1, 2);"
+    )
+    .is_empty());
+}
+
+#[test]
+fn deep_nesting_is_reported_once_per_block() {
+    let deep = "x = 1;\nif (x) {\nif (x) {\nif (x) {\nif (x) {\nif (x) {\nif (x) { print(x); }\n}\n}\n}\n}\n}";
+    assert_eq!(
+        warnings(deep),
+        vec!["6: block is nested 5 levels deep (at most 4 allowed) [deep-nesting]"]
+    );
+    let chained = "x = 1;\nif (x) { if (x) { if (x) { if (x) { print(x); } else if (x) { print(x); } else if (x) { print(x); } } } }";
+    assert!(warnings(chained).is_empty());
+    let mut options = LintOptions::default();
+    options.set_max_depth(1);
+    assert_eq!(
+        warnings_with("x = 1;\nif (x) {\nwhile (x) { x = 0; }\n}", &options),
+        vec!["3: block is nested 2 levels deep (at most 1 allowed) [deep-nesting]"]
+    );
+}
+
+#[test]
+fn rules_can_be_turned_off() {
+    let source = "len = 1;\n# olv-lint: allow empty-block, compare-to-boolean\nif (len == true) {}\nif (len) {}";
+    assert_eq!(
+        warnings(source),
+        vec![
+            "1: assignment shadows the builtin 'len' [shadowed-builtin]",
+            "4: empty block [empty-block]",
+        ]
+    );
+    let mut options = LintOptions::default();
+    options.allow(LintRule::ShadowedBuiltin);
+    options.allow(LintRule::EmptyBlock);
+    assert!(warnings_with(source, &options).is_empty());
+    assert_eq!(
+        LintRule::from_name("unused-parameter"),
+        Some(LintRule::UnusedParameter)
+    );
+    assert_eq!(LintRule::from_name("unknown"), None);
+}
@@ -0,0 +1,469 @@
+use mistake::Mistake::{Fail, Fine};
+use olive::ast::{Expression, Located, Statement};
+use olive::testing::eval;
+use olive::{Engine, EnginePolicy, Hooks, Plugin, Value};
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+#[test]
+fn shebang_line_is_a_comment() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(&mut engine, "#!/usr/bin/env olv\nreturn 1;"),
+        Value::Integer(1)
+    );
+}
+
+#[test]
+fn argv_returns_the_arguments() {
+    let mut engine = Engine::new();
+    engine.set_args(vec!["-v", "file name"]);
+    assert_eq!(
+        eval(&mut engine, "return argv();"),
+        Value::List(vec![
+            Value::String(String::from("-v")),
+            Value::String(String::from("file name")),
+        ])
+    );
+}
+
+#[test]
+fn eval_runs_in_the_global_scope() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "y = eval(\"x = 20; return x + 1;\");\nreturn x + y;"
+        ),
+        Value::Integer(41)
+    );
+}
+
+#[test]
+fn eval_calls_the_hooks_of_the_engine() {
+    #[derive(Default)]
+    struct Calls(RefCell<Vec<String>>);
+    impl Hooks for Calls {
+        fn on_call(&self, name: &str, _arg_count: usize) {
+            self.0.borrow_mut().push(String::from(name));
+        }
+    }
+    let mut engine = Engine::new();
+    let calls = engine.add_hooks(Calls::default());
+    eval(&mut engine, "f = fun() {};\neval(\"f();\");");
+    assert_eq!(*calls.0.borrow(), vec!["eval", "f"]);
+}
+
+#[test]
+fn compile_returns_a_function() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "f = compile(\"return n * 2;\");\nn = 4;\nreturn f();"
+        ),
+        Value::Integer(8)
+    );
+}
+
+#[test]
+fn eval_reports_errors_in_the_code() {
+    let mut engine = Engine::new();
+    match engine.eval_str("eval(\"x = 1 +;\");") {
+        Fail(errors) => assert!(errors[0].to_string().contains("eval: at ln 1 col 8")),
+        Fine(..) => panic!("broken code was evaluated"),
+    }
+}
+
+#[test]
+fn trailing_commas() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "f = fun(a, b,) { return a + b; };\nl = new [f(1, 2,), 4,];\nb = new {x: l[0], y: l[1],};\nreturn b.x + b.y;"
+        ),
+        Value::Integer(7)
+    );
+}
+
+#[test]
+fn nested_comments() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "x = 1; ## a # in a comment ##\n###\nx = 2; ## inner ##\n###\nreturn x;"
+        ),
+        Value::Integer(1)
+    );
+}
+
+#[test]
+fn bendy_shorthand_and_computed_keys() {
+    let mut engine = Engine::new();
+    let value = eval(
+        &mut engine,
+        "x = 1; prefix = \"dyn\";\nreturn new { x, [prefix $ \"amic\"]: 2 };",
+    );
+    let mut expected = std::collections::HashMap::new();
+    expected.insert(String::from("x"), Value::Integer(1));
+    expected.insert(String::from("dynamic"), Value::Integer(2));
+    assert_eq!(value, Value::Bendy(expected));
+}
+
+#[test]
+fn builtins_lists_the_callable_natives() {
+    let mut engine = Engine::with_policy(EnginePolicy::allow_builtins(vec!["len", "keys"]));
+    engine.register_fn("double", 1, |args| match &args[0] {
+        Value::Integer(value) => Ok(Value::Integer(value * 2)),
+        _ => Err(String::from("double expects an integer")),
+    });
+    assert_eq!(engine.builtin_names(), vec!["double", "keys", "len"]);
+    assert_eq!(
+        eval(&mut engine, "return builtins.double(len(keys(builtins)));"),
+        Value::Integer(6)
+    );
+}
+
+#[test]
+fn pipe_calls_with_the_value_first() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "sub = fun(a, b) { return a - b; };\nreturn new [1, 2, 3] |> len |> sub(1) |> sub(10);"
+        ),
+        Value::Integer(-8)
+    );
+}
+
+#[test]
+fn optional_access_gives_none_for_missing_values() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "data = new { user: new { tags: new [\"a\"] } };\nreturn new [data?.user?.tags?[0], data?.other?.tags, data.user.tags?[5], none?[\"x\"]];"
+        ),
+        Value::List(vec![
+            Value::String(String::from("a")),
+            Value::None,
+            Value::None,
+            Value::None,
+        ])
+    );
+}
+
+// turns x = sql("..."); into x = "SQL: ...";, and rejects while loops
+struct Sql;
+
+impl Plugin for Sql {
+    fn rewrite<'a>(
+        &self,
+        statements: &mut Vec<Located<Statement<'a>>>,
+    ) -> Result<(), Located<String>> {
+        for statement in statements {
+            match &mut statement.inner {
+                Statement::Assign { right, .. } => {
+                    if let Expression::Call { expression, args } = &mut right.inner {
+                        if let (Expression::Variable { name: "sql" }, [query]) =
+                            (&expression.inner, &args[..])
+                        {
+                            if let Expression::String { value } = &query.inner {
+                                right.inner = Expression::String {
+                                    value: format!("SQL: {}", value),
+                                };
+                            }
+                        }
+                    }
+                }
+                Statement::While { .. } => {
+                    return Err(Located {
+                        start: statement.start,
+                        end: statement.end,
+                        inner: String::from("loops aren't allowed"),
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn plugins_rewrite_the_ast() {
+    let mut engine = Engine::new();
+    engine.add_plugin(Sql);
+    assert_eq!(
+        eval(&mut engine, "q = sql(\"select 1\");\nreturn q;"),
+        Value::String(String::from("SQL: select 1"))
+    );
+    match engine.eval_str("x = 1;\nwhile (x) { x = 0; }") {
+        Fine(_, errors) | Fail(errors) => assert_eq!(
+            errors[0].to_string(),
+            "error (in '<eval>'): at ln 2 col 1: loops aren't allowed"
+        ),
+    }
+}
+
+fn root<'a>(snapshot: &'a serde_json::Value, name: &str) -> &'a serde_json::Value {
+    let to = snapshot["roots"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|root| root["name"] == name)
+        .unwrap()["to"]
+        .as_u64()
+        .unwrap();
+    &snapshot["objects"][to as usize]
+}
+
+#[test]
+fn heap_snapshots_follow_the_globals() {
+    let mut engine = Engine::new();
+    eval(
+        &mut engine,
+        "kept = new { label: \"kept\" };\n\
+         ring = new { other: none }; ring.other = new { other: ring };\n\
+         dropped = fun() { a = new { other: none }; a.other = a; };\n\
+         dropped();",
+    );
+    let mut other = Engine::new();
+    eval(&mut other, "secret = \"another engine\";");
+    let snapshot: serde_json::Value = serde_json::from_str(&engine.heap_snapshot()).unwrap();
+    let objects = snapshot["objects"].as_array().unwrap();
+    let label = &root(&snapshot, "kept")["edges"][0];
+    assert_eq!(label["key"], "label");
+    assert_eq!(
+        objects[label["to"].as_u64().unwrap() as usize]["preview"],
+        "kept"
+    );
+    // the two bendys of the ring point at each other, the cycle the function
+    // left behind isn't reachable anymore
+    let ring = root(&snapshot, "ring");
+    let next = &objects[ring["edges"][0]["to"].as_u64().unwrap() as usize];
+    assert_eq!(next["edges"][0]["to"], ring["id"]);
+    assert_eq!(ring["refcount"], 2);
+    assert_eq!(next["refcount"], 1);
+    let cycles = objects
+        .iter()
+        .filter(|object| object["type"] == "bendy" && object["edges"][0]["key"] == "other")
+        .count();
+    assert_eq!(cycles, 2);
+    assert!(objects
+        .iter()
+        .all(|object| object["preview"] != "another engine"));
+}
+
+#[test]
+fn heap_snapshots_include_the_variables_of_running_functions() {
+    let path = std::env::temp_dir().join(format!("olv-heap-{}.json", std::process::id()));
+    let mut engine = Engine::new();
+    eval(
+        &mut engine,
+        &format!(
+            "dump = fun(path) {{ local = new [\"in the function\"]; heapdump(path); }};\n\
+             dump({:?});",
+            path.to_str().unwrap()
+        ),
+    );
+    let snapshot: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let local = snapshot["roots"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|root| root["name"] == "local")
+        .unwrap();
+    assert_eq!(local["frame"], 0);
+    let list = &snapshot["objects"][local["to"].as_u64().unwrap() as usize];
+    assert_eq!(
+        snapshot["objects"][list["edges"][0]["to"].as_u64().unwrap() as usize]["preview"],
+        "in the function"
+    );
+}
+
+#[test]
+fn inspector_evaluates_lines_while_the_script_runs() {
+    let mut engine = Engine::new();
+    let address = engine.enable_inspector("127.0.0.1:0").unwrap();
+    eval(&mut engine, "count = 41; done = false;");
+    let client = std::thread::spawn(move || {
+        let stream = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut replies = Vec::new();
+        for line in &[":vars", "count + 1", "missing", "done = true;"] {
+            writeln!(writer, "{}", line).unwrap();
+            let mut reply = String::new();
+            reader.read_line(&mut reply).unwrap();
+            replies.push(String::from(reply.trim_end()));
+        }
+        replies
+    });
+    eval(
+        &mut engine,
+        "i = 0; while (!done and i < 100000000) { i = i + 1; }",
+    );
+    assert_eq!(eval(&mut engine, "return done;"), Value::Boolean(true));
+    let replies = client.join().unwrap();
+    assert!(replies[0].starts_with("ok count: integer, done: boolean"));
+    assert_eq!(
+        replies[1..].to_vec(),
+        vec![
+            "ok 42",
+            "error couldn't find variable 'missing' in scope",
+            "ok none",
+        ]
+    );
+}
+
+#[test]
+fn inspector_needs_a_policy_with_eval() {
+    let mut engine = Engine::with_policy(EnginePolicy::allow_builtins(vec!["print"]));
+    assert!(engine.enable_inspector("127.0.0.1:0").is_err());
+}
+
+#[test]
+fn inspector_only_listens_on_loopback_addresses_by_default() {
+    let mut engine = Engine::new();
+    let message = engine
+        .enable_inspector("0.0.0.0:0")
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("loopback"), "{}", message);
+    let mut engine = Engine::with_policy(EnginePolicy::allow_all().allow_remote_inspector());
+    assert!(engine.enable_inspector("0.0.0.0:0").is_ok());
+}
+
+#[test]
+fn inspector_closes_with_the_engine() {
+    let mut engine = Engine::new();
+    let address = engine.enable_inspector("127.0.0.1:0").unwrap();
+    let stream = TcpStream::connect(address).unwrap();
+    // the client is only known to the engine once it was accepted
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    writeln!(writer, ":vars").unwrap();
+    eval(&mut engine, "i = 0; while (i < 1000) { i = i + 1; }");
+    let mut reply = String::new();
+    reader.read_line(&mut reply).unwrap();
+    assert!(reply.starts_with("ok"), "{}", reply);
+    drop(engine);
+    reply.clear();
+    assert_eq!(reader.read_line(&mut reply).unwrap(), 0);
+    assert!(TcpStream::connect(address).is_err());
+}
+
+#[test]
+fn par_map_copies_only_the_globals_it_loads() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "x = new {}; x.me = x;\n\
+             offset = 10;\n\
+             add = fun(v) { return v + offset; };\n\
+             return par_map(new [1, 2], fun(v) { return add(v); });"
+        ),
+        Value::List(vec![Value::Integer(11), Value::Integer(12)])
+    );
+}
+
+#[test]
+fn par_map_rejects_globals_that_contain_themselves() {
+    let mut engine = Engine::new();
+    match engine.eval_str("x = new {}; x.me = x;\npar_map(new [1], fun(v) { return x; });") {
+        Fine(_, errors) | Fail(errors) => assert!(errors[0]
+            .to_string()
+            .contains("par_map can't copy a value that contains itself, global 'x' does")),
+    }
+}
+
+#[test]
+fn signals_run_the_handlers_of_each_engine() {
+    let handled =
+        "state = new {hit: 0};\non_signal(\"INT\", fun() { state.hit = state.hit + 1; });";
+    let policy = EnginePolicy::allow_all().allow_signals();
+    let mut first = Engine::with_policy(policy.clone());
+    let mut second = Engine::with_policy(policy);
+    let mut without = Engine::new();
+    eval(&mut first, handled);
+    eval(&mut second, handled);
+    eval(&mut without, "state = new {hit: 0};");
+    signal_hook::low_level::raise(signal_hook::consts::SIGINT).unwrap();
+    let count = "i = 0;\nwhile (i < 3) { i = i + 1; }\nreturn state.hit;";
+    assert_eq!(eval(&mut without, count), Value::Integer(0));
+    assert_eq!(eval(&mut first, count), Value::Integer(1));
+    assert_eq!(eval(&mut second, count), Value::Integer(1));
+    // every signal is handled once
+    assert_eq!(eval(&mut first, count), Value::Integer(1));
+}
+
+#[test]
+fn signals_are_only_handled_when_the_policy_allows_it() {
+    let mut engine = Engine::new();
+    assert!(!engine.builtin_names().contains(&String::from("on_signal")));
+    let listed = Engine::with_policy(EnginePolicy::allow_builtins(vec!["on_signal"]));
+    assert!(listed.builtin_names().contains(&String::from("on_signal")));
+    assert_eq!(
+        eval(&mut engine, "return builtins?.on_signal;"),
+        Value::None
+    );
+}
+
+// set for the copy of the test binary that handles and then raises SIGINT
+const SIGNAL_CHILD: &str = "OLIVE_SIGNAL_CHILD";
+
+#[test]
+fn signals_terminate_again_once_their_engine_is_dropped() {
+    if std::env::var_os(SIGNAL_CHILD).is_some() {
+        let mut engine = Engine::with_policy(EnginePolicy::allow_all().allow_signals());
+        eval(&mut engine, "on_signal(\"INT\", fun() { print(1); });");
+        drop(engine);
+        signal_hook::low_level::raise(signal_hook::consts::SIGINT).unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(5));
+        std::process::exit(0);
+    }
+    let status = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([
+            "signals_terminate_again_once_their_engine_is_dropped",
+            "--exact",
+            "--nocapture",
+        ])
+        .env(SIGNAL_CHILD, "1")
+        .status()
+        .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(status.signal(), Some(signal_hook::consts::SIGINT));
+    }
+    assert!(!status.success());
+}
+
+#[test]
+fn containers_inside_themselves_print_as_cycles() {
+    let mut engine = Engine::new();
+    let printed = engine.capture_print();
+    eval(
+        &mut engine,
+        "x = new {}; x.me = x;\n\
+         print(x); print(repr(new [x, x])); pprint(x);\n\
+         y = new {}; y.a_key_long_enough_to_not_fit_in_one_line_with_the_cycle_placeholder_behind_it = y;\n\
+         pprint(y);",
+    );
+    assert_eq!(
+        *printed.borrow(),
+        "{me: <cycle>}\n\
+         [{me: <cycle>}, {me: <cycle>}]\n\
+         {me: <cycle>}\n\
+         {\n    \
+         a_key_long_enough_to_not_fit_in_one_line_with_the_cycle_placeholder_behind_it: <cycle>\n\
+         }\n"
+    );
+}
@@ -0,0 +1,44 @@
+use mistake::Mistake::{Fail, Fine};
+use olive::typecheck;
+
+fn warnings(source: &str) -> Vec<String> {
+    match typecheck(source, "check.olv") {
+        Fine(warnings, _) => warnings
+            .into_iter()
+            .map(|warning| format!("{}: {}", warning.line, warning.message))
+            .collect(),
+        Fail(errors) => panic!("{:?}", errors),
+    }
+}
+
+#[test]
+fn probable_type_errors() {
+    let source = "config = new { name: \"olive\" };\n\
+                  print(config.colour);\n\
+                  greet = fun(who: string) { return \"hi \" $ who; };\n\
+                  greet(5);\n\
+                  x = none;\n\
+                  print(x.field $ x?.field);\n\
+                  total = greet(\"a\") + 1;";
+    assert_eq!(
+        warnings(source),
+        vec![
+            "2: bendy has no key 'colour'",
+            "4: expected type string, got type integer",
+            "6: expected one of types [list, string, bendy], got type none",
+            "7: operation not supported for type string and type integer",
+        ]
+    );
+}
+
+#[test]
+fn unknown_values_are_not_reported() {
+    let source = "data = new {};\n\
+                  fill = fun(b) { b.extra = 1; };\n\
+                  fill(data);\n\
+                  y = 1;\n\
+                  i = 0;\n\
+                  while (i < 3) { y = \"s\" $ i; i = i + 1; }\n\
+                  print(data.extra + y);";
+    assert!(warnings(source).is_empty());
+}
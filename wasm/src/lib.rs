@@ -0,0 +1,19 @@
+use olive::{Engine, Mistake};
+use wasm_bindgen::prelude::*;
+
+// runs a script in a fresh engine and returns everything it printed,
+// followed by the error messages if it failed
+#[wasm_bindgen]
+pub fn run(source: &str) -> String {
+    let mut engine = Engine::new();
+    let output = engine.capture_print();
+    let errors = match engine.eval_str(source) {
+        Mistake::Fine(_, errors) => errors,
+        Mistake::Fail(errors) => errors,
+    };
+    let mut result = output.borrow().clone();
+    for err in errors {
+        result.push_str(&format!("{}\n", err));
+    }
+    result
+}